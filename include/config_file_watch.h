@@ -0,0 +1,64 @@
+/*
+ * C API for config-file-watch, available when the crate is built with the
+ * `ffi` feature. See `src/ffi.rs` for details.
+ */
+#ifndef CONFIG_FILE_WATCH_H
+#define CONFIG_FILE_WATCH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A watch on a single file. */
+typedef struct CfwWatch CfwWatch;
+
+/* A copy of a watch's value. Valid until passed to cfw_buffer_free(). */
+typedef struct CfwBuffer {
+    /* The contents of the file. Not null-terminated. */
+    const uint8_t *data;
+    /* The length of data in bytes. */
+    size_t len;
+    /* The generation of this value. 0 until the file is first loaded. */
+    uint64_t generation;
+    /* Private. */
+    void *handle;
+} CfwBuffer;
+
+/* Called on the watch's background thread after each reload. */
+typedef void (*CfwCallback)(void *user_data, uint64_t generation);
+
+/*
+ * Start watching the file at path. Returns NULL on failure, and if error is
+ * not NULL sets it to a message which must be freed with cfw_string_free().
+ */
+CfwWatch *cfw_watch_new(const char *path, char **error);
+
+/*
+ * Stop a watch and free it. Must not be called from the watch's own callback.
+ * Buffers returned by cfw_watch_value() remain valid.
+ */
+void cfw_watch_free(CfwWatch *watch);
+
+/* Set the function to call after each reload. Pass NULL to remove it. */
+void cfw_watch_set_callback(CfwWatch *watch, CfwCallback callback, void *user_data);
+
+/* Get the generation of the watch's current value. */
+uint64_t cfw_watch_generation(const CfwWatch *watch);
+
+/* Get the watch's current value. Free it with cfw_buffer_free(). */
+CfwBuffer cfw_watch_value(const CfwWatch *watch);
+
+/* Free a buffer returned by cfw_watch_value(). */
+void cfw_buffer_free(CfwBuffer buffer);
+
+/* Free an error message returned by cfw_watch_new(). */
+void cfw_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CONFIG_FILE_WATCH_H */
@@ -0,0 +1,111 @@
+use std::future::Future;
+
+use tokio::runtime::Handle;
+
+use crate::{Context, Guard, Loader, UpdatedHandler};
+
+/// Loads a configuration file with async I/O, such as fetching secrets which
+/// the file refers to. Set with `Builder::load_async()`.
+///
+/// Implementations can use `async fn load(...)`. Closures which take a
+/// `&mut Context` and return a future are also loaders, as long as the future
+/// doesn't borrow the context; copy anything it needs (such as
+/// `context.path()`) before the `async` block.
+pub trait AsyncLoader<T> {
+    /// Called when a file changes. See `Loader::load()`.
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> impl Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>;
+}
+
+/// Allow passing in a closure which returns a future as an async loader.
+impl<T, F, Fut> AsyncLoader<T> for F
+where
+    F: FnMut(&mut Context) -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> impl Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>> {
+        self(context)
+    }
+}
+
+/// Handles updates with async code, such as sending the new value to an
+/// async channel. Set with `Builder::after_update_async()`.
+///
+/// Closures which take a `&mut Context` and the new value and return a future
+/// are also handlers, as long as the future doesn't borrow the context.
+pub trait AsyncUpdatedHandler<T> {
+    /// Called after the value has been loaded from disk. See
+    /// `UpdatedHandler::after_update()`.
+    fn after_update(&mut self, context: &mut Context, value: Guard<T>) -> impl Future<Output = ()>;
+}
+
+/// Allow passing in a closure which returns a future as an async handler.
+impl<T, F, Fut> AsyncUpdatedHandler<T> for F
+where
+    F: FnMut(&mut Context, Guard<T>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    fn after_update(&mut self, context: &mut Context, value: Guard<T>) -> impl Future<Output = ()> {
+        self(context, value)
+    }
+}
+
+/// Runs an `AsyncLoader` on a tokio runtime. The watch's thread waits for
+/// each load to finish, so loads still happen one at a time.
+#[derive(Debug)]
+pub struct TokioLoader<L> {
+    inner: L,
+    handle: Handle,
+}
+
+impl<L> TokioLoader<L> {
+    /// Create a new TokioLoader which runs `inner` on the runtime `handle`
+    /// belongs to.
+    pub fn new(inner: L, handle: Handle) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<T, L> Loader<T> for TokioLoader<L>
+where
+    L: AsyncLoader<T>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        self.handle.block_on(self.inner.load(context))
+    }
+}
+
+/// Runs an `AsyncUpdatedHandler` on a tokio runtime. The watch's thread waits
+/// for the handler to finish, so updates are handled one at a time and in
+/// order.
+#[derive(Debug)]
+pub struct TokioUpdatedHandler<H> {
+    inner: H,
+    handle: Handle,
+}
+
+impl<H> TokioUpdatedHandler<H> {
+    /// Create a new TokioUpdatedHandler which runs `inner` on the runtime
+    /// `handle` belongs to.
+    pub fn new(inner: H, handle: Handle) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<T, H> UpdatedHandler<T> for TokioUpdatedHandler<H>
+where
+    H: AsyncUpdatedHandler<T>,
+{
+    fn after_update(&mut self, context: &mut Context, value: Guard<T>) {
+        self.handle
+            .block_on(self.inner.after_update(context, value))
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+/// The state of a watch's circuit breaker, set with
+/// `Builder::circuit_breaker()`. Reported in `WatchStats::circuit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The loader is called for every change. This is the state of a watch
+    /// without a circuit breaker.
+    #[default]
+    Closed,
+    /// The loader has failed too many times in a row, and is not being called
+    /// until the cool-down period is over.
+    Open,
+    /// The cool-down period is over, and the next load will decide whether
+    /// the circuit closes again or stays open for another cool-down period.
+    HalfOpen,
+}
+
+/// Stops calling a loader which keeps failing. After `threshold` failures in
+/// a row the circuit opens, and loads are skipped for `cooldown`. After that
+/// one load is let through: if it succeeds the circuit closes, and if it
+/// fails the circuit opens again.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    /// The number of failures in a row.
+    failures: u32,
+    state: CircuitState,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            failures: 0,
+            state: CircuitState::Closed,
+            opened_at: None,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Called before each load. Returns false if the loader should not be
+    /// called.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        match (self.state, self.opened_at) {
+            (CircuitState::Open, Some(opened_at)) if now < opened_at + self.cooldown => false,
+            (CircuitState::Open, _) => {
+                self.state = CircuitState::HalfOpen;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Called when a load succeeds. Returns the number of failures in a row
+    /// before this load if this closed the circuit.
+    pub fn on_success(&mut self) -> Option<u32> {
+        let failures = std::mem::take(&mut self.failures);
+        let reopened = self.state != CircuitState::Closed;
+        self.state = CircuitState::Closed;
+        self.opened_at = None;
+        reopened.then_some(failures)
+    }
+
+    /// Called when a load fails. Returns the number of failures in a row if
+    /// this opened the circuit.
+    pub fn on_failure(&mut self, now: Instant) -> Option<u32> {
+        self.failures += 1;
+        let open = self.state == CircuitState::HalfOpen || self.failures >= self.threshold;
+        if !open || self.state == CircuitState::Open {
+            return None;
+        }
+        self.state = CircuitState::Open;
+        self.opened_at = Some(now);
+        Some(self.failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{CircuitBreaker, CircuitState};
+
+    #[test]
+    fn should_open_after_repeated_failures() {
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        let mut breaker = CircuitBreaker::new(3, cooldown);
+
+        assert!(breaker.allow(start));
+        assert_eq!(breaker.on_failure(start), None);
+        assert_eq!(breaker.on_failure(start), None);
+        assert_eq!(breaker.on_failure(start), Some(3));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // Loads are skipped until the cool-down is over.
+        assert!(!breaker.allow(start + Duration::from_secs(9)));
+        assert!(breaker.allow(start + cooldown));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // A single failure while half-open opens the circuit again.
+        let reopened = start + cooldown;
+        assert_eq!(breaker.on_failure(reopened), Some(4));
+        assert!(!breaker.allow(reopened + Duration::from_secs(1)));
+
+        assert!(breaker.allow(reopened + cooldown));
+        assert_eq!(breaker.on_success(), Some(4));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.on_success(), None);
+    }
+}
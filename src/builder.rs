@@ -1,30 +1,79 @@
 use std::{
     path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
 use arc_swap::ArcSwap;
 
 use crate::{
-    types::{DefaultErrorHandler, DefaultLoader, DefaultUpdatedHandler},
-    Context, Error, ErrorHandler, Loader, UpdatedHandler, Watch,
+    breaker::CircuitBreaker,
+    file_watcher::FallbackHandler,
+    rate_alarm::RateAlarm,
+    reloader::ReloaderGuard,
+    roots::DependencyRoots,
+    suppress::ErrorSuppressor,
+    types::{DefaultErrorHandler, DefaultLoader, DefaultUpdatedHandler, DefaultWarningHandler},
+    Backend, ChangeDetection, ConfigSources, DebounceMode, DedupPolicy, DedupValues, Error,
+    ErrorHandler, FileSystem, FirstOfLoader, LintLoader, Loader, NoDedup, NotifyConfig, Pipeline,
+    PollCompare, ProgressHandler, StdFileSystem, UpdatedHandler, ValidateLoader, WarningHandler,
+    Watch, WatchOptions, WatcherOptions,
 };
 
-const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
-
 /// Used to create file watches.
 ///
-pub struct Builder<Load, Updated, ErrHandler> {
+pub struct Builder<Load, Updated, ErrHandler, Dedup = NoDedup> {
     /// The initial set of files to watch for changes.
     files: Vec<PathBuf>,
-    /// The time to debounce changes before calling the loader.
-    debounce: Option<Duration>,
+    /// Glob patterns for files to watch.
+    globs: Vec<String>,
+    /// Options controlling how files are watched.
+    options: WatcherOptions,
     /// The loader to use to load the file or files.
     loader: Load,
     /// The error handler to use when an error occurs.
     error_handler: ErrHandler,
     /// The handler to use when the configuration is updated.
     after_update: Updated,
+    /// The handler to call with warnings reported by the loader.
+    warning_handler: Box<dyn WarningHandler + Send>,
+    /// The handler to call with progress reported by the loader.
+    progress_handler: Option<Box<dyn ProgressHandler + Send>>,
+    /// A default document, parsed by the loader when the file doesn't exist.
+    embedded_default: Option<Arc<str>>,
+    /// Warn if loading takes longer than this.
+    slow_reload_threshold: Option<Duration>,
+    /// Warn if the watch reloads more than this many times in this long.
+    reload_rate_limit: Option<(u32, Duration)>,
+    /// How often to report the same error again.
+    repeated_error_interval: Option<Duration>,
+    /// The file system to read and watch files on.
+    file_system: Arc<dyn FileSystem>,
+    /// The folders loaders may add dependencies from, if restricted.
+    dependency_roots: Option<Vec<PathBuf>>,
+    /// Candidate files, in priority order, of which only the first that
+    /// exists is loaded.
+    first_of: Vec<PathBuf>,
+    /// Return an error from `build()` if the initial load fails.
+    fail_on_initial_error: bool,
+    /// Run the initial load on a background thread.
+    lazy_initial_load: bool,
+    /// Fail to build if the watched files don't exist.
+    require_exists: bool,
+    /// Stop loading after this many failures in a row, for this long.
+    circuit_breaker: Option<(u32, Duration)>,
+    /// Report loads which take longer than this.
+    load_timeout: Option<Duration>,
+    /// Discard the result of a load which timed out.
+    abandon_timed_out_loads: bool,
+    /// The number of recent values to keep for `Watch::value_at()`.
+    history: usize,
+    /// Called when a folder is polled instead of watched natively.
+    fallback_handler: Option<FallbackHandler>,
+    /// Settings for individual watched files.
+    watch_options: Vec<(PathBuf, WatchOptions)>,
+    /// Decides whether a new value is the same as the current one.
+    dedup: Dedup,
 }
 
 impl Builder<DefaultLoader, DefaultUpdatedHandler, DefaultErrorHandler> {
@@ -32,10 +81,30 @@ impl Builder<DefaultLoader, DefaultUpdatedHandler, DefaultErrorHandler> {
     pub fn new() -> Self {
         Self {
             files: vec![],
-            debounce: Some(DEFAULT_DEBOUNCE),
+            globs: vec![],
+            options: WatcherOptions::default(),
             loader: DefaultLoader,
             error_handler: DefaultErrorHandler,
             after_update: DefaultUpdatedHandler,
+            warning_handler: Box::new(DefaultWarningHandler),
+            progress_handler: None,
+            embedded_default: None,
+            slow_reload_threshold: None,
+            reload_rate_limit: None,
+            repeated_error_interval: None,
+            file_system: Arc::new(StdFileSystem),
+            dependency_roots: None,
+            first_of: vec![],
+            fail_on_initial_error: false,
+            lazy_initial_load: false,
+            require_exists: false,
+            circuit_breaker: None,
+            load_timeout: None,
+            abandon_timed_out_loads: false,
+            history: 0,
+            fallback_handler: None,
+            watch_options: vec![],
+            dedup: NoDedup,
         }
     }
 }
@@ -47,13 +116,105 @@ impl Default for Builder<DefaultLoader, DefaultUpdatedHandler, DefaultErrorHandl
 }
 
 /// A builder for creating a new Watch instance.
-impl<Load, Updated, ErrHandler> Builder<Load, Updated, ErrHandler> {
+impl<Load, Updated, ErrHandler, Dedup> Builder<Load, Updated, ErrHandler, Dedup> {
     /// Add a file to the watch. This is the initial set of files to watch for changes.
     pub fn watch_file(mut self, file: impl AsRef<Path>) -> Self {
         self.files.push(file.as_ref().to_path_buf());
         self
     }
 
+    /// Add a file or directory to the watch, with its own settings for how
+    /// it's watched, such as watching a directory of templates recursively
+    /// alongside a single config file. See `WatchOptions`.
+    pub fn watch_file_with(mut self, file: impl AsRef<Path>, options: WatchOptions) -> Self {
+        let file = file.as_ref().to_path_buf();
+        self.files.push(file.clone());
+        self.watch_options.push((file, options));
+        self
+    }
+
+    /// Use a document compiled into the program, such as
+    /// `include_str!("default.toml")`, as the default configuration. The
+    /// document is parsed by the watch's loader to produce the initial value,
+    /// and again whenever the watched file doesn't exist, so the built-in
+    /// defaults can live in the same format as the file on disk instead of
+    /// being duplicated in a `Default` impl. Building the watch fails if the
+    /// document can't be parsed.
+    ///
+    /// This is supported by the built-in single file loaders (such as
+    /// `load_json()` and `load_toml()`). Custom loaders can get the document
+    /// from `Context::embedded_default()`.
+    pub fn embedded_default(mut self, contents: impl Into<Arc<str>>) -> Self {
+        self.embedded_default = Some(contents.into());
+        self
+    }
+
+    /// Only allow loaders to add dependencies from inside these folders. This
+    /// should be used whenever users control the config being loaded, so an
+    /// include directive can't point the watch at a sensitive file.
+    ///
+    /// Files added with `Context::update_watched_files()` or
+    /// `Context::add_watched_files()` which are outside every root are
+    /// rejected with `Error::PathNotAllowed`, and the load fails. Paths are
+    /// normalized and symlinks are followed before they are checked. The
+    /// built-in `IncludeResolver` and `RefResolver` check each file before
+    /// reading it, and custom loaders can do the same with
+    /// `Context::check_dependency()`. Files passed to the `Builder` and files
+    /// which are already being watched are always allowed.
+    pub fn dependency_roots<I>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let roots = roots.into_iter().map(|r| r.as_ref().to_path_buf());
+        self.dependency_roots = Some(roots.collect());
+        self
+    }
+
+    /// Watch a list of candidate files in priority order, such as
+    /// `["/etc/app.conf", "~/.config/app.conf"]`, and load only the first one
+    /// which exists. If that file is deleted the watch falls back to the next
+    /// candidate, and if a higher priority file is created it switches back.
+    /// If none of them exist, the loader is given the first candidate, so the
+    /// watch gets its default value. See `FirstOfLoader`.
+    pub fn watch_first_of<I>(mut self, files: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        for f in files {
+            let f = f.as_ref().to_path_buf();
+            self.files.push(f.clone());
+            self.first_of.push(f);
+        }
+        self
+    }
+
+    /// Watch every candidate in `sources`, and load the highest precedence
+    /// one which exists: the command line flag, then the environment
+    /// variable, then the default locations. If a higher precedence file is
+    /// created later, the watch switches to it. See `watch_first_of()`, and
+    /// `ConfigSources::resolve()` to report which file was chosen.
+    pub fn watch_sources(self, sources: ConfigSources) -> Self {
+        self.watch_first_of(sources.into_paths())
+    }
+
+    /// Watch a config file in the standard per-user and system-wide config
+    /// folders, such as `watch_xdg_config("myapp/config.toml")`, and load the
+    /// highest priority one which exists. See `watch_first_of()`.
+    ///
+    /// The folders are searched in this order:
+    ///
+    /// - On Linux and other Unix systems, `$XDG_CONFIG_HOME` (or
+    ///   `~/.config`), then each folder in `$XDG_CONFIG_DIRS` (or
+    ///   `/etc/xdg`).
+    /// - On macOS, `~/Library/Application Support`, followed by the same
+    ///   folders as other Unix systems.
+    /// - On Windows, `%APPDATA%`, then `%PROGRAMDATA%`.
+    pub fn watch_xdg_config(self, file: impl AsRef<Path>) -> Self {
+        self.watch_first_of(crate::xdg::config_candidates(file.as_ref()))
+    }
+
     /// Add a set of files to the watch. This is the initial set of files to watch for changes.
     pub fn watch_files<I>(mut self, files: I) -> Self
     where
@@ -66,55 +227,419 @@ impl<Load, Updated, ErrHandler> Builder<Load, Updated, ErrHandler> {
         self
     }
 
+    /// Watch every file matching a glob pattern, such as `conf.d/*.yaml`.
+    /// Files which are created later and match the pattern will also be
+    /// watched. Matching files are included in `Context::watched_files()` and
+    /// `Watch::watched_files()`.
+    pub fn watch_glob(mut self, pattern: impl AsRef<str>) -> Self {
+        self.globs.push(pattern.as_ref().to_string());
+        self
+    }
+
     /// Set the duration to wait after a change before calling the loader.
     /// The default is 100ms.
     pub fn debounce(mut self, duration: Duration) -> Self {
-        self.debounce = Some(duration);
+        self.options.debounce = Some(duration);
         self
     }
 
     /// Clear the debounce duration.
     pub fn no_debounce(mut self) -> Self {
-        self.debounce = None;
+        self.options.debounce = None;
+        self.options.max_debounce = None;
+        self
+    }
+
+    /// Use a debounce duration which starts at `min`, and grows up to `max`
+    /// while files are being written in bursts, such as a large generated
+    /// file which is written in pieces. See `WatcherOptions::max_debounce`.
+    pub fn adaptive_debounce(mut self, min: Duration, max: Duration) -> Self {
+        self.options.debounce = Some(min);
+        self.options.max_debounce = Some(max);
+        self
+    }
+
+    /// Wait at least `interval` between reloads caused by file changes, to
+    /// protect against a writer which rewrites a file constantly. Changes
+    /// which arrive sooner are loaded together once the interval has passed.
+    /// See `WatcherOptions::min_reload_interval`.
+    pub fn min_reload_interval(mut self, interval: Duration) -> Self {
+        self.options.min_reload_interval = Some(interval);
+        self
+    }
+
+    /// Set whether the loader is called at the end of the debounce duration,
+    /// or straight away at the start of it. See `DebounceMode`.
+    pub fn debounce_mode(mut self, mode: DebounceMode) -> Self {
+        self.options.debounce_mode = mode;
+        self
+    }
+
+    /// Expand `~` and environment variables such as `$HOME` or `%APPDATA%` in
+    /// watched paths, including paths added later by a loader. See
+    /// `WatcherOptions::expand_paths`.
+    pub fn expand_paths(mut self) -> Self {
+        self.options.expand_paths = true;
+        self
+    }
+
+    /// Resolve relative watched paths against `dir` instead of the process's
+    /// current directory, including paths added later by a loader. See
+    /// `WatcherOptions::base_dir`.
+    pub fn base_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.options.base_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set how changes to files are detected. The default is
+    /// `Backend::Native`.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    /// Pass `config` to the underlying `notify` watcher, for tuning the
+    /// backend directly. See `WatcherOptions::notify_config`.
+    pub fn notify_config(mut self, config: NotifyConfig) -> Self {
+        self.options.notify_config = Some(config);
+        self
+    }
+
+    /// Set how the polling backend decides whether a file has changed. The
+    /// default is `PollCompare::Metadata`.
+    pub fn poll_compare(mut self, poll_compare: PollCompare) -> Self {
+        self.options.poll_compare = poll_compare;
+        self
+    }
+
+    /// Set whether folders on network file systems are polled instead of
+    /// watched natively. See `WatcherOptions::poll_network_folders`.
+    pub fn poll_network_folders(mut self, enabled: bool) -> Self {
+        self.options.poll_network_folders = enabled;
+        self
+    }
+
+    /// Call `handler` with each watched folder which is polled instead of
+    /// being watched with the backend that was asked for, and why, such as
+    /// a folder on NFS. This is meant for logging. It's called while the
+    /// watcher is busy, so it must not call back into the watch.
+    pub fn on_poll_fallback(mut self, handler: impl FnMut(&Path, &str) + Send + 'static) -> Self {
+        self.fallback_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Reload for events which only change a file's metadata, such as `chmod`
+    /// or `chown`. See `WatcherOptions::metadata_events`.
+    pub fn include_metadata_events(mut self) -> Self {
+        self.options.metadata_events = true;
+        self
+    }
+
+    /// Set how the watch decides whether a file really changed before calling
+    /// the loader. The default is `ChangeDetection::Always`.
+    pub fn change_detection(mut self, change_detection: ChangeDetection) -> Self {
+        self.options.change_detection = change_detection;
+        self
+    }
+
+    /// Ignore changes to paths matching a glob pattern, such as `*.swp`. A
+    /// pattern which contains a `/` is matched against the full path of the
+    /// changed file, otherwise it is matched against the file name.
+    pub fn ignore(mut self, pattern: impl AsRef<str>) -> Self {
+        self.options.ignore.push(pattern.as_ref().to_string());
+        self
+    }
+
+    /// Run loaders for file changes with reduced CPU and IO priority. See
+    /// `WatcherOptions::low_priority`.
+    pub fn low_priority(mut self) -> Self {
+        self.options.low_priority = true;
+        self
+    }
+
+    /// Match file system events against watched paths literally, without
+    /// canonicalizing them. See `WatcherOptions::literal_paths`.
+    pub fn literal_paths(mut self) -> Self {
+        self.options.literal_paths = true;
+        self
+    }
+
+    /// Read and watch files on a different file system, such as a
+    /// `MemoryFileSystem` in tests. The default is `StdFileSystem`. Loaders
+    /// read files through `Context::file_system()`.
+    pub fn file_system(mut self, fs: impl FileSystem) -> Self {
+        self.file_system = Arc::new(fs);
+        self
+    }
+
+    /// Replace all the options controlling how files are watched.
+    pub fn options(mut self, options: WatcherOptions) -> Self {
+        self.options = options;
         self
     }
 
     /// Set the loader to use to load the file or files.
-    pub fn load<Load2>(self, loader: Load2) -> Builder<Load2, Updated, ErrHandler> {
-        Builder {
-            files: self.files,
-            debounce: self.debounce,
-            loader,
-            error_handler: self.error_handler,
-            after_update: self.after_update,
-        }
+    pub fn load<Load2>(self, loader: Load2) -> Builder<Load2, Updated, ErrHandler, Dedup> {
+        self.map_loader(|_| loader)
+    }
+
+    /// Load files with a `Pipeline` of separate stages, such as decrypting,
+    /// parsing, and validating. The time each stage takes is reported in
+    /// `WatchStats::stage_durations`.
+    pub fn load_pipeline<T>(
+        self,
+        pipeline: Pipeline<T>,
+    ) -> Builder<Pipeline<T>, Updated, ErrHandler, Dedup> {
+        self.load(pipeline)
     }
 
     /// Set the error handler to use when an error occurs.
     pub fn on_error<ErrHandler2>(
         self,
         error_handler: ErrHandler2,
-    ) -> Builder<Load, Updated, ErrHandler2> {
-        Builder {
-            files: self.files,
-            debounce: self.debounce,
-            loader: self.loader,
-            error_handler,
-            after_update: self.after_update,
-        }
+    ) -> Builder<Load, Updated, ErrHandler2, Dedup> {
+        self.map(|loader, after_update, _| (loader, after_update, error_handler))
     }
 
     /// Set the handler to call when the loaded value changes.
     pub fn after_update<Updated2>(
         self,
         after_update: Updated2,
-    ) -> Builder<Load, Updated2, ErrHandler> {
+    ) -> Builder<Load, Updated2, ErrHandler, Dedup> {
+        self.map(|loader, _, error_handler| (loader, after_update, error_handler))
+    }
+
+    /// Set the handler to call with warnings reported by the loader, such as
+    /// the findings from `lint()`. Warnings are delivered after the new value
+    /// is stored, and before `after_update` is called. By default warnings are
+    /// printed to stderr.
+    pub fn on_warning(mut self, warning_handler: impl WarningHandler + Send + 'static) -> Self {
+        self.warning_handler = Box::new(warning_handler);
+        self
+    }
+
+    /// Set the handler to call when the loader reports progress with
+    /// `Context::report_progress()`, so a UI or admin endpoint can show how a
+    /// slow reload is going. The handler is called on the loader's thread, so
+    /// it should return quickly. The latest progress is also available from
+    /// `Watch::progress()`.
+    pub fn on_progress(mut self, progress_handler: impl ProgressHandler + Send + 'static) -> Self {
+        self.progress_handler = Some(Box::new(progress_handler));
+        self
+    }
+
+    /// Report a warning if the loader takes longer than `threshold`, or if the
+    /// average time taken by the loader keeps going up (for example because a
+    /// config file is growing). Warnings are passed to the handler set with
+    /// `on_warning()`. The average is always available in `Watch::stats()`.
+    pub fn warn_on_slow_reload(mut self, threshold: Duration) -> Self {
+        self.slow_reload_threshold = Some(threshold);
+        self
+    }
+
+    /// Report a warning if the watch reloads more than `max` times within
+    /// `window`, which usually means a file is flapping or something keeps
+    /// rewriting it. The warning is passed to the handler set with
+    /// `on_warning()`, and isn't repeated until the rate has dropped back to
+    /// `max` or below. Every change counts, whether or not it loads
+    /// successfully.
+    pub fn warn_on_reload_rate(mut self, max: u32, window: Duration) -> Self {
+        self.reload_rate_limit = Some((max, window));
+        self
+    }
+
+    /// Limit how often the same error is passed to the error handler, such as
+    /// a parse error which repeats on every save while someone is editing the
+    /// file. The first occurrence of an error is always reported, and then the
+    /// same error is reported at most once every `interval`. Errors are
+    /// compared by their message. Once a load succeeds, if any errors were
+    /// suppressed, a warning saying the problem is resolved is passed to the
+    /// handler set with `on_warning()`.
+    pub fn suppress_repeated_errors(mut self, interval: Duration) -> Self {
+        self.repeated_error_interval = Some(interval);
+        self
+    }
+
+    /// Stop calling the loader after it fails `failures` times in a row, such
+    /// as when the file refers to a service which is down. For `cooldown`
+    /// after that, changes are still recorded in
+    /// `Watch::explain_last_reload()` and `Watch::stats()`, but are skipped
+    /// instead of loaded. Once the cool-down is over the watched files are
+    /// loaded again: if this succeeds the watch goes back to loading every
+    /// change, and if it fails loading stops for another `cooldown`.
+    ///
+    /// Each time loading stops, `Error::CircuitOpen` is passed to the error
+    /// handler. When loading succeeds again, a warning is passed to the
+    /// handler set with `on_warning()`. The current state is available in
+    /// `WatchStats::circuit`.
+    pub fn circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((failures, cooldown));
+        self
+    }
+
+    /// Report a load which takes longer than `timeout` by passing
+    /// `Error::LoadTimeout` to the error handler. This is reported while the
+    /// load is still running, so a loader which hangs (such as on a read from
+    /// an unresponsive network file system) doesn't silently stop the watch
+    /// from reloading. The handler is called from another thread, where
+    /// anything which would wait for the watch returns `Error::WouldDeadlock`.
+    /// The number of loads which timed out is in `WatchStats::timed_out_loads`.
+    ///
+    /// By default the load's result is still used once it finishes. See
+    /// `abandon_timed_out_loads()`.
+    pub fn load_timeout(mut self, timeout: Duration) -> Self {
+        self.load_timeout = Some(timeout);
+        self
+    }
+
+    /// Discard the result of a load which takes longer than the timeout set
+    /// with `load_timeout()`, and keep the current value. Once the load has
+    /// timed out, `Context::cancelled()` returns true, so a loader which
+    /// checks it can give up early.
+    pub fn abandon_timed_out_loads(mut self) -> Self {
+        self.abandon_timed_out_loads = true;
+        self
+    }
+
+    /// Keep the last `versions` values of the watch, along with when each
+    /// was loaded, so `Watch::value_at()` can tell which config was live at
+    /// a given time, such as when an incident started. `Watch::history()`
+    /// returns all of them. Each value is kept alive until it falls out of
+    /// the history, so keep `versions` small for large configs.
+    pub fn keep_history(mut self, versions: usize) -> Self {
+        self.history = versions;
+        self
+    }
+
+    /// Make `build()` fail if the watched files can't be loaded, for services
+    /// which must refuse to start with an invalid config. By default a failed
+    /// initial load is passed to the error handler, and the watch starts with
+    /// its default value.
+    ///
+    /// The error is returned instead of being passed to the error handler.
+    /// Files which don't exist aren't an error unless the loader treats them
+    /// as one. Later reloads are unaffected.
+    pub fn fail_on_initial_error(mut self) -> Self {
+        self.fail_on_initial_error = true;
+        self
+    }
+
+    /// Don't load the watched files in `build()`. Instead the watch starts
+    /// with its default (or initial) value, and the files are loaded on a
+    /// background thread, keeping slow loads out of startup. The
+    /// `after_update` handler is called once the first load finishes.
+    ///
+    /// Since `build()` returns before the files are loaded, this can't be
+    /// combined with `fail_on_initial_error()`. If both are set, `build()`
+    /// returns `Error::InvalidConfiguration`.
+    pub fn lazy_initial_load(mut self) -> Self {
+        self.lazy_initial_load = true;
+        self
+    }
+
+    /// Make `build()` fail with `Error::FileNotFound` if any watched file
+    /// doesn't exist, instead of starting with the default value. This
+    /// catches a missing or misnamed config file at boot. For files added
+    /// with `watch_first_of()`, at least one of the candidates must exist.
+    /// Files matched by `watch_glob()` aren't checked.
+    pub fn require_exists(mut self) -> Self {
+        self.require_exists = true;
+        self
+    }
+
+    /// Check each loaded value for non-fatal problems, such as deprecated
+    /// settings. The value is still used, and any warnings are passed to the
+    /// handler set with `on_warning()` and counted in `Watch::stats()`. This
+    /// wraps the current loader, so it must be called after the loader is set.
+    pub fn lint<Lint>(
+        self,
+        linter: Lint,
+    ) -> Builder<LintLoader<Load, Lint>, Updated, ErrHandler, Dedup> {
+        self.map_loader(|loader| LintLoader::new(loader, linter))
+    }
+
+    /// Check each loaded value before it is used. If the validator returns an
+    /// error, the new value is rejected, the watch keeps its current value, and
+    /// the error handler is called with an `Error::ValidationError`. This wraps
+    /// the current loader, so it must be called after the loader is set.
+    pub fn validate<Validate>(
+        self,
+        validator: Validate,
+    ) -> Builder<ValidateLoader<Load, Validate>, Updated, ErrHandler, Dedup> {
+        self.map_loader(|loader| ValidateLoader::new(loader, validator))
+    }
+
+    /// Don't store a newly loaded value if it is equal to the current one, so
+    /// saving a file without changing it doesn't call `after_update` or wake
+    /// up subscribers. The load is recorded as `ReloadOutcome::Unchanged`, and
+    /// counted in `WatchStats::unchanged_reloads`. The value must implement
+    /// `PartialEq`. The comparison is made on the value the whole loader
+    /// produces, so this can be called before or after the loader and any
+    /// wrappers, such as `strict()` or `with_overrides()`, are set.
+    pub fn dedup_values(self) -> Builder<Load, Updated, ErrHandler, DedupValues> {
+        self.map_all(|loader, after_update, error_handler, _| {
+            (loader, after_update, error_handler, DedupValues)
+        })
+    }
+
+    /// Replace the loader, such as with a wrapper around the current one,
+    /// keeping every other setting.
+    fn map_loader<Load2>(
+        self,
+        f: impl FnOnce(Load) -> Load2,
+    ) -> Builder<Load2, Updated, ErrHandler, Dedup> {
+        self.map(|loader, after_update, error_handler| (f(loader), after_update, error_handler))
+    }
+
+    /// Replace the loader and handlers, keeping every other setting.
+    fn map<Load2, Updated2, ErrHandler2>(
+        self,
+        f: impl FnOnce(Load, Updated, ErrHandler) -> (Load2, Updated2, ErrHandler2),
+    ) -> Builder<Load2, Updated2, ErrHandler2, Dedup> {
+        self.map_all(|loader, after_update, error_handler, dedup| {
+            let (loader, after_update, error_handler) = f(loader, after_update, error_handler);
+            (loader, after_update, error_handler, dedup)
+        })
+    }
+
+    /// Replace the loader, handlers and dedup policy, keeping every other
+    /// setting.
+    fn map_all<Load2, Updated2, ErrHandler2, Dedup2>(
+        self,
+        f: impl FnOnce(Load, Updated, ErrHandler, Dedup) -> (Load2, Updated2, ErrHandler2, Dedup2),
+    ) -> Builder<Load2, Updated2, ErrHandler2, Dedup2> {
+        let (loader, after_update, error_handler, dedup) = f(
+            self.loader,
+            self.after_update,
+            self.error_handler,
+            self.dedup,
+        );
         Builder {
             files: self.files,
-            debounce: self.debounce,
-            loader: self.loader,
-            error_handler: self.error_handler,
+            globs: self.globs,
+            options: self.options,
+            loader,
+            error_handler,
             after_update,
+            warning_handler: self.warning_handler,
+            progress_handler: self.progress_handler,
+            embedded_default: self.embedded_default,
+            slow_reload_threshold: self.slow_reload_threshold,
+            reload_rate_limit: self.reload_rate_limit,
+            repeated_error_interval: self.repeated_error_interval,
+            file_system: self.file_system,
+            dependency_roots: self.dependency_roots,
+            first_of: self.first_of,
+            fail_on_initial_error: self.fail_on_initial_error,
+            lazy_initial_load: self.lazy_initial_load,
+            require_exists: self.require_exists,
+            circuit_breaker: self.circuit_breaker,
+            load_timeout: self.load_timeout,
+            abandon_timed_out_loads: self.abandon_timed_out_loads,
+            history: self.history,
+            fallback_handler: self.fallback_handler,
+            watch_options: self.watch_options,
+            dedup,
         }
     }
 
@@ -125,38 +650,119 @@ impl<Load, Updated, ErrHandler> Builder<Load, Updated, ErrHandler> {
         Load: Loader<T> + Send + 'static,
         Updated: UpdatedHandler<T> + Send + 'static,
         ErrHandler: ErrorHandler + Send + 'static,
+        Dedup: DedupPolicy<T> + Send + 'static,
+    {
+        self.build_with(T::default())
+    }
+
+    /// Build the Watch instance, starting from `initial` instead of
+    /// `T::default()`. This allows watching types which don't implement
+    /// `Default`. `initial` is the value until the watched files are loaded,
+    /// and is kept if they can't be loaded.
+    ///
+    /// The built-in loaders return `T::default()` when a file is missing, so
+    /// they still need `Default`; use a custom loader for other types.
+    pub fn build_with<T>(self, initial: T) -> Result<Watch<T>, Error>
+    where
+        T: Send + Sync + 'static,
+        Load: Loader<T> + Send + 'static,
+        Updated: UpdatedHandler<T> + Send + 'static,
+        ErrHandler: ErrorHandler + Send + 'static,
+        Dedup: DedupPolicy<T> + Send + 'static,
     {
-        let mut loader = self.loader;
-        let mut error_handler = self.error_handler;
-        let mut after_update = self.after_update;
-
-        let mut files = self.files.clone();
-
-        // Try to load here to set the initial value.
-        let changed_files: Vec<_> = self.files.iter().map(|f| f.as_ref()).collect();
-        let mut context = Context::for_paths(&changed_files, &mut files);
-        let value = if changed_files.is_empty() {
-            // If there are no files, just use the default value.
-            ArcSwap::from_pointee(T::default())
+        if self.lazy_initial_load && self.fail_on_initial_error {
+            return Err(Error::InvalidConfiguration(
+                "lazy_initial_load() can't be combined with fail_on_initial_error()".to_string(),
+            ));
+        }
+
+        // Resolve candidates the same way the watcher resolves files, so they
+        // can be compared.
+        let first_of: Vec<PathBuf> = self
+            .first_of
+            .iter()
+            .map(|p| self.options.resolve_path(p))
+            .collect();
+        if self.require_exists {
+            self.check_exists(&first_of)?;
+        }
+        let dependency_roots = self
+            .dependency_roots
+            .map(|roots| DependencyRoots::new(roots, &self.options));
+
+        let watch = Watch::create(
+            self.files,
+            ArcSwap::from_pointee(initial),
+            self.options,
+            self.file_system,
+            FirstOfLoader::new(first_of, self.loader),
+            self.after_update,
+            self.error_handler,
+        )?;
+        {
+            let mut reloader = ReloaderGuard::wait(&watch.reloader);
+            reloader.set_warning_handler(self.warning_handler);
+            reloader.set_progress_handler(self.progress_handler);
+            reloader.set_dependency_roots(dependency_roots);
+            reloader.set_slow_reload_threshold(self.slow_reload_threshold);
+            reloader.set_rate_alarm(
+                self.reload_rate_limit
+                    .map(|(max, window)| RateAlarm::new(max, window)),
+            );
+            reloader.set_error_suppressor(self.repeated_error_interval.map(ErrorSuppressor::new));
+            reloader.set_circuit_breaker(
+                self.circuit_breaker
+                    .map(|(failures, cooldown)| CircuitBreaker::new(failures, cooldown)),
+            );
+            reloader.set_load_timeout(self.load_timeout, self.abandon_timed_out_loads);
+            reloader.set_history_limit(self.history);
+            reloader.set_dedup(self.dedup.comparer());
+        }
+        if let Some(handler) = self.fallback_handler {
+            watch.watcher.on_fallback(handler);
+        }
+        for (file, options) in &self.watch_options {
+            watch.watcher.set_watch_options(file, *options)?;
+        }
+        if !self.globs.is_empty() {
+            watch.watcher.update_globs(&self.globs)?;
+        }
+        if let Some(embedded_default) = self.embedded_default {
+            watch.load_embedded_default(embedded_default)?;
+        }
+        // Changes which arrived while the watch was being configured are
+        // loaded now, with every setting in place.
+        watch.resume();
+
+        if self.lazy_initial_load {
+            let watch = watch.clone();
+            std::thread::spawn(move || {
+                let _ = watch.initial_load(false);
+            });
         } else {
-            match loader.load(&mut context) {
-                Ok(v) => ArcSwap::from_pointee(v),
-                Err(e) => {
-                    error_handler.on_error(&mut context, Error::LoadError(e));
-                    ArcSwap::from_pointee(T::default())
-                }
-            }
-        };
-        after_update.after_update(&mut context, value.load());
+            // Load here to set the initial value.
+            watch.initial_load(self.fail_on_initial_error)?;
+        }
 
-        Watch::create(
-            files,
-            value,
-            self.debounce,
-            loader,
-            after_update,
-            error_handler,
-        )
+        Ok(watch)
+    }
+
+    /// Return an error if a watched file doesn't exist. `first_of` is the
+    /// resolved list of candidates, of which only one needs to exist.
+    fn check_exists(&self, first_of: &[PathBuf]) -> Result<(), Error> {
+        let exists = |path: &Path| self.file_system.metadata(path).is_ok_and(|m| !m.is_dir);
+        for file in &self.files {
+            let file = self.options.resolve_path(file);
+            if !first_of.contains(&file) && !exists(&file) {
+                return Err(Error::FileNotFound(file));
+            }
+        }
+        match first_of.first() {
+            Some(first) if !first_of.iter().any(|f| exists(f)) => {
+                Err(Error::FileNotFound(first.clone()))
+            }
+            _ => Ok(()),
+        }
     }
 
     #[cfg(feature = "tokio")]
@@ -166,19 +772,255 @@ impl<Load, Updated, ErrHandler> Builder<Load, Updated, ErrHandler> {
         Load: Loader<T> + Send + 'static,
         Updated: UpdatedHandler<T> + Send + 'static,
         ErrHandler: ErrorHandler + Send + 'static,
+        Dedup: DedupPolicy<T> + Send + 'static,
     {
         tokio::task::spawn_blocking(move || self.build())
             .await
             .unwrap()
     }
 
+    /// Build the Watch instance on a blocking thread, starting from
+    /// `initial`. See `build_with()`.
+    #[cfg(feature = "tokio")]
+    pub async fn build_with_async<T>(self, initial: T) -> Result<Watch<T>, Error>
+    where
+        T: Send + Sync + 'static,
+        Load: Loader<T> + Send + 'static,
+        Updated: UpdatedHandler<T> + Send + 'static,
+        ErrHandler: ErrorHandler + Send + 'static,
+        Dedup: DedupPolicy<T> + Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || self.build_with(initial))
+            .await
+            .unwrap()
+    }
+
+    /// Set a loader which does async I/O, such as fetching secrets referred to
+    /// by the config file. Loads run on the current tokio runtime, and the
+    /// watch's thread waits for each one to finish. See `AsyncLoader`.
+    ///
+    /// This must be called from within a tokio runtime, and the watch must be
+    /// built with `build_async()` (or `lazy_initial_load()`), since the
+    /// initial load can't wait for the runtime from one of its own threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn load_async<Load2>(
+        self,
+        loader: Load2,
+    ) -> Builder<crate::TokioLoader<Load2>, Updated, ErrHandler, Dedup> {
+        self.load(crate::TokioLoader::new(
+            loader,
+            tokio::runtime::Handle::current(),
+        ))
+    }
+
+    /// Set a handler which runs async code when the loaded value changes, such
+    /// as sending it to an async channel. Handlers run on the current tokio
+    /// runtime, and the watch's thread waits for each one to finish. See
+    /// `AsyncUpdatedHandler`.
+    ///
+    /// As with `load_async()`, this must be called from within a tokio
+    /// runtime, and the watch must be built with `build_async()` (or
+    /// `lazy_initial_load()`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn after_update_async<Updated2>(
+        self,
+        after_update: Updated2,
+    ) -> Builder<Load, crate::TokioUpdatedHandler<Updated2>, ErrHandler, Dedup> {
+        self.after_update(crate::TokioUpdatedHandler::new(
+            after_update,
+            tokio::runtime::Handle::current(),
+        ))
+    }
+
     /// Configure the watch to load files from JSON.
     ///
     /// If the file is removed, the watch will be updated with the default value.
     /// If the file cannot be parsed, the watch's current value will be unchanged.
     ///
     #[cfg(feature = "json")]
-    pub fn load_json(self) -> Builder<crate::loaders::JsonLoader, Updated, ErrHandler> {
+    pub fn load_json(self) -> Builder<crate::loaders::JsonLoader, Updated, ErrHandler, Dedup> {
         self.load(crate::loaders::JsonLoader)
     }
+
+    /// Configure the watch to load files from JSON into an untyped
+    /// `serde_json::Value`, for configuration whose keys are only known at
+    /// runtime. Settings can be read with `Watch::get_path()`.
+    ///
+    /// If the file is removed, the watch will be updated with `null`.
+    /// If the file cannot be parsed, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "json")]
+    pub fn load_json_value(
+        self,
+    ) -> Builder<crate::loaders::JsonValueLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::JsonValueLoader)
+    }
+
+    /// Configure the watch to load every watched file and deep-merge them,
+    /// with later files taking priority. See `LayeredLoader` for details.
+    ///
+    #[cfg(feature = "json")]
+    pub fn load_layered(
+        self,
+    ) -> Builder<crate::loaders::LayeredLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::LayeredLoader::new())
+    }
+
+    /// Configure the watch to load the first watched file, along with every
+    /// file it lists under an `include` key, deep-merged into a single
+    /// document. Included files are added to the watch automatically. See
+    /// `IncludeResolver` for details.
+    ///
+    #[cfg(feature = "json")]
+    pub fn load_includes(
+        self,
+    ) -> Builder<crate::loaders::IncludeResolver, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::IncludeResolver::new())
+    }
+
+    /// Watch a `conf.d` style directory, and load every file in it. See
+    /// `DirectoryLoader` for details.
+    ///
+    #[cfg(feature = "json")]
+    pub fn load_directory(
+        self,
+        dir: impl AsRef<Path>,
+    ) -> Builder<crate::loaders::DirectoryLoader, Updated, ErrHandler, Dedup> {
+        let loader = crate::loaders::DirectoryLoader::new(&dir);
+        self.watch_file(dir).load(loader)
+    }
+
+    /// Apply a set of `key=value` overrides, such as values parsed from the
+    /// command line, on top of the loaded document every time it is loaded.
+    /// This wraps the current loader, so it must be called after the loader
+    /// is set, and the loader must be able to load a `serde_json::Value`. See
+    /// `OverrideLoader` for details.
+    ///
+    #[cfg(feature = "json")]
+    pub fn with_overrides<I, K, V>(
+        self,
+        overrides: I,
+    ) -> Builder<crate::loaders::OverrideLoader<Load>, Updated, ErrHandler, Dedup>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.map_loader(|loader| crate::loaders::OverrideLoader::new(loader).overrides(overrides))
+    }
+
+    /// Reject documents which contain keys the target type doesn't know about,
+    /// to catch typos in config files. This wraps the current loader, so it
+    /// must be called after the loader is set, and the loader must be able to
+    /// load a `serde_json::Value`. See `StrictLoader` for details.
+    ///
+    #[cfg(feature = "json")]
+    pub fn strict(self) -> Builder<crate::loaders::StrictLoader<Load>, Updated, ErrHandler, Dedup> {
+        self.map_loader(crate::loaders::StrictLoader::new)
+    }
+
+    /// Only load the config files once a manifest listing them and their
+    /// checksums has been written, and every file matches it. The manifest is
+    /// watched instead of the config files, so don't add them with
+    /// `watch_file()`. This wraps the current loader, so it must be called
+    /// after the loader is set. See `ManifestLoader` for details.
+    ///
+    #[cfg(feature = "manifest")]
+    pub fn verify_manifest(
+        mut self,
+        manifest: impl AsRef<Path>,
+    ) -> Builder<crate::loaders::ManifestLoader<Load>, Updated, ErrHandler, Dedup> {
+        self.files.push(manifest.as_ref().to_path_buf());
+        self.map_loader(|loader| crate::loaders::ManifestLoader::new(loader, &manifest))
+    }
+
+    /// Watch a PEM certificate chain and private key, and load them into a
+    /// `TlsPair` or an `Option<Arc<rustls::sign::CertifiedKey>>`. See
+    /// `TlsPairLoader` for details.
+    ///
+    #[cfg(feature = "rustls")]
+    pub fn load_tls_pair(
+        self,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Builder<crate::loaders::TlsPairLoader, Updated, ErrHandler, Dedup> {
+        let loader = crate::loaders::TlsPairLoader::new(&cert, &key);
+        self.watch_file(cert).watch_file(key).load(loader)
+    }
+
+    /// Configure the watch to load every certificate from a PEM file, such as
+    /// a CA bundle, into a `Vec<CertificateDer>`.
+    ///
+    /// If the file is removed, the watch will be updated with an empty list.
+    /// If the file cannot be parsed, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "rustls")]
+    pub fn load_pem_bundle(
+        self,
+    ) -> Builder<crate::loaders::PemBundleLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::PemBundleLoader)
+    }
+
+    /// Configure the watch to load a secret, such as an API token, into a
+    /// `SecretString` which is wiped from memory when it's replaced.
+    ///
+    /// If the file is removed, the watch will be updated with an empty secret.
+    /// If the file cannot be read, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "secret")]
+    pub fn load_secret(self) -> Builder<crate::loaders::SecretLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::SecretLoader)
+    }
+
+    /// Configure the watch to load files from YAML.
+    ///
+    /// If the file is removed, the watch will be updated with the default value.
+    /// If the file cannot be parsed, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "yaml")]
+    pub fn load_yaml(self) -> Builder<crate::loaders::YamlLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::YamlLoader)
+    }
+
+    /// Configure the watch to load files from TOML.
+    ///
+    /// If the file is removed, the watch will be updated with the default value.
+    /// If the file cannot be parsed, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "toml")]
+    pub fn load_toml(self) -> Builder<crate::loaders::TomlLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::TomlLoader)
+    }
+
+    /// Configure the watch to pick a loader based on each file's extension.
+    /// Only formats enabled via feature flags are supported. Use
+    /// `load(AutoFormatLoader::new().with_default(...))` to set a fallback
+    /// format for unrecognized extensions.
+    ///
+    #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "env"))]
+    pub fn load_auto(
+        self,
+    ) -> Builder<crate::loaders::AutoFormatLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::AutoFormatLoader::new())
+    }
+
+    /// Configure the watch to load `.env` style `KEY=VALUE` files.
+    ///
+    /// If the file is removed, the watch will be updated with the default value.
+    /// If the file cannot be parsed, the watch's current value will be unchanged.
+    ///
+    #[cfg(feature = "env")]
+    pub fn load_env_file(
+        self,
+    ) -> Builder<crate::loaders::EnvFileLoader, Updated, ErrHandler, Dedup> {
+        self.load(crate::loaders::EnvFileLoader)
+    }
 }
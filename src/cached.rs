@@ -0,0 +1,198 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use arc_swap::ArcSwap;
+
+use crate::Watch;
+
+/// A handle for reading a watch's value very often from one thread, returned
+/// by `Watch::cached_reader()`.
+///
+/// The reader keeps the last value it loaded, and only loads the value again
+/// once the watch has stored a new one. Checking for a new value is a single
+/// atomic read, which is cheaper than `Watch::value()`. Each thread needs its
+/// own reader, since `value()` takes `&mut self`. Cloning a reader gives it
+/// its own cache.
+///
+/// Like `WatchReader`, this doesn't keep the watch running. Holding a reader
+/// keeps the value it last loaded alive until it is next called.
+pub struct CachedReader<T> {
+    value: Arc<ArcSwap<T>>,
+    version: Arc<AtomicU64>,
+    /// The value we last loaded, and the version it had.
+    cached: Arc<T>,
+    cached_version: u64,
+}
+
+impl<T> CachedReader<T> {
+    /// Get the current value of the watch.
+    pub fn value(&mut self) -> &Arc<T> {
+        // This pairs with the release store after the value is stored, so if
+        // we see the new version we also see the new value. On most platforms
+        // an acquire load costs the same as a relaxed one.
+        let version = self.version.load(Ordering::Acquire);
+        if version != self.cached_version {
+            self.cached = self.value.load_full();
+            self.cached_version = version;
+        }
+        &self.cached
+    }
+}
+
+impl<T> Clone for CachedReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            version: self.version.clone(),
+            cached: self.cached.clone(),
+            cached_version: self.cached_version,
+        }
+    }
+}
+
+impl<T> fmt::Debug for CachedReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedReader")
+            .field("cached_version", &self.cached_version)
+            .finish_non_exhaustive()
+    }
+}
+
+thread_local! {
+    /// Values cached by `Watch::cached_value()` on this thread, keyed by the
+    /// address of the watch's value.
+    static CACHED_VALUES: RefCell<HashMap<usize, CachedValue>> = RefCell::new(HashMap::new());
+}
+
+/// A value cached by `Watch::cached_value()`.
+struct CachedValue {
+    /// The watch's value. Holding this keeps its address from being reused by
+    /// another watch while it's a key in `CACHED_VALUES`.
+    watch: Weak<dyn Any + Send + Sync>,
+    /// The `Arc<T>` which was loaded.
+    value: Box<dyn Any>,
+    loaded: Instant,
+}
+
+impl<T> Watch<T> {
+    /// Get the value of this watch, as cached on the current thread. The
+    /// cached value is loaded again once it's older than `max_age`, so the
+    /// value returned may be up to `max_age` out of date. Clones of a watch
+    /// share the same cache.
+    ///
+    /// This is for code which can accept a bounded delay in seeing a new
+    /// value. `cached_reader()` is usually faster, and never returns an old
+    /// value, but needs a reader for each thread; this needs no setup.
+    pub fn cached_value(&self, max_age: Duration) -> Arc<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let key = Arc::as_ptr(&self.value) as usize;
+        CACHED_VALUES.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let now = Instant::now();
+            if let Some(cached) = cache.get(&key) {
+                if now.duration_since(cached.loaded) < max_age {
+                    if let Some(value) = cached.value.downcast_ref::<Arc<T>>() {
+                        return value.clone();
+                    }
+                }
+            }
+
+            let value = self.value.load_full();
+            // Forget values from watches which have been dropped.
+            cache.retain(|_, cached| cached.watch.strong_count() > 0);
+            let watch: Weak<dyn Any + Send + Sync> = Arc::downgrade(&self.value) as _;
+            cache.insert(
+                key,
+                CachedValue {
+                    watch,
+                    value: Box::new(value.clone()),
+                    loaded: now,
+                },
+            );
+            value
+        })
+    }
+
+    /// Get a handle which caches the value of this watch, for code which
+    /// reads it millions of times a second. See `CachedReader`.
+    pub fn cached_reader(&self) -> CachedReader<T> {
+        // Read the version first, so if a value is stored in between, the
+        // reader loads it again next time.
+        let cached_version = self.version.load(Ordering::Acquire);
+        CachedReader {
+            value: self.value.clone(),
+            version: self.version.clone(),
+            cached: self.value.load_full(),
+            cached_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use arc_swap::ArcSwap;
+
+    use crate::{Context, StdFileSystem, Watch, WatcherOptions};
+
+    #[test]
+    fn should_reload_cached_reader_when_version_changes() {
+        let watch = Watch::create(
+            Vec::<&str>::new(),
+            ArcSwap::from_pointee(1),
+            WatcherOptions::default(),
+            Arc::new(StdFileSystem),
+            |_c: &mut Context| Ok(1),
+            |_c: &mut Context, _v| {},
+            |_c: &mut Context, _err| {},
+        )
+        .unwrap();
+        let mut reader = watch.cached_reader();
+        assert_eq!(**reader.value(), 1);
+
+        // Without a new version, the reader keeps its cached value.
+        watch.value.store(2.into());
+        assert_eq!(**reader.value(), 1);
+
+        watch.version.store(1, Ordering::Release);
+        assert_eq!(**reader.value(), 2);
+    }
+
+    #[test]
+    fn should_reload_cached_value_once_it_is_too_old() {
+        let watch = Watch::create(
+            Vec::<&str>::new(),
+            ArcSwap::from_pointee(1),
+            WatcherOptions::default(),
+            Arc::new(StdFileSystem),
+            |_c: &mut Context| Ok(1),
+            |_c: &mut Context, _v| {},
+            |_c: &mut Context, _err| {},
+        )
+        .unwrap();
+        assert_eq!(*watch.cached_value(Duration::from_secs(60)), 1);
+
+        // Until the cached value is too old, it's returned, even from a clone.
+        watch.value.store(2.into());
+        assert_eq!(*watch.cached_value(Duration::from_secs(60)), 1);
+        assert_eq!(*watch.clone().cached_value(Duration::from_secs(60)), 1);
+
+        assert_eq!(*watch.cached_value(Duration::ZERO), 2);
+        assert_eq!(*watch.cached_value(Duration::from_secs(60)), 2);
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+/// Tells a loader whether the load it is running has been superseded by a
+/// newer change to a watched file, or abandoned for taking longer than
+/// `Builder::load_timeout()`. Returned by `Context::cancel_token()`.
+///
+/// The token can be cloned and moved to other threads, such as ones making a
+/// remote lookup for the loader. Once it is cancelled, whatever the loader
+/// returns is discarded.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    /// The number of changes the watcher has seen, or `None` for a load which
+    /// can't be cancelled.
+    changes: Option<Arc<AtomicU64>>,
+    /// The number of changes when the load started.
+    started_at: u64,
+    /// Set when the load times out and its result will be abandoned.
+    abandoned: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Create a token which is cancelled once `changes` is incremented.
+    pub(crate) fn new(changes: Arc<AtomicU64>) -> Self {
+        let started_at = changes.load(Ordering::SeqCst);
+        Self {
+            changes: Some(changes),
+            started_at,
+            abandoned: Arc::default(),
+        }
+    }
+
+    /// Returns true if a watched file has changed since the load started, or
+    /// the load has been abandoned.
+    pub fn is_cancelled(&self) -> bool {
+        self.abandoned.load(Ordering::SeqCst)
+            || self
+                .changes
+                .as_ref()
+                .is_some_and(|changes| changes.load(Ordering::SeqCst) != self.started_at)
+    }
+
+    /// Cancel the load, because it timed out and its result will be
+    /// abandoned.
+    pub(crate) fn abandon(&self) {
+        self.abandoned.store(true, Ordering::SeqCst);
+    }
+}
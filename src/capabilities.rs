@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, Watcher, WatcherKind};
+
+use crate::WatcherOptions;
+
+/// What this build of the library supports, and anything about the machine
+/// it is running on which could stop changes from being noticed. Returned by
+/// `capabilities()`, for including in bug reports and diagnostics pages when
+/// hot reload doesn't work on a particular machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// The version of this library.
+    pub version: &'static str,
+    /// The file notification API used by `Backend::Native` on this platform:
+    /// `inotify`, `fsevent`, `kqueue`, `windows`, `poll`, or `none`.
+    pub native_backend: &'static str,
+    /// The debounce duration watches use unless it is changed, or `None` if
+    /// watches aren't debounced by default.
+    pub default_debounce: Option<Duration>,
+    /// The optional features this library was compiled with, such as `json`
+    /// or `tokio`.
+    pub features: Vec<&'static str>,
+    /// Problems detected on this machine which could stop changes from being
+    /// noticed, such as low inotify limits. Empty if none were found.
+    pub quirks: Vec<String>,
+}
+
+/// Describe what this build of the library supports on this machine. See
+/// `Capabilities`.
+pub fn capabilities() -> Capabilities {
+    let native_backend = match RecommendedWatcher::kind() {
+        WatcherKind::Inotify => "inotify",
+        WatcherKind::Fsevent => "fsevent",
+        WatcherKind::Kqueue => "kqueue",
+        WatcherKind::ReadDirectoryChangesWatcher => "windows",
+        WatcherKind::PollWatcher => "poll",
+        _ => "none",
+    };
+
+    let mut quirks = vec![];
+    if native_backend == "none" {
+        quirks.push(
+            "there is no native file notification API on this platform; use Backend::Poll"
+                .to_string(),
+        );
+    } else if let Err(err) = RecommendedWatcher::new(|_| {}, notify::Config::default()) {
+        quirks.push(format!(
+            "the native file notification API is unavailable ({err}), so Backend::Native \
+             falls back to polling"
+        ));
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let read = |path: &str| std::fs::read_to_string(path).ok();
+        quirks.extend(linux_quirks(
+            read("/proc/sys/fs/inotify/max_user_watches"),
+            read("/proc/sys/fs/inotify/max_user_instances"),
+            read("/proc/sys/kernel/osrelease"),
+        ));
+    }
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        native_backend,
+        default_debounce: WatcherOptions::default().debounce,
+        features: features(),
+        quirks,
+    }
+}
+
+#[cfg(feature = "json")]
+impl Capabilities {
+    /// Convert to JSON, such as for a diagnostics endpoint.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": self.version,
+            "native_backend": self.native_backend,
+            "default_debounce_ms": self.default_debounce.map(|d| d.as_millis() as u64),
+            "features": self.features,
+            "quirks": self.quirks,
+        })
+    }
+}
+
+/// The optional features this library was compiled with.
+fn features() -> Vec<&'static str> {
+    let features = [
+        ("tokio", cfg!(feature = "tokio")),
+        ("json", cfg!(feature = "json")),
+        ("env", cfg!(feature = "env")),
+        ("yaml", cfg!(feature = "yaml")),
+        ("toml", cfg!(feature = "toml")),
+        ("rustls", cfg!(feature = "rustls")),
+        ("secret", cfg!(feature = "secret")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("crossbeam", cfg!(feature = "crossbeam")),
+        ("manifest", cfg!(feature = "manifest")),
+    ];
+    features
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The inotify limits at or below which watching can start failing once a
+/// program has a few watches. These are the kernel's defaults.
+#[cfg(any(target_os = "linux", test))]
+const LOW_MAX_USER_WATCHES: u64 = 8192;
+#[cfg(any(target_os = "linux", test))]
+const LOW_MAX_USER_INSTANCES: u64 = 128;
+
+/// Find problems from the contents of the inotify limits and kernel release
+/// in `/proc`. Files which couldn't be read are `None`.
+#[cfg(any(target_os = "linux", test))]
+fn linux_quirks(
+    max_user_watches: Option<String>,
+    max_user_instances: Option<String>,
+    osrelease: Option<String>,
+) -> Vec<String> {
+    let parse = |value: Option<String>| value.and_then(|v| v.trim().parse::<u64>().ok());
+    let mut quirks = vec![];
+
+    if let Some(limit) = parse(max_user_watches).filter(|&l| l <= LOW_MAX_USER_WATCHES) {
+        quirks.push(format!(
+            "fs.inotify.max_user_watches is {limit}, so watching many folders may fail \
+             with \"No space left on device\""
+        ));
+    }
+    if let Some(limit) = parse(max_user_instances).filter(|&l| l <= LOW_MAX_USER_INSTANCES) {
+        quirks.push(format!(
+            "fs.inotify.max_user_instances is {limit}, and each watch uses one instance, \
+             so creating many watches may fail with \"Too many open files\""
+        ));
+    }
+    if osrelease.is_some_and(|r| r.to_lowercase().contains("microsoft")) {
+        quirks.push(
+            "running under WSL, where changes made from Windows to files under /mnt are \
+             not reported; use Backend::Poll for those files"
+                .to_string(),
+        );
+    }
+    quirks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capabilities, linux_quirks};
+
+    #[test]
+    fn should_report_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            capabilities.features.contains(&"json"),
+            cfg!(feature = "json")
+        );
+        if cfg!(target_os = "linux") {
+            assert_eq!(capabilities.native_backend, "inotify");
+        }
+    }
+
+    #[test]
+    fn should_detect_linux_quirks() {
+        let quirks = linux_quirks(
+            Some("8192\n".to_string()),
+            Some("1024\n".to_string()),
+            Some("5.15.153.1-microsoft-standard-WSL2\n".to_string()),
+        );
+        assert_eq!(quirks.len(), 2);
+        assert!(quirks[0].contains("max_user_watches is 8192"), "{quirks:?}");
+        assert!(quirks[1].contains("WSL"), "{quirks:?}");
+
+        assert!(linux_quirks(Some("524288".to_string()), None, None).is_empty());
+    }
+}
@@ -1,6 +1,14 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
-use crate::{Error, WeakFileWatcher};
+use crate::{
+    roots::DependencyRoots, CancelToken, Error, FileSystem, Stage, StdFileSystem, Warning,
+    WeakFileWatcher,
+};
 
 /// This enum controls how we update the watched paths. Before we create the FileWatcher,
 /// we can update the paths by adding them to the vector. After we create the FileWatcher,
@@ -10,10 +18,21 @@ enum Paths<'a> {
     Vector(&'a mut Vec<PathBuf>),
 }
 
+/// Called with each fraction and message passed to `Context::report_progress()`.
+pub(crate) type ProgressSink = Arc<dyn Fn(f32, String) + Send + Sync>;
+
 /// Context is used to control the Watch from within the loader.
 pub struct Context<'a> {
     modified_paths: &'a [&'a Path],
     paths: Paths<'a>,
+    warnings: Vec<Warning>,
+    embedded_default: Option<Arc<str>>,
+    fs: Arc<dyn FileSystem>,
+    progress: Option<ProgressSink>,
+    roots: Option<DependencyRoots>,
+    buffer: Vec<u8>,
+    stage_durations: Vec<(Stage, Duration)>,
+    cancel: CancelToken,
 }
 
 impl<'a> Context<'a> {
@@ -24,6 +43,14 @@ impl<'a> Context<'a> {
         Self {
             modified_paths,
             paths: Paths::Vector(watch_paths),
+            warnings: vec![],
+            embedded_default: None,
+            fs: Arc::new(StdFileSystem),
+            progress: None,
+            roots: None,
+            buffer: vec![],
+            stage_durations: vec![],
+            cancel: CancelToken::default(),
         }
     }
 
@@ -31,6 +58,14 @@ impl<'a> Context<'a> {
         Self {
             modified_paths,
             paths: Paths::Watcher(watcher),
+            warnings: vec![],
+            embedded_default: None,
+            fs: Arc::new(StdFileSystem),
+            progress: None,
+            roots: None,
+            buffer: vec![],
+            stage_durations: vec![],
+            cancel: CancelToken::default(),
         }
     }
 
@@ -44,8 +79,104 @@ impl<'a> Context<'a> {
         self.modified_paths.first().copied()
     }
 
+    /// Get the full set of files currently being watched, in the order
+    /// described in `WatchedFiles`.
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        match &self.paths {
+            Paths::Vector(paths) => paths.to_vec(),
+            Paths::Watcher(watcher) => {
+                let guard = watcher.lock().unwrap();
+                match guard.as_ref().and_then(|w| w.upgrade()) {
+                    Some(watcher) => watcher.watched_files().to_vec(),
+                    None => vec![],
+                }
+            }
+        }
+    }
+
+    /// Returns true once the watch is shutting down, because every clone of
+    /// the `Watch` has been dropped or its `WatchScope` has been closed.
+    /// Loaders which do slow work should check this regularly and give up
+    /// early, so shutting down doesn't have to wait for them. An error
+    /// returned once the watch is shutting down isn't passed to the error
+    /// handler.
+    pub fn is_shutting_down(&self) -> bool {
+        let Paths::Watcher(watcher) = &self.paths else {
+            return false;
+        };
+        match watcher.lock().unwrap().as_ref() {
+            // The watcher hasn't been created yet.
+            None => false,
+            Some(weak) => match weak.upgrade() {
+                Some(watcher) => watcher.is_stopped(),
+                None => true,
+            },
+        }
+    }
+
+    /// Returns true if a watched file has changed since this load started, so
+    /// whatever the loader returns will be discarded and the files loaded
+    /// again. Slow loaders, such as ones which make remote lookups, can check
+    /// this regularly and give up early. The initial load isn't cancelled by
+    /// changes. This also returns true once a load has timed out, if it will
+    /// be abandoned. See `Builder::abandon_timed_out_loads()`.
+    pub fn cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Get a token which tells whether this load has been cancelled, which
+    /// can be passed to other threads. See `cancelled()`.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Get the watcher this context updates, if any.
+    pub(crate) fn watcher(&self) -> Option<WeakFileWatcher> {
+        match &self.paths {
+            Paths::Watcher(watcher) => Some(Arc::clone(watcher)),
+            Paths::Vector(_) => None,
+        }
+    }
+
+    pub(crate) fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = cancel;
+    }
+
+    /// Load the files this load was for again, because it was cancelled.
+    /// Does nothing before the watcher is created.
+    pub(crate) fn load_again(&self) {
+        let Paths::Watcher(watcher) = &self.paths else {
+            return;
+        };
+        let Some(watcher) = watcher.lock().unwrap().as_ref().and_then(|w| w.upgrade()) else {
+            return;
+        };
+        watcher.requeue(self.modified_paths);
+    }
+
+    /// Reload every watched file after `delay`, unless the watch has been
+    /// dropped by then. Does nothing before the watcher is created.
+    pub(crate) fn retry_after(&self, delay: Duration) {
+        let Paths::Watcher(watcher) = &self.paths else {
+            return;
+        };
+        let Some(watcher) = watcher.lock().unwrap().clone() else {
+            return;
+        };
+        thread::spawn(move || {
+            thread::sleep(delay);
+            if let Some(watcher) = watcher.upgrade() {
+                watcher.retry();
+            }
+        });
+    }
+
     /// Update the set of files to watch for changes.
+    ///
+    /// Returns `Error::PathNotAllowed` if a file which isn't already being
+    /// watched is outside the folders set with `Builder::dependency_roots()`.
     pub fn update_watched_files(&mut self, files: &[impl AsRef<Path>]) -> Result<(), Error> {
+        self.check_new_dependencies(files)?;
         match &mut self.paths {
             Paths::Vector(paths) => {
                 let mut files: Vec<_> = files.iter().map(|f| f.as_ref().to_path_buf()).collect();
@@ -63,4 +194,168 @@ impl<'a> Context<'a> {
         }
         Ok(())
     }
+
+    /// Add files to the set of files to watch for changes. Files which are
+    /// already being watched are ignored.
+    ///
+    /// Returns `Error::PathNotAllowed` if a file which isn't already being
+    /// watched is outside the folders set with `Builder::dependency_roots()`.
+    pub fn add_watched_files(&mut self, files: &[impl AsRef<Path>]) -> Result<(), Error> {
+        self.check_new_dependencies(files)?;
+        match &mut self.paths {
+            Paths::Vector(paths) => {
+                for file in files {
+                    let file = file.as_ref();
+                    if !paths.iter().any(|p| p == file) {
+                        paths.push(file.to_path_buf());
+                    }
+                }
+            }
+            Paths::Watcher(watcher) => {
+                let guard = watcher.lock().unwrap();
+                if let Some(watcher) = guard.as_ref().and_then(|w| w.upgrade()) {
+                    watcher.add_files(files)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the loader is allowed to read `path`, such as a file named
+    /// in an include directive. Returns `Error::PathNotAllowed` if dependency
+    /// roots were set with `Builder::dependency_roots()` and `path` is outside
+    /// of them, unless it is already being watched. Loaders which read files
+    /// named in user-controlled config should call this before reading them.
+    pub fn check_dependency(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.check_new_dependencies(&[path])
+    }
+
+    fn check_new_dependencies(&self, files: &[impl AsRef<Path>]) -> Result<(), Error> {
+        let Some(roots) = &self.roots else {
+            return Ok(());
+        };
+        let watched = self.watched_files();
+        for file in files {
+            let file = file.as_ref();
+            if !watched.iter().any(|w| w == file) {
+                roots.check(&*self.fs, file)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_dependency_roots(&mut self, roots: Option<DependencyRoots>) {
+        self.roots = roots;
+    }
+
+    /// Report a non-fatal problem with the value being loaded. If the load
+    /// succeeds, warnings are passed to the watch's warning handler after the
+    /// new value is stored. If it fails, they are discarded.
+    pub fn warn(&mut self, warning: impl Into<Warning>) {
+        self.warnings.push(warning.into());
+    }
+
+    /// Get the default document compiled into the program with
+    /// `Builder::embedded_default()`, if there is one. The built-in loaders
+    /// parse this whenever the watched file doesn't exist.
+    pub fn embedded_default(&self) -> Option<&str> {
+        self.embedded_default.as_deref()
+    }
+
+    pub(crate) fn set_embedded_default(&mut self, embedded_default: Option<Arc<str>>) {
+        self.embedded_default = embedded_default;
+    }
+
+    /// Get the file system the watch reads files from. Loaders should read
+    /// files through this rather than `std::fs`, so they work with the file
+    /// system set with `Builder::file_system()`.
+    pub fn file_system(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    pub(crate) fn set_file_system(&mut self, fs: Arc<dyn FileSystem>) {
+        self.fs = fs;
+    }
+
+    /// Get a scratch buffer for reading files into. The buffer is kept
+    /// between loads, so a loader which reads a large file on every reload
+    /// can reuse the same allocation instead of allocating a new one each
+    /// time. It is empty each time this is called. The built-in loaders
+    /// (except `SecretLoader`) read files into it, so it holds the contents
+    /// of the last file loaded until the next load.
+    pub fn buffer(&mut self) -> &mut Vec<u8> {
+        self.buffer.clear();
+        &mut self.buffer
+    }
+
+    pub(crate) fn set_buffer(&mut self, buffer: Vec<u8>) {
+        self.buffer = buffer;
+    }
+
+    pub(crate) fn take_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// Report how far through a slow load the loader is, such as
+    /// `context.report_progress(0.6, "reading routing table")`. `fraction` is
+    /// clamped to between 0 and 1. Progress is passed to the handler set with
+    /// `Builder::on_progress()`, and can be read with `Watch::progress()`
+    /// until the load finishes. This does nothing outside of a reload, such as
+    /// during `Watch::dry_run()`.
+    pub fn report_progress(&mut self, fraction: f32, message: impl Into<String>) {
+        if let Some(progress) = &self.progress {
+            progress(fraction.clamp(0.0, 1.0), message.into());
+        }
+    }
+
+    pub(crate) fn set_progress_sink(&mut self, progress: Option<ProgressSink>) {
+        self.progress = progress;
+    }
+
+    /// Call `f` with a copy of this context which reports `modified_paths`
+    /// as the modified paths, for loaders which wrap another loader and
+    /// choose which file it should load. Warnings reported to the copy are
+    /// kept.
+    pub(crate) fn with_modified_paths<R>(
+        &mut self,
+        modified_paths: &[&Path],
+        f: impl FnOnce(&mut Context) -> R,
+    ) -> R {
+        let paths = match &mut self.paths {
+            Paths::Watcher(watcher) => Paths::Watcher(watcher),
+            Paths::Vector(paths) => Paths::Vector(paths),
+        };
+        let mut context = Context {
+            modified_paths,
+            paths,
+            warnings: std::mem::take(&mut self.warnings),
+            embedded_default: self.embedded_default.clone(),
+            fs: self.fs.clone(),
+            progress: self.progress.clone(),
+            roots: self.roots.clone(),
+            buffer: std::mem::take(&mut self.buffer),
+            stage_durations: std::mem::take(&mut self.stage_durations),
+            cancel: self.cancel.clone(),
+        };
+        let result = f(&mut context);
+        self.warnings = context.warnings;
+        self.buffer = context.buffer;
+        self.stage_durations = context.stage_durations;
+        result
+    }
+
+    /// Record how long a stage of a `Pipeline` took.
+    pub(crate) fn record_stage(&mut self, stage: Stage, duration: Duration) {
+        self.stage_durations.push((stage, duration));
+    }
+
+    /// Take the stage durations recorded so far.
+    pub(crate) fn take_stage_durations(&mut self) -> Vec<(Stage, Duration)> {
+        std::mem::take(&mut self.stage_durations)
+    }
+
+    /// Take all the warnings reported so far.
+    pub(crate) fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
 }
@@ -0,0 +1,854 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{priority, DebounceMode};
+
+/// Collects paths reported by a file watcher, and delivers them in batches
+/// from a background thread. After the first path arrives, paths are collected
+/// for the debounce duration, and then every path collected is delivered at
+/// once. If there is no debounce duration, each call to `add` is delivered as
+/// its own batch. The debounce duration can be changed at any time, and can
+/// be adaptive (see `Adaptive`). Paths added with `add_delayed()` are
+/// collected into separate batches with their own debounce duration. If there
+/// is a minimum interval, batches which are ready too soon after the last one
+/// wait, and are delivered together once it has passed.
+#[derive(Debug)]
+pub(crate) struct Debouncer {
+    state: Mutex<State>,
+    wakeup: Condvar,
+    /// The delivery thread, until it is joined.
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Batches which are ready to deliver, from when debouncing is disabled.
+    ready: VecDeque<Vec<PathBuf>>,
+    pending: Vec<PathBuf>,
+    /// When the first pending path arrived.
+    first_event: Option<Instant>,
+    debounce: Option<Duration>,
+    /// Chooses the debounce duration, if it is adaptive.
+    adaptive: Option<Adaptive>,
+    mode: DebounceMode,
+    /// With `DebounceMode::Leading`, recently delivered paths, and when
+    /// they can be delivered again.
+    suppressed: Vec<(PathBuf, Instant)>,
+    /// Whether the delivery thread should run at low priority.
+    low_priority: bool,
+    /// While paused, paths are collected but not delivered.
+    paused: bool,
+    /// Deliver the pending paths without waiting for the debounce duration,
+    /// after being resumed.
+    flush: bool,
+    /// Batches of paths with their own debounce duration.
+    delayed: Vec<Delayed>,
+    /// The shortest time between two deliveries.
+    min_interval: Option<Duration>,
+    /// When the last delivered batch caused a reload.
+    last_reload: Option<Instant>,
+    stopped: bool,
+}
+
+/// A batch of paths collected over a debounce duration of their own.
+#[derive(Debug)]
+struct Delayed {
+    debounce: Duration,
+    first_event: Instant,
+    paths: Vec<PathBuf>,
+    /// True if this batch holds paths suppressed in leading mode, to deliver
+    /// when their window ends (see `State::trail()`), rather than paths from
+    /// `add_delayed()`. The two are never merged.
+    trailing: bool,
+}
+
+impl Debouncer {
+    fn new(debounce: Option<Duration>, max_debounce: Option<Duration>, low_priority: bool) -> Self {
+        Debouncer {
+            state: Mutex::new(State {
+                ready: VecDeque::new(),
+                pending: vec![],
+                first_event: None,
+                debounce,
+                adaptive: Adaptive::new(debounce, max_debounce),
+                mode: DebounceMode::default(),
+                suppressed: vec![],
+                low_priority,
+                paused: false,
+                flush: false,
+                delayed: vec![],
+                min_interval: None,
+                last_reload: None,
+                stopped: false,
+            }),
+            wakeup: Condvar::new(),
+            thread: Mutex::new(None),
+        }
+    }
+
+    /// Create a Debouncer which is already stopped. No thread is started, and
+    /// anything queued on it is dropped.
+    pub fn stopped() -> Arc<Self> {
+        let mut debouncer = Self::new(None, None, false);
+        debouncer.state.get_mut().unwrap().stopped = true;
+        Arc::new(debouncer)
+    }
+
+    /// Create a new Debouncer, and start a thread which calls `deliver` with
+    /// each batch of paths and the debounce duration they were collected over.
+    /// `deliver` returns true if the batch caused a reload, which is what the
+    /// minimum interval is measured from.
+    pub fn start<F>(
+        debounce: Option<Duration>,
+        max_debounce: Option<Duration>,
+        low_priority: bool,
+        mut deliver: F,
+    ) -> Result<Arc<Self>, std::io::Error>
+    where
+        F: FnMut(&[PathBuf], Option<Duration>) -> bool + Send + 'static,
+    {
+        let debouncer = Arc::new(Self::new(debounce, max_debounce, low_priority));
+
+        let result = debouncer.clone();
+        let thread = thread::Builder::new()
+            .name("config-file-watch".to_string())
+            .spawn(move || {
+                let mut state = debouncer.state.lock().unwrap();
+                let mut low_priority = false;
+                loop {
+                    if state.stopped {
+                        return;
+                    }
+                    if state.low_priority != low_priority {
+                        low_priority = state.low_priority;
+                        priority::set_low_priority(low_priority);
+                    }
+                    if state.paused {
+                        state = debouncer.wakeup.wait(state).unwrap();
+                        continue;
+                    }
+                    if let Some(wait) = state.throttle(Instant::now()) {
+                        state = debouncer.wakeup.wait_timeout(state, wait).unwrap().0;
+                        continue;
+                    }
+
+                    if let Some(batch) = state.take_ready() {
+                        let started = Instant::now();
+                        drop(state);
+                        let reloaded = deliver(&batch, None);
+                        state = debouncer.state.lock().unwrap();
+                        if reloaded {
+                            state.last_reload = Some(started);
+                        }
+                        continue;
+                    }
+
+                    if let Some(delayed) = state.take_due(Instant::now()) {
+                        let started = Instant::now();
+                        drop(state);
+                        let reloaded = deliver(&delayed.paths, Some(delayed.debounce));
+                        state = debouncer.state.lock().unwrap();
+                        if reloaded {
+                            state.last_reload = Some(started);
+                        }
+                        continue;
+                    }
+                    let next_delayed = state.next_delayed();
+
+                    let Some(first_event) = state.first_event else {
+                        let shrink_at = state.adaptive.as_ref().and_then(Adaptive::shrink_at);
+                        let wake_at = [shrink_at, next_delayed].into_iter().flatten().min();
+                        state = match wake_at {
+                            None => debouncer.wakeup.wait(state).unwrap(),
+                            Some(at) => {
+                                let now = Instant::now();
+                                if now >= at {
+                                    if shrink_at.is_some_and(|shrink_at| now >= shrink_at) {
+                                        if let Some(adaptive) = &mut state.adaptive {
+                                            adaptive.shrink(now);
+                                        }
+                                    }
+                                    continue;
+                                }
+                                debouncer.wakeup.wait_timeout(state, at - now).unwrap().0
+                            }
+                        };
+                        continue;
+                    };
+
+                    let debounce = state.debounce();
+                    let elapsed = first_event.elapsed();
+                    let wait = debounce.unwrap_or(Duration::ZERO);
+                    if elapsed < wait && !state.flush {
+                        let mut timeout = wait - elapsed;
+                        if let Some(at) = next_delayed {
+                            timeout = timeout.min(at.saturating_duration_since(Instant::now()));
+                        }
+                        state = debouncer.wakeup.wait_timeout(state, timeout).unwrap().0;
+                        continue;
+                    }
+
+                    let pending = std::mem::take(&mut state.pending);
+                    state.first_event = None;
+                    state.flush = false;
+                    if let Some(adaptive) = &mut state.adaptive {
+                        adaptive.on_deliver(&pending, Instant::now());
+                    }
+                    let started = Instant::now();
+                    drop(state);
+                    let reloaded = deliver(&pending, debounce);
+                    state = debouncer.state.lock().unwrap();
+                    if reloaded {
+                        state.last_reload = Some(started);
+                    }
+                }
+            })?;
+        *result.thread.lock().unwrap() = Some(thread);
+
+        Ok(result)
+    }
+
+    /// Add paths to the current batch.
+    pub fn add(&self, paths: Vec<PathBuf>) {
+        self.queue(paths, true);
+    }
+
+    /// Add paths which need to be delivered again, such as ones whose load
+    /// was cancelled. With `DebounceMode::Leading`, these are delivered even
+    /// though they were just delivered.
+    pub fn requeue(&self, paths: Vec<PathBuf>) {
+        self.queue(paths, false);
+    }
+
+    /// Add paths to the current batch. If `suppress` is false, paths aren't
+    /// checked against, or added to, the paths suppressed in leading mode.
+    fn queue(&self, paths: Vec<PathBuf>, suppress: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return;
+        }
+        if state.debounce.is_none() && state.first_event.is_none() && !state.paused {
+            state.ready.push_back(paths);
+            self.wakeup.notify_one();
+            return;
+        }
+        if state.mode == DebounceMode::Leading && !state.paused && !suppress {
+            state.ready.push_back(paths);
+            self.wakeup.notify_one();
+            return;
+        }
+        if state.mode == DebounceMode::Leading && !state.paused {
+            let now = Instant::now();
+            state.suppressed.retain(|(_, until)| now < *until);
+            let mut leading = vec![];
+            for path in paths {
+                let until = state.suppressed.iter().find(|(p, _)| *p == path);
+                match until.map(|(_, until)| *until) {
+                    Some(until) => state.trail(path, now, until),
+                    None => leading.push(path),
+                }
+            }
+            let paths = leading;
+            if paths.is_empty() {
+                self.wakeup.notify_one();
+                return;
+            }
+            if let Some(debounce) = state.debounce() {
+                let until = now + debounce;
+                let suppressed = paths.iter().map(|path| (path.clone(), until));
+                state.suppressed.extend(suppressed.collect::<Vec<_>>());
+            }
+            state.ready.push_back(paths);
+            self.wakeup.notify_one();
+            return;
+        }
+        if state.first_event.is_none() {
+            let now = Instant::now();
+            state.first_event = Some(now);
+            if let Some(adaptive) = &mut state.adaptive {
+                adaptive.on_batch_start(&paths, now);
+            }
+        }
+        for path in paths {
+            if !state.pending.contains(&path) {
+                state.pending.push(path);
+            }
+        }
+        self.wakeup.notify_one();
+    }
+
+    /// Add paths to a batch of their own, which is delivered `debounce` after
+    /// the first of them arrives, whatever the main debounce duration is.
+    pub fn add_delayed(&self, paths: Vec<PathBuf>, debounce: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return;
+        }
+        match state
+            .delayed
+            .iter_mut()
+            .find(|d| !d.trailing && d.debounce == debounce)
+        {
+            Some(delayed) => {
+                for path in paths {
+                    if !delayed.paths.contains(&path) {
+                        delayed.paths.push(path);
+                    }
+                }
+            }
+            None => state.delayed.push(Delayed {
+                debounce,
+                first_event: Instant::now(),
+                paths,
+                trailing: false,
+            }),
+        }
+        self.wakeup.notify_one();
+    }
+
+    /// Change the debounce duration, and the longest it can adapt to. This
+    /// applies to the current batch. If the limits haven't changed, an
+    /// adaptive debounce keeps the duration it has chosen.
+    pub fn set_debounce(&self, debounce: Option<Duration>, max_debounce: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        state.debounce = debounce;
+        let adaptive = Adaptive::new(debounce, max_debounce);
+        let unchanged = match (&state.adaptive, &adaptive) {
+            (Some(current), Some(new)) => current.min == new.min && current.max == new.max,
+            _ => false,
+        };
+        if !unchanged {
+            state.adaptive = adaptive;
+        }
+        self.wakeup.notify_one();
+    }
+
+    /// Change when batches are delivered. See `DebounceMode`.
+    pub fn set_mode(&self, mode: DebounceMode) {
+        let mut state = self.state.lock().unwrap();
+        state.mode = mode;
+        state.suppressed.clear();
+    }
+
+    /// Change the shortest time between two deliveries.
+    pub fn set_min_interval(&self, min_interval: Option<Duration>) {
+        self.state.lock().unwrap().min_interval = min_interval;
+        self.wakeup.notify_one();
+    }
+
+    /// Get the debounce duration currently in use.
+    pub fn debounce(&self) -> Option<Duration> {
+        self.state.lock().unwrap().debounce()
+    }
+
+    /// Change the priority the background thread runs at.
+    pub fn set_low_priority(&self, low_priority: bool) {
+        self.state.lock().unwrap().low_priority = low_priority;
+        self.wakeup.notify_one();
+    }
+
+    /// Stop delivering paths until `resume()` is called. Paths which arrive in
+    /// the meantime are collected into a single batch.
+    pub fn pause(&self) {
+        self.state.lock().unwrap().paused = true;
+    }
+
+    /// Start delivering paths again after `pause()`. Everything collected
+    /// while paused is delivered straight away, as one batch.
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !std::mem::replace(&mut state.paused, false) {
+            return;
+        }
+        // Fold any batches which were ready before the pause into the batch
+        // collected since.
+        let mut paths: Vec<PathBuf> = state.ready.drain(..).flatten().collect();
+        let delayed: Vec<PathBuf> = state.delayed.drain(..).flat_map(|d| d.paths).collect();
+        for path in delayed
+            .into_iter()
+            .chain(std::mem::take(&mut state.pending))
+        {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        if !paths.is_empty() {
+            state.pending = paths;
+            state.first_event.get_or_insert_with(Instant::now);
+            state.flush = true;
+        }
+        self.wakeup.notify_one();
+    }
+
+    /// Returns true if `pause()` has been called without `resume()`.
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    /// Stop the background thread. Any pending paths are discarded.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stopped = true;
+        state.ready.clear();
+        state.pending.clear();
+        state.delayed.clear();
+        self.wakeup.notify_one();
+    }
+
+    /// Wait for the delivery thread to exit, after `stop()`. If this is called
+    /// from the delivery thread itself, it is left to exit on its own.
+    pub fn join(&self) {
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl State {
+    /// If there's a batch ready to deliver, but the minimum interval since
+    /// the last reload hasn't passed, how much longer to wait.
+    fn throttle(&self, now: Instant) -> Option<Duration> {
+        let next = self.last_reload? + self.min_interval?;
+        if now >= next {
+            return None;
+        }
+        let pending_due = self.first_event.is_some_and(|first_event| {
+            self.flush || now >= first_event + self.debounce().unwrap_or(Duration::ZERO)
+        });
+        let ready = !self.ready.is_empty()
+            || self.next_delayed().is_some_and(|at| now >= at)
+            || pending_due;
+        ready.then(|| next - now)
+    }
+
+    /// Take the next batch which is ready to deliver. With a minimum
+    /// interval, batches which built up while waiting are delivered as one.
+    fn take_ready(&mut self) -> Option<Vec<PathBuf>> {
+        let mut batch = self.ready.pop_front()?;
+        if self.min_interval.is_some() {
+            for path in self.ready.drain(..).flatten() {
+                if !batch.contains(&path) {
+                    batch.push(path);
+                }
+            }
+        }
+        Some(batch)
+    }
+
+    /// Deliver a suppressed path once more when its leading mode window ends
+    /// at `until`, so the last write to it isn't missed.
+    fn trail(&mut self, path: PathBuf, now: Instant, until: Instant) {
+        let window = self
+            .delayed
+            .iter_mut()
+            .find(|delayed| delayed.trailing && delayed.first_event + delayed.debounce == until);
+        match window {
+            Some(delayed) => {
+                if !delayed.paths.contains(&path) {
+                    delayed.paths.push(path);
+                }
+            }
+            None => self.delayed.push(Delayed {
+                debounce: until - now,
+                first_event: now,
+                paths: vec![path],
+                trailing: true,
+            }),
+        }
+    }
+
+    /// Remove a delayed batch whose debounce duration has passed, if any.
+    fn take_due(&mut self, now: Instant) -> Option<Delayed> {
+        let index = self
+            .delayed
+            .iter()
+            .position(|delayed| now >= delayed.first_event + delayed.debounce)?;
+        Some(self.delayed.remove(index))
+    }
+
+    /// When the next delayed batch is due.
+    fn next_delayed(&self) -> Option<Instant> {
+        self.delayed
+            .iter()
+            .map(|delayed| delayed.first_event + delayed.debounce)
+            .min()
+    }
+
+    /// The debounce duration to collect the current batch over.
+    fn debounce(&self) -> Option<Duration> {
+        match &self.adaptive {
+            Some(adaptive) => Some(adaptive.current),
+            None => self.debounce,
+        }
+    }
+}
+
+/// The number of debounce periods without any changes before an adaptive
+/// debounce shrinks.
+const QUIET_PERIODS: u32 = 4;
+
+/// Chooses the debounce duration when it is adaptive. It starts at the
+/// minimum, and doubles (up to the maximum) whenever a burst of writes to the
+/// same file is split across two batches. Once there have been no changes for
+/// a while, it halves again until it is back to the minimum.
+#[derive(Debug)]
+struct Adaptive {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+    /// The paths in the most recently delivered batch.
+    last_batch: Vec<PathBuf>,
+    /// When the most recent batch was delivered.
+    last_delivered: Option<Instant>,
+    /// When the last batch was delivered, or the debounce last shrank.
+    quiet_since: Option<Instant>,
+}
+
+impl Adaptive {
+    /// Returns `None` unless there is a debounce duration which can grow.
+    fn new(debounce: Option<Duration>, max_debounce: Option<Duration>) -> Option<Self> {
+        match (debounce, max_debounce) {
+            (Some(min), Some(max)) if max > min => Some(Self {
+                min,
+                max,
+                current: min,
+                last_batch: vec![],
+                last_delivered: None,
+                quiet_since: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Called with the first paths of a new batch. If one of them was in the
+    /// previous batch, and the previous batch was delivered less than the
+    /// current debounce ago, a burst of writes was split in two.
+    fn on_batch_start(&mut self, paths: &[PathBuf], now: Instant) {
+        let Some(last_delivered) = self.last_delivered else {
+            return;
+        };
+        let split = now.saturating_duration_since(last_delivered) < self.current
+            && paths.iter().any(|path| self.last_batch.contains(path));
+        if split {
+            self.current = (self.current * 2).min(self.max);
+        }
+    }
+
+    /// Called when a batch is delivered.
+    fn on_deliver(&mut self, paths: &[PathBuf], now: Instant) {
+        self.last_batch = paths.to_vec();
+        self.last_delivered = Some(now);
+        self.quiet_since = Some(now);
+    }
+
+    /// When the debounce should shrink if nothing else changes, or `None` if
+    /// it is already at the minimum.
+    fn shrink_at(&self) -> Option<Instant> {
+        if self.current <= self.min {
+            return None;
+        }
+        Some(self.quiet_since? + self.current * QUIET_PERIODS)
+    }
+
+    /// Halve the debounce, after a quiet period.
+    fn shrink(&mut self, now: Instant) {
+        self.current = (self.current / 2).max(self.min);
+        self.quiet_since = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use super::{Adaptive, Debouncer};
+    use crate::DebounceMode;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn should_only_adapt_with_a_range() {
+        assert!(Adaptive::new(None, Some(ms(500))).is_none());
+        assert!(Adaptive::new(Some(ms(100)), None).is_none());
+        assert!(Adaptive::new(Some(ms(100)), Some(ms(100))).is_none());
+    }
+
+    #[test]
+    fn should_grow_during_bursts_and_shrink_afterward() {
+        let file = vec![PathBuf::from("config.json")];
+        let other = vec![PathBuf::from("other.json")];
+        let mut adaptive = Adaptive::new(Some(ms(100)), Some(ms(300))).unwrap();
+        let start = Instant::now();
+
+        // A write to a different file right after a batch isn't a burst.
+        adaptive.on_deliver(&file, start);
+        adaptive.on_batch_start(&other, start + ms(10));
+        assert_eq!(adaptive.current, ms(100));
+        assert_eq!(adaptive.shrink_at(), None);
+
+        // Writes to the same file which keep arriving just after each batch
+        // grow the debounce, up to the maximum.
+        adaptive.on_batch_start(&file, start + ms(50));
+        assert_eq!(adaptive.current, ms(200));
+        adaptive.on_deliver(&file, start + ms(250));
+        adaptive.on_batch_start(&file, start + ms(300));
+        assert_eq!(adaptive.current, ms(300));
+        adaptive.on_deliver(&file, start + ms(600));
+
+        // A write long after the last batch isn't part of the burst.
+        adaptive.on_batch_start(&file, start + ms(1000));
+        assert_eq!(adaptive.current, ms(300));
+
+        // Once things are quiet, it shrinks back to the minimum.
+        let at = adaptive.shrink_at().unwrap();
+        assert_eq!(at, start + ms(600) + ms(1200));
+        adaptive.shrink(at);
+        assert_eq!(adaptive.current, ms(150));
+        adaptive.shrink(adaptive.shrink_at().unwrap());
+        assert_eq!(adaptive.current, ms(100));
+        assert_eq!(adaptive.shrink_at(), None);
+    }
+
+    #[test]
+    fn should_deliver_everything_collected_while_paused_on_resume() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(None, None, false, move |paths, _| {
+            tx.send(paths.to_vec()).unwrap();
+            true
+        })
+        .unwrap();
+
+        debouncer.pause();
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        debouncer.add(vec![PathBuf::from("b.json"), PathBuf::from("a.json")]);
+        assert!(rx.recv_timeout(ms(100)).is_err());
+
+        debouncer.resume();
+        assert_eq!(
+            rx.recv_timeout(ms(1000)).unwrap(),
+            vec![PathBuf::from("a.json"), PathBuf::from("b.json")]
+        );
+        assert!(rx.recv_timeout(ms(100)).is_err());
+
+        // Once resumed, paths are delivered as they arrive again.
+        debouncer.add(vec![PathBuf::from("c.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(1000)).unwrap(),
+            vec![PathBuf::from("c.json")]
+        );
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_deliver_delayed_paths_after_their_own_debounce() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(Some(ms(50)), None, false, move |paths, debounce| {
+            tx.send((paths.to_vec(), debounce)).unwrap();
+            true
+        })
+        .unwrap();
+
+        let start = Instant::now();
+        debouncer.add_delayed(vec![PathBuf::from("metrics.json")], ms(400));
+        debouncer.add(vec![PathBuf::from("config.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(1000)).unwrap(),
+            (vec![PathBuf::from("config.json")], Some(ms(50)))
+        );
+        assert!(start.elapsed() < ms(400));
+
+        debouncer.add_delayed(vec![PathBuf::from("metrics.json")], ms(400));
+        assert_eq!(
+            rx.recv_timeout(ms(1000)).unwrap(),
+            (vec![PathBuf::from("metrics.json")], Some(ms(400)))
+        );
+        assert!(start.elapsed() >= ms(400));
+        assert!(rx.recv_timeout(ms(100)).is_err());
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_deliver_the_first_path_straight_away_in_leading_mode() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(Some(ms(300)), None, false, move |paths, _| {
+            tx.send(paths.to_vec()).unwrap();
+            true
+        })
+        .unwrap();
+        debouncer.set_mode(DebounceMode::Leading);
+
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap(),
+            vec![PathBuf::from("a.json")]
+        );
+
+        // Further events for the same path within the debounce duration are
+        // held until it ends, but other paths aren't held up.
+        debouncer.add(vec![PathBuf::from("a.json"), PathBuf::from("b.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap(),
+            vec![PathBuf::from("b.json")]
+        );
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        assert!(rx.recv_timeout(ms(100)).is_err());
+        assert_eq!(
+            rx.recv_timeout(ms(400)).unwrap(),
+            vec![PathBuf::from("a.json")]
+        );
+        assert!(rx.recv_timeout(ms(100)).is_err());
+
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap(),
+            vec![PathBuf::from("a.json")]
+        );
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_not_merge_trailing_paths_with_delayed_paths_in_leading_mode() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(Some(ms(300)), None, false, move |paths, debounce| {
+            tx.send((paths.to_vec(), debounce)).unwrap();
+            true
+        })
+        .unwrap();
+        debouncer.set_mode(DebounceMode::Leading);
+
+        let start = Instant::now();
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap().0,
+            vec![PathBuf::from("a.json")]
+        );
+
+        // A path with its own debounce, the same as the main one, and a
+        // suppressed path whose window ends first.
+        std::thread::sleep(ms(100));
+        debouncer.add_delayed(vec![PathBuf::from("b.json")], ms(300));
+        debouncer.add(vec![PathBuf::from("a.json")]);
+
+        let (paths, _) = rx.recv_timeout(ms(1000)).unwrap();
+        assert_eq!(paths, vec![PathBuf::from("a.json")]);
+        assert!(start.elapsed() < ms(400));
+        assert_eq!(
+            rx.recv_timeout(ms(1000)).unwrap(),
+            (vec![PathBuf::from("b.json")], Some(ms(300)))
+        );
+        assert!(start.elapsed() >= ms(400));
+        assert!(rx.recv_timeout(ms(100)).is_err());
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_deliver_requeued_paths_straight_away_in_leading_mode() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(Some(ms(300)), None, false, move |paths, _| {
+            tx.send(paths.to_vec()).unwrap();
+            true
+        })
+        .unwrap();
+        debouncer.set_mode(DebounceMode::Leading);
+
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap(),
+            vec![PathBuf::from("a.json")]
+        );
+
+        // A path whose load was cancelled isn't suppressed.
+        debouncer.requeue(vec![PathBuf::from("a.json")]);
+        assert_eq!(
+            rx.recv_timeout(ms(100)).unwrap(),
+            vec![PathBuf::from("a.json")]
+        );
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_wait_the_minimum_interval_between_batches() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(None, None, false, move |paths, _| {
+            tx.send((paths.to_vec(), Instant::now())).unwrap();
+            true
+        })
+        .unwrap();
+        debouncer.set_min_interval(Some(ms(300)));
+
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        let (paths, first) = rx.recv_timeout(ms(1000)).unwrap();
+        assert_eq!(paths, vec![PathBuf::from("a.json")]);
+
+        // Everything which arrives during the interval is delivered at once
+        // when it ends.
+        debouncer.add(vec![PathBuf::from("b.json")]);
+        debouncer.add(vec![PathBuf::from("a.json"), PathBuf::from("c.json")]);
+        let (paths, second) = rx.recv_timeout(ms(1000)).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("b.json"),
+                PathBuf::from("a.json"),
+                PathBuf::from("c.json")
+            ]
+        );
+        assert!(second - first >= ms(300));
+        assert!(rx.recv_timeout(ms(100)).is_err());
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_measure_the_minimum_interval_from_the_last_reload() {
+        let (tx, rx) = mpsc::channel();
+        let debouncer = Debouncer::start(None, None, false, move |paths, _| {
+            tx.send((paths.to_vec(), Instant::now())).unwrap();
+            // Only batches with a watched file cause a reload.
+            paths.contains(&PathBuf::from("a.json"))
+        })
+        .unwrap();
+        debouncer.set_min_interval(Some(ms(300)));
+
+        // A batch which didn't reload anything doesn't hold up the next one.
+        debouncer.add(vec![PathBuf::from(".a.json.swp")]);
+        let (_, swap) = rx.recv_timeout(ms(1000)).unwrap();
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        let (paths, first) = rx.recv_timeout(ms(1000)).unwrap();
+        assert_eq!(paths, vec![PathBuf::from("a.json")]);
+        assert!(first - swap < ms(300));
+
+        // But one which did reload does.
+        debouncer.add(vec![PathBuf::from("a.json")]);
+        let (_, second) = rx.recv_timeout(ms(1000)).unwrap();
+        assert!(second - first >= ms(300));
+        debouncer.stop();
+    }
+
+    #[test]
+    fn should_wait_for_the_thread_to_exit_when_joined() {
+        let (tx, rx) = mpsc::channel::<Vec<PathBuf>>();
+        let debouncer = Debouncer::start(None, None, false, move |paths, _| {
+            tx.send(paths.to_vec()).unwrap();
+            true
+        })
+        .unwrap();
+        debouncer.stop();
+        debouncer.join();
+        // The thread has exited, and dropped `deliver` with it.
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    }
+}
@@ -0,0 +1,35 @@
+/// Decides whether a newly loaded value is the same as the watch's current
+/// one, so the watch can keep the current value instead of storing the new
+/// one and calling its handlers. This is the last type parameter of
+/// `Builder`, and is set with `Builder::dedup_values()`.
+pub trait DedupPolicy<T> {
+    /// Returns a function which returns true if the new value (its second
+    /// argument) is the same as the current one (its first), or `None` if
+    /// every value should be stored.
+    fn comparer(self) -> Option<Comparer<T>>;
+}
+
+/// Compares the current value with a newly loaded one. See `DedupPolicy`.
+pub type Comparer<T> = Box<dyn Fn(&T, &T) -> bool + Send>;
+
+/// Store every loaded value, even if it's equal to the current one. This is
+/// the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDedup;
+
+impl<T> DedupPolicy<T> for NoDedup {
+    fn comparer(self) -> Option<Comparer<T>> {
+        None
+    }
+}
+
+/// Keep the current value when a newly loaded one is equal to it. See
+/// `Builder::dedup_values()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DedupValues;
+
+impl<T: PartialEq> DedupPolicy<T> for DedupValues {
+    fn comparer(self) -> Option<Comparer<T>> {
+        Some(Box::new(|current, new| current == new))
+    }
+}
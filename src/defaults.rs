@@ -0,0 +1,128 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Error, Watch};
+
+/// A setting whose current value differs from its default, returned by
+/// `Watch::overridden_settings()`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct OverriddenSetting {
+    /// The path to the setting, in the same format as `Error::ParseError`,
+    /// such as `servers[0].port`.
+    pub path: String,
+    /// The default value of the setting, or `null` if it has no default.
+    pub default: Value,
+    /// The current value of the setting, or `null` if it has been removed.
+    pub current: Value,
+}
+
+impl fmt::Display for OverriddenSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} = {} (default {})",
+            self.path, self.current, self.default
+        )
+    }
+}
+
+impl<T> Watch<T>
+where
+    T: Serialize + Default,
+{
+    /// Get `T::default()` as a JSON document, to show which settings a
+    /// program uses when nothing is configured.
+    pub fn defaults_snapshot(&self) -> Result<Value, Error> {
+        to_value(&T::default())
+    }
+
+    /// List every setting in the current value which differs from its
+    /// default, such as for an admin page showing which settings have been
+    /// overridden. Objects are compared key by key, and arrays element by
+    /// element if they are the same length; otherwise the whole array is
+    /// listed as one setting.
+    pub fn overridden_settings(&self) -> Result<Vec<OverriddenSetting>, Error> {
+        let default = to_value(&T::default())?;
+        let current = to_value(&**self.value())?;
+        let mut settings = vec![];
+        diff(String::new(), &default, &current, &mut settings);
+        Ok(settings)
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(|err| Error::LoadError(Box::new(err)))
+}
+
+/// Add every difference between `default` and `current` to `settings`.
+fn diff(path: String, default: &Value, current: &Value, settings: &mut Vec<OverriddenSetting>) {
+    match (default, current) {
+        (Value::Object(default), Value::Object(current)) => {
+            let keys = default
+                .keys()
+                .chain(current.keys().filter(|k| !default.contains_key(*k)));
+            for key in keys {
+                let child = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{path}.{key}"),
+                };
+                diff(
+                    child,
+                    default.get(key).unwrap_or(&Value::Null),
+                    current.get(key).unwrap_or(&Value::Null),
+                    settings,
+                );
+            }
+        }
+        (Value::Array(default), Value::Array(current)) if default.len() == current.len() => {
+            for (index, (default, current)) in default.iter().zip(current).enumerate() {
+                diff(format!("{path}[{index}]"), default, current, settings);
+            }
+        }
+        _ if default != current => settings.push(OverriddenSetting {
+            path: if path.is_empty() {
+                ".".to_string()
+            } else {
+                path
+            },
+            default: default.clone(),
+            current: current.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{diff, OverriddenSetting};
+
+    fn overrides(default: serde_json::Value, current: serde_json::Value) -> Vec<String> {
+        let mut settings: Vec<OverriddenSetting> = vec![];
+        diff(String::new(), &default, &current, &mut settings);
+        settings.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn should_list_overridden_settings() {
+        let default =
+            json!({"host": "localhost", "port": 80, "servers": [{"port": 1}], "tags": []});
+        let current = json!({"host": "localhost", "port": 8080, "servers": [{"port": 2}], "tags": ["a"], "extra": true});
+        assert_eq!(
+            overrides(default, current),
+            vec![
+                "port = 8080 (default 80)",
+                "servers[0].port = 2 (default 1)",
+                "tags = [\"a\"] (default [])",
+                "extra = true (default null)",
+            ]
+        );
+
+        assert_eq!(overrides(json!(1), json!(2)), vec![". = 2 (default 1)"]);
+        assert!(overrides(json!({"a": 1}), json!({"a": 1})).is_empty());
+    }
+}
@@ -1,11 +1,111 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use thiserror::Error;
 
+use crate::Stage;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Error watching files: {0}")]
     WatchError(String),
     #[error("Load error: {0}")]
     LoadError(Box<dyn std::error::Error + Send + Sync>),
+    /// A file was loaded, but the value was rejected by the validator set with
+    /// `Builder::validate()`.
+    #[error("Validation error: {0}")]
+    ValidationError(Box<dyn std::error::Error + Send + Sync>),
+    /// A file was loaded, but couldn't be parsed or didn't match the expected
+    /// type. The built-in loaders report all parse failures this way, and a
+    /// custom loader can do the same by returning a boxed `Error::ParseError`.
+    #[error("Parse error{}: {message}", describe_location(.path, .field_path, .line, .column))]
+    ParseError {
+        /// The file which failed to parse. This is `None` if the value was
+        /// built from several files, such as by `LayeredLoader`.
+        path: Option<PathBuf>,
+        /// The path to the field which was invalid, such as `servers[0].port`,
+        /// or `.` if the problem was with the document as a whole.
+        field_path: String,
+        /// The 1-based line the error was found on, if known.
+        line: Option<usize>,
+        /// The 1-based column the error was found on, if known.
+        column: Option<usize>,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A loader or handler tried to wait for a watch which was busy reloading,
+    /// such as by calling `dry_run()` on its own watch. Waiting could deadlock,
+    /// so the call fails instead.
+    #[error("Can't wait for a watch to finish reloading from within a loader or handler")]
+    WouldDeadlock,
+    /// A loader tried to read or watch a file outside the folders set with
+    /// `Builder::dependency_roots()`.
+    #[error("{} is outside the allowed dependency folders", .0.display())]
+    PathNotAllowed(PathBuf),
+    /// A watched file didn't exist when the watch was built with
+    /// `Builder::require_exists()`.
+    #[error("{} does not exist", .0.display())]
+    FileNotFound(PathBuf),
+    /// A stage of a `Pipeline` failed.
+    #[error("{stage} failed: {source}")]
+    StageError {
+        /// The stage which failed.
+        stage: Stage,
+        /// The error the stage returned.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The loader failed too many times in a row, so changes won't be loaded
+    /// until the cool-down set with `Builder::circuit_breaker()` is over.
+    #[error("Stopped loading for {cooldown:?} after {failures} failures in a row")]
+    CircuitOpen {
+        /// The number of failures in a row.
+        failures: u32,
+        /// How long loading is stopped for.
+        cooldown: Duration,
+    },
+    /// A load took longer than the timeout set with `Builder::load_timeout()`.
+    /// This is reported while the load is still running.
+    #[error("Loading took longer than {0:?}")]
+    LoadTimeout(Duration),
+    /// The `Builder` was given settings which can't be used together, such
+    /// as `lazy_initial_load()` with `fail_on_initial_error()`.
+    #[error("Invalid configuration: {0}")]
+    InvalidConfiguration(String),
+}
+
+impl Error {
+    /// Convert an error returned by a loader into an `Error`. Loaders can
+    /// return an `Error` directly (such as `Error::ParseError`), in which
+    /// case it is unwrapped, otherwise this is an `Error::LoadError`.
+    pub(crate) fn from_load_error(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => *err,
+            Err(err) => Error::LoadError(err),
+        }
+    }
+}
+
+fn describe_location(
+    path: &Option<PathBuf>,
+    field_path: &str,
+    line: &Option<usize>,
+    column: &Option<usize>,
+) -> String {
+    let mut result = String::new();
+    if let Some(path) = path {
+        result.push_str(&format!(" in {}", Path::display(path)));
+    }
+    match (line, column) {
+        (Some(line), Some(column)) => result.push_str(&format!(" at line {line} column {column}")),
+        (Some(line), None) => result.push_str(&format!(" at line {line}")),
+        _ => {}
+    }
+    if field_path != "." {
+        result.push_str(&format!(" in field {field_path}"));
+    }
+    result
 }
 
 impl From<notify::Error> for Error {
@@ -0,0 +1,157 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Expand a leading `~`, and `$VAR`, `${VAR}`, and `%VAR%` environment
+/// variables in `path`. See `WatcherOptions::expand_paths`.
+pub(crate) fn expand_path(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(path) => PathBuf::from(expand(path, |name| env::var(name).ok())),
+        // Paths which aren't valid UTF-8 are left alone.
+        None => path.to_path_buf(),
+    }
+}
+
+/// Expand `pattern`, using `var` to read environment variables. Variables
+/// which aren't set are left unchanged.
+pub(crate) fn expand(pattern: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    let home = || var(if cfg!(windows) { "USERPROFILE" } else { "HOME" });
+
+    let mut result = String::new();
+    let mut rest = pattern;
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            if let Some(home) = home() {
+                result.push_str(&home);
+                rest = after;
+            }
+        }
+    }
+
+    while let Some(start) = rest.find(['$', '%']) {
+        result.push_str(&rest[..start]);
+        let (name, len) = variable_at(&rest[start..]);
+        match name.and_then(&var) {
+            Some(value) => {
+                result.push_str(&value);
+                rest = &rest[start + len..];
+            }
+            None => {
+                result.push_str(&rest[start..start + 1]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replace `${VAR}` in `text`, using `var` to read environment variables.
+/// Unlike `expand()`, `~`, `$VAR` and `%VAR%` are left alone, since they are
+/// likely to appear in a document for other reasons. Variables which aren't
+/// set are left unchanged.
+pub(crate) fn interpolate(text: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let (name, len) = variable_at(&rest[start..]);
+        match name.and_then(&var) {
+            Some(value) => {
+                result.push_str(&value);
+                rest = &rest[start + len..];
+            }
+            None => {
+                result.push_str("${");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parse the variable reference at the start of `s`, which starts with `$` or
+/// `%`. Returns the variable's name and the length of the reference.
+fn variable_at(s: &str) -> (Option<&str>, usize) {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let name_len = |s: &str| s.find(|c| !is_name(c)).unwrap_or(s.len());
+
+    if let Some(after) = s.strip_prefix("${") {
+        return match after.find('}') {
+            Some(end) if end > 0 => (Some(&after[..end]), end + 3),
+            _ => (None, 0),
+        };
+    }
+    if let Some(after) = s.strip_prefix('$') {
+        let len = name_len(after);
+        return match len {
+            0 => (None, 0),
+            len => (Some(&after[..len]), len + 1),
+        };
+    }
+    if let Some(after) = s.strip_prefix('%') {
+        let len = name_len(after);
+        if len > 0 && after[len..].starts_with('%') {
+            return (Some(&after[..len]), len + 2);
+        }
+    }
+    (None, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, interpolate};
+
+    fn var(name: &str) -> Option<String> {
+        match name {
+            "HOME" | "USERPROFILE" => Some("/home/me".to_string()),
+            "APPDATA" => Some("C:\\Users\\me\\AppData".to_string()),
+            "APP" => Some("myapp".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn should_expand_home() {
+        assert_eq!(
+            expand("~/.config/app.json", var),
+            "/home/me/.config/app.json"
+        );
+        assert_eq!(expand("~", var), "/home/me");
+        assert_eq!(expand("~other/app.json", var), "~other/app.json");
+        assert_eq!(expand("/etc/~/app.json", var), "/etc/~/app.json");
+    }
+
+    #[test]
+    fn should_expand_variables() {
+        assert_eq!(expand("$HOME/$APP.json", var), "/home/me/myapp.json");
+        assert_eq!(expand("${APP}_1.json", var), "myapp_1.json");
+        assert_eq!(
+            expand("%APPDATA%\\app.json", var),
+            "C:\\Users\\me\\AppData\\app.json"
+        );
+    }
+
+    #[test]
+    fn should_leave_unknown_variables() {
+        assert_eq!(
+            expand("$MISSING/100%/${}.json", var),
+            "$MISSING/100%/${}.json"
+        );
+        assert_eq!(expand("%MISSING%/a$", var), "%MISSING%/a$");
+    }
+
+    #[test]
+    fn should_only_interpolate_braced_variables() {
+        assert_eq!(
+            interpolate(
+                "{\"home\": \"${HOME}\", \"cost\": \"$APP 100%\", \"x\": \"${NOPE}\"}",
+                var
+            ),
+            "{\"home\": \"/home/me\", \"cost\": \"$APP 100%\", \"x\": \"${NOPE}\"}"
+        );
+        assert_eq!(interpolate("~/${APP}", var), "~/myapp");
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+
+use crate::{reloader::ReloaderGuard, Context, Error, Guard, ReloadOutcome, UpdatedHandler, Watch};
+
+/// A chain of watches, created with `Watch::or()`, whose value comes from the
+/// first watch in the chain which is healthy. A watch is healthy once it has
+/// loaded a value, until a load fails.
+///
+/// If the watch in use fails to load, the value switches to the next healthy
+/// watch in the chain. To avoid flapping between sources, a higher priority
+/// watch which recovers is only switched back to once it has loaded
+/// successfully `recover_after()` times in a row. If none of the watches are
+/// healthy, the value is unchanged.
+///
+/// The fallback keeps its watches running for as long as it is alive.
+pub struct Fallback<T> {
+    sources: Vec<Watch<T>>,
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    value: ArcSwap<T>,
+    /// The value of each source.
+    values: Vec<Arc<ArcSwap<T>>>,
+    state: Mutex<State>,
+    subscribers: Mutex<Vec<Box<dyn UpdatedHandler<T> + Send>>>,
+}
+
+#[derive(Debug)]
+struct State {
+    active: usize,
+    /// Whether each source is healthy.
+    healthy: Vec<bool>,
+    /// The number of successful loads in a row for each source.
+    streak: Vec<u32>,
+    recover_after: u32,
+}
+
+impl State {
+    /// The source which should be in use.
+    fn choose(&self) -> usize {
+        (0..self.healthy.len())
+            .find(|&i| {
+                self.healthy[i] && (i >= self.active || self.streak[i] >= self.recover_after)
+            })
+            .unwrap_or(self.active)
+    }
+}
+
+impl<T> Clone for Fallback<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sources: self.sources.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Fallback<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fallback")
+            .field("state", &self.inner.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Watch<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Create a fallback chain which uses the value of this watch while it is
+    /// healthy, and the value of `other` while it isn't, such as a config
+    /// file written by a config service with a local cached copy as a
+    /// fallback. See `Fallback`.
+    ///
+    /// This will block while a reload of either watch is in progress. If it
+    /// is called from within a loader or handler while one of them is busy,
+    /// it returns `Error::WouldDeadlock` instead.
+    pub fn or(&self, other: &Watch<T>) -> Result<Fallback<T>, Error> {
+        Fallback::new(vec![self.clone(), other.clone()], 1)
+    }
+}
+
+impl<T> Fallback<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn new(sources: Vec<Watch<T>>, recover_after: u32) -> Result<Self, Error> {
+        let values: Vec<_> = sources.iter().map(|s| s.value.clone()).collect();
+        let inner = Arc::new(Inner {
+            value: ArcSwap::new(values[0].load_full()),
+            values,
+            state: Mutex::new(State {
+                active: 0,
+                healthy: vec![false; sources.len()],
+                streak: vec![0; sources.len()],
+                recover_after,
+            }),
+            subscribers: Mutex::new(vec![]),
+        });
+
+        for (index, source) in sources.iter().enumerate() {
+            // Hold the reloader, so the source can't load between checking
+            // its health and adding the listener.
+            let mut reloader = ReloaderGuard::lock(&source.reloader)?;
+            let healthy = is_healthy(source);
+            {
+                let mut state = inner.state.lock().unwrap();
+                state.healthy[index] = healthy;
+                state.streak[index] = if healthy { recover_after } else { 0 };
+            }
+
+            let weak = Arc::downgrade(&inner);
+            reloader.add_outcome_listener(Box::new(move |context, outcome| {
+                let Some(inner) = weak.upgrade() else {
+                    return false;
+                };
+                inner.on_load(context, index, outcome);
+                true
+            }));
+        }
+
+        {
+            let mut state = inner.state.lock().unwrap();
+            state.active = state.choose();
+            inner.value.store(inner.values[state.active].load_full());
+        }
+        Ok(Self { sources, inner })
+    }
+
+    /// Add another watch to the end of the chain, to use when every watch
+    /// before it is unhealthy.
+    pub fn or(&self, other: &Watch<T>) -> Result<Fallback<T>, Error> {
+        let mut sources = self.sources.clone();
+        sources.push(other.clone());
+        let recover_after = self.inner.state.lock().unwrap().recover_after;
+        Fallback::new(sources, recover_after)
+    }
+
+    /// Only switch back to a higher priority watch once it has loaded
+    /// successfully `loads` times in a row. The default is 1, which switches
+    /// back as soon as it recovers.
+    pub fn recover_after(self, loads: u32) -> Self {
+        self.inner.state.lock().unwrap().recover_after = loads.max(1);
+        self
+    }
+}
+
+impl<T> Fallback<T> {
+    /// Produces a temporary borrow of the value of the watch in use.
+    pub fn value(&self) -> Guard<T> {
+        self.inner.value.load()
+    }
+
+    /// The position in the chain of the watch in use, starting from 0.
+    pub fn active(&self) -> usize {
+        self.inner.state.lock().unwrap().active
+    }
+
+    /// Add a handler to call whenever the value changes, either because the
+    /// watch in use was reloaded or because the fallback switched to another
+    /// watch. Handlers are called on the thread of the watch which caused the
+    /// change.
+    pub fn subscribe(&self, handler: impl UpdatedHandler<T> + Send + 'static) {
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Box::new(handler));
+    }
+}
+
+impl<T> Inner<T> {
+    /// Called after the source at `index` tried to load a new value. Only a
+    /// failed load makes a source unhealthy: one which loaded a value equal to
+    /// the current one is still healthy, and a cancelled load says nothing
+    /// either way.
+    fn on_load(&self, context: &mut Context, index: usize, outcome: &ReloadOutcome) {
+        let changed = {
+            let mut state = self.state.lock().unwrap();
+            match outcome {
+                ReloadOutcome::Updated | ReloadOutcome::Unchanged => {
+                    state.healthy[index] = true;
+                    state.streak[index] = state.streak[index].saturating_add(1);
+                }
+                ReloadOutcome::LoadFailed(_) => {
+                    state.healthy[index] = false;
+                    state.streak[index] = 0;
+                }
+                _ => return,
+            }
+
+            let updated = *outcome == ReloadOutcome::Updated;
+            let previous = state.active;
+            state.active = state.choose();
+            if state.active != previous || (updated && index == state.active) {
+                self.value.store(self.values[state.active].load_full());
+                true
+            } else {
+                false
+            }
+        };
+
+        if changed {
+            for subscriber in self.subscribers.lock().unwrap().iter_mut() {
+                subscriber.after_update(context, self.value.load());
+            }
+        }
+    }
+}
+
+/// Returns true if `watch` has loaded a value, and its last load succeeded.
+fn is_healthy<T>(watch: &Watch<T>) -> bool {
+    let failed = watch
+        .last_trace
+        .load()
+        .as_ref()
+        .is_some_and(|trace| matches!(trace.outcome, ReloadOutcome::LoadFailed(_)));
+    watch.snapshot.load().version() > 0 && !failed
+}
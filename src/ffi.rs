@@ -0,0 +1,294 @@
+//! A C API for watching a file from C or C++. See `include/config_file_watch.h`.
+//!
+//! The watch's value is the raw contents of the file, so the caller can parse
+//! it with whatever it already uses. Each value carries a generation number
+//! (see `Snapshot::version()`), so callers can tell when the file has changed.
+//!
+//! These functions are exported from whichever `staticlib` or `cdylib` crate
+//! depends on this crate with the `ffi` feature enabled.
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    io, ptr,
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+
+use crate::{Builder, Context, Snapshot, Watch};
+
+/// Called with the `user_data` passed to `cfw_watch_set_callback()` and the
+/// generation of the new value, whenever the watched file is reloaded.
+pub type CfwCallback = extern "C" fn(user_data: *mut c_void, generation: u64);
+
+/// A watch created with `cfw_watch_new()`.
+pub struct CfwWatch {
+    watch: Watch<Vec<u8>>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// State shared between a `CfwWatch` and its update handler.
+#[derive(Default)]
+struct Shared {
+    callback: Option<(CfwCallback, usize)>,
+    snapshot: Option<Arc<ArcSwap<Snapshot<Vec<u8>>>>>,
+}
+
+/// A copy of a watch's value, returned by `cfw_watch_value()`. The data stays
+/// valid, and never changes, until the buffer is passed to
+/// `cfw_buffer_free()`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CfwBuffer {
+    /// The contents of the file. Not null-terminated.
+    pub data: *const u8,
+    /// The length of `data` in bytes.
+    pub len: usize,
+    /// The generation of this value. 0 until the file is first loaded.
+    pub generation: u64,
+    handle: *mut c_void,
+}
+
+/// Load the contents of the first modified file. A missing file loads as an
+/// empty buffer.
+fn load_bytes(context: &mut Context) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = context.path() else {
+        return Ok(vec![]);
+    };
+    match context.file_system().read(path) {
+        Ok(contents) => Ok(contents),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+/// Store `message` in `error`, if `error` isn't null.
+unsafe fn set_error(error: *mut *mut c_char, message: impl ToString) {
+    if !error.is_null() {
+        let message = message.to_string().replace('\0', " ");
+        *error = CString::new(message).unwrap_or_default().into_raw();
+    }
+}
+
+/// Start watching the file at `path`, a null-terminated UTF-8 string.
+///
+/// Returns null on failure, and if `error` isn't null sets it to a message
+/// which must be freed with `cfw_string_free()`.
+///
+/// # Safety
+///
+/// `path` must be a valid null-terminated string, and `error` must be null or
+/// point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_watch_new(
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut CfwWatch {
+    if path.is_null() {
+        set_error(error, "path is null");
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(err) => {
+            set_error(error, err);
+            return ptr::null_mut();
+        }
+    };
+
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let handler = {
+        let shared = shared.clone();
+        move |_context: &mut Context, _value: crate::Guard<Vec<u8>>| {
+            // Holding the lock while calling back means `cfw_watch_free()`
+            // waits for any callback in progress.
+            let shared = shared.lock().unwrap();
+            if let (Some((callback, user_data)), Some(snapshot)) =
+                (shared.callback, &shared.snapshot)
+            {
+                callback(user_data as *mut c_void, snapshot.load().version());
+            }
+        }
+    };
+
+    let watch = Builder::new()
+        .watch_file(path)
+        .load(load_bytes)
+        .after_update(handler)
+        .build();
+    match watch {
+        Ok(watch) => {
+            shared.lock().unwrap().snapshot = Some(watch.snapshot.clone());
+            Box::into_raw(Box::new(CfwWatch { watch, shared }))
+        }
+        Err(err) => {
+            set_error(error, err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Stop a watch and free it. Waits for any callback in progress to return,
+/// so this must not be called from within the watch's own callback. Buffers
+/// returned by `cfw_watch_value()` remain valid.
+///
+/// # Safety
+///
+/// `watch` must be null or a pointer returned by `cfw_watch_new()` which has
+/// not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_watch_free(watch: *mut CfwWatch) {
+    if watch.is_null() {
+        return;
+    }
+    let watch = Box::from_raw(watch);
+    watch.shared.lock().unwrap().callback = None;
+    drop(watch);
+}
+
+/// Set the function to call after each reload, replacing any previous
+/// callback. Pass a null `callback` to stop being called. The callback runs on
+/// the watch's background thread.
+///
+/// # Safety
+///
+/// `watch` must be a live pointer returned by `cfw_watch_new()`, and
+/// `user_data` must be safe to use from the watch's background thread.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_watch_set_callback(
+    watch: *mut CfwWatch,
+    callback: Option<CfwCallback>,
+    user_data: *mut c_void,
+) {
+    let watch = &*watch;
+    watch.shared.lock().unwrap().callback = callback.map(|callback| (callback, user_data as usize));
+}
+
+/// Get the generation of the watch's current value. This starts at 0 and goes
+/// up by one every time the file is reloaded.
+///
+/// # Safety
+///
+/// `watch` must be a live pointer returned by `cfw_watch_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_watch_generation(watch: *const CfwWatch) -> u64 {
+    (*watch).watch.snapshot().version()
+}
+
+/// Get the watch's current value. The buffer must be freed with
+/// `cfw_buffer_free()`.
+///
+/// # Safety
+///
+/// `watch` must be a live pointer returned by `cfw_watch_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_watch_value(watch: *const CfwWatch) -> CfwBuffer {
+    let snapshot = Box::new((*watch).watch.snapshot());
+    CfwBuffer {
+        data: snapshot.as_ptr(),
+        len: snapshot.len(),
+        generation: snapshot.version(),
+        handle: Box::into_raw(snapshot) as *mut c_void,
+    }
+}
+
+/// Free a buffer returned by `cfw_watch_value()`.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by `cfw_watch_value()`, and must not
+/// already have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_buffer_free(buffer: CfwBuffer) {
+    if !buffer.handle.is_null() {
+        drop(Box::from_raw(buffer.handle as *mut Snapshot<Vec<u8>>));
+    }
+}
+
+/// Free an error message returned by `cfw_watch_new()`.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library, which has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn cfw_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{c_char, c_void, CStr, CString},
+        fs, ptr, slice,
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use super::*;
+
+    extern "C" fn on_update(user_data: *mut c_void, generation: u64) {
+        let sender = unsafe { &*(user_data as *const mpsc::Sender<u64>) };
+        let _ = sender.send(generation);
+    }
+
+    #[test]
+    fn should_watch_a_file_from_c() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "{\"port\": 80}").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut error: *mut c_char = ptr::null_mut();
+            let watch = cfw_watch_new(c_path.as_ptr(), &mut error);
+            assert!(!watch.is_null());
+            assert!(error.is_null());
+            assert_eq!(cfw_watch_generation(watch), 1);
+
+            let buffer = cfw_watch_value(watch);
+            assert_eq!(buffer.generation, 1);
+            assert_eq!(
+                slice::from_raw_parts(buffer.data, buffer.len),
+                b"{\"port\": 80}"
+            );
+
+            let (sender, receiver) = mpsc::channel::<u64>();
+            let user_data = &sender as *const _ as *mut c_void;
+            cfw_watch_set_callback(watch, Some(on_update), user_data);
+
+            fs::write(&path, "{\"port\": 8080}").unwrap();
+            let generation = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(generation > 1);
+            let updated = cfw_watch_value(watch);
+            assert_eq!(
+                slice::from_raw_parts(updated.data, updated.len),
+                b"{\"port\": 8080}"
+            );
+
+            // Old buffers stay valid after a reload, and after the watch is freed.
+            cfw_watch_free(watch);
+            assert_eq!(
+                slice::from_raw_parts(buffer.data, buffer.len),
+                b"{\"port\": 80}"
+            );
+            cfw_buffer_free(buffer);
+            cfw_buffer_free(updated);
+        }
+    }
+
+    #[test]
+    fn should_report_errors() {
+        let path = CString::new("/i/do/not/exist/config.json").unwrap();
+        unsafe {
+            let mut error: *mut c_char = ptr::null_mut();
+            let watch = cfw_watch_new(path.as_ptr(), &mut error);
+            assert!(watch.is_null());
+            assert!(!error.is_null());
+            assert!(!CStr::from_ptr(error).to_bytes().is_empty());
+            cfw_string_free(error);
+        }
+    }
+}
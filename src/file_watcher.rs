@@ -1,39 +1,88 @@
 use std::{
-    collections::HashSet,
-    path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-    time::Duration,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::Hasher,
+    io,
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use arc_swap::ArcSwap;
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
-use notify_debouncer_mini::{DebounceEventResult, Debouncer};
 
-use crate::{Error, Guard};
+use crate::{
+    debouncer::Debouncer, Backend, CancelToken, ChangeDetection, Error, FileSystem,
+    FileSystemWatcher, WatchOptions, WatchedFiles, WatcherOptions,
+};
 
 /// Watches a set of files for changes.  This is essentially a thin wrapper around
-/// `notify::RecommendedWatcher` which takes care of watching parent directories
+/// a `FileSystemWatcher` which takes care of watching parent directories
 /// instead of individual files, so we can be notified when files are created or
 /// deleted.
-#[derive(Debug)]
 pub struct FileWatcher {
-    watcher: Arc<Mutex<InnerWatcher>>,
-    watched_files: Arc<ArcSwap<Vec<PathBuf>>>,
+    /// The underlying watcher. This is `None` once the FileWatcher is stopped.
+    watcher: SharedWatcher,
+    fs: Arc<dyn FileSystem>,
+    options: Mutex<WatcherOptions>,
+    paths: Arc<WatchedPaths>,
+    ignore: Arc<ArcSwap<Vec<IgnorePattern>>>,
+    debouncer: Arc<Debouncer>,
+    /// The callback to call on changes. This is `None` once the FileWatcher is
+    /// stopped. Callbacks are called with this lock held, so taking the lock
+    /// guarantees no callback is in progress.
+    on_change: SharedCallback,
 }
 
+type SharedCallback = Arc<Mutex<Option<Box<dyn FnMut(Result<Changes, Error>) + Send>>>>;
+type SharedWatcher = Arc<Mutex<Option<Box<dyn FileSystemWatcher>>>>;
+pub(crate) type FallbackHandler = Box<dyn FnMut(&Path, &str) + Send>;
+
+/// Maximum number of ignored events to remember.
+const MAX_IGNORED_EVENTS: usize = 100;
+
+/// How long to wait for a watched file which was just removed to be created
+/// again, such as by an editor which deletes a file before writing it.
+const RECREATE_GRACE: Duration = Duration::from_millis(50);
+const RECREATE_POLL: Duration = Duration::from_millis(5);
+
+/// A batch of changes reported by a FileWatcher. This derefs to the list of
+/// watched files which changed.
 #[derive(Debug)]
-enum InnerWatcher {
-    Watcher(RecommendedWatcher),
-    Debouncer(Debouncer<RecommendedWatcher>),
+pub struct Changes<'a> {
+    /// The watched files which changed.
+    pub modified: &'a [&'a Path],
+    /// Every path reported by the underlying watcher in this batch, including
+    /// paths which didn't match a watched file.
+    pub events: &'a [PathBuf],
+    /// The debounce duration the events were collected over.
+    pub debounce: Option<Duration>,
+    /// Cancelled once a watched file changes again.
+    pub cancel: CancelToken,
 }
 
-impl InnerWatcher {
-    /// Get the underlying Watcher instance.
-    fn watcher(&mut self) -> &mut dyn Watcher {
-        match self {
-            InnerWatcher::Watcher(w) => w,
-            InnerWatcher::Debouncer(d) => d.watcher(),
-        }
+impl<'a> Deref for Changes<'a> {
+    type Target = [&'a Path];
+
+    fn deref(&self) -> &Self::Target {
+        self.modified
+    }
+}
+
+impl std::fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher")
+            .field("paths", &self.paths)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Call the callback, unless the FileWatcher has been stopped.
+fn notify(on_change: &SharedCallback, res: Result<Changes, Error>) {
+    if let Some(on_change) = on_change.lock().unwrap().as_mut() {
+        on_change(res);
     }
 }
 
@@ -42,61 +91,122 @@ impl FileWatcher {
     /// call `on_change` whenever a file changes. Files do not have to exist at
     /// the time the FileWatcher is created; we will notify when files are
     /// created or deleted. The parent of the file DOES have to exist, however.
+    #[cfg(test)]
     pub fn create<FilesIter, Callback>(
         files: FilesIter,
         debounce: Option<Duration>,
-        mut on_change: Callback,
+        on_change: Callback,
     ) -> Result<Self, Error>
     where
         FilesIter: IntoIterator,
         FilesIter::Item: AsRef<Path>,
-        Callback: (FnMut(Result<&[&Path], Error>)) + Send + 'static,
+        Callback: (FnMut(Result<Changes, Error>)) + Send + 'static,
     {
-        let watched_files: Arc<ArcSwap<Vec<PathBuf>>> = Arc::new(ArcSwap::from_pointee(vec![]));
-
-        let watcher = {
-            let watched_files = watched_files.clone();
-
-            match debounce {
-                None => InnerWatcher::Watcher(notify::recommended_watcher(
-                    move |res: Result<Event, notify::Error>| match res {
-                        Ok(event) => {
-                            // Ignore any events not for our desired path.
-                            let watched_files = watched_files.load();
-                            let changed = matching_files(&watched_files, event.paths);
-                            if !changed.is_empty() {
-                                on_change(Ok(&changed));
-                            }
-                        }
-                        Err(err) => {
-                            on_change(Err(err.into()));
-                        }
-                    },
-                )?),
-                Some(debounce) => InnerWatcher::Debouncer(notify_debouncer_mini::new_debouncer(
-                    debounce,
-                    move |res: DebounceEventResult| match res {
-                        Ok(events) => {
-                            // Find the set of all files that have changed.
-                            let watched_files = watched_files.load();
-                            let changed_files = events.iter().map(|e| e.path.clone());
-                            let changed = matching_files(&watched_files, changed_files);
-                            if !changed.is_empty() {
-                                on_change(Ok(&changed));
-                            }
-                        }
-                        Err(err) => {
-                            on_change(Err(err.into()));
-                        }
-                    },
-                )?),
-            }
+        let options = WatcherOptions {
+            debounce,
+            ..Default::default()
+        };
+        Self::with_options(
+            files,
+            options,
+            Arc::new(crate::StdFileSystem),
+            false,
+            on_change,
+        )
+    }
+
+    /// Create a new file watcher with the given options. If `paused` is true,
+    /// changes are held until `resume()` is called, so the caller can finish
+    /// setting up before `on_change` is first called.
+    pub fn with_options<FilesIter, Callback>(
+        files: FilesIter,
+        options: WatcherOptions,
+        fs: Arc<dyn FileSystem>,
+        paused: bool,
+        on_change: Callback,
+    ) -> Result<Self, Error>
+    where
+        FilesIter: IntoIterator,
+        FilesIter::Item: AsRef<Path>,
+        Callback: (FnMut(Result<Changes, Error>)) + Send + 'static,
+    {
+        let paths = Arc::new(WatchedPaths::new(fs.clone()));
+        paths
+            .literal
+            .store(options.literal_paths, Ordering::Relaxed);
+        paths.set_change_detection(options.change_detection);
+        let on_change: SharedCallback = Arc::new(Mutex::new(Some(Box::new(on_change))));
+        let ignore = Arc::new(ArcSwap::from_pointee(IgnorePattern::compile(
+            &options.ignore,
+        )?));
+
+        let watcher: SharedWatcher = Arc::default();
+
+        // Called with each batch of paths reported by the watcher.
+        let debouncer = {
+            let paths = paths.clone();
+            let on_change = on_change.clone();
+            let watcher = Arc::downgrade(&watcher);
+            Debouncer::start(
+                options.debounce,
+                options.max_debounce,
+                options.low_priority,
+                move |events, debounce| {
+                    let cancel = CancelToken::new(paths.changes.clone());
+                    let mut changed = paths.matching_files(events);
+                    if changed.is_empty() {
+                        // A file saved by renaming a temporary file over it
+                        // may only be reported as a rename of the temporary
+                        // file.
+                        changed = paths.replaced_files(events);
+                    }
+                    if changed.is_empty() {
+                        paths.ignore(events);
+                        return false;
+                    }
+                    let recreated = paths.wait_for_recreated(&changed);
+                    // A watched symlink may now point somewhere else.
+                    if let Some(watcher) = watcher.upgrade() {
+                        paths.follow_links(&watcher);
+                    }
+                    let changed = paths.really_changed(changed);
+                    paths.recreated.lock().unwrap().extend(recreated);
+                    if changed.is_empty() {
+                        return false;
+                    }
+                    paths.ignored.lock().unwrap().clear();
+                    let changed: Vec<&Path> = changed.iter().map(|p| p.as_ref()).collect();
+                    notify(
+                        &on_change,
+                        Ok(Changes {
+                            modified: &changed,
+                            events,
+                            debounce,
+                            cancel,
+                        }),
+                    );
+                    true
+                },
+            )
+            .map_err(|err| Error::WatchError(err.to_string()))?
         };
 
+        debouncer.set_mode(options.debounce_mode);
+        debouncer.set_min_interval(options.min_reload_interval);
+        if paused {
+            debouncer.pause();
+        }
+        let new_watcher = new_watcher(&*fs, &options, &paths, &debouncer, &ignore, &on_change);
         let result = FileWatcher {
-            watcher: Arc::new(Mutex::new(watcher)),
-            watched_files,
+            watcher,
+            fs,
+            options: Mutex::new(options),
+            paths,
+            ignore,
+            debouncer,
+            on_change,
         };
+        *result.watcher.lock().unwrap() = Some(new_watcher?);
 
         let files: Vec<_> = files
             .into_iter()
@@ -107,12 +217,185 @@ impl FileWatcher {
         Ok(result)
     }
 
-    /// Get the set of files this watcher is watching.
-    pub fn watched_files(&self) -> Guard<Vec<PathBuf>> {
-        self.watched_files.load()
+    /// Create a watcher which is already stopped and watches nothing. No
+    /// filesystem watcher or debouncer thread is started.
+    pub fn stopped(fs: Arc<dyn FileSystem>) -> Self {
+        FileWatcher {
+            watcher: Arc::default(),
+            paths: Arc::new(WatchedPaths::new(fs.clone())),
+            fs,
+            options: Mutex::default(),
+            ignore: Arc::default(),
+            debouncer: Debouncer::stopped(),
+            on_change: Arc::default(),
+        }
+    }
+
+    /// Get the set of files this watcher is watching. This includes any files
+    /// which currently match a watched glob pattern.
+    pub fn watched_files(&self) -> WatchedFiles {
+        WatchedFiles::new(self.paths.watched.load_full())
+    }
+
+    /// Report every watched file as changed, as if they had all been
+    /// modified.
+    pub fn retry(&self) {
+        // Make sure the retry isn't dropped for not changing anything.
+        self.paths.fingerprints.lock().unwrap().clear();
+        self.debouncer.add(self.watched_files().to_vec());
+    }
+
+    /// Report `files` as changed again, because loading their last change was
+    /// cancelled.
+    pub fn requeue(&self, files: &[&Path]) {
+        let mut fingerprints = self.paths.fingerprints.lock().unwrap();
+        for file in files {
+            fingerprints.remove(*file);
+        }
+        drop(fingerprints);
+        self.debouncer
+            .requeue(files.iter().map(|file| file.to_path_buf()).collect());
+    }
+
+    /// Get the paths reported by the underlying watcher since the last change
+    /// was delivered, which didn't match any watched file.
+    pub fn ignored_events(&self) -> Vec<PathBuf> {
+        self.paths.ignored.lock().unwrap().clone()
+    }
+
+    /// Get the current options for this watcher.
+    pub fn options(&self) -> WatcherOptions {
+        self.options.lock().unwrap().clone()
+    }
+
+    /// Get the debounce duration currently in use, which may differ from the
+    /// options if it is adaptive.
+    pub fn debounce(&self) -> Option<Duration> {
+        self.debouncer.debounce()
+    }
+
+    /// Change the options for this watcher. If the backend (or how the polling
+    /// backend compares files, or which events it reports) changes, a new
+    /// underlying watcher is started and watching every folder before the old
+    /// one is stopped, so no changes are missed. If `expand_paths` or
+    /// `base_dir` changes, the watched paths are resolved again, and any which
+    /// now point to a different file are reported as changed.
+    pub fn reconfigure(&self, options: WatcherOptions) -> Result<(), Error> {
+        let ignore = IgnorePattern::compile(&options.ignore)?;
+        let mut current = self.options.lock().unwrap();
+        let mut fallbacks = vec![];
+
+        // If `expand_paths` or `base_dir` changed, the watched paths need to
+        // be resolved again. Compile the globs first, so a bad one is
+        // reported before anything changes.
+        let globs = if options.expand_paths != current.expand_paths
+            || options.base_dir != current.base_dir
+        {
+            let requested = self.paths.spec.lock().unwrap().requested_globs.clone();
+            Some(compile_globs(&requested, &options)?)
+        } else {
+            None
+        };
+
+        let poll_compare_changed = matches!(options.backend, Backend::Poll { .. })
+            && options.poll_compare != current.poll_compare;
+        if options.backend != current.backend
+            || poll_compare_changed
+            || options.metadata_events != current.metadata_events
+            || options.notify_config != current.notify_config
+            || options.poll_network_folders != current.poll_network_folders
+        {
+            // Hold the spec, so the watched folders don't change while the
+            // watcher is replaced.
+            let _spec = self.paths.spec.lock().unwrap();
+            let mut watcher = new_watcher(
+                &*self.fs,
+                &options,
+                &self.paths,
+                &self.debouncer,
+                &self.ignore,
+                &self.on_change,
+            )?;
+            for (folder, watch) in self.paths.folders.lock().unwrap().iter() {
+                watch_folder(&mut *watcher, folder, *watch)?;
+                if let Some(reason) = watcher.fallback(folder) {
+                    fallbacks.push((folder.clone(), reason));
+                }
+            }
+
+            let mut watcher_lock = self.watcher.lock().unwrap();
+            if watcher_lock.is_none() {
+                return Err(Error::WatchError("Watch has been stopped".to_string()));
+            }
+            *watcher_lock = Some(watcher);
+        }
+
+        self.ignore.store(Arc::new(ignore));
+        self.debouncer
+            .set_debounce(options.debounce, options.max_debounce);
+        self.debouncer.set_low_priority(options.low_priority);
+        self.debouncer.set_min_interval(options.min_reload_interval);
+        if options.debounce_mode != current.debounce_mode {
+            self.debouncer.set_mode(options.debounce_mode);
+        }
+        if options.literal_paths != current.literal_paths {
+            self.paths
+                .literal
+                .store(options.literal_paths, Ordering::Relaxed);
+            self.paths.resolve(&self.paths.spec.lock().unwrap());
+        }
+        if options.change_detection != current.change_detection {
+            self.paths.set_change_detection(options.change_detection);
+        }
+        *current = options.clone();
+        drop(current);
+        self.paths.report_fallbacks(fallbacks);
+
+        // Resolve the watched paths again from how they were given, and load
+        // any files which are now somewhere else.
+        if let Some(globs) = globs {
+            let mut moved_files = vec![];
+            self.update(|spec| {
+                let resolved: HashMap<PathBuf, PathBuf> = spec
+                    .requested
+                    .iter()
+                    .map(|(file, requested)| (file.clone(), options.resolve_path(requested)))
+                    .collect();
+                let moved = |file: PathBuf| resolved.get(&file).cloned().unwrap_or(file);
+                let mut files: Vec<PathBuf> = vec![];
+                for old in spec.files.drain(..) {
+                    let file = moved(old.clone());
+                    if !files.contains(&file) {
+                        if file != old {
+                            moved_files.push(file.clone());
+                        }
+                        files.push(file);
+                    }
+                }
+                spec.files = files;
+                spec.options = spec
+                    .options
+                    .drain()
+                    .map(|(file, options)| (moved(file), options))
+                    .collect();
+                spec.requested = spec
+                    .requested
+                    .drain()
+                    .map(|(file, requested)| (moved(file), requested))
+                    .collect();
+                spec.globs = globs;
+            })?;
+            if !moved_files.is_empty() {
+                let moved_files: Vec<&Path> = moved_files.iter().map(|p| p.as_path()).collect();
+                self.requeue(&moved_files);
+            }
+        }
+        Ok(())
     }
 
-    /// Update the set of files this watcher is watching.
+    /// Update the set of files this watcher is watching. Files which are
+    /// already being watched keep their place in the list, and new files are
+    /// added to the end in the order given.
     pub fn update_files<I>(&self, files: I) -> Result<(), Error>
     where
         I: IntoIterator,
@@ -120,69 +403,964 @@ impl FileWatcher {
     {
         let files: Vec<_> = files
             .into_iter()
-            .map(|f| f.as_ref().to_path_buf())
+            .map(|f| (self.resolve(f.as_ref()), f.as_ref().to_path_buf()))
             .collect();
 
-        let old_watched_files = self.watched_files.load();
-        self.watched_files.store(Arc::new(files.clone()));
+        self.update(|paths| {
+            paths
+                .files
+                .retain(|f| files.iter().any(|(file, _)| file == f));
+            for (file, requested) in files {
+                if !paths.files.contains(&file) {
+                    paths.files.push(file.clone());
+                }
+                paths.requested.insert(file, requested);
+            }
+        })
+    }
+
+    /// Add files to the set of files this watcher is watching. Returns the
+    /// files which weren't already being watched.
+    pub fn add_files<I>(&self, files: I) -> Result<Vec<PathBuf>, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let files: Vec<_> = files
+            .into_iter()
+            .map(|f| (self.resolve(f.as_ref()), f.as_ref().to_path_buf()))
+            .collect();
+        let mut added = vec![];
+        self.update(|paths| {
+            for (file, requested) in files {
+                if !paths.files.contains(&file) {
+                    paths.files.push(file.clone());
+                    paths.requested.insert(file.clone(), requested);
+                    added.push(file);
+                }
+            }
+        })?;
+        Ok(added)
+    }
+
+    /// Change how `file` is watched. The file doesn't have to be watched yet,
+    /// and keeps its settings if it's removed and added again.
+    pub fn set_watch_options(&self, file: &Path, options: WatchOptions) -> Result<(), Error> {
+        let resolved = self.resolve(file);
+        self.update(|paths| {
+            paths.options.insert(resolved.clone(), options);
+            paths.requested.insert(resolved, file.to_path_buf());
+        })
+    }
+
+    /// Remove files from the set of files this watcher is watching.
+    pub fn remove_files(&self, files: &[PathBuf]) -> Result<(), Error> {
+        self.update(|paths| paths.files.retain(|f| !files.contains(f)))
+    }
+
+    /// Update the set of glob patterns this watcher is watching. Any file which
+    /// matches one of the patterns will be treated as a watched file.
+    pub fn update_globs<I>(&self, patterns: I) -> Result<(), Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(|p| p.as_ref().to_string())
+            .collect();
+        let globs = compile_globs(&patterns, &self.options.lock().unwrap())?;
+
+        self.update(|paths| {
+            paths.globs = globs;
+            paths.requested_globs = patterns;
+        })
+    }
+
+    /// Expand `path` and make it absolute, as set in the options.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.options.lock().unwrap().resolve_path(path)
+    }
+
+    /// Update the watched paths, and update the underlying watcher to match.
+    fn update(&self, f: impl FnOnce(&mut PathSpec)) -> Result<(), Error> {
+        let fallbacks = {
+            let mut spec_lock = self.paths.spec.lock().unwrap();
+            f(&mut spec_lock);
+            let spec = &mut *spec_lock;
+            spec.requested
+                .retain(|file, _| spec.files.contains(file) || spec.options.contains_key(file));
+            self.paths.refresh(spec);
+            self.paths.take_fingerprints();
 
-        {
-            let old_folders = folders(&old_watched_files);
-            let new_folders = folders(&files);
             let mut watcher_lock = self.watcher.lock().unwrap();
-            let watcher = watcher_lock.watcher();
+            let watcher = match watcher_lock.as_mut() {
+                Some(watcher) => watcher,
+                None => return Err(Error::WatchError("Watch has been stopped".to_string())),
+            };
+            self.paths.watch_folders(spec, &mut **watcher)?
+        };
+        // The handler may use the watch, so it's called without holding any
+        // locks.
+        self.paths.report_fallbacks(fallbacks);
+        Ok(())
+    }
+
+    /// Call `handler` with each watched folder which is polled instead of
+    /// being watched with the backend that was asked for, and why. It's
+    /// called right away for folders which are already polled, and then
+    /// whenever a newly watched folder falls back. See
+    /// `WatcherOptions::poll_network_folders`.
+    pub fn on_fallback(&self, mut handler: FallbackHandler) {
+        let watcher = self.watcher.lock().unwrap();
+        if let Some(watcher) = watcher.as_ref() {
+            for folder in self.paths.folders.lock().unwrap().keys() {
+                if let Some(reason) = watcher.fallback(folder) {
+                    handler(folder, &reason);
+                }
+            }
+        }
+        *self.paths.on_fallback.lock().unwrap() = Some(handler);
+    }
+
+    /// Hold changes until `resume()` is called. See `Debouncer::pause()`.
+    pub fn pause(&self) {
+        self.debouncer.pause();
+    }
+
+    /// Load any changes held since `pause()`, and go back to loading changes
+    /// as they happen.
+    pub fn resume(&self) {
+        self.debouncer.resume();
+    }
+
+    /// Returns true if `pause()` has been called without `resume()`.
+    pub fn is_paused(&self) -> bool {
+        self.debouncer.is_paused()
+    }
+
+    /// Describe why the underlying watcher isn't using the backend it was
+    /// asked for, if it isn't.
+    pub fn degraded(&self) -> Option<String> {
+        self.watcher.lock().unwrap().as_ref()?.degraded()
+    }
+
+    /// Returns true if `stop()` has been called.
+    pub fn is_stopped(&self) -> bool {
+        self.watcher.lock().unwrap().is_none()
+    }
+
+    /// Stop watching for changes. After this returns, the callback passed to
+    /// `create` will not be called again, any call which was in progress has
+    /// finished, and the watcher's threads have exited.
+    ///
+    /// This must not be called from within the callback, or it will deadlock.
+    pub fn stop(&self) {
+        // Dropping the underlying watcher stops new events from being generated.
+        let watcher = self.watcher.lock().unwrap().take();
+        drop(watcher);
+        self.debouncer.stop();
+
+        // Wait for any in-flight callback, and then drop the callback.
+        let on_change = self.on_change.lock().unwrap().take();
+        drop(on_change);
+        self.debouncer.join();
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        // Let the debouncer thread exit.
+        self.debouncer.stop();
+    }
+}
+
+/// Create a new underlying watcher, which sends events to `debouncer`.
+fn new_watcher(
+    fs: &dyn FileSystem,
+    options: &WatcherOptions,
+    paths: &Arc<WatchedPaths>,
+    debouncer: &Arc<Debouncer>,
+    ignore: &Arc<ArcSwap<Vec<IgnorePattern>>>,
+    on_change: &SharedCallback,
+) -> Result<Box<dyn FileSystemWatcher>, Error> {
+    let watched = paths.clone();
+    let debouncer = debouncer.clone();
+    let ignore = ignore.clone();
+    let on_change = on_change.clone();
+    let handler = move |res: Result<Vec<PathBuf>, Error>| match res {
+        Ok(paths) => {
+            let ignore = ignore.load();
+            let mut batch = vec![];
+            // Files with their own debounce duration are collected separately.
+            let mut delayed: Vec<(Duration, Vec<PathBuf>)> = vec![];
+            let mut changed = false;
+            for path in paths {
+                if ignore.iter().any(|pattern| pattern.matches(&path)) {
+                    continue;
+                }
+                let matched = watched.lookup(&path);
+                changed |= matched.is_some();
+                let Some(debounce) = matched.flatten() else {
+                    batch.push(path);
+                    continue;
+                };
+                match delayed.iter_mut().find(|(d, _)| *d == debounce) {
+                    Some((_, paths)) => paths.push(path),
+                    None => delayed.push((debounce, vec![path])),
+                }
+            }
+            if changed {
+                // Cancel the load in progress, if any.
+                watched.changes.fetch_add(1, Ordering::SeqCst);
+            }
+            if !batch.is_empty() {
+                debouncer.add(batch);
+            }
+            for (debounce, paths) in delayed {
+                debouncer.add_delayed(paths, debounce);
+            }
+        }
+        Err(err) => notify(&on_change, Err(err)),
+    };
+
+    fs.watcher(options, Box::new(handler))
+}
+
+/// A pattern for paths which should be ignored.
+#[derive(Debug)]
+struct IgnorePattern {
+    pattern: glob::Pattern,
+    /// If true, match against the full path instead of the file name.
+    full_path: bool,
+}
+
+impl IgnorePattern {
+    fn compile(patterns: &[String]) -> Result<Vec<Self>, Error> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Ok(IgnorePattern {
+                    pattern: glob::Pattern::new(pattern).map_err(|err| {
+                        Error::WatchError(format!("Invalid ignore pattern {pattern:?}: {err}"))
+                    })?,
+                    full_path: pattern.contains('/'),
+                })
+            })
+            .collect()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        if self.full_path {
+            self.pattern.matches_path(path)
+        } else {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| self.pattern.matches(name))
+        }
+    }
+}
+
+/// The set of files and glob patterns being watched.
+#[derive(Debug, Default)]
+struct PathSpec {
+    files: Vec<PathBuf>,
+    globs: Vec<Glob>,
+    /// Settings for individual files, set with `set_watch_options()`.
+    options: HashMap<PathBuf, WatchOptions>,
+    /// The path each file (and each file in `options`) was given as, before
+    /// `expand_paths` and `base_dir` were applied, so it can be resolved
+    /// again if they change.
+    requested: HashMap<PathBuf, PathBuf>,
+    /// The glob patterns as they were given.
+    requested_globs: Vec<String>,
+}
+
+/// How a folder is watched.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Folder {
+    recursive: bool,
+    /// Poll the folder at this interval instead of using the backend.
+    poll: Option<Duration>,
+}
+
+impl Folder {
+    /// Watch this folder the way both `self` and `other` need.
+    fn merge(&mut self, other: Folder) {
+        self.recursive |= other.recursive;
+        self.poll = match (self.poll, other.poll) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl PathSpec {
+    /// Get the set of folders we need to watch to be notified about changes to
+    /// the watched paths. For a file, this is the folder containing the file.
+    /// For a directory, this is the directory itself, so we're notified when
+    /// files in the directory change. For a glob, this is the deepest folder
+    /// that contains every possible match.
+    fn folders(&self, fs: &dyn FileSystem) -> HashMap<PathBuf, Folder> {
+        let mut folders: HashMap<PathBuf, Folder> = HashMap::new();
+
+        for f in &self.files {
+            let options = self.options.get(f).copied().unwrap_or_default();
+            let (folder, recursive) = if is_dir(fs, f) {
+                (Some(f.as_path()), options.recursive)
+            } else {
+                (f.parent(), false)
+            };
+            let watch = Folder {
+                recursive,
+                poll: options.poll,
+            };
+            if let Some(folder) = folder {
+                folders
+                    .entry(folder.to_path_buf())
+                    .or_default()
+                    .merge(watch);
+            }
+            if options.follow_symlinks {
+                if let Some(target_folder) = link_target_folder(fs, f) {
+                    folders.entry(target_folder).or_default().merge(Folder {
+                        recursive: false,
+                        poll: options.poll,
+                    });
+                }
+            }
+        }
+
+        for glob in &self.globs {
+            folders.entry(glob.base.clone()).or_default().merge(Folder {
+                recursive: glob.recursive,
+                poll: None,
+            });
+        }
+
+        folders
+    }
+
+    /// The watched directories which should report changes anywhere below
+    /// them.
+    fn recursive_dirs(&self) -> Vec<PathBuf> {
+        self.options
+            .iter()
+            .filter(|(path, options)| options.recursive && self.files.contains(path))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+/// The watched files, keyed by the path events for them are reported with
+/// once resolved, so events can be matched on the backend's thread without
+/// resolving every watched file each time.
+#[derive(Debug, Default)]
+struct ResolvedFiles(HashMap<PathBuf, ResolvedFile>);
+
+#[derive(Debug)]
+struct ResolvedFile {
+    path: PathBuf,
+    /// The file's own debounce duration, if it has one.
+    debounce: Option<Duration>,
+    /// True if this is a directory which reports changes anywhere below it.
+    recursive: bool,
+}
+
+impl ResolvedFiles {
+    fn new(fs: &dyn FileSystem, spec: &PathSpec, watched: &[PathBuf], literal: bool) -> Self {
+        let files = watched
+            .iter()
+            .filter_map(|file| {
+                let resolved = resolve(fs, file, literal).ok()?;
+                let options = spec.options.get(file);
+                let in_spec = spec.files.contains(file);
+                let resolved_file = ResolvedFile {
+                    path: file.clone(),
+                    debounce: options.and_then(|o| o.debounce).filter(|_| in_spec),
+                    recursive: options.is_some_and(|o| o.recursive) && in_spec,
+                };
+                Some((resolved, resolved_file))
+            })
+            .collect();
+        Self(files)
+    }
+
+    /// Find the watched file a resolved event path is for, matching the same
+    /// way as `matching_files()`.
+    fn find(&self, fs: &dyn FileSystem, event: &Path) -> Option<&ResolvedFile> {
+        if let Some(file) = self.0.get(event) {
+            return Some(file);
+        }
+        let mut ancestors = event.ancestors().skip(1);
+        if let Some(file) = ancestors.next().and_then(|parent| self.0.get(parent)) {
+            if is_dir(fs, &file.path) {
+                return Some(file);
+            }
+        }
+        ancestors
+            .filter_map(|ancestor| self.0.get(ancestor))
+            .find(|file| file.recursive && is_dir(fs, &file.path))
+    }
+}
+
+/// Tracks which paths a FileWatcher is interested in.
+struct WatchedPaths {
+    fs: Arc<dyn FileSystem>,
+    spec: Mutex<PathSpec>,
+    /// The watched files, plus any files which currently match a glob.
+    watched: ArcSwap<Vec<PathBuf>>,
+    /// Watched directories which report changes anywhere below them.
+    recursive_dirs: ArcSwap<Vec<PathBuf>>,
+    /// The watched files, resolved when they change, for matching events on
+    /// the backend's thread.
+    resolved: ArcSwap<ResolvedFiles>,
+    /// Paths reported since the last change which didn't match anything.
+    ignored: Mutex<Vec<PathBuf>>,
+    /// Compare paths as they are, without canonicalizing them. See
+    /// `WatcherOptions::literal_paths`.
+    literal: AtomicBool,
+    change_detection: Mutex<ChangeDetection>,
+    /// The state of each watched file when it was last checked.
+    fingerprints: Mutex<HashMap<PathBuf, Fingerprint>>,
+    /// Files which were removed and created again, whose change has already
+    /// been reported. The event for the create is dropped if the file hasn't
+    /// changed since.
+    recreated: Mutex<HashSet<PathBuf>>,
+    /// The folders being watched by the underlying watcher, and how.
+    folders: Mutex<HashMap<PathBuf, Folder>>,
+    /// Called when a folder is polled instead of watched natively.
+    on_fallback: Mutex<Option<FallbackHandler>>,
+    /// The number of times the backend has reported a change to a watched
+    /// file. A load is cancelled if this goes up while it's running.
+    changes: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for WatchedPaths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchedPaths")
+            .field("spec", &self.spec)
+            .field("watched", &self.watched)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WatchedPaths {
+    fn new(fs: Arc<dyn FileSystem>) -> Self {
+        Self {
+            fs,
+            spec: Mutex::default(),
+            watched: ArcSwap::default(),
+            recursive_dirs: ArcSwap::default(),
+            resolved: ArcSwap::default(),
+            ignored: Mutex::default(),
+            literal: AtomicBool::new(false),
+            change_detection: Mutex::new(ChangeDetection::Always),
+            fingerprints: Mutex::default(),
+            recreated: Mutex::default(),
+            folders: Mutex::default(),
+            on_fallback: Mutex::default(),
+            changes: Arc::default(),
+        }
+    }
+
+    /// Change how files are checked for changes, and record the state of
+    /// every watched file to check against.
+    fn set_change_detection(&self, change_detection: ChangeDetection) {
+        *self.change_detection.lock().unwrap() = change_detection;
+        self.fingerprints.lock().unwrap().clear();
+        self.take_fingerprints();
+    }
+
+    /// Record the state of any watched files which don't have one yet, and
+    /// forget files which are no longer watched.
+    fn take_fingerprints(&self) {
+        let change_detection = *self.change_detection.lock().unwrap();
+        let watched = self.watched.load();
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        fingerprints.retain(|path, _| watched.contains(path));
+        for path in watched.iter() {
+            if !fingerprints.contains_key(path) {
+                if let Some(fingerprint) = Fingerprint::of(&*self.fs, path, change_detection) {
+                    fingerprints.insert(path.clone(), fingerprint);
+                }
+            }
+        }
+    }
+
+    /// Drop the files from `changed` which haven't changed since they were
+    /// last checked, according to `change_detection`.
+    fn really_changed(&self, changed: Vec<PathBuf>) -> Vec<PathBuf> {
+        let change_detection = *self.change_detection.lock().unwrap();
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        let mut recreated = self.recreated.lock().unwrap();
+        changed
+            .into_iter()
+            .filter(|path| {
+                let was_recreated = recreated.remove(path);
+                match Fingerprint::of(&*self.fs, path, change_detection) {
+                    Some(fingerprint) => {
+                        let unchanged =
+                            fingerprints.insert(path.clone(), fingerprint) == Some(fingerprint);
+                        !unchanged
+                            || (change_detection == ChangeDetection::Always && !was_recreated)
+                    }
+                    // If we can't tell, assume it changed.
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Find watched files in the same folder as any of `events` which have
+    /// changed since they were last checked. This catches a file being
+    /// replaced when the watcher only reports the path it was moved from.
+    fn replaced_files(&self, events: &[PathBuf]) -> Vec<PathBuf> {
+        let literal = self.literal.load(Ordering::Relaxed);
+        let folders: Vec<PathBuf> = events
+            .iter()
+            .filter_map(|event| resolve(&*self.fs, event, literal).ok())
+            .filter_map(|event| event.parent().map(Path::to_path_buf))
+            .collect();
+        if folders.is_empty() {
+            return vec![];
+        }
+
+        let change_detection = *self.change_detection.lock().unwrap();
+        let fingerprints = self.fingerprints.lock().unwrap();
+        self.watched
+            .load()
+            .iter()
+            .filter(|file| {
+                let in_folder = resolve(&*self.fs, file, literal)
+                    .ok()
+                    .and_then(|file| {
+                        file.parent()
+                            .map(|parent| folders.contains(&parent.to_path_buf()))
+                    })
+                    .unwrap_or(false);
+                in_folder
+                    && fingerprints.get(*file).is_some_and(|old| {
+                        Fingerprint::of(&*self.fs, file, change_detection)
+                            .is_some_and(|new| new != *old)
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Some tools save a file by removing it and then creating a new one. If
+    /// any of `changed` existed before but is missing now, give it a moment
+    /// to be created again, so the loader doesn't see it missing. Returns the
+    /// files which came back.
+    fn wait_for_recreated(&self, changed: &[PathBuf]) -> Vec<PathBuf> {
+        let missing = |path: &PathBuf| matches!(self.fs.metadata(path), Err(err) if err.kind() == io::ErrorKind::NotFound);
+        let removed: Vec<PathBuf> = {
+            let fingerprints = self.fingerprints.lock().unwrap();
+            changed
+                .iter()
+                .filter(|path| {
+                    !matches!(fingerprints.get(*path), None | Some(Fingerprint::Missing))
+                        && missing(path)
+                })
+                .cloned()
+                .collect()
+        };
+        if removed.is_empty() {
+            return vec![];
+        }
+
+        let deadline = Instant::now() + RECREATE_GRACE;
+        while removed.iter().any(missing) && Instant::now() < deadline {
+            std::thread::sleep(RECREATE_POLL);
+        }
+        removed.into_iter().filter(|path| !missing(path)).collect()
+    }
+
+    /// Update `watcher` to watch the folders needed by `spec`.
+    fn watch_folders(
+        &self,
+        spec: &PathSpec,
+        watcher: &mut dyn FileSystemWatcher,
+    ) -> Result<Vec<(PathBuf, String)>, Error> {
+        let new_folders = spec.folders(&*self.fs);
+        let mut fallbacks = vec![];
+        let mut folders = self.folders.lock().unwrap();
+
+        let mut tracked = self.watched.load().to_vec();
+        tracked.extend(self.recursive_dirs.load().iter().cloned());
+        watcher.track(&tracked);
 
-            // Note that instead of watching the files directly, we watch the
-            // parent folder, so we can be notified if the file is created.
-            let added_folders = new_folders.difference(&old_folders);
-            for folder in added_folders {
-                watcher.watch(folder, RecursiveMode::NonRecursive)?;
+        // Note that instead of watching the files directly, we watch the
+        // parent folder, so we can be notified if the file is created.
+        for (folder, watch) in &new_folders {
+            if folders.get(folder) != Some(watch) {
+                if folders.remove(folder).is_some() {
+                    let _ = watcher.unwatch(folder).ok();
+                }
+                watch_folder(watcher, folder, *watch)?;
+                folders.insert(folder.clone(), *watch);
+                if let Some(reason) = watcher.fallback(folder) {
+                    fallbacks.push((folder.clone(), reason));
+                }
             }
+        }
 
-            let removed_folders = old_folders.difference(&new_folders);
-            for folder in removed_folders {
+        folders.retain(|folder, _| {
+            let keep = new_folders.contains_key(folder);
+            if !keep {
                 let _ = watcher.unwatch(folder).ok();
             }
+            keep
+        });
+
+        Ok(fallbacks)
+    }
+
+    /// Tell the fallback handler about folders which started being polled,
+    /// as returned by `watch_folders()`.
+    fn report_fallbacks(&self, fallbacks: Vec<(PathBuf, String)>) {
+        if fallbacks.is_empty() {
+            return;
+        }
+        if let Some(handler) = self.on_fallback.lock().unwrap().as_mut() {
+            for (folder, reason) in fallbacks {
+                handler(&folder, &reason);
+            }
         }
+    }
 
-        Ok(())
+    /// Watch the folders containing the targets of any watched symlinks, after
+    /// a change which might have re-pointed one.
+    fn follow_links(&self, watcher: &Mutex<Option<Box<dyn FileSystemWatcher>>>) {
+        if self.literal.load(Ordering::Relaxed) {
+            return;
+        }
+        let fallbacks = {
+            let spec = self.spec.lock().unwrap();
+            self.resolve(&spec);
+            // A target which can't be watched is picked up on the next change.
+            watcher
+                .lock()
+                .unwrap()
+                .as_mut()
+                .and_then(|watcher| self.watch_folders(&spec, &mut **watcher).ok())
+        };
+        self.report_fallbacks(fallbacks.unwrap_or_default());
+    }
+
+    /// Recompute the list of watched files from the spec.
+    fn refresh(&self, spec: &PathSpec) {
+        let mut watched = spec.files.clone();
+        for glob in &spec.globs {
+            for path in glob.matches(&*self.fs) {
+                if !watched.contains(&path) {
+                    watched.push(path);
+                }
+            }
+        }
+        self.watched.store(Arc::new(watched));
+        self.recursive_dirs.store(Arc::new(spec.recursive_dirs()));
+        self.resolve(spec);
+    }
+
+    /// Resolve the watched files again, such as after a symlink changes.
+    fn resolve(&self, spec: &PathSpec) {
+        let literal = self.literal.load(Ordering::Relaxed);
+        let resolved = ResolvedFiles::new(&*self.fs, spec, &self.watched.load(), literal);
+        self.resolved.store(Arc::new(resolved));
+    }
+
+    /// Check if `event` is for a watched file. Returns `None` if it isn't, or
+    /// the file's own debounce duration if it is. This resolves `event`, but
+    /// not the watched files, so it's cheap enough to call for every event
+    /// from the backend.
+    fn lookup(&self, event: &Path) -> Option<Option<Duration>> {
+        let resolved = self.resolved.load();
+        if resolved.0.is_empty() {
+            return None;
+        }
+        let event = resolve(&*self.fs, event, self.literal.load(Ordering::Relaxed)).ok()?;
+        resolved.find(&*self.fs, &event).map(|file| file.debounce)
+    }
+
+    /// Remember events which didn't match any watched file.
+    fn ignore(&self, events: &[PathBuf]) {
+        let mut ignored = self.ignored.lock().unwrap();
+        for event in events {
+            if ignored.len() >= MAX_IGNORED_EVENTS {
+                break;
+            }
+            if !ignored.contains(event) {
+                ignored.push(event.clone());
+            }
+        }
+    }
+
+    /// Returns the set of changed files that we are interested in.
+    fn matching_files(&self, changed_files: &[PathBuf]) -> Vec<PathBuf> {
+        let old_watched = self.watched.load_full();
+        let literal = self.literal.load(Ordering::Relaxed);
+
+        // If any of the changes are in a folder covered by a glob, then a file
+        // which matches the glob might have been created or removed.
+        {
+            let spec = self.spec.lock().unwrap();
+            let affects_glob = spec.globs.iter().any(|glob| {
+                changed_files
+                    .iter()
+                    .any(|changed| glob.covers(&*self.fs, changed, literal))
+            });
+            if affects_glob {
+                self.refresh(&spec);
+            }
+        }
+
+        // Match against both the old and new set of files, so we report files
+        // which were just removed as well as files which were just created.
+        let watched = self.watched.load();
+        let mut all_watched = old_watched.to_vec();
+        for file in watched.iter() {
+            if !all_watched.contains(file) {
+                all_watched.push(file.clone());
+            }
+        }
+
+        matching_files(
+            &*self.fs,
+            &all_watched,
+            &self.recursive_dirs.load(),
+            changed_files,
+            literal,
+        )
+        .into_iter()
+        .map(|f| f.to_path_buf())
+        .collect()
     }
 }
 
-/// Get the set of folders containing the given files.
-fn folders(files: &[PathBuf]) -> HashSet<&Path> {
-    files.iter().filter_map(|f| f.parent()).collect()
+/// The state of a watched file, compared before and after an event to see if
+/// the file really changed. See `ChangeDetection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fingerprint {
+    Missing,
+    Metadata { modified: SystemTime, len: u64 },
+    Hash(u64),
+}
+
+impl Fingerprint {
+    /// Get the fingerprint of `path`, or `None` if it can't be compared, such
+    /// as for a folder.
+    fn of(fs: &dyn FileSystem, path: &Path, change_detection: ChangeDetection) -> Option<Self> {
+        let metadata = match fs.metadata(path) {
+            Ok(metadata) if metadata.is_dir => return None,
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Some(Fingerprint::Missing),
+            Err(_) => return None,
+        };
+        match change_detection {
+            // Without a modification time, changes can't be detected reliably.
+            ChangeDetection::Always | ChangeDetection::Metadata => Some(Fingerprint::Metadata {
+                modified: metadata.modified?,
+                len: metadata.len,
+            }),
+            ChangeDetection::Hash => {
+                let contents = fs.read(path).ok()?;
+                let mut hasher = DefaultHasher::new();
+                hasher.write(&contents);
+                Some(Fingerprint::Hash(hasher.finish()))
+            }
+        }
+    }
+}
+
+/// A glob pattern being watched.
+#[derive(Debug, Clone)]
+struct Glob {
+    pattern: glob::Pattern,
+    /// The deepest folder which contains every path that could match.
+    base: PathBuf,
+    /// True if matches could be in subfolders of `base`.
+    recursive: bool,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Result<Self, Error> {
+        let compiled = glob::Pattern::new(pattern)
+            .map_err(|err| Error::WatchError(format!("Invalid glob {pattern:?}: {err}")))?;
+
+        let mut base = PathBuf::new();
+        let mut components = Path::new(pattern).components();
+        for component in components.by_ref() {
+            let part = component.as_os_str().to_string_lossy();
+            if part.contains(['*', '?', '[']) {
+                break;
+            }
+            base.push(component);
+        }
+
+        // If there are any components left after the first wildcard, then the
+        // wildcard is in a folder name and matches could be nested.
+        let recursive = components.next().is_some() || pattern.contains("**");
+        if base.as_os_str().is_empty() {
+            base.push(".");
+        }
+
+        Ok(Glob {
+            pattern: compiled,
+            base,
+            recursive,
+        })
+    }
+
+    /// Get the files which currently match this glob, in sorted order.
+    fn matches(&self, fs: &dyn FileSystem) -> Vec<PathBuf> {
+        let mut matches = vec![];
+        self.find_matches(fs, &self.base, &mut matches);
+        matches.sort();
+        matches
+    }
+
+    fn find_matches(&self, fs: &dyn FileSystem, dir: &Path, matches: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs.read_dir(dir) else {
+            return;
+        };
+        for path in entries {
+            // Patterns without a folder are relative to the current directory,
+            // and shouldn't have a `./` prefix.
+            let path = match path.strip_prefix(".") {
+                Ok(relative) if self.base == Path::new(".") => relative.to_path_buf(),
+                _ => path,
+            };
+            let Ok(metadata) = fs.metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir {
+                if self.recursive {
+                    self.find_matches(fs, &path, matches);
+                }
+            } else if self.pattern.matches_path(&path) {
+                matches.push(path);
+            }
+        }
+    }
+
+    /// Returns true if `path` is somewhere this glob could match.
+    fn covers(&self, fs: &dyn FileSystem, path: &Path, literal: bool) -> bool {
+        let (Ok(base), Some(Ok(parent))) = (
+            resolve(fs, &self.base, literal),
+            path.parent().map(|parent| resolve(fs, parent, literal)),
+        ) else {
+            return false;
+        };
+        if self.recursive {
+            parent.starts_with(base)
+        } else {
+            parent == base
+        }
+    }
 }
 
-/// Returns the set of changed files that match files in `watched_files`.
-fn matching_files<I>(watched_files: &Vec<PathBuf>, changed_files: I) -> Vec<&Path>
+/// Returns the set of changed files that match files in `watched_files`. If a
+/// watched path is a directory, then any change to a file directly inside that
+/// directory will match the directory, as will any change below it if it's in
+/// `recursive_dirs`. If `literal` is true, paths are
+/// compared as they are instead of being canonicalized.
+fn matching_files<'a, I>(
+    fs: &dyn FileSystem,
+    watched_files: &'a [PathBuf],
+    recursive_dirs: &[PathBuf],
+    changed_files: I,
+    literal: bool,
+) -> Vec<&'a Path>
 where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
-    // Collect changes into a HashSet to deduplicate.
-    changed_files
-        .into_iter()
-        .filter_map(|changed_file| {
-            // We need to canonicalize the paths from the event here and from
-            // the list of files to watch, since either could include
-            // a symlink.
-            if let Ok(event_path) = canonicalize(changed_file.as_ref()) {
-                for file in watched_files {
-                    if let Ok(file_path) = canonicalize(file) {
-                        if event_path == file_path {
-                            return Some(file as &Path);
-                        }
-                    }
+    let mut result: Vec<&Path> = vec![];
+
+    for changed_file in changed_files {
+        // Unless paths are compared literally, we need to canonicalize the
+        // paths from the event here and from the list of files to watch,
+        // since either could include a symlink.
+        let Ok(event_path) = resolve(fs, changed_file.as_ref(), literal) else {
+            continue;
+        };
+
+        let matched = watched_files
+            .iter()
+            .find(|file| match resolve(fs, file, literal) {
+                Ok(file_path) => {
+                    event_path == file_path
+                        || ((event_path.parent() == Some(&file_path)
+                            || (recursive_dirs.contains(file)
+                                && event_path.starts_with(&file_path)))
+                            && is_dir(fs, file))
                 }
+                Err(_) => false,
+            });
+
+        // Deduplicate, since multiple changes can map to the same watched path.
+        if let Some(file) = matched {
+            if !result.contains(&file.as_path()) {
+                result.push(file);
             }
-            None
-        })
+        }
+    }
+
+    result
+}
+
+/// Start watching `folder` with `watcher`.
+fn watch_folder(
+    watcher: &mut dyn FileSystemWatcher,
+    folder: &Path,
+    watch: Folder,
+) -> Result<(), Error> {
+    match watch.poll {
+        Some(interval) => watcher.poll(folder, watch.recursive, interval),
+        None => watcher.watch(folder, watch.recursive),
+    }
+}
+
+fn is_dir(fs: &dyn FileSystem, path: &Path) -> bool {
+    fs.metadata(path).is_ok_and(|metadata| metadata.is_dir)
+}
+
+/// If `path` is a symlink to a file in a different folder, get that folder,
+/// so changes to the target can be watched too.
+fn link_target_folder(fs: &dyn FileSystem, path: &Path) -> Option<PathBuf> {
+    if is_dir(fs, path) {
+        return None;
+    }
+    let target = fs.canonicalize(path).ok()?;
+    let target_folder = target.parent()?;
+    let folder = fs.canonicalize(path.parent()?).ok()?;
+    (target_folder != folder).then(|| target_folder.to_path_buf())
+}
+
+/// Compile glob `patterns`, after applying `expand_paths` and `base_dir`.
+fn compile_globs(patterns: &[String], options: &WatcherOptions) -> Result<Vec<Glob>, Error> {
+    patterns
+        .iter()
+        .map(|p| Glob::new(&options.resolve_path(Path::new(p)).to_string_lossy()))
         .collect()
 }
 
-fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
-    match path.canonicalize() {
+/// Get the form of `path` to compare against other paths: canonicalized, or
+/// if `literal` is true, the path as it is without any `.` components.
+fn resolve(fs: &dyn FileSystem, path: &Path, literal: bool) -> std::io::Result<PathBuf> {
+    match literal {
+        true => Ok(path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect()),
+        false => canonicalize(fs, path),
+    }
+}
+
+fn canonicalize(fs: &dyn FileSystem, path: &Path) -> std::io::Result<PathBuf> {
+    match fs.canonicalize(path) {
         Ok(path) => Ok(path),
         Err(_) => {
             // If the file doesn't exist, canonicalize will fail. If the file is
@@ -191,7 +1369,7 @@ fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
             match (path.parent(), path.file_name()) {
                 (Some(parent), Some(file_name)) => {
                     // Canonicalize the parent path, then add in our path
-                    let parent = parent.canonicalize()?;
+                    let parent = fs.canonicalize(parent)?;
                     let path = parent.join(file_name);
                     Ok(parent.join(path))
                 }
@@ -206,7 +1384,79 @@ mod tests {
     use map_macro::hash_set;
 
     use super::*;
-    use std::{fs, sync::mpsc, thread};
+    use crate::StdFileSystem;
+    use std::{collections::HashSet, fs, sync::mpsc, thread};
+
+    #[cfg(unix)]
+    #[test]
+    fn should_match_paths_literally() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.json");
+        let link = dir.path().join("link.json");
+        fs::write(&file, "{}").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        let fs = StdFileSystem;
+        let watched = [link.clone(), file.clone()];
+        assert_eq!(
+            matching_files(&fs, &watched, &[], [&file], false),
+            vec![link.as_path()]
+        );
+        assert_eq!(
+            matching_files(&fs, &watched, &[], [&file], true),
+            vec![file.as_path()]
+        );
+
+        let dotted = dir.path().join(".").join("config.json");
+        assert_eq!(
+            matching_files(&fs, std::slice::from_ref(&file), &[], [&dotted], true),
+            vec![file.as_path()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_follow_symlinks_to_other_folders() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["links", "a", "b"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+        }
+        let link = dir.path().join("links").join("config");
+        let target_a = dir.path().join("a").join("config");
+        let target_b = dir.path().join("b").join("config");
+        fs::write(&target_a, "a").unwrap();
+        fs::write(&target_b, "b").unwrap();
+        std::os::unix::fs::symlink(&target_a, &link).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let _watcher =
+            FileWatcher::create(&[&link], Some(Duration::from_millis(100)), move |res| {
+                let files = res
+                    .unwrap()
+                    .iter()
+                    .map(|f| f.to_path_buf())
+                    .collect::<HashSet<_>>();
+                tx.send(files).unwrap();
+            })
+            .unwrap();
+
+        fs::write(&target_a, "a2").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![link.clone()]);
+
+        // Re-point the link, the way deployment tools switch releases.
+        let new_link = dir.path().join("links").join("config.new");
+        std::os::unix::fs::symlink(&target_b, &new_link).unwrap();
+        fs::rename(&new_link, &link).unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![link.clone()]);
+
+        // Now the new target is watched, and the old one isn't.
+        fs::write(&target_a, "a3").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        fs::write(&target_b, "b2").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![link]);
+    }
 
     #[test]
     fn should_watch_a_file() {
@@ -238,6 +1488,193 @@ mod tests {
         assert_eq!(rx.recv().unwrap(), hash_set![config_file]);
     }
 
+    #[test]
+    fn should_report_a_file_replaced_by_a_rename() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("test");
+        let temp_file = dir.path().join("test.tmp");
+        fs::write(&config_file, "test").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let _watcher = FileWatcher::create(
+            &[&config_file],
+            Some(Duration::from_millis(100)),
+            move |res| {
+                let files = res
+                    .unwrap()
+                    .iter()
+                    .map(|f| f.to_path_buf())
+                    .collect::<HashSet<_>>();
+                tx.send(files).unwrap();
+            },
+        )
+        .unwrap();
+
+        fs::write(&temp_file, "test2").unwrap();
+        fs::rename(&temp_file, &config_file).unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![config_file]);
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
+    #[test]
+    fn should_find_files_replaced_in_the_same_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("test");
+        let temp_file = dir.path().join("test.tmp");
+        fs::write(&config_file, "test").unwrap();
+
+        let paths = WatchedPaths::new(Arc::new(StdFileSystem));
+        paths.watched.store(Arc::new(vec![config_file.clone()]));
+        paths.take_fingerprints();
+
+        // Only the temporary file is reported, as some watchers do.
+        fs::write(&temp_file, "test2").unwrap();
+        assert!(paths
+            .replaced_files(std::slice::from_ref(&temp_file))
+            .is_empty());
+        fs::rename(&temp_file, &config_file).unwrap();
+        assert_eq!(
+            paths.replaced_files(std::slice::from_ref(&temp_file)),
+            vec![config_file.clone()]
+        );
+
+        // A file which is removed and created again is reported once.
+        fs::remove_file(&config_file).unwrap();
+        let writer = {
+            let config_file = config_file.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                fs::write(config_file, "test3").unwrap();
+            })
+        };
+        let recreated = paths.wait_for_recreated(std::slice::from_ref(&config_file));
+        writer.join().unwrap();
+        assert_eq!(recreated, vec![config_file.clone()]);
+        assert_eq!(
+            paths.really_changed(vec![config_file.clone()]),
+            vec![config_file.clone()]
+        );
+        paths.recreated.lock().unwrap().extend(recreated);
+        assert!(paths.really_changed(vec![config_file]).is_empty());
+    }
+
+    #[test]
+    fn should_report_every_event_except_a_repeated_recreate() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("test");
+        fs::write(&config_file, "test").unwrap();
+
+        let paths = WatchedPaths::new(Arc::new(StdFileSystem));
+        paths.watched.store(Arc::new(vec![config_file.clone()]));
+        paths.take_fingerprints();
+        let changed = || paths.really_changed(vec![config_file.clone()]);
+
+        // Events for a file which hasn't changed are still reported.
+        assert_eq!(changed(), vec![config_file.clone()]);
+        assert_eq!(changed(), vec![config_file.clone()]);
+
+        // The create event for a recreated file, which was already reported,
+        // is dropped once.
+        paths.recreated.lock().unwrap().insert(config_file.clone());
+        assert!(changed().is_empty());
+        assert_eq!(changed(), vec![config_file.clone()]);
+
+        // But not if the file changed again since.
+        paths.recreated.lock().unwrap().insert(config_file.clone());
+        fs::write(&config_file, "changed").unwrap();
+        assert_eq!(changed(), vec![config_file.clone()]);
+    }
+
+    #[test]
+    fn should_watch_a_directory_recursively() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("templates");
+        let nested = templates.join("email");
+        let config_file = dir.path().join("config");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(&config_file, "1").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let watcher = FileWatcher::create(
+            &[&templates, &config_file],
+            Some(Duration::from_millis(100)),
+            move |res| {
+                let files = res
+                    .unwrap()
+                    .iter()
+                    .map(|f| f.to_path_buf())
+                    .collect::<HashSet<_>>();
+                tx.send(files).unwrap();
+            },
+        )
+        .unwrap();
+
+        // Without `recursive`, only files directly in the directory count.
+        fs::write(nested.join("welcome.html"), "hi").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+        let options = WatchOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        watcher.set_watch_options(&templates, options).unwrap();
+        fs::write(nested.join("welcome.html"), "hello").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![templates]);
+
+        // Polled files are still reported.
+        let options = WatchOptions {
+            poll: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        watcher.set_watch_options(&config_file, options).unwrap();
+        fs::write(&config_file, "22").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![config_file]);
+    }
+
+    #[test]
+    fn should_debounce_a_file_with_its_own_window() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("config");
+        let metrics_file = dir.path().join("metrics");
+        fs::write(&config_file, "1").unwrap();
+        fs::write(&metrics_file, "1").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let watcher = FileWatcher::create(
+            &[&config_file, &metrics_file],
+            Some(Duration::from_millis(50)),
+            move |res| {
+                let files = res
+                    .unwrap()
+                    .iter()
+                    .map(|f| f.to_path_buf())
+                    .collect::<HashSet<_>>();
+                tx.send(files).unwrap();
+            },
+        )
+        .unwrap();
+        let options = WatchOptions {
+            debounce: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        watcher.set_watch_options(&metrics_file, options).unwrap();
+
+        // The config file isn't held back by the metrics file's window, and
+        // every write to the metrics file is collected into one batch.
+        fs::write(&metrics_file, "2").unwrap();
+        fs::write(&config_file, "2").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![config_file]);
+        fs::write(&metrics_file, "3").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![metrics_file]);
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
     #[test]
     fn should_debounce() {
         let (tx, rx) = mpsc::channel();
@@ -338,6 +1775,62 @@ mod tests {
         assert_eq!(rx.recv().unwrap(), hash_set![config_file_c.clone()]);
     }
 
+    #[test]
+    fn should_watch_a_directory() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+        fs::create_dir(&conf_d).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let _watcher = FileWatcher::create(&[&conf_d], None, move |res| {
+            let files = res
+                .unwrap()
+                .iter()
+                .map(|f| f.to_path_buf())
+                .collect::<HashSet<_>>();
+            tx.send(files).unwrap();
+        })
+        .unwrap();
+
+        // Creating a file in the directory should report the directory.
+        fs::write(conf_d.join("a.json"), "{}").unwrap();
+        assert_eq!(rx.recv().unwrap(), hash_set![conf_d]);
+    }
+
+    #[test]
+    fn should_ignore_matching_paths() {
+        let (tx, rx) = mpsc::channel();
+
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+        fs::create_dir(&conf_d).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let options = WatcherOptions {
+            ignore: vec!["*.swp".to_string()],
+            ..Default::default()
+        };
+        let _watcher = FileWatcher::with_options(
+            &[&conf_d],
+            options,
+            Arc::new(StdFileSystem),
+            false,
+            move |res| {
+                tx.send(res.unwrap().events.to_vec()).unwrap();
+            },
+        )
+        .unwrap();
+
+        fs::write(conf_d.join("a.json.swp"), "{}").unwrap();
+        rx.recv_timeout(Duration::from_millis(300)).unwrap_err();
+
+        fs::write(conf_d.join("a.json"), "{}").unwrap();
+        let events = rx.recv().unwrap();
+        assert!(events.iter().all(|e| e.extension().unwrap() == "json"));
+    }
+
     #[test]
     fn should_not_generate_event_when_adding_file() {
         let (tx, rx) = mpsc::channel();
@@ -366,4 +1859,85 @@ mod tests {
 
         rx.recv_timeout(Duration::from_millis(100)).unwrap_err();
     }
+
+    #[test]
+    fn should_not_start_a_thread_for_a_stopped_watcher() {
+        let watcher = FileWatcher::stopped(Arc::new(StdFileSystem));
+        assert!(watcher.is_stopped());
+        assert!(watcher.watched_files().is_empty());
+        // The debouncer thread would hold its own reference.
+        assert_eq!(Arc::strong_count(&watcher.debouncer), 1);
+    }
+
+    /// Counts how many paths are canonicalized.
+    #[derive(Debug)]
+    struct CountingFileSystem {
+        inner: crate::MemoryFileSystem,
+        canonicalized: std::sync::atomic::AtomicUsize,
+    }
+
+    impl FileSystem for CountingFileSystem {
+        fn open(&self, path: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+            self.inner.open(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<crate::FileMetadata> {
+            self.inner.metadata(path)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.inner.read_dir(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            self.canonicalized.fetch_add(1, Ordering::SeqCst);
+            self.inner.canonicalize(path)
+        }
+
+        fn watcher(
+            &self,
+            options: &WatcherOptions,
+            on_change: crate::ChangeHandler,
+        ) -> Result<Box<dyn FileSystemWatcher>, Error> {
+            self.inner.watcher(options, on_change)
+        }
+    }
+
+    #[test]
+    fn should_not_resolve_every_watched_file_for_each_event() {
+        let memory = crate::MemoryFileSystem::new();
+        let files: Vec<PathBuf> = (0..50)
+            .map(|i| PathBuf::from(format!("/etc/app/{i}.conf")))
+            .collect();
+        for file in &files {
+            memory.write(file, "1");
+        }
+        memory.write("/etc/app/other", "1");
+        let fs = Arc::new(CountingFileSystem {
+            inner: memory,
+            canonicalized: Default::default(),
+        });
+
+        let paths = WatchedPaths::new(fs.clone());
+        let mut spec = paths.spec.lock().unwrap();
+        spec.files = files.clone();
+        spec.options.insert(
+            files[7].clone(),
+            WatchOptions {
+                debounce: Some(Duration::from_millis(300)),
+                ..Default::default()
+            },
+        );
+        paths.refresh(&spec);
+        drop(spec);
+
+        fs.canonicalized.store(0, Ordering::SeqCst);
+        assert_eq!(paths.lookup(&files[3]), Some(None));
+        assert_eq!(
+            paths.lookup(&files[7]),
+            Some(Some(Duration::from_millis(300)))
+        );
+        assert_eq!(paths.lookup(Path::new("/etc/app/other")), None);
+        assert_eq!(fs.canonicalized.load(Ordering::SeqCst), 3);
+    }
 }
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Context, Loader};
+
+/// Wraps another loader, and passes it only the highest priority candidate
+/// file which exists, such as `/etc/app.conf` before `~/.config/app.conf`.
+/// If the chosen file is deleted the next candidate is loaded instead, and if
+/// a higher priority file is created the watch switches back to it. If none
+/// of the candidates exist, the inner loader is given the first candidate, so
+/// it falls back to its default value. See `Builder::watch_first_of()`.
+///
+/// If none of the modified paths are candidates (such as when a loader has
+/// added its own dependencies to the watch), the inner loader is called
+/// unchanged.
+#[derive(Debug)]
+pub struct FirstOfLoader<L> {
+    candidates: Vec<PathBuf>,
+    inner: L,
+}
+
+impl<L> FirstOfLoader<L> {
+    /// Create a new FirstOfLoader which chooses between `candidates`, in
+    /// priority order.
+    pub fn new(candidates: Vec<PathBuf>, inner: L) -> Self {
+        Self { candidates, inner }
+    }
+}
+
+impl<T, L> Loader<T> for FirstOfLoader<L>
+where
+    L: Loader<T>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let is_candidate = |path: &&Path| self.candidates.iter().any(|c| c == path);
+        if !context.modified_paths().iter().any(is_candidate) {
+            return self.inner.load(context);
+        }
+
+        let fs = context.file_system();
+        let chosen = self
+            .candidates
+            .iter()
+            .find(|path| fs.metadata(path).is_ok_and(|metadata| !metadata.is_dir))
+            .unwrap_or(&self.candidates[0]);
+        let inner = &mut self.inner;
+        context.with_modified_paths(&[chosen.as_path()], |context| inner.load(context))
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
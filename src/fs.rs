@@ -0,0 +1,758 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use notify::{
+    event::{EventKind, MetadataKind, ModifyKind},
+    Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+use crate::{netfs, poller::Poller, Backend, Error, WatcherOptions};
+
+/// Information about a file or directory, returned by
+/// `FileSystem::metadata()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// True if this is a directory.
+    pub is_dir: bool,
+    /// The size of the file in bytes.
+    pub len: u64,
+    /// When the file was last modified, if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// Called by a `FileSystemWatcher` with the paths which changed, or with an
+/// error if watching failed.
+pub type ChangeHandler = Box<dyn FnMut(Result<Vec<PathBuf>, Error>) + Send>;
+
+/// The file system a watch reads and watches files on. By default this is
+/// `StdFileSystem`, which uses `std::fs` and the `notify` crate, but a virtual
+/// file system (such as `MemoryFileSystem`) can be set with
+/// `Builder::file_system()` for tests, or on platforms `notify` doesn't
+/// support.
+///
+/// The built-in loaders read files through `Context::file_system()`, and
+/// custom loaders should do the same.
+pub trait FileSystem: Send + Sync + 'static {
+    /// Open a file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Get information about a file or directory.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// List the paths of the entries in a directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Return the canonical form of a path, which is used to match changed
+    /// paths against watched files. This should fail if the path doesn't
+    /// exist. The default returns the path unchanged if it exists, which is
+    /// fine for file systems without symlinks.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.metadata(path)?;
+        Ok(path.to_path_buf())
+    }
+
+    /// Create a watcher which calls `on_change` whenever something changes in
+    /// one of the folders it is watching. `options` is the watch's current
+    /// options; a virtual file system may ignore the backend.
+    fn watcher(
+        &self,
+        options: &WatcherOptions,
+        on_change: ChangeHandler,
+    ) -> Result<Box<dyn FileSystemWatcher>, Error>;
+
+    /// Read the entire contents of a file.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut contents = vec![];
+        self.open(path)?.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Read the entire contents of a file as a string.
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Watches folders on a `FileSystem`, returned by `FileSystem::watcher()`.
+/// Dropping the watcher stops it.
+pub trait FileSystemWatcher: Send {
+    /// Start watching a folder. If `recursive` is true, changes in
+    /// subfolders should be reported too.
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error>;
+
+    /// Stop watching a folder.
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error>;
+
+    /// If the watcher couldn't use the backend it was asked for and is using
+    /// a less capable one instead, describe why. Reported in
+    /// `WatchStats::degraded`.
+    fn degraded(&self) -> Option<String> {
+        None
+    }
+
+    /// Start polling a folder every `interval` instead of watching it with
+    /// the backend, for `WatchOptions::poll`. By default this watches the
+    /// folder as usual.
+    fn poll(&mut self, path: &Path, recursive: bool, _interval: Duration) -> Result<(), Error> {
+        self.watch(path, recursive)
+    }
+
+    /// If `path` is being polled instead of watched with the backend that
+    /// was asked for, such as because it's on a network file system,
+    /// describe why.
+    fn fallback(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Set the files the watch is interested in, each time they change.
+    /// Watchers which poll can use this to only read the contents of these
+    /// files, rather than every file in the watched folders.
+    fn track(&mut self, _files: &[PathBuf]) {}
+}
+
+/// The real file system, using `std::fs` and the backend picked in
+/// `WatcherOptions`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileMetadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn watcher(
+        &self,
+        options: &WatcherOptions,
+        on_change: ChangeHandler,
+    ) -> Result<Box<dyn FileSystemWatcher>, Error> {
+        // If the native watcher can't be created we need the handler again for
+        // the poller, so share it between them.
+        let on_change = Arc::new(Mutex::new(on_change));
+        let metadata_events = options.metadata_events;
+        let handler = move || {
+            let on_change = on_change.clone();
+            move |res: Result<Event, notify::Error>| {
+                let mut on_change = on_change.lock().unwrap();
+                match res {
+                    Ok(event) if !metadata_events && is_metadata_only(&event) => {}
+                    Ok(event) => on_change(Ok(event.paths)),
+                    Err(err) => on_change(Err(err.into())),
+                }
+            }
+        };
+        // The files pollers compare the contents of, set by `track()`.
+        let tracked: Arc<ArcSwap<Vec<PathBuf>>> = Arc::default();
+        let poll = {
+            let handler = handler.clone();
+            let poll_compare = options.poll_compare;
+            let tracked = tracked.clone();
+            move |interval| {
+                Poller::start(handler(), interval, poll_compare, Some(tracked.clone()))
+                    .map_err(|err| Error::WatchError(err.to_string()))
+            }
+        };
+
+        let watcher: Box<dyn FileSystemWatcher> = match (options.backend, options.notify_config) {
+            (Backend::Native, config) => {
+                let native = match config {
+                    None => notify::recommended_watcher(handler()),
+                    Some(config) => RecommendedWatcher::new(handler(), config),
+                };
+                native_or_poll(native, &poll)?
+            }
+            (Backend::Poll { interval }, None) => Box::new(NotifyWatcher(poll(interval)?)),
+            (Backend::Poll { interval }, Some(config)) => Box::new(NotifyWatcher(
+                PollWatcher::new(handler(), config.with_poll_interval(interval))?,
+            )),
+            (Backend::NotifyPoll { interval }, config) => {
+                Box::new(NotifyWatcher(PollWatcher::new(
+                    handler(),
+                    config.unwrap_or_default().with_poll_interval(interval),
+                )?))
+            }
+        };
+        // Network file systems only need polling if the native backend is
+        // actually in use.
+        let detect = (options.backend == Backend::Native
+            && options.poll_network_folders
+            && watcher.degraded().is_none())
+        .then_some(netfs::network_fs_type as fn(&Path) -> Option<&'static str>);
+        Ok(Box::new(FolderPolling::new(watcher, poll, detect, tracked)))
+    }
+}
+
+/// Returns true if `event` only changed a file's metadata, such as its
+/// permissions or owner, and not its contents. A new modification time isn't
+/// counted, since notify's `PollWatcher` reports writes that way.
+fn is_metadata_only(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Modify(ModifyKind::Metadata(kind)) if kind != MetadataKind::WriteTime
+    )
+}
+
+/// Use the native watcher if it could be created. Some sandboxes and
+/// platforms don't allow file notifications at all, so if it couldn't, poll
+/// instead: this is slower, but better than not watching.
+fn native_or_poll<W: Watcher + Send + 'static>(
+    native: Result<W, notify::Error>,
+    poll: impl FnOnce(Duration) -> Result<Poller, Error>,
+) -> Result<Box<dyn FileSystemWatcher>, Error> {
+    Ok(match native {
+        Ok(watcher) => Box::new(NotifyWatcher(watcher)),
+        Err(err) => Box::new(Degraded {
+            watcher: NotifyWatcher(poll(FALLBACK_POLL_INTERVAL)?),
+            reason: format!(
+                "the native file notification API is unavailable ({err}), \
+                 so files are being polled every {FALLBACK_POLL_INTERVAL:?}"
+            ),
+        }),
+    })
+}
+
+/// How often to poll for changes when `Backend::Native` is unavailable.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls some folders instead of watching them with the backend: those with
+/// `WatchOptions::poll` set, and, if `detect` is set, those on network file
+/// systems, where the native backend only reports changes made by this
+/// machine.
+struct FolderPolling {
+    watcher: Box<dyn FileSystemWatcher>,
+    /// A poller for each interval in use, started when first needed.
+    pollers: HashMap<Duration, NotifyWatcher<Poller>>,
+    start_poller: Box<dyn Fn(Duration) -> Result<Poller, Error> + Send>,
+    /// Returns the type of network file system a folder is on, if any.
+    detect: Option<fn(&Path) -> Option<&'static str>>,
+    /// The folders being polled, how often, and why if they fell back to
+    /// polling.
+    polled: HashMap<PathBuf, (Duration, Option<String>)>,
+    /// The files the watch is interested in, shared with every poller.
+    tracked: Arc<ArcSwap<Vec<PathBuf>>>,
+}
+
+impl FolderPolling {
+    fn new(
+        watcher: Box<dyn FileSystemWatcher>,
+        start_poller: impl Fn(Duration) -> Result<Poller, Error> + Send + 'static,
+        detect: Option<fn(&Path) -> Option<&'static str>>,
+        tracked: Arc<ArcSwap<Vec<PathBuf>>>,
+    ) -> Self {
+        Self {
+            watcher,
+            pollers: HashMap::new(),
+            start_poller: Box::new(start_poller),
+            detect,
+            polled: HashMap::new(),
+            tracked,
+        }
+    }
+
+    fn start_polling(
+        &mut self,
+        path: &Path,
+        recursive: bool,
+        interval: Duration,
+        reason: Option<String>,
+    ) -> Result<(), Error> {
+        let poller = match self.pollers.entry(interval) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(NotifyWatcher((self.start_poller)(interval)?)),
+        };
+        poller.watch(path, recursive)?;
+        self.polled.insert(path.to_path_buf(), (interval, reason));
+        Ok(())
+    }
+}
+
+impl FileSystemWatcher for FolderPolling {
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error> {
+        let Some(fs_type) = self.detect.and_then(|detect| detect(path)) else {
+            return self.watcher.watch(path, recursive);
+        };
+        let reason = format!(
+            "{} is on a network file system ({fs_type}), where changes made by \
+             other machines aren't reported, so it is polled every \
+             {FALLBACK_POLL_INTERVAL:?}",
+            path.display()
+        );
+        self.start_polling(path, recursive, FALLBACK_POLL_INTERVAL, Some(reason))
+    }
+
+    fn poll(&mut self, path: &Path, recursive: bool, interval: Duration) -> Result<(), Error> {
+        self.start_polling(path, recursive, interval, None)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+        let Some((interval, _)) = self.polled.remove(path) else {
+            return self.watcher.unwatch(path);
+        };
+        let result = match self.pollers.get_mut(&interval) {
+            Some(poller) => poller.unwatch(path),
+            None => Ok(()),
+        };
+        // Stop the poller once nothing uses it.
+        if !self.polled.values().any(|(i, _)| *i == interval) {
+            self.pollers.remove(&interval);
+        }
+        result
+    }
+
+    fn degraded(&self) -> Option<String> {
+        let reasons: Vec<&String> = self
+            .polled
+            .values()
+            .filter_map(|(_, reason)| reason.as_ref())
+            .collect();
+        self.watcher.degraded().or_else(|| match reasons[..] {
+            [] => None,
+            [reason] => Some(reason.clone()),
+            _ => Some(format!(
+                "{} folders are on network file systems, where changes made by other \
+                 machines aren't reported, so they are polled every {FALLBACK_POLL_INTERVAL:?}",
+                reasons.len()
+            )),
+        })
+    }
+
+    fn fallback(&self, path: &Path) -> Option<String> {
+        match self.polled.get(path) {
+            Some((_, reason)) => reason.clone(),
+            None => self.watcher.fallback(path),
+        }
+    }
+
+    fn track(&mut self, files: &[PathBuf]) {
+        self.tracked.store(Arc::new(files.to_vec()));
+        self.watcher.track(files);
+    }
+}
+
+/// A watcher used in place of the one which was asked for, along with why.
+struct Degraded<W> {
+    watcher: W,
+    reason: String,
+}
+
+impl<W: FileSystemWatcher> FileSystemWatcher for Degraded<W> {
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error> {
+        self.watcher.watch(path, recursive)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+        self.watcher.unwatch(path)
+    }
+
+    fn degraded(&self) -> Option<String> {
+        Some(self.reason.clone())
+    }
+}
+
+/// Adapts a `notify::Watcher` to a `FileSystemWatcher`.
+struct NotifyWatcher<W>(W);
+
+impl<W: Watcher + Send> FileSystemWatcher for NotifyWatcher<W> {
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error> {
+        let mode = match recursive {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        };
+        Ok(self.0.watch(path, mode)?)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+        Ok(self.0.unwatch(path)?)
+    }
+}
+
+/// An in-memory file system, for tests and for programs which generate their
+/// configuration. Cloning a MemoryFileSystem gives another handle to the same
+/// files.
+///
+/// Changes made with `write()` and `remove()` are reported to watchers
+/// straight away, on the calling thread, so a test doesn't have to wait for
+/// the operating system to notice them. Directories are created automatically
+/// when a file is written inside them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, MemoryFile>,
+    dirs: BTreeSet<PathBuf>,
+    watchers: Vec<Weak<MemoryWatcherState>>,
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    contents: Arc<[u8]>,
+    modified: SystemTime,
+}
+
+struct MemoryWatcherState {
+    /// The watched folders, and whether each is watched recursively.
+    folders: Mutex<HashMap<PathBuf, bool>>,
+    on_change: Mutex<ChangeHandler>,
+}
+
+impl MemoryWatcherState {
+    fn covers(&self, path: &Path) -> bool {
+        let folders = self.folders.lock().unwrap();
+        path.ancestors().skip(1).enumerate().any(|(depth, folder)| {
+            folders
+                .get(folder)
+                .is_some_and(|recursive| depth == 0 || *recursive)
+        })
+    }
+}
+
+impl std::fmt::Debug for MemoryWatcherState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryWatcherState")
+            .field("folders", &self.folders)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemoryFileSystem {
+    /// Create a new, empty, MemoryFileSystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create or replace a file.
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) {
+        let path = path.as_ref();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(parent) = path.parent() {
+                state.add_dir(parent);
+            }
+            state.files.insert(
+                path.to_path_buf(),
+                MemoryFile {
+                    contents: Arc::from(contents.as_ref()),
+                    modified: SystemTime::now(),
+                },
+            );
+        }
+        self.changed(path);
+    }
+
+    /// Remove a file. Does nothing if the file doesn't exist.
+    pub fn remove(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let removed = self.state.lock().unwrap().files.remove(path).is_some();
+        if removed {
+            self.changed(path);
+        }
+    }
+
+    /// Create a directory, and any missing parents.
+    pub fn create_dir(&self, path: impl AsRef<Path>) {
+        self.state.lock().unwrap().add_dir(path.as_ref());
+    }
+
+    /// Tell every watcher covering `path` that it changed.
+    fn changed(&self, path: &Path) {
+        let watchers: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            state.watchers.retain(|w| w.strong_count() > 0);
+            state.watchers.iter().filter_map(Weak::upgrade).collect()
+        };
+
+        // Call the handlers without holding the lock, in case they read files.
+        for watcher in watchers {
+            if watcher.covers(path) {
+                (watcher.on_change.lock().unwrap())(Ok(vec![path.to_path_buf()]));
+            }
+        }
+    }
+}
+
+impl MemoryState {
+    fn add_dir(&mut self, path: &Path) {
+        for dir in path.ancestors() {
+            if !dir.as_os_str().is_empty() {
+                self.dirs.insert(dir.to_path_buf());
+            }
+        }
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let contents = self.read(path)?;
+        Ok(Box::new(io::Cursor::new(contents)))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let state = self.state.lock().unwrap();
+        if let Some(file) = state.files.get(path) {
+            return Ok(FileMetadata {
+                is_dir: false,
+                len: file.contents.len() as u64,
+                modified: Some(file.modified),
+            });
+        }
+        if state.dirs.contains(path) {
+            return Ok(FileMetadata {
+                is_dir: true,
+                len: 0,
+                modified: None,
+            });
+        }
+        Err(not_found(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        if !state.dirs.contains(path) {
+            return Err(not_found(path));
+        }
+        let children = state.files.keys().chain(state.dirs.iter());
+        Ok(children
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.state.lock().unwrap().files.get(path) {
+            Some(file) => Ok(file.contents.to_vec()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn watcher(
+        &self,
+        _options: &WatcherOptions,
+        on_change: ChangeHandler,
+    ) -> Result<Box<dyn FileSystemWatcher>, Error> {
+        let watcher = Arc::new(MemoryWatcherState {
+            folders: Mutex::new(HashMap::new()),
+            on_change: Mutex::new(on_change),
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .watchers
+            .push(Arc::downgrade(&watcher));
+        Ok(Box::new(MemoryWatcher(watcher)))
+    }
+}
+
+/// A watcher on a `MemoryFileSystem`.
+struct MemoryWatcher(Arc<MemoryWatcherState>);
+
+impl FileSystemWatcher for MemoryWatcher {
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error> {
+        self.0
+            .folders
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), recursive);
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+        match self.0.folders.lock().unwrap().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(Error::WatchError(format!(
+                "{} is not being watched",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Reads one file from memory, and everything else from another file system.
+/// Used by `Watch::dry_run_contents()`.
+pub(crate) struct Overlay {
+    inner: Arc<dyn FileSystem>,
+    path: PathBuf,
+    contents: Arc<[u8]>,
+}
+
+impl Overlay {
+    pub fn new(inner: Arc<dyn FileSystem>, path: PathBuf, contents: Arc<[u8]>) -> Self {
+        Self {
+            inner,
+            path,
+            contents,
+        }
+    }
+}
+
+impl FileSystem for Overlay {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        if path == self.path {
+            return Ok(Box::new(io::Cursor::new(self.contents.clone())));
+        }
+        self.inner.open(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        if path == self.path {
+            return Ok(FileMetadata {
+                is_dir: false,
+                len: self.contents.len() as u64,
+                modified: None,
+            });
+        }
+        self.inner.metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = self.inner.read_dir(path)?;
+        if self.path.parent() == Some(path) && !entries.contains(&self.path) {
+            entries.push(self.path.clone());
+        }
+        Ok(entries)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        if path == self.path {
+            return Ok(path.to_path_buf());
+        }
+        self.inner.canonicalize(path)
+    }
+
+    fn watcher(
+        &self,
+        options: &WatcherOptions,
+        on_change: ChangeHandler,
+    ) -> Result<Box<dyn FileSystemWatcher>, Error> {
+        self.inner.watcher(options, on_change)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        if path == self.path {
+            return Ok(self.contents.to_vec());
+        }
+        self.inner.read(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use notify::RecommendedWatcher;
+
+    use super::{native_or_poll, FileSystemWatcher, FolderPolling};
+    use crate::{poller::Poller, Error, PollCompare};
+
+    #[test]
+    fn should_poll_if_native_watcher_is_unavailable() {
+        let native: Result<RecommendedWatcher, _> = Err(notify::Error::generic("denied"));
+        let watcher = native_or_poll(native, |interval| {
+            assert_eq!(interval, Duration::from_secs(1));
+            Poller::start(|_| {}, interval, PollCompare::default(), None)
+                .map_err(|err| Error::WatchError(err.to_string()))
+        })
+        .unwrap();
+        assert_eq!(
+            watcher.degraded().unwrap(),
+            "the native file notification API is unavailable (denied), \
+             so files are being polled every 1s"
+        );
+    }
+
+    /// Records the folders it's asked to watch.
+    struct Recorder(Arc<Mutex<Vec<PathBuf>>>);
+
+    impl FileSystemWatcher for Recorder {
+        fn watch(&mut self, path: &Path, _recursive: bool) -> Result<(), Error> {
+            self.0.lock().unwrap().push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+            self.0.lock().unwrap().retain(|p| p != path);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_poll_folders_on_network_file_systems() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = dir.path().join("local");
+        let remote = dir.path().join("remote");
+        std::fs::create_dir(&local).unwrap();
+        std::fs::create_dir(&remote).unwrap();
+
+        let watched = Arc::new(Mutex::new(vec![]));
+        let mut watcher = FolderPolling::new(
+            Box::new(Recorder(watched.clone())),
+            |interval| {
+                Poller::start(|_| {}, interval, PollCompare::default(), None)
+                    .map_err(|err| Error::WatchError(err.to_string()))
+            },
+            Some(|path| path.ends_with("remote").then_some("nfs")),
+            Arc::default(),
+        );
+
+        watcher.watch(&local, false).unwrap();
+        watcher.watch(&remote, false).unwrap();
+        assert_eq!(*watched.lock().unwrap(), vec![local.clone()]);
+        assert!(watcher.fallback(&local).is_none());
+        let reason = watcher.fallback(&remote).unwrap();
+        assert!(reason.contains("(nfs)"), "{reason}");
+        assert_eq!(watcher.degraded(), Some(reason));
+
+        watcher.unwatch(&remote).unwrap();
+        assert!(watcher.degraded().is_none());
+    }
+
+    #[test]
+    fn should_not_detect_local_folders_as_network_file_systems() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(crate::netfs::network_fs_type(dir.path()), None);
+    }
+}
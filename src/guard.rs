@@ -0,0 +1,56 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+
+use crate::{file_watcher::FileWatcher, Error, Watch};
+
+/// Returned by `Watch::watch_additionally()`. Stops watching the extra files
+/// when dropped.
+#[must_use = "the files are removed from the watch as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct WatchedFilesGuard {
+    watcher: Weak<FileWatcher>,
+    /// The files this guard added, which weren't already being watched.
+    added: Vec<PathBuf>,
+}
+
+impl WatchedFilesGuard {
+    /// The files which were added by this guard. Files which were already
+    /// being watched when the guard was created aren't included, and won't be
+    /// removed when the guard is dropped.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.added
+    }
+}
+
+impl Drop for WatchedFilesGuard {
+    fn drop(&mut self) {
+        if let Some(watcher) = self.watcher.upgrade() {
+            // If the watch has been stopped there's nothing to clean up.
+            let _ = watcher.remove_files(&self.added);
+        }
+    }
+}
+
+impl<T> Watch<T> {
+    /// Temporarily add files to the set of watched files, such as a debug
+    /// override file which only exists during an incident. The files are
+    /// removed again when the returned guard is dropped.
+    ///
+    /// Adding files doesn't call the loader; the files are picked up on the
+    /// next change to any watched file. If the loader replaces the set of
+    /// watched files with `Context::update_watched_files()` while the guard is
+    /// alive, the files may stop being watched early.
+    pub fn watch_additionally<I>(&self, files: I) -> Result<WatchedFilesGuard, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let added = self.watcher.add_files(files)?;
+        Ok(WatchedFilesGuard {
+            watcher: Arc::downgrade(&self.watcher),
+            added,
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use crate::{Snapshot, Watch};
+
+/// The most recent values of a watch, kept with `Builder::keep_history()`.
+#[derive(Debug)]
+pub(crate) struct History<T> {
+    /// The number of values to keep. History is disabled if this is 0.
+    limit: usize,
+    /// Oldest first.
+    snapshots: VecDeque<Snapshot<T>>,
+}
+
+impl<T> History<T> {
+    pub fn new() -> Self {
+        Self {
+            limit: 0,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Start keeping the last `limit` values, starting with `current`.
+    pub fn enable(&mut self, limit: usize, current: Snapshot<T>) {
+        self.limit = limit;
+        self.snapshots.clear();
+        self.record(current);
+    }
+
+    /// Record a newly stored value.
+    pub fn record(&mut self, snapshot: Snapshot<T>) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.snapshots.len() >= self.limit {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Find the value which was live at `time`.
+    pub fn at(&self, time: SystemTime) -> Option<Snapshot<T>> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| !matches!(snapshot.loaded_at(), Some(at) if at > time))
+            .cloned()
+    }
+
+    pub fn snapshots(&self) -> Vec<Snapshot<T>> {
+        self.snapshots.iter().cloned().collect()
+    }
+}
+
+impl<T> Watch<T> {
+    /// Get the value which was live at `time`, for working out which config
+    /// was in use when something went wrong. This needs
+    /// `Builder::keep_history()`, and returns `None` without it, or if `time`
+    /// is before the oldest value kept. The watch's default value counts as
+    /// live from before the watch was built until the first load.
+    pub fn value_at(&self, time: SystemTime) -> Option<Snapshot<T>> {
+        self.history.lock().unwrap().at(time)
+    }
+
+    /// Get the values kept with `Builder::keep_history()`, oldest first. The
+    /// last one is the current value. This is empty without
+    /// `keep_history()`.
+    pub fn history(&self) -> Vec<Snapshot<T>> {
+        self.history.lock().unwrap().snapshots()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::History;
+    use crate::Snapshot;
+
+    #[test]
+    fn should_find_the_value_live_at_a_time() {
+        let mut history = History::new();
+        let initial = Snapshot::initial(Arc::new(0));
+        history.enable(3, initial.clone());
+
+        let mut latest = initial;
+        let mut times = vec![];
+        for value in 1..=3 {
+            latest = latest.next(Arc::new(value));
+            times.push(latest.loaded_at().unwrap());
+            history.record(latest.clone());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // Only the last three values are kept, so the default is gone.
+        let versions = history
+            .snapshots()
+            .iter()
+            .map(Snapshot::version)
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec![1, 2, 3]);
+
+        assert_eq!(*history.at(times[1]).unwrap(), 2);
+        assert_eq!(*history.at(times[1] + Duration::from_millis(1)).unwrap(), 2);
+        assert_eq!(*history.at(SystemTime::now()).unwrap(), 3);
+        assert!(history.at(times[0] - Duration::from_millis(1)).is_none());
+    }
+}
@@ -1,35 +1,183 @@
 #[doc = include_str!("../README.md")]
 use std::{
     path::{Path, PathBuf},
-    sync::{Arc, Mutex, Weak},
-    time::Duration,
+    sync::{atomic::AtomicU64, mpsc, Arc, Condvar, Mutex, Weak},
+    time::{Duration, Instant},
 };
 
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use file_watcher::FileWatcher;
+use history::History;
+use reloader::{Reloader, ReloaderGuard};
 
+#[cfg(feature = "tokio")]
+mod async_loader;
+mod breaker;
 mod builder;
+mod cached;
+mod cancel;
+mod capabilities;
 mod context;
+mod debouncer;
+mod dedup;
+#[cfg(feature = "json")]
+mod defaults;
 mod error;
+mod expand;
+mod fallback;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod file_watcher;
+mod first_of;
+mod fs;
+mod guard;
+mod history;
+mod lint;
 mod loaders;
+mod mapped;
+mod netfs;
+mod options;
+mod patch;
+mod pipeline;
+mod poller;
+mod priority;
+mod progress;
+mod rate_alarm;
+mod reader;
+mod reloader;
+mod roots;
+mod scope;
+#[cfg(feature = "json")]
+mod section;
+mod snapshot;
+mod sources;
+mod stats;
+#[cfg(feature = "tokio")]
+mod stream;
+mod suppress;
+mod trace;
 mod types;
+mod validate;
+#[cfg(feature = "json")]
+mod watch_set;
+mod watchdog;
+mod watched_files;
+mod weak;
+mod xdg;
 
+#[cfg(feature = "tokio")]
+pub use async_loader::{AsyncLoader, AsyncUpdatedHandler, TokioLoader, TokioUpdatedHandler};
+pub use breaker::CircuitState;
 pub use builder::Builder;
+pub use cached::CachedReader;
+pub use cancel::CancelToken;
+pub use capabilities::{capabilities, Capabilities};
 pub use context::Context;
+pub use dedup::{Comparer, DedupPolicy, DedupValues, NoDedup};
+#[cfg(feature = "json")]
+pub use defaults::OverriddenSetting;
 pub use error::Error;
+pub use fallback::Fallback;
+pub use first_of::FirstOfLoader;
+pub use fs::{
+    ChangeHandler, FileMetadata, FileSystem, FileSystemWatcher, MemoryFileSystem, StdFileSystem,
+};
+pub use guard::WatchedFilesGuard;
+pub use lint::{LintLoader, Warning};
+// Loaders are all behind feature flags, so this may be empty.
+#[allow(unused_imports)]
 pub use loaders::*;
+pub use mapped::{Mapped, Zipped};
+pub use notify::Config as NotifyConfig;
+pub use options::{
+    Backend, ChangeDetection, DebounceMode, PollCompare, WatchOptions, WatcherOptions,
+};
+pub use patch::PatchLoader;
+pub use pipeline::{Pipeline, Stage};
+pub use progress::Progress;
+pub use reader::WatchReader;
+#[cfg(feature = "tokio")]
+pub use scope::scope_async;
+pub use scope::{scope, WatchScope};
+#[cfg(feature = "json")]
+pub use section::Section;
+pub use snapshot::Snapshot;
+pub use sources::{ConfigSource, ConfigSources, Resolution};
+pub use stats::WatchStats;
+#[cfg(feature = "tokio")]
+pub use stream::WatchStream;
+pub use trace::{ReloadOutcome, ReloadTrace, ReloadTrigger};
 pub use types::*;
+pub use validate::ValidateLoader;
+#[cfg(feature = "json")]
+pub use watch_set::WatchSet;
+pub use watched_files::WatchedFiles;
+pub use weak::WeakWatch;
 
 /// A guard for the current value of a Watch.
 pub type Guard<T> = arc_swap::Guard<Arc<T>>;
 
+/// The number of values `Watch::subscribe_broadcast()` receivers can fall
+/// behind by before they start missing values.
+#[cfg(feature = "tokio")]
+pub const BROADCAST_CAPACITY: usize = 16;
+
 type WeakFileWatcher = Arc<Mutex<Option<Weak<FileWatcher>>>>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Watch<T> {
     value: Arc<ArcSwap<T>>,
+    reloader: Arc<Mutex<Reloader<T>>>,
     watcher: Arc<FileWatcher>,
+    last_trace: Arc<ArcSwapOption<ReloadTrace>>,
+    stats: Arc<Mutex<WatchStats>>,
+    snapshot: Arc<ArcSwap<Snapshot<T>>>,
+    version: Arc<AtomicU64>,
+    history: Arc<Mutex<History<T>>>,
+    progress: Arc<ArcSwapOption<Progress>>,
+    /// Senders for the receivers returned by `subscribe()`.
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Arc<T>>>>>,
+    /// Counts the values stored, and is notified each time one is, for
+    /// `wait_for_update()` and `expect_change()`.
+    stored: Arc<(Mutex<u64>, Condvar)>,
+    /// Senders for the receivers returned by `subscribe_crossbeam()`.
+    #[cfg(feature = "crossbeam")]
+    crossbeam_subscribers: Arc<Mutex<Vec<crossbeam_channel::Sender<Arc<T>>>>>,
+    /// Changes each time a new value is stored, for `changed()`.
+    #[cfg(feature = "tokio")]
+    updates: tokio::sync::watch::Receiver<()>,
+    /// Holds each new value as it is stored, for `to_tokio_watch()`.
+    #[cfg(feature = "tokio")]
+    values: tokio::sync::watch::Receiver<Arc<T>>,
+    /// Sends each new value to the receivers from `subscribe_broadcast()`.
+    #[cfg(feature = "tokio")]
+    broadcast: tokio::sync::broadcast::Sender<Arc<T>>,
+}
+
+impl<T> Clone for Watch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            reloader: self.reloader.clone(),
+            watcher: self.watcher.clone(),
+            last_trace: self.last_trace.clone(),
+            stats: self.stats.clone(),
+            snapshot: self.snapshot.clone(),
+            version: self.version.clone(),
+            history: self.history.clone(),
+            progress: self.progress.clone(),
+            subscribers: self.subscribers.clone(),
+            stored: self.stored.clone(),
+            #[cfg(feature = "crossbeam")]
+            crossbeam_subscribers: self.crossbeam_subscribers.clone(),
+            #[cfg(feature = "tokio")]
+            updates: self.updates.clone(),
+            #[cfg(feature = "tokio")]
+            values: self.values.clone(),
+            #[cfg(feature = "tokio")]
+            broadcast: self.broadcast.clone(),
+        }
+    }
 }
 
 impl<T> Watch<T> {
@@ -39,18 +187,23 @@ impl<T> Watch<T> {
     ///
     /// - `files` is the initial set of files to watch for changes.
     /// - `default` is the initial value for the configuration to use.
-    /// - `debounce` is the duration to wait after a change before calling the loader.
+    /// - `options` controls how files are watched.
+    /// - `fs` is the file system to watch files on.
     /// - `loader` is a function that will be called to update the value whenever
     ///   the file changes.  Loader returns the new value, and a new list of files
     ///   to watch including any dependencies
     ///
+    /// The watch starts paused, so changes which arrive while the caller
+    /// finishes configuring the reloader and watcher are held rather than
+    /// loaded with a half-configured watch. Call `resume()` once it's ready.
     fn create<FilesIter, LoaderImpl, Updated, ErrorHandlerImpl>(
         files: FilesIter,
         default: ArcSwap<T>,
-        debounce: Option<Duration>,
-        mut loader: LoaderImpl,
-        mut after_update: Updated,
-        mut error_handler: ErrorHandlerImpl,
+        options: WatcherOptions,
+        fs: Arc<dyn FileSystem>,
+        loader: LoaderImpl,
+        after_update: Updated,
+        error_handler: ErrorHandlerImpl,
     ) -> Result<Self, Error>
     where
         FilesIter: IntoIterator,
@@ -66,33 +219,106 @@ impl<T> Watch<T> {
             .map(|f| f.as_ref().to_path_buf())
             .collect::<Vec<_>>();
 
+        let mut reloader = Reloader::new(value.clone(), loader, after_update, error_handler);
+        reloader.set_file_system(fs.clone());
+
+        Self::with_reloader(value, reloader, move |reloader, weak| {
+            FileWatcher::with_options(files, options, fs, true, move |res| {
+                let mut reloader = ReloaderGuard::wait(&reloader);
+                match res {
+                    Ok(changes) => {
+                        let mut context = Context::for_watch(changes.modified, &weak);
+                        context.set_cancel_token(changes.cancel.clone());
+                        let trigger = ReloadTrigger::FileChange {
+                            events: changes.events.to_vec(),
+                            debounce: changes.debounce,
+                        };
+                        reloader.reload(&mut context, trigger);
+                    }
+                    Err(e) => {
+                        let mut context = Context::for_watch(&[], &weak);
+                        reloader.on_watch_error(&mut context, e.to_string());
+                    }
+                }
+            })
+        })
+    }
+
+    /// Assemble a watch around `reloader`. `create_watcher` is given the
+    /// shared reloader and a weak reference to fill in with the watcher, so
+    /// loaders can update the watcher while they run.
+    fn with_reloader<CreateWatcher>(
+        value: Arc<ArcSwap<T>>,
+        mut reloader: Reloader<T>,
+        create_watcher: CreateWatcher,
+    ) -> Result<Self, Error>
+    where
+        T: Send + Sync + 'static,
+        CreateWatcher:
+            FnOnce(Arc<Mutex<Reloader<T>>>, WeakFileWatcher) -> Result<FileWatcher, Error>,
+    {
         // We want to be able to update the watcher from within the loader, so
         // we need a weak reference to the watcher.
         let weak: WeakFileWatcher = Arc::new(Mutex::new(None));
 
-        let watcher = {
+        let last_trace = reloader.last_trace();
+        let stats = reloader.stats();
+        let snapshot = reloader.snapshot();
+        let version = reloader.version();
+        let history = reloader.history();
+        let progress = reloader.progress();
+        let subscribers: Arc<Mutex<Vec<mpsc::Sender<Arc<T>>>>> = Arc::default();
+        let stored: Arc<(Mutex<u64>, Condvar)> = Arc::default();
+        #[cfg(feature = "crossbeam")]
+        let crossbeam_subscribers: Arc<Mutex<Vec<crossbeam_channel::Sender<Arc<T>>>>> =
+            Arc::default();
+        {
             let value = value.clone();
-            let weak = weak.clone();
-
-            FileWatcher::create(files.clone(), debounce, move |res| match res {
-                Ok(modified_files) => {
-                    let mut context = Context::for_watch(modified_files, &weak);
-                    match loader.load(&mut context) {
-                        Ok(v) => {
-                            value.store(Arc::new(v));
-                            after_update.after_update(&mut context, value.load());
-                        }
-                        Err(e) => {
-                            error_handler.on_error(&mut context, Error::LoadError(e));
-                        }
-                    }
+            let subscribers = subscribers.clone();
+            let stored = stored.clone();
+            #[cfg(feature = "crossbeam")]
+            let crossbeam_subscribers = crossbeam_subscribers.clone();
+            reloader.add_outcome_listener(Box::new(move |_context, outcome| {
+                if *outcome == ReloadOutcome::Updated {
+                    let (count, stored) = &*stored;
+                    *count.lock().unwrap() += 1;
+                    stored.notify_all();
+                    let new_value = value.load_full();
+                    subscribers
+                        .lock()
+                        .unwrap()
+                        .retain(|tx| tx.send(new_value.clone()).is_ok());
+                    #[cfg(feature = "crossbeam")]
+                    crossbeam_subscribers
+                        .lock()
+                        .unwrap()
+                        .retain(|tx| tx.send(new_value.clone()).is_ok());
                 }
-                Err(e) => {
-                    let mut context = Context::for_watch(&[], &weak);
-                    error_handler.on_error(&mut context, Error::WatchError(e.to_string()));
+                true
+            }));
+        }
+        #[cfg(feature = "tokio")]
+        let (updates, values, broadcast) = {
+            let (updates_tx, updates_rx) = tokio::sync::watch::channel(());
+            let (values_tx, values_rx) = tokio::sync::watch::channel(value.load_full());
+            let (broadcast, _) = tokio::sync::broadcast::channel(BROADCAST_CAPACITY);
+            let broadcast_tx = broadcast.clone();
+            let value = value.clone();
+            reloader.add_outcome_listener(Box::new(move |_context, outcome| {
+                if *outcome == ReloadOutcome::Updated {
+                    let new_value = value.load_full();
+                    // This only fails if nobody is subscribed.
+                    let _ = broadcast_tx.send(new_value.clone());
+                    values_tx.send_replace(new_value);
+                    updates_tx.send_replace(());
                 }
-            })?
+                true
+            }));
+            (updates_rx, values_rx, broadcast)
         };
+        let reloader = Arc::new(Mutex::new(reloader));
+
+        let watcher = create_watcher(reloader.clone(), weak.clone())?;
 
         // Fill in the WeakFileWatcher with a reference to the watcher.
         let watcher = Arc::new(watcher);
@@ -101,11 +327,83 @@ impl<T> Watch<T> {
             *weak_lock = Some(Arc::downgrade(&watcher));
         }
 
-        Ok(Watch { value, watcher })
+        Ok(Watch {
+            value,
+            reloader,
+            watcher,
+            last_trace,
+            stats,
+            snapshot,
+            version,
+            history,
+            progress,
+            subscribers,
+            stored,
+            #[cfg(feature = "crossbeam")]
+            crossbeam_subscribers,
+            #[cfg(feature = "tokio")]
+            updates,
+            #[cfg(feature = "tokio")]
+            values,
+            #[cfg(feature = "tokio")]
+            broadcast,
+        })
+    }
+
+    /// Create a watch which always holds `value`, for passing to code which
+    /// takes a `Watch<T>` in unit tests, or when running without config
+    /// files. No files are watched, no filesystem watcher or thread is
+    /// started, and the value never changes. The watch reports itself as
+    /// stopped.
+    pub fn fixed(value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        let value = Arc::new(ArcSwap::from_pointee(value));
+        let fs: Arc<dyn FileSystem> = Arc::new(MemoryFileSystem::new());
+        let mut reloader = Reloader::new(
+            value.clone(),
+            |_context: &mut Context| -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+                Err("a fixed watch can't be reloaded".into())
+            },
+            DefaultUpdatedHandler,
+            DefaultErrorHandler,
+        );
+        reloader.set_file_system(fs.clone());
+        Self::with_reloader(value, reloader, |_reloader, _weak| {
+            Ok(FileWatcher::stopped(fs))
+        })
+        .expect("a stopped watcher can't fail to start")
+    }
+
+    /// Load the initial value for the watch from the watched files. If there
+    /// are no watched files, the loader is not called and the watch keeps its
+    /// default value. If `fail_on_error` is true, a failed load is returned
+    /// as an error.
+    fn initial_load(&self, fail_on_error: bool) -> Result<(), Error> {
+        let weak: WeakFileWatcher = Arc::new(Mutex::new(Some(Arc::downgrade(&self.watcher))));
+        let files = self.watcher.watched_files();
+        let modified_paths: Vec<&Path> = files.iter().map(|f| f.as_ref()).collect();
+        let mut context = Context::for_watch(&modified_paths, &weak);
+        let mut reloader = ReloaderGuard::wait(&self.reloader);
+        // A lazy initial load can start after the watch is shut down.
+        if self.watcher.is_stopped() {
+            return Ok(());
+        }
+        reloader.initial_load(&mut context, !modified_paths.is_empty(), fail_on_error)
+    }
+
+    /// Load the embedded default document to use as the initial value, before
+    /// the watched files are loaded.
+    fn load_embedded_default(&self, embedded_default: Arc<str>) -> Result<(), Error> {
+        let weak: WeakFileWatcher = Arc::new(Mutex::new(Some(Arc::downgrade(&self.watcher))));
+        let mut context = Context::for_watch(&[], &weak);
+        ReloaderGuard::wait(&self.reloader).set_embedded_default(&mut context, embedded_default)
     }
 
-    /// Return the set of files this watcher is watching.
-    pub fn watched_files(&self) -> Guard<Vec<PathBuf>> {
+    /// Return the set of files this watcher is watching, in the order
+    /// described in `WatchedFiles`.
+    pub fn watched_files(&self) -> WatchedFiles {
         self.watcher.watched_files()
     }
 
@@ -118,26 +416,262 @@ impl<T> Watch<T> {
         self.watcher.update_files(files)
     }
 
+    /// Get the options controlling how files are watched.
+    pub fn options(&self) -> WatcherOptions {
+        self.watcher.options()
+    }
+
+    /// Change how files are watched, such as the debounce duration or the
+    /// backend, without interrupting the watch. The current value is kept,
+    /// and if the backend changes the new backend is watching before the old
+    /// one is stopped, so no changes are missed.
+    pub fn reconfigure(&self, options: WatcherOptions) -> Result<(), Error> {
+        self.watcher.reconfigure(options)
+    }
+
+    /// Stop reloading, such as while an operator is editing several files
+    /// which only make sense together. Changes made while the watch is paused
+    /// are collected, and when `resume()` is called the loader runs once with
+    /// all of them. Pausing doesn't affect `dry_run()`.
+    pub fn pause(&self) {
+        self.watcher.pause();
+    }
+
+    /// Start reloading again after `pause()`. If any files changed while the
+    /// watch was paused, they are loaded straight away.
+    pub fn resume(&self) {
+        self.watcher.resume();
+    }
+
+    /// Returns true if the watch is paused. See `pause()`.
+    pub fn is_paused(&self) -> bool {
+        self.watcher.is_paused()
+    }
+
+    /// Stop the watch, and return its final value. This stops watching every
+    /// file, waits for any load which is in progress to finish, and makes
+    /// sure no loader or handler is called again, so the final value can't
+    /// race with the rest of the program shutting down. Other clones of the
+    /// watch keep returning the final value, but never update again.
+    ///
+    /// Returns `Error::WouldDeadlock` if called from within a loader or
+    /// handler, since waiting for the load in progress could deadlock.
+    pub fn shutdown(self) -> Result<Arc<T>, Error> {
+        if ReloaderGuard::<T>::is_held() {
+            return Err(Error::WouldDeadlock);
+        }
+        self.watcher.stop();
+        // Wait for loads which don't run on the watcher's thread, such as a
+        // lazy initial load.
+        drop(ReloaderGuard::wait(&self.reloader));
+        Ok(self.value.load_full())
+    }
+
     /// Produces a temporary borrow of the current configuration value. If the
     /// underlying value is changed, the value in the guard will not be updated
     /// to preserve consistency.
     pub fn value(&self) -> Guard<T> {
         self.value.load()
     }
+
+    /// Get an owned reference to the current configuration value. Unlike the
+    /// guard returned by `value()`, this can be stored in a struct or sent to
+    /// another thread for as long as you like. It keeps the value it was
+    /// created with; call this again to get the latest value. Use
+    /// `snapshot()` if you also need the value's version.
+    pub fn value_arc(&self) -> Arc<T> {
+        self.value.load_full()
+    }
+
+    /// Call `f` with the current configuration value, and return its result.
+    /// The guard is dropped as soon as `f` returns, so unlike with `value()`
+    /// it can't be held for long by mistake, which would stop `arc-swap` from
+    /// freeing old values.
+    pub fn with_value<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.value.load())
+    }
+
+    /// Returns a channel which receives each new value stored from now on, so
+    /// a thread can block waiting for updates without being wired in with
+    /// `Builder::after_update()`. Values queue up until they are received.
+    /// Dropping the receiver unsubscribes it, and once every clone of the
+    /// watch is dropped the receiver is disconnected.
+    pub fn subscribe(&self) -> mpsc::Receiver<Arc<T>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Block until the next new value is stored, and return the current
+    /// value, or return `None` if no value is stored within `timeout`. Values
+    /// stored before this is called don't count. This is handy in command line
+    /// tools and tests; to handle every update, use `subscribe()`.
+    pub fn wait_for_update(&self, timeout: Duration) -> Option<Guard<T>> {
+        let (count, stored) = &*self.stored;
+        let count = count.lock().unwrap();
+        let seen = *count;
+        let (_count, result) = stored
+            .wait_timeout_while(count, timeout, |count| *count == seen)
+            .unwrap();
+        if result.timed_out() {
+            return None;
+        }
+        Some(self.value())
+    }
+
+    /// Block until the watch holds a value which matches `predicate`, and
+    /// return it, or return `None` if that doesn't happen within `timeout`.
+    /// This lets deployment tooling which writes a new file confirm that the
+    /// running process picked it up. The current value is checked first, so
+    /// a reload which finished before this was called isn't missed.
+    pub fn expect_change(
+        &self,
+        mut predicate: impl FnMut(&T) -> bool,
+        timeout: Duration,
+    ) -> Option<Arc<T>> {
+        let deadline = Instant::now() + timeout;
+        let (count, stored) = &*self.stored;
+        loop {
+            // Read the count before checking the current value, so nothing
+            // stored in between is missed.
+            let seen = *count.lock().unwrap();
+            let value = self.value.load_full();
+            if predicate(&value) {
+                return Some(value);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            let (_count, result) = stored
+                .wait_timeout_while(count.lock().unwrap(), remaining, |count| *count == seen)
+                .unwrap();
+            if result.timed_out() {
+                return None;
+            }
+        }
+    }
+
+    /// Like `subscribe()`, but returns a `crossbeam_channel::Receiver`, which
+    /// can be used in a crossbeam `select!` loop.
+    #[cfg(feature = "crossbeam")]
+    pub fn subscribe_crossbeam(&self) -> crossbeam_channel::Receiver<Arc<T>> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.crossbeam_subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Returns a future which resolves the next time a new value is stored,
+    /// such as to restart an async task with the new config. Values stored
+    /// before this is called don't count. The future also resolves if every
+    /// clone of the watch is dropped while it is waiting.
+    #[cfg(feature = "tokio")]
+    pub fn changed(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut updates = self.updates.clone();
+        updates.borrow_and_update();
+        async move {
+            let _ = updates.changed().await;
+        }
+    }
+
+    /// Get a `tokio::sync::watch::Receiver` which always holds the current
+    /// value, for tasks which already use tokio's watch API. The value it
+    /// holds now is marked as seen, so `changed()` waits for the next value to
+    /// be stored. The receiver sees the sender as closed once every clone of
+    /// the watch is dropped.
+    #[cfg(feature = "tokio")]
+    pub fn to_tokio_watch(&self) -> tokio::sync::watch::Receiver<Arc<T>> {
+        let mut values = self.values.clone();
+        values.borrow_and_update();
+        values
+    }
+
+    /// Get a `tokio::sync::broadcast::Receiver` which receives every value
+    /// stored from now on, so any number of tasks can each see every update.
+    /// The channel holds the last `BROADCAST_CAPACITY` values; a receiver
+    /// which falls further behind than that gets `RecvError::Lagged` and
+    /// skips ahead.
+    #[cfg(feature = "tokio")]
+    pub fn subscribe_broadcast(&self) -> tokio::sync::broadcast::Receiver<Arc<T>> {
+        self.broadcast.subscribe()
+    }
+
+    /// Explain what happened during the most recent reload: the events which
+    /// caused it, the files the loader was given, how long the loader took, and
+    /// whether the new value was stored. The trace also includes any events
+    /// which have arrived since and were ignored because they didn't match a
+    /// watched file, which is useful if a reload you expected didn't happen.
+    pub fn explain_last_reload(&self) -> Option<ReloadTrace> {
+        let mut trace = ReloadTrace::clone(&*self.last_trace.load_full()?);
+        trace.ignored_events = self.watcher.ignored_events();
+        Some(trace)
+    }
+
+    /// Get counters for this watch, such as the number of reloads and the
+    /// warnings reported by the most recent load.
+    pub fn stats(&self) -> WatchStats {
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.debounce = self.watcher.debounce();
+        stats.degraded = self.watcher.degraded();
+        stats
+    }
+
+    /// Run the loader against `path` without storing the result, to find out
+    /// if the file would be accepted. This can be used to check a candidate
+    /// configuration file before copying it over the live one.
+    ///
+    /// Any changes the loader makes to the set of watched files are discarded,
+    /// and state the loader keeps between loads (such as the base version
+    /// `PatchLoader` patches) is left as it was. This will block while a
+    /// reload is in progress. If it is called from within a loader or handler
+    /// while this watch is busy (including from this watch's own loader), it
+    /// returns `Error::WouldDeadlock` instead.
+    pub fn dry_run(&self, path: impl AsRef<Path>) -> Result<T, Error> {
+        self.dry_run_with(path.as_ref(), None)
+    }
+
+    /// Like `dry_run()`, but the loader reads `contents` as the contents of
+    /// `path`, which doesn't need to exist. This checks a candidate received
+    /// some other way, such as the body of a request to an admin endpoint,
+    /// without writing it to disk first. The path is still used to pick the
+    /// format, and other files are read as usual.
+    pub fn dry_run_contents(
+        &self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Vec<u8>>,
+    ) -> Result<T, Error> {
+        let path = path.as_ref();
+        let candidate = (path.to_path_buf(), contents.into().into());
+        self.dry_run_with(path, Some(candidate))
+    }
+
+    fn dry_run_with(
+        &self,
+        path: &Path,
+        candidate: Option<(PathBuf, Arc<[u8]>)>,
+    ) -> Result<T, Error> {
+        let modified_paths = [path];
+        let mut watch_paths = vec![];
+        let mut context = Context::for_paths(&modified_paths, &mut watch_paths);
+        ReloaderGuard::lock(&self.reloader)?.dry_run(&mut context, candidate)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::{mpsc, Arc},
+        time::Duration,
+    };
+
     use arc_swap::ArcSwap;
 
-    use crate::{Context, Watch};
+    use crate::{Context, MemoryFileSystem, StdFileSystem, Watch, WatcherOptions};
 
     #[test]
     fn should_error_if_folder_does_not_exist() -> Result<(), Box<dyn std::error::Error>> {
         let err = Watch::create(
             &["/i/do/not/exist"],
             ArcSwap::from_pointee(1),
-            None,
+            WatcherOptions::default(),
+            Arc::new(StdFileSystem),
             |_c: &mut Context| Ok(1),
             |_c: &mut Context, _v| {},
             |_c: &mut Context, _err| {},
@@ -147,4 +681,47 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn should_not_leave_subscribers_behind_after_waiting() {
+        let watch = Watch::fixed(1);
+        for _ in 0..3 {
+            assert!(watch.wait_for_update(Duration::from_millis(10)).is_none());
+            assert!(watch
+                .expect_change(|value| *value == 2, Duration::from_millis(10))
+                .is_none());
+        }
+        assert!(watch.subscribers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_not_start_a_watcher_for_a_fixed_watch() {
+        let watch = Watch::fixed(1);
+        assert!(watch.watcher.is_stopped());
+    }
+
+    #[test]
+    fn should_hold_changes_until_a_created_watch_is_resumed() {
+        let (tx, rx) = mpsc::channel();
+        let fs = MemoryFileSystem::new();
+        fs.write("/etc/app.conf", "1");
+
+        let watch = Watch::create(
+            &["/etc/app.conf"],
+            ArcSwap::from_pointee(0),
+            WatcherOptions::default(),
+            Arc::new(fs.clone()),
+            |_c: &mut Context| Ok(1),
+            move |_c: &mut Context, _v| tx.send(()).unwrap(),
+            |_c: &mut Context, _err| {},
+        )
+        .unwrap();
+        assert!(watch.is_paused());
+
+        fs.write("/etc/app.conf", "2");
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        watch.resume();
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
 }
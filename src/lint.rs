@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::{Context, Linter, Loader};
+
+/// A non-fatal problem found in a configuration value, such as a deprecated
+/// setting. Warnings are reported with `Context::warn()`, and are delivered
+/// to the watch's warning handler after the value is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// The setting the warning is about, if any.
+    pub key: Option<String>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Warning {
+    /// Create a new warning.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            key: None,
+            message: message.into(),
+        }
+    }
+
+    /// Create a new warning about a specific setting.
+    pub fn for_key(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: Some(key.into()),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{key}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<String> for Warning {
+    fn from(message: String) -> Self {
+        Warning::new(message)
+    }
+}
+
+impl From<&str> for Warning {
+    fn from(message: &str) -> Self {
+        Warning::new(message)
+    }
+}
+
+/// Wraps another loader, and checks each value it loads with a `Linter`. Any
+/// warnings the linter finds are reported with `Context::warn()`, but the
+/// value is still used. See `Builder::lint()`.
+#[derive(Debug)]
+pub struct LintLoader<L, F> {
+    inner: L,
+    linter: F,
+}
+
+impl<L, F> LintLoader<L, F> {
+    /// Create a new LintLoader.
+    pub fn new(inner: L, linter: F) -> Self {
+        Self { inner, linter }
+    }
+}
+
+impl<T, L, F> Loader<T> for LintLoader<L, F>
+where
+    L: Loader<T>,
+    F: Linter<T>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        for warning in self.linter.lint(&value) {
+            context.warn(warning);
+        }
+        Ok(value)
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
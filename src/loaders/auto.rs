@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Context, Loader};
+
+/// A configuration file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "env")]
+    Env,
+}
+
+impl Format {
+    /// Guess the format of a file from its extension. Only formats which are
+    /// enabled via feature flags will be returned.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        #[cfg(feature = "env")]
+        if path.file_name().is_some_and(|name| name == ".env") {
+            return Some(Format::Env);
+        }
+
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    /// Get a format from its name or usual file extension, such as `"yaml"`
+    /// or `"yml"`, ignoring case. Only formats which are enabled via feature
+    /// flags will be returned.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "json")]
+            "json" => Some(Format::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Format::Yaml),
+            #[cfg(feature = "toml")]
+            "toml" => Some(Format::Toml),
+            #[cfg(feature = "env")]
+            "env" => Some(Format::Env),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned when the format of a file can't be determined.
+#[derive(Debug, thiserror::Error)]
+#[error("Unable to determine the format of {0:?}")]
+pub struct UnknownFormatError(PathBuf);
+
+/// Picks a loader based on the extension of the file being loaded, so an
+/// application can accept `config.json`, `config.yaml`, or `config.toml`
+/// interchangeably.
+///
+/// Files with an unrecognized extension are loaded with the default format if
+/// one has been set, otherwise loading fails.
+#[derive(Debug, Default)]
+pub struct AutoFormatLoader {
+    default: Option<Format>,
+}
+
+impl AutoFormatLoader {
+    /// Create a new AutoFormatLoader with no default format.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the format to use for files with an unrecognized extension.
+    pub fn with_default(mut self, format: Format) -> Self {
+        self.default = Some(format);
+        self
+    }
+}
+
+impl<T> Loader<T> for AutoFormatLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let path = match context.path() {
+            Some(path) => path.to_path_buf(),
+            // With no file to load, the embedded default is parsed in the
+            // same format as the watched file.
+            None if context.embedded_default().is_some() => context
+                .watched_files()
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            None => return Ok(T::default()),
+        };
+
+        let format = Format::from_path(&path)
+            .or(self.default)
+            .ok_or(UnknownFormatError(path))?;
+        load_format(format, context)
+    }
+}
+
+/// Load the first modified file in `format`.
+pub(crate) fn load_format<T>(
+    format: Format,
+    context: &mut Context,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => super::JsonLoader.load(context),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => super::YamlLoader.load(context),
+        #[cfg(feature = "toml")]
+        Format::Toml => super::TomlLoader.load(context),
+        #[cfg(feature = "env")]
+        Format::Env => super::EnvFileLoader.load(context),
+    }
+}
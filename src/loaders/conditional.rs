@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{Context, Loader};
+
+use super::{
+    merge::{merge, MergePolicy},
+    value::from_value,
+};
+
+/// Error returned when a `when(...)` key can't be parsed.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid condition {0:?}, expected when(name=value, ...)")]
+pub struct ConditionError(String);
+
+/// Wraps another loader and applies conditional sections to the document
+/// before it is deserialized.
+///
+/// Any object key of the form `when(name=value)` is treated as a conditional
+/// section. If the condition matches the variables given to the
+/// ConditionalLoader, the contents of the section are deep-merged into the
+/// object that contains it; otherwise the section is dropped. Conditions can
+/// use `!=`, and several conditions can be separated by commas, in which case
+/// they must all match:
+///
+/// ```json
+/// {
+///     "database": { "host": "localhost" },
+///     "when(env=prod)": {
+///         "database": { "host": "db.internal" }
+///     },
+///     "when(env=prod, region!=eu)": {
+///         "database": { "replica": "db-us.internal" }
+///     }
+/// }
+/// ```
+///
+/// Conditional sections can appear at any depth, and are applied in the order
+/// they appear in the document. A variable which has not been set never
+/// matches `=`, and always matches `!=`.
+#[derive(Debug)]
+pub struct ConditionalLoader<L> {
+    inner: L,
+    variables: HashMap<String, String>,
+}
+
+impl<L> ConditionalLoader<L> {
+    /// Create a new ConditionalLoader which applies conditions to the document
+    /// loaded by `inner`. `inner` should be a loader which can load a
+    /// `serde_json::Value`, such as `JsonLoader`.
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Set a variable which conditions can be evaluated against.
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Apply conditional sections to `value`.
+    fn apply(&self, value: Value) -> Result<Value, ConditionError> {
+        match value {
+            Value::Object(map) => {
+                let mut result = serde_json::Map::new();
+                let mut sections = vec![];
+                for (key, value) in map {
+                    match condition(&key) {
+                        Some(condition) => {
+                            if self.matches(condition).ok_or(ConditionError(key.clone()))? {
+                                sections.push((key, value));
+                            }
+                        }
+                        None => {
+                            result.insert(key, self.apply(value)?);
+                        }
+                    }
+                }
+
+                let mut result = Value::Object(result);
+                for (key, section) in sections {
+                    let section = self.apply(section)?;
+                    if !section.is_object() {
+                        return Err(ConditionError(key));
+                    }
+                    merge(&mut result, section, &MergePolicy::default());
+                }
+                Ok(result)
+            }
+            Value::Array(items) => Ok(Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.apply(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            value => Ok(value),
+        }
+    }
+
+    /// Evaluate the body of a `when(...)` key. Returns `None` if the condition
+    /// is malformed.
+    fn matches(&self, condition: &str) -> Option<bool> {
+        let mut result = true;
+        for term in condition.split(',') {
+            let (name, negate, expected) = match term.split_once("!=") {
+                Some((name, expected)) => (name, true, expected),
+                None => {
+                    let (name, expected) = term.split_once('=')?;
+                    (name, false, expected)
+                }
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+
+            let equal = self.variables.get(name).map(String::as_str) == Some(expected.trim());
+            result &= equal != negate;
+        }
+        Some(result)
+    }
+}
+
+/// If `key` is a conditional section, return the condition.
+fn condition(key: &str) -> Option<&str> {
+    key.strip_prefix("when(")?.strip_suffix(')')
+}
+
+impl<T, L> Loader<T> for ConditionalLoader<L>
+where
+    T: serde::de::DeserializeOwned + Default,
+    L: Loader<Value>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        from_value(self.apply(value)?)
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn should_apply_matching_sections() {
+        let loader = ConditionalLoader::new(())
+            .variable("env", "prod")
+            .variable("region", "us");
+
+        let value = loader
+            .apply(json!({
+                "host": "localhost",
+                "port": 80,
+                "when(env=prod)": { "host": "db.internal" },
+                "when(env=dev)": { "port": 8080 },
+                "nested": {
+                    "when(env=prod, region!=eu)": { "replicas": 3 }
+                },
+                "when(region=eu)": { "nested": { "replicas": 1 } }
+            }))
+            .unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "host": "db.internal",
+                "port": 80,
+                "nested": { "replicas": 3 }
+            })
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_conditions() {
+        let loader = ConditionalLoader::new(());
+        assert!(loader.apply(json!({ "when(env)": {} })).is_err());
+        assert!(loader.apply(json!({ "when(env!=prod)": 1 })).is_err());
+    }
+}
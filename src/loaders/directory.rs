@@ -0,0 +1,135 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{Context, FileSystem, Loader};
+
+use super::{
+    merge::{merge, ArrayMerge, MergePolicy},
+    parse,
+    value::read_value,
+};
+
+/// Loads every file in a directory (e.g. `/etc/myapp/conf.d/`), sorted by
+/// file name, and deep-merges them into a single document before
+/// deserializing it. Files later in the sort order take priority.
+///
+/// Hidden files and editor backup files (ending in `~`) are skipped. Each file
+/// is parsed based on its extension, defaulting to JSON. The directory and
+/// every file in it are registered as watched files, so adding, changing, or
+/// removing a file in the directory will cause a reload.
+#[derive(Debug)]
+pub struct DirectoryLoader {
+    dir: PathBuf,
+    extensions: Vec<String>,
+    policy: MergePolicy,
+}
+
+impl DirectoryLoader {
+    /// Create a new DirectoryLoader for the given directory.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            extensions: vec![],
+            policy: MergePolicy::default(),
+        }
+    }
+
+    /// Only load files with the given extension. This can be called more than
+    /// once to allow multiple extensions. By default all files are loaded.
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Set how arrays are combined when merging files. The default is
+    /// `ArrayMerge::Replace`.
+    pub fn array_merge(mut self, arrays: ArrayMerge) -> Self {
+        self.policy = self.policy.arrays(arrays);
+        self
+    }
+
+    /// Set how files are merged, including rules for specific paths. See
+    /// `MergePolicy`.
+    pub fn merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get the sorted list of files in the directory which should be loaded.
+    fn files(&self, fs: &dyn FileSystem) -> std::io::Result<Vec<PathBuf>> {
+        let entries = match fs.read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut files = vec![];
+        for path in entries {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let is_file = fs.metadata(&path).is_ok_and(|m| !m.is_dir);
+            if name.starts_with('.') || name.ends_with('~') || !is_file {
+                continue;
+            }
+            if !self.extensions.is_empty() {
+                let extension = path.extension().and_then(|e| e.to_str());
+                if !self
+                    .extensions
+                    .iter()
+                    .any(|e| Some(e.as_str()) == extension)
+                {
+                    continue;
+                }
+            }
+            files.push(path);
+        }
+
+        files.sort();
+        Ok(files)
+    }
+}
+
+impl<T> Loader<T> for DirectoryLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let fs = context.file_system();
+        let files = self.files(&*fs)?;
+
+        // Keep watching anything outside our directory, and replace anything
+        // inside it with the current list of files.
+        let mut watched: Vec<PathBuf> = context
+            .watched_files()
+            .into_iter()
+            .filter(|f| f != &self.dir && f.parent() != Some(&self.dir))
+            .collect();
+        watched.push(self.dir.clone());
+        watched.extend(files.iter().cloned());
+        context.update_watched_files(&watched)?;
+
+        let mut merged: Option<Value> = None;
+        for file in &files {
+            if let Some(layer) = read_value(&*fs, file)? {
+                match merged.as_mut() {
+                    Some(merged) => merge(merged, layer, &self.policy),
+                    None => merged = Some(layer),
+                }
+            }
+        }
+
+        match merged {
+            Some(merged) => Ok(parse::value(None, merged)?),
+            None => Ok(T::default()),
+        }
+    }
+}
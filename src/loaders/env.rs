@@ -0,0 +1,269 @@
+use serde::de::{
+    self,
+    value::{Error as ValueError, MapDeserializer, SeqDeserializer},
+    IntoDeserializer, Visitor,
+};
+
+use crate::{Context, Loader};
+
+use super::{load_from_buffer, to_str};
+
+/// Loads `.env` style files made up of `KEY=VALUE` lines.
+///
+/// The file can be loaded into a `HashMap<String, String>`, or into any
+/// struct that implements `Deserialize`. When loading into a struct, keys are
+/// lowercased before being matched against field names (so `PORT=8080` will
+/// fill in a `port` field), and values are parsed into numbers, booleans, or
+/// comma separated lists as required by the struct.
+///
+/// Blank lines and lines starting with `#` are ignored, and keys may
+/// optionally be prefixed with `export`. Values may be wrapped in single or
+/// double quotes; double quoted values support `\n`, `\t`, `\"` and `\\`
+/// escapes.
+#[derive(Debug)]
+pub struct EnvFileLoader;
+
+impl<T> Loader<T> for EnvFileLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| {
+            let vars = parse(to_str(contents)?)?;
+            Ok(super::parse::deserialize(
+                Some(path),
+                EnvDeserializer(vars),
+                super::parse::describe,
+            )?)
+        })
+    }
+}
+
+/// Error returned when a line in an env file can't be parsed.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid env file on line {line}: {message}")]
+pub struct EnvParseError {
+    line: usize,
+    message: &'static str,
+}
+
+/// Parse the contents of an env file into a list of key/value pairs.
+fn parse(contents: &str) -> Result<Vec<(String, String)>, EnvParseError> {
+    let mut vars = vec![];
+
+    for (index, line) in contents.lines().enumerate() {
+        let err = |message| EnvParseError {
+            line: index + 1,
+            message,
+        };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+
+        let (key, value) = line.split_once('=').ok_or(err("expected KEY=VALUE"))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(err("invalid key"));
+        }
+
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            let mut result = String::new();
+            let mut chars = quoted.chars();
+            loop {
+                match chars.next() {
+                    None => return Err(err("unterminated double quote")),
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => result.push('\n'),
+                        Some('t') => result.push('\t'),
+                        Some('r') => result.push('\r'),
+                        Some(c) => result.push(c),
+                        None => return Err(err("unterminated double quote")),
+                    },
+                    Some(c) => result.push(c),
+                }
+            }
+            result
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            match quoted.split_once('\'') {
+                Some((quoted, _)) => quoted.to_string(),
+                None => return Err(err("unterminated single quote")),
+            }
+        } else {
+            // Unquoted values can have a trailing comment.
+            match value.split_once(" #") {
+                Some((value, _)) => value.trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Deserializes a list of variables into a map or a struct.
+struct EnvDeserializer(Vec<(String, String)>);
+
+impl<'de> de::Deserializer<'de> for EnvDeserializer {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let iter = self.0.into_iter().map(|(k, v)| (k, EnvValue(v)));
+        MapDeserializer::new(iter).deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let iter = self
+            .0
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), EnvValue(v)));
+        MapDeserializer::new(iter).deserialize_any(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// A single value from an env file. Values are parsed on demand into whatever
+/// type the target struct asks for.
+struct EnvValue(String);
+
+impl<'de> IntoDeserializer<'de, ValueError> for EnvValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(err) => Err(de::Error::custom(format!("{:?}: {err}", self.0))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for EnvValue {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let items = self
+            .0
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+            .map(|item| EnvValue(item.to_string()))
+            .collect::<Vec<_>>();
+        SeqDeserializer::new(items.into_iter()).deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_env_file() {
+        let vars = parse(
+            r#"
+            # A comment
+            export HOST=localhost
+            PORT = 8080 # trailing comment
+            GREETING="hello \"world\"\n"
+            RAW='no $expansion # here'
+            EMPTY=
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vars,
+            vec![
+                ("HOST".to_string(), "localhost".to_string()),
+                ("PORT".to_string(), "8080".to_string()),
+                ("GREETING".to_string(), "hello \"world\"\n".to_string()),
+                ("RAW".to_string(), "no $expansion # here".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_line_of_invalid_entry() {
+        let err = parse("A=1\nB\n").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = parse("A=\"unterminated\n").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::Value;
+
+use crate::{Context, Loader};
+
+use super::value::{from_value, set_path};
+
+/// Wraps another loader and overrides values in the loaded document with
+/// environment variables before it is deserialized.
+///
+/// With a prefix of `MYAPP`, the variable `MYAPP__DATABASE__PORT=5432` will
+/// set `database.port` in the document to `5432`. Path segments are matched
+/// against existing keys case insensitively, and new keys are created in
+/// lower case. Values are parsed as JSON where possible (so numbers and
+/// booleans have the right type), unless they replace an existing string, in
+/// which case they are always used as strings.
+///
+/// Environment variables are read every time the file is loaded, but changes
+/// to environment variables will not trigger a reload on their own.
+#[derive(Debug)]
+pub struct EnvOverlay<L> {
+    inner: L,
+    prefix: String,
+    separator: String,
+}
+
+impl<L> EnvOverlay<L> {
+    /// Create a new EnvOverlay which applies variables starting with `prefix`
+    /// to the document loaded by `inner`. `inner` should be a loader which can
+    /// load a `serde_json::Value`, such as `JsonLoader`.
+    pub fn new(prefix: impl Into<String>, inner: L) -> Self {
+        Self {
+            inner,
+            prefix: prefix.into(),
+            separator: "__".to_string(),
+        }
+    }
+
+    /// Set the separator between the prefix and each path segment. The
+    /// default is `__`.
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Apply any matching variables from `vars` to `value`.
+    fn apply(&self, mut value: Value, vars: impl IntoIterator<Item = (String, String)>) -> Value {
+        let prefix = format!("{}{}", self.prefix, self.separator).to_ascii_lowercase();
+
+        // Sort so that `A__B` is always applied after `A`.
+        let mut vars: Vec<_> = vars
+            .into_iter()
+            .filter(|(name, _)| name.to_ascii_lowercase().starts_with(&prefix))
+            .collect();
+        vars.sort();
+
+        for (name, var) in vars {
+            let path: Vec<String> = name[prefix.len()..]
+                .split(&self.separator)
+                .map(str::to_lowercase)
+                .collect();
+            if path.iter().any(|segment| segment.is_empty()) {
+                continue;
+            }
+            set_path(&mut value, &path, var);
+        }
+
+        value
+    }
+}
+
+impl<T, L> Loader<T> for EnvOverlay<L>
+where
+    T: serde::de::DeserializeOwned + Default,
+    L: Loader<Value>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        from_value(self.apply(value, std::env::vars()))
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_override_values() {
+        let overlay = EnvOverlay::new("MYAPP", ());
+
+        let value = overlay.apply(
+            json!({
+                "database": { "Host": "localhost", "port": 80 },
+                "name": "app"
+            }),
+            vars(&[
+                ("MYAPP__DATABASE__HOST", "db.internal"),
+                ("MYAPP__DATABASE__PORT", "5432"),
+                ("MYAPP__NAME", "123"),
+                ("MYAPP__FEATURES__BETA", "true"),
+                ("OTHER__NAME", "ignored"),
+            ]),
+        );
+
+        assert_eq!(
+            value,
+            json!({
+                "database": { "Host": "db.internal", "port": 5432 },
+                "name": "123",
+                "features": { "beta": true }
+            })
+        );
+    }
+
+    #[test]
+    fn should_create_document_if_file_is_missing() {
+        let overlay = EnvOverlay::new("MYAPP", ()).separator("_");
+        let value = overlay.apply(Value::Null, vars(&[("MYAPP_PORT", "80")]));
+        assert_eq!(value, json!({ "port": 80 }));
+    }
+}
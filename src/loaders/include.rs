@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{Context, Loader};
+
+use super::{
+    merge::{merge, MergePolicy},
+    parse,
+    value::read_value,
+};
+
+/// Error returned when an include can't be resolved.
+#[derive(Debug, thiserror::Error)]
+pub enum IncludeError {
+    #[error("Included file {0:?} does not exist")]
+    MissingFile(PathBuf),
+    #[error("{key:?} in {file:?} must be a file name or a list of file names")]
+    InvalidInclude { file: PathBuf, key: String },
+    #[error("Circular include of {0:?}")]
+    Circular(PathBuf),
+}
+
+/// Resolves `"include": ["other.json", ...]` directives across files.
+///
+/// Included files are loaded relative to the file which includes them, can
+/// include other files themselves, and are deep-merged in order. The file
+/// doing the including is merged on top, so its own settings take priority
+/// over anything it includes. The include key is removed from the result. Any
+/// supported format can be included (see `Format`), so a YAML file can include
+/// a JSON file.
+///
+/// Every included file is checked with `Context::check_dependency()` before
+/// it is read, and added to the watch, so changes to any included file will
+/// cause a reload. Files which are no longer included are removed
+/// from the watch again, unless they were already being watched before they
+/// were included.
+///
+/// This can be used as a loader, in which case the first watched file is
+/// the root document, or from within a custom loader with `load_file()`.
+#[derive(Debug)]
+pub struct IncludeResolver {
+    key: String,
+    policy: MergePolicy,
+    /// The files this resolver added to the watch.
+    added: Vec<PathBuf>,
+}
+
+impl Default for IncludeResolver {
+    fn default() -> Self {
+        Self {
+            key: "include".to_string(),
+            policy: MergePolicy::default(),
+            added: vec![],
+        }
+    }
+}
+
+impl IncludeResolver {
+    /// Create a new IncludeResolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a key other than `include` for includes.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Set how included files are merged. See `MergePolicy`.
+    pub fn merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Load `path` and resolve all includes in it. Returns `None` if `path`
+    /// does not exist.
+    ///
+    /// The watched files are updated even if an include can't be loaded, so
+    /// creating a missing included file will cause a reload.
+    pub fn load_file(
+        &mut self,
+        context: &mut Context,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut included = vec![];
+        let mut stack = vec![];
+        let result = self.resolve(context, path.as_ref(), &mut included, &mut stack);
+        self.watch(context, included)?;
+        result
+    }
+
+    /// Load `file` and everything it includes, merged into one document.
+    /// `included` collects every included file, and `stack` is the chain of
+    /// files which included this one.
+    fn resolve(
+        &self,
+        context: &Context,
+        file: &Path,
+        included: &mut Vec<PathBuf>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        if stack.iter().any(|f| f == file) {
+            return Err(Box::new(IncludeError::Circular(file.to_path_buf())));
+        }
+        let Some(mut document) = read_value(&*context.file_system(), file)? else {
+            return Ok(None);
+        };
+
+        let mut merged: Option<Value> = None;
+        stack.push(file.to_path_buf());
+        for name in self.take_includes(file, &mut document)? {
+            let target = match file.parent() {
+                Some(parent) => parent.join(name),
+                None => PathBuf::from(name),
+            };
+            context.check_dependency(&target)?;
+            if !included.contains(&target) {
+                included.push(target.clone());
+            }
+
+            let layer = self
+                .resolve(context, &target, included, stack)?
+                .ok_or_else(|| IncludeError::MissingFile(target.clone()))?;
+            match merged.as_mut() {
+                Some(merged) => merge(merged, layer, &self.policy),
+                None => merged = Some(layer),
+            }
+        }
+        stack.pop();
+
+        match merged {
+            Some(mut merged) => {
+                merge(&mut merged, document, &self.policy);
+                Ok(Some(merged))
+            }
+            None => Ok(Some(document)),
+        }
+    }
+
+    /// Remove the include key from `document`, and return the files it lists.
+    fn take_includes(
+        &self,
+        file: &Path,
+        document: &mut Value,
+    ) -> Result<Vec<String>, IncludeError> {
+        let invalid = || IncludeError::InvalidInclude {
+            file: file.to_path_buf(),
+            key: self.key.clone(),
+        };
+
+        let Some(map) = document.as_object_mut() else {
+            return Ok(vec![]);
+        };
+        match map.remove(&self.key) {
+            None | Some(Value::Null) => Ok(vec![]),
+            Some(Value::String(name)) => Ok(vec![name]),
+            Some(Value::Array(names)) => names
+                .into_iter()
+                .map(|name| match name {
+                    Value::String(name) => Ok(name),
+                    _ => Err(invalid()),
+                })
+                .collect(),
+            Some(_) => Err(invalid()),
+        }
+    }
+
+    /// Replace the files this resolver previously added to the watch with
+    /// `included`.
+    fn watch(&mut self, context: &mut Context, included: Vec<PathBuf>) -> Result<(), crate::Error> {
+        let mut files: Vec<PathBuf> = context
+            .watched_files()
+            .into_iter()
+            .filter(|file| !self.added.contains(file))
+            .collect();
+
+        let added: Vec<PathBuf> = included
+            .into_iter()
+            .filter(|file| !files.contains(file))
+            .collect();
+        files.extend(added.iter().cloned());
+
+        context.update_watched_files(&files)?;
+        self.added = added;
+        Ok(())
+    }
+}
+
+impl<T> Loader<T> for IncludeResolver
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let root = context
+            .watched_files()
+            .into_iter()
+            .find(|file| !self.added.contains(file));
+        let Some(root) = root else {
+            return Ok(T::default());
+        };
+
+        match self.load_file(context, &root)? {
+            Some(value) => Ok(parse::value(Some(&root), value)?),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use serde_json::{json, Value};
+
+    use crate::{Context, MemoryFileSystem};
+
+    use super::{IncludeError, IncludeResolver};
+
+    fn load(
+        fs: &MemoryFileSystem,
+        resolver: &mut IncludeResolver,
+        watched: &mut Vec<PathBuf>,
+    ) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let modified_paths: [&Path; 0] = [];
+        let mut context = Context::for_paths(&modified_paths, watched);
+        context.set_file_system(std::sync::Arc::new(fs.clone()));
+        resolver.load_file(&mut context, "/app/config.json")
+    }
+
+    #[test]
+    fn should_merge_nested_includes() {
+        let fs = MemoryFileSystem::new();
+        fs.write(
+            "/app/config.json",
+            r#"{"include": ["base/defaults.json"], "port": 8080}"#,
+        );
+        fs.write(
+            "/app/base/defaults.json",
+            r#"{"include": "db.json", "port": 80, "host": "localhost"}"#,
+        );
+        fs.write("/app/base/db.json", r#"{"db": {"port": 5432}}"#);
+
+        let mut resolver = IncludeResolver::new();
+        let mut watched = vec![PathBuf::from("/app/config.json")];
+        let value = load(&fs, &mut resolver, &mut watched).unwrap();
+        assert_eq!(
+            value,
+            Some(json!({"port": 8080, "host": "localhost", "db": {"port": 5432}}))
+        );
+        assert_eq!(
+            watched,
+            vec![
+                PathBuf::from("/app/config.json"),
+                PathBuf::from("/app/base/defaults.json"),
+                PathBuf::from("/app/base/db.json"),
+            ]
+        );
+
+        // Files which are no longer included stop being watched.
+        fs.write("/app/config.json", r#"{"port": 8080}"#);
+        let value = load(&fs, &mut resolver, &mut watched).unwrap();
+        assert_eq!(value, Some(json!({"port": 8080})));
+        assert_eq!(watched, vec![PathBuf::from("/app/config.json")]);
+    }
+
+    #[test]
+    fn should_report_missing_and_circular_includes() {
+        let fs = MemoryFileSystem::new();
+        fs.write("/app/config.json", r#"{"include": ["missing.json"]}"#);
+
+        let mut resolver = IncludeResolver::new();
+        let mut watched = vec![PathBuf::from("/app/config.json")];
+        let err = load(&fs, &mut resolver, &mut watched).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IncludeError>(),
+            Some(IncludeError::MissingFile(_))
+        ));
+        // The missing file is watched, so creating it will cause a reload.
+        assert!(watched.contains(&PathBuf::from("/app/missing.json")));
+
+        fs.write("/app/config.json", r#"{"include": ["other.json"]}"#);
+        fs.write("/app/other.json", r#"{"include": ["config.json"]}"#);
+        let err = load(&fs, &mut resolver, &mut watched).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IncludeError>(),
+            Some(IncludeError::Circular(_))
+        ));
+    }
+}
@@ -1,8 +1,10 @@
-use std::io::BufReader;
+use std::{ops::Deref, sync::Arc};
 
-use crate::{Context, Loader};
+use serde_json::Value;
 
-use super::load_from_file;
+use crate::{Context, Error, Loader, Watch};
+
+use super::{load_from_buffer, parse, value::get_path};
 
 #[derive(Debug)]
 pub struct JsonLoader;
@@ -15,9 +17,120 @@ where
         &mut self,
         context: &mut Context,
     ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
-        load_from_file(context, |file| {
-            let reader = BufReader::new(file);
-            Ok(serde_json::from_reader(reader)?)
+        load_from_buffer(context, |path, contents| {
+            Ok(parse::json(Some(path), contents)?)
         })
     }
 }
+
+impl<T> Loader<WithRaw<T>> for JsonLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<WithRaw<T>, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| {
+            let raw: serde_json::Value = parse::json(Some(path), contents)?;
+            let value = parse::deserialize(Some(path), &raw, parse::describe_json)?;
+            Ok(WithRaw::new(raw, value))
+        })
+    }
+}
+
+/// Loads JSON files into an untyped `serde_json::Value`, for configuration
+/// whose keys are only known at runtime. This is the same as `JsonLoader`,
+/// except that it can only produce a `Watch<serde_json::Value>`, so the type
+/// of the watch doesn't need to be spelled out. See `Watch::get_path()`.
+///
+/// If the file is removed, the watch will be updated with `null`.
+#[derive(Debug)]
+pub struct JsonValueLoader;
+
+impl Loader<Value> for JsonValueLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        JsonLoader.load(context)
+    }
+}
+
+impl Watch<Value> {
+    /// Get the setting at `path` from the current document, converted to `T`.
+    /// Paths use the same format as `Error::ParseError`, such as `server.port`
+    /// or `servers[0].port`. An empty path selects the whole document.
+    ///
+    /// Returns `Ok(None)` if there is nothing at `path`, or an
+    /// `Error::ParseError` if the setting can't be converted to `T`. Keys which
+    /// contain a `.` or `[` can be read with `serde_json::Value::pointer()`
+    /// instead.
+    pub fn get_path<T>(&self, path: &str) -> Result<Option<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let document = self.value();
+        let Some(value) = get_path(&document, path) else {
+            return Ok(None);
+        };
+        T::deserialize(value)
+            .map(Some)
+            .map_err(|err| Error::ParseError {
+                path: None,
+                field_path: if path.is_empty() { "." } else { path }.to_string(),
+                line: None,
+                column: None,
+                message: err.to_string(),
+            })
+    }
+}
+
+/// A loaded value, along with the untyped document it was deserialized from.
+///
+/// Declaring a watch as a `Watch<WithRaw<T>>` instead of a `Watch<T>` keeps the
+/// parsed document around, so generic tooling (diffing, admin UIs, etc...) can
+/// inspect the configuration without having to read and parse the file again.
+/// `WithRaw<T>` derefs to `T`. This works with the JSON, YAML, and TOML
+/// loaders; YAML and TOML documents are converted to a `serde_json::Value`.
+///
+/// If the file does not exist, the raw document will be `null`.
+#[derive(Debug, Default)]
+pub struct WithRaw<T> {
+    raw: Arc<serde_json::Value>,
+    value: T,
+}
+
+impl<T> WithRaw<T> {
+    pub(crate) fn new(raw: serde_json::Value, value: T) -> Self {
+        WithRaw {
+            raw: Arc::new(raw),
+            value,
+        }
+    }
+
+    /// Get the typed value.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Get the untyped document the value was deserialized from.
+    pub fn raw(&self) -> &serde_json::Value {
+        &self.raw
+    }
+}
+
+impl<T> Deref for WithRaw<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Watch<WithRaw<T>> {
+    /// Get the untyped document the current value was deserialized from.
+    pub fn raw_value(&self) -> Arc<serde_json::Value> {
+        self.value().raw.clone()
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_json::Value;
+
+use crate::{Context, FileSystem};
+
+use super::value::read_value;
+
+/// Error returned when a `$ref` can't be resolved.
+#[derive(Debug, thiserror::Error)]
+pub enum RefError {
+    #[error("Referenced file {0:?} does not exist")]
+    MissingFile(PathBuf),
+    #[error("Reference {reference:?} in {file:?} does not point to a value")]
+    InvalidPointer { file: PathBuf, reference: String },
+    #[error("Circular reference to {0:?}")]
+    Circular(String),
+}
+
+/// Resolves `{"$ref": "other.json#/pointer"}` style references across files.
+///
+/// This is intended to be used from within a custom `Loader`. Every object
+/// with a `$ref` key is replaced by the value it refers to. References are
+/// resolved relative to the file containing them, and can point to a whole
+/// file (`"other.json"`), a value inside another file
+/// (`"other.json#/database/primary"`), or a value in the same file
+/// (`"#/defaults"`). Pointers use JSON pointer syntax.
+///
+/// Every referenced file is checked with `Context::check_dependency()` before
+/// it is read, and added to the watch via `Context::add_watched_files`, so
+/// changes to any referenced file will cause a reload. Parsed files are cached
+/// between loads, and are re-read when they appear in
+/// `Context::modified_paths()`.
+#[derive(Debug)]
+pub struct RefResolver {
+    key: String,
+    cache: HashMap<PathBuf, Arc<Value>>,
+}
+
+impl Default for RefResolver {
+    fn default() -> Self {
+        Self {
+            key: "$ref".to_string(),
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl RefResolver {
+    /// Create a new RefResolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a key other than `$ref` for references.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Load `path` and resolve all references in it. Returns `None` if `path`
+    /// does not exist.
+    pub fn load(
+        &mut self,
+        context: &mut Context,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        // Drop anything that has changed from the cache.
+        for modified in context.modified_paths() {
+            self.cache.remove(*modified);
+        }
+
+        let path = path.as_ref();
+        let Some(document) = self.document(&*context.file_system(), path)? else {
+            return Ok(None);
+        };
+
+        let mut referenced = vec![];
+        let mut stack = vec![];
+        let value = self.resolve(
+            context,
+            path,
+            &document,
+            (*document).clone(),
+            &mut referenced,
+            &mut stack,
+        )?;
+        context.add_watched_files(&referenced)?;
+
+        Ok(Some(value))
+    }
+
+    /// Get a parsed file from the cache, or read it from disk.
+    fn document(
+        &mut self,
+        fs: &dyn FileSystem,
+        path: &Path,
+    ) -> Result<Option<Arc<Value>>, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(document) = self.cache.get(path) {
+            return Ok(Some(document.clone()));
+        }
+
+        let document = read_value(fs, path)?.map(Arc::new);
+        if let Some(document) = &document {
+            self.cache.insert(path.to_path_buf(), document.clone());
+        }
+        Ok(document)
+    }
+
+    /// Replace every reference in `value`. `file` is the file `value` came
+    /// from, and `document` is the full contents of that file.
+    fn resolve(
+        &mut self,
+        context: &Context,
+        file: &Path,
+        document: &Arc<Value>,
+        value: Value,
+        referenced: &mut Vec<PathBuf>,
+        stack: &mut Vec<String>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get(&self.key) {
+                    return self.follow(context, file, document, reference, referenced, stack);
+                }
+
+                let mut result = serde_json::Map::new();
+                for (key, value) in map {
+                    let value = self.resolve(context, file, document, value, referenced, stack)?;
+                    result.insert(key, value);
+                }
+                Ok(Value::Object(result))
+            }
+            Value::Array(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| self.resolve(context, file, document, item, referenced, stack))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(items))
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Resolve a single reference found in `file`.
+    fn follow(
+        &mut self,
+        context: &Context,
+        file: &Path,
+        document: &Arc<Value>,
+        reference: &str,
+        referenced: &mut Vec<PathBuf>,
+        stack: &mut Vec<String>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let (target_file, pointer) = match reference.split_once('#') {
+            Some((target, pointer)) => (target, pointer),
+            None => (reference, ""),
+        };
+
+        let (target_file, target_document) = if target_file.is_empty() {
+            (file.to_path_buf(), document.clone())
+        } else {
+            let target_file = match file.parent() {
+                Some(parent) => parent.join(target_file),
+                None => PathBuf::from(target_file),
+            };
+            context.check_dependency(&target_file)?;
+            if !referenced.contains(&target_file) {
+                referenced.push(target_file.clone());
+            }
+            let document = self
+                .document(&*context.file_system(), &target_file)?
+                .ok_or_else(|| RefError::MissingFile(target_file.clone()))?;
+            (target_file, document)
+        };
+
+        let id = format!("{}#{pointer}", target_file.display());
+        if stack.contains(&id) {
+            return Err(Box::new(RefError::Circular(id)));
+        }
+
+        let target = target_document
+            .pointer(pointer)
+            .ok_or_else(|| RefError::InvalidPointer {
+                file: file.to_path_buf(),
+                reference: reference.to_string(),
+            })?
+            .clone();
+
+        stack.push(id);
+        let result = self.resolve(
+            context,
+            &target_file,
+            &target_document,
+            target,
+            referenced,
+            stack,
+        );
+        stack.pop();
+        result
+    }
+}
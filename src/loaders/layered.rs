@@ -0,0 +1,69 @@
+use serde_json::Value;
+
+use crate::{Context, Loader};
+
+use super::{
+    merge::{merge, ArrayMerge, MergePolicy},
+    parse,
+    value::read_value,
+};
+
+/// Loads every watched file in the order they were added to the watch, and
+/// deep-merges them into a single document before deserializing it. Later
+/// files take priority over earlier ones, so a watch on `base.json` and
+/// `override.json` will use values from `override.json` where they are set.
+///
+/// Files which don't exist are skipped, so optional layers can be watched
+/// and will be picked up when they are created. If none of the files exist,
+/// the default value is used.
+#[derive(Debug, Default)]
+pub struct LayeredLoader {
+    policy: MergePolicy,
+}
+
+impl LayeredLoader {
+    /// Create a new LayeredLoader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how arrays are combined when merging layers. The default is
+    /// `ArrayMerge::Replace`.
+    pub fn array_merge(mut self, arrays: ArrayMerge) -> Self {
+        self.policy = self.policy.arrays(arrays);
+        self
+    }
+
+    /// Set how layers are merged, including rules for specific paths. See
+    /// `MergePolicy`.
+    pub fn merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<T> Loader<T> for LayeredLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let fs = context.file_system();
+        let mut merged: Option<Value> = None;
+        for file in context.watched_files() {
+            if let Some(layer) = read_value(&*fs, &file)? {
+                match merged.as_mut() {
+                    Some(merged) => merge(merged, layer, &self.policy),
+                    None => merged = Some(layer),
+                }
+            }
+        }
+
+        match merged {
+            Some(merged) => Ok(parse::value(None, merged)?),
+            None => Ok(T::default()),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Context, Loader};
+
+/// Error returned when a manifest can't be read, or a file doesn't match it.
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error("Line {line} of manifest {manifest:?} is not a SHA-256 checksum followed by a path")]
+    InvalidLine { manifest: PathBuf, line: usize },
+    #[error("File {0:?} listed in the manifest does not exist")]
+    MissingFile(PathBuf),
+    #[error("File {path:?} has checksum {actual}, but the manifest expects {expected}")]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Wraps another loader, so a set of config files is only loaded once a
+/// manifest listing each of them and its checksum has been written, and
+/// every file matches it. This gives atomic rollouts of several files: write
+/// the files, and then the manifest.
+///
+/// The manifest is in the format written by `sha256sum`: one file per line,
+/// as a hex SHA-256 checksum, whitespace, and the path, which is relative to
+/// the manifest's folder. Blank lines and lines starting with `#` are
+/// ignored. Only the manifest is watched, so changes to the config files on
+/// their own don't cause a reload.
+///
+/// When the manifest changes, each file it lists is checked, and then the
+/// inner loader is called with those files as `Context::modified_paths()`, in
+/// the order they are listed. If a file is missing or doesn't match its
+/// checksum, the load fails with a `ManifestError` and the watch's current
+/// value is kept. If the manifest doesn't exist, the inner loader is called
+/// with no modified paths.
+///
+/// Files are read in full to check them, so this is best suited to small
+/// files. See `Builder::verify_manifest()`.
+#[derive(Debug)]
+pub struct ManifestLoader<L> {
+    inner: L,
+    manifest: PathBuf,
+}
+
+impl<L> ManifestLoader<L> {
+    /// Create a new ManifestLoader which checks files against `manifest`.
+    pub fn new(inner: L, manifest: impl AsRef<Path>) -> Self {
+        Self {
+            inner,
+            manifest: manifest.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Read the manifest, and check each file it lists. Returns the files, or
+    /// `None` if there is no manifest.
+    fn verified_files(
+        &self,
+        context: &Context,
+    ) -> Result<Option<Vec<PathBuf>>, Box<dyn std::error::Error + Send + Sync>> {
+        let fs = context.file_system();
+        let contents = match fs.read_to_string(&self.manifest) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let folder = self.manifest.parent().unwrap_or(Path::new(""));
+
+        let mut files = vec![];
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (expected, path) = parse_line(line).ok_or_else(|| ManifestError::InvalidLine {
+                manifest: self.manifest.clone(),
+                line: index + 1,
+            })?;
+            let path = folder.join(path);
+            context.check_dependency(&path)?;
+
+            let contents = match fs.read(&path) {
+                Ok(contents) => contents,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Box::new(ManifestError::MissingFile(path)))
+                }
+                Err(err) => return Err(Box::new(err)),
+            };
+            let actual = hex(&Sha256::digest(&contents));
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(Box::new(ManifestError::ChecksumMismatch {
+                    path,
+                    expected: expected.to_string(),
+                    actual,
+                }));
+            }
+            files.push(path);
+        }
+        Ok(Some(files))
+    }
+}
+
+impl<T, L> Loader<T> for ManifestLoader<L>
+where
+    L: Loader<T>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let files = self.verified_files(context)?.unwrap_or_default();
+        let modified_paths = files.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+        let inner = &mut self.inner;
+        context.with_modified_paths(&modified_paths, |context| inner.load(context))
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
+
+/// Split a `sha256sum` line into the checksum and the path. `sha256sum` puts
+/// a `*` in front of paths it read in binary mode.
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let (checksum, path) = line.split_once(char::is_whitespace)?;
+    let path = path.trim_start();
+    let path = path.strip_prefix('*').unwrap_or(path);
+    let valid = checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit());
+    (valid && !path.is_empty()).then_some((checksum, path))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_line;
+
+    const CHECKSUM: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn should_parse_sha256sum_lines() {
+        assert_eq!(
+            parse_line(&format!("{CHECKSUM}  app.json")),
+            Some((CHECKSUM, "app.json"))
+        );
+        assert_eq!(
+            parse_line(&format!("{CHECKSUM} *conf.d/db.json")),
+            Some((CHECKSUM, "conf.d/db.json"))
+        );
+        assert_eq!(parse_line("abc  app.json"), None);
+        assert_eq!(parse_line(CHECKSUM), None);
+    }
+}
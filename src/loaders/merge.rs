@@ -0,0 +1,193 @@
+use serde_json::Value;
+
+/// Controls how arrays are combined when merging documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// Arrays in later documents replace arrays in earlier documents.
+    #[default]
+    Replace,
+    /// Arrays in later documents are appended to arrays in earlier documents.
+    Append,
+    /// Items in later arrays are appended to arrays in earlier documents,
+    /// unless an equal item is already present.
+    Union,
+}
+
+/// Controls how `LayeredLoader` and `DirectoryLoader` merge documents.
+///
+/// By default maps are merged key by key, arrays follow `ArrayMerge::Replace`,
+/// and a `null` in a later document is stored like any other value. Rules can
+/// be set for specific paths, such as `server.tls`, using the same dotted
+/// format as `Error::ParseError`. A rule applies to the value at that exact
+/// path; values nested inside it use the defaults unless they have their own
+/// rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergePolicy {
+    arrays: ArrayMerge,
+    null_deletes: bool,
+    rules: Vec<(String, Rule)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    /// Replace the value from earlier documents, even if both are maps.
+    Replace,
+    /// Combine arrays at this path in a specific way.
+    Arrays(ArrayMerge),
+}
+
+impl MergePolicy {
+    /// Create a new MergePolicy with the default behavior.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how arrays are combined, for paths which don't have their own rule.
+    pub fn arrays(mut self, arrays: ArrayMerge) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    /// If true, a `null` in a later document removes the key from the merged
+    /// document, so an override file can unset a setting from the base file.
+    pub fn null_deletes(mut self, null_deletes: bool) -> Self {
+        self.null_deletes = null_deletes;
+        self
+    }
+
+    /// Replace the value at `path` as a whole, instead of merging maps key by
+    /// key. Use this for settings which only make sense as a unit, where
+    /// mixing keys from two documents would produce something invalid.
+    pub fn replace_at(mut self, path: impl Into<String>) -> Self {
+        self.rules.push((path.into(), Rule::Replace));
+        self
+    }
+
+    /// Set how the arrays at `path` are combined.
+    pub fn arrays_at(mut self, path: impl Into<String>, arrays: ArrayMerge) -> Self {
+        self.rules.push((path.into(), Rule::Arrays(arrays)));
+        self
+    }
+
+    /// Find the rule for `path`. Later rules take priority.
+    fn rule(&self, path: &str) -> Option<Rule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(rule_path, _)| rule_path == path)
+            .map(|(_, rule)| *rule)
+    }
+}
+
+impl From<ArrayMerge> for MergePolicy {
+    fn from(arrays: ArrayMerge) -> Self {
+        MergePolicy::new().arrays(arrays)
+    }
+}
+
+/// Deep-merge `layer` into `base` according to `policy`.
+pub(crate) fn merge(base: &mut Value, layer: Value, policy: &MergePolicy) {
+    merge_at(base, layer, policy, "");
+}
+
+fn merge_at(base: &mut Value, layer: Value, policy: &MergePolicy, path: &str) {
+    let rule = policy.rule(path);
+    let arrays = match rule {
+        Some(Rule::Replace) => {
+            *base = layer;
+            return;
+        }
+        Some(Rule::Arrays(arrays)) => arrays,
+        None => policy.arrays,
+    };
+
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                if value.is_null() && policy.null_deletes {
+                    base.remove(&key);
+                    continue;
+                }
+                let child_path = match path {
+                    "" => key.clone(),
+                    _ => format!("{path}.{key}"),
+                };
+                match base.get_mut(&key) {
+                    Some(existing) => merge_at(existing, value, policy, &child_path),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(layer)) => match arrays {
+            ArrayMerge::Replace => *base = layer,
+            ArrayMerge::Append => base.extend(layer),
+            ArrayMerge::Union => {
+                for item in layer {
+                    if !base.contains(&item) {
+                        base.push(item);
+                    }
+                }
+            }
+        },
+        (base, layer) => *base = layer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn should_deep_merge_maps() {
+        let mut base = json!({"a": 1, "b": {"c": 2, "d": 3}, "list": [1]});
+        merge(
+            &mut base,
+            json!({"b": {"c": 4}, "e": 5, "list": [2]}),
+            &MergePolicy::default(),
+        );
+        assert_eq!(
+            base,
+            json!({"a": 1, "b": {"c": 4, "d": 3}, "e": 5, "list": [2]})
+        );
+    }
+
+    #[test]
+    fn should_append_arrays() {
+        let mut base = json!({"list": [1]});
+        merge(&mut base, json!({"list": [2]}), &ArrayMerge::Append.into());
+        assert_eq!(base, json!({"list": [1, 2]}));
+    }
+
+    #[test]
+    fn should_apply_rules_by_path() {
+        let policy = MergePolicy::new()
+            .arrays(ArrayMerge::Append)
+            .null_deletes(true)
+            .replace_at("tls")
+            .arrays_at("server.hosts", ArrayMerge::Union);
+
+        let mut base = json!({
+            "tls": {"cert": "a.pem", "key": "a.key"},
+            "server": {"hosts": ["a", "b"], "ports": [80], "debug": true},
+        });
+        merge(
+            &mut base,
+            json!({
+                "tls": {"cert": "b.pem"},
+                "server": {"hosts": ["b", "c"], "ports": [443], "debug": null},
+            }),
+            &policy,
+        );
+        assert_eq!(
+            base,
+            json!({
+                "tls": {"cert": "b.pem"},
+                "server": {"hosts": ["a", "b", "c"], "ports": [80, 443]},
+            })
+        );
+    }
+}
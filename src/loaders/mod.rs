@@ -1,29 +1,191 @@
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "env"))]
+mod auto;
+#[cfg(feature = "json")]
+mod conditional;
+#[cfg(feature = "json")]
+mod directory;
+#[cfg(feature = "env")]
+mod env;
+#[cfg(feature = "json")]
+mod env_overlay;
+#[cfg(feature = "json")]
+mod include;
 #[cfg(feature = "json")]
 mod json;
-
 #[cfg(feature = "json")]
-pub use json::JsonLoader;
+mod json_ref;
+#[cfg(feature = "json")]
+mod layered;
+#[cfg(feature = "manifest")]
+mod manifest;
+#[cfg(feature = "json")]
+mod merge;
+#[cfg(feature = "json")]
+mod overrides;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "env"))]
+mod parse;
+#[cfg(feature = "rustls")]
+mod pem;
+#[cfg(feature = "secret")]
+mod secret;
+#[cfg(feature = "json")]
+mod strict;
+#[cfg(feature = "rustls")]
+mod tls;
+#[cfg(feature = "toml")]
+mod toml;
+#[cfg(feature = "json")]
+pub(crate) mod value;
+#[cfg(feature = "yaml")]
+mod yaml;
 
 #[cfg(feature = "json")]
+pub(crate) use auto::load_format;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "env"))]
+pub use auto::{AutoFormatLoader, Format, UnknownFormatError};
+#[cfg(feature = "json")]
+pub use conditional::{ConditionError, ConditionalLoader};
+#[cfg(feature = "json")]
+pub use directory::DirectoryLoader;
+#[cfg(feature = "env")]
+pub use env::{EnvFileLoader, EnvParseError};
+#[cfg(feature = "json")]
+pub use env_overlay::EnvOverlay;
+#[cfg(feature = "json")]
+pub use include::{IncludeError, IncludeResolver};
+#[cfg(feature = "json")]
+pub use json::{JsonLoader, JsonValueLoader, WithRaw};
+#[cfg(feature = "json")]
+pub use json_ref::{RefError, RefResolver};
+#[cfg(feature = "json")]
+pub use layered::LayeredLoader;
+#[cfg(feature = "manifest")]
+pub use manifest::{ManifestError, ManifestLoader};
+#[cfg(feature = "json")]
+pub use merge::{ArrayMerge, MergePolicy};
+#[cfg(feature = "json")]
+pub use overrides::OverrideLoader;
+#[cfg(feature = "rustls")]
+pub use pem::PemBundleLoader;
+#[cfg(feature = "secret")]
+pub use secret::{SecretLoader, SecretString};
+#[cfg(feature = "json")]
+pub use strict::StrictLoader;
+#[cfg(feature = "rustls")]
+pub use tls::{TlsError, TlsPair, TlsPairLoader};
+#[cfg(feature = "toml")]
+pub use toml::TomlLoader;
+#[cfg(feature = "yaml")]
+pub use yaml::YamlLoader;
+
+#[cfg(any(
+    feature = "json",
+    feature = "yaml",
+    feature = "toml",
+    feature = "env",
+    feature = "rustls",
+    feature = "secret"
+))]
+/// The path used in errors for the embedded default document.
+const EMBEDDED_DEFAULT: &str = "<embedded default>";
+
+#[cfg(feature = "secret")]
+/// The contents being loaded by `load_from_file`: either the file, or the
+/// watch's embedded default.
+enum Source<'a> {
+    File(Box<dyn std::io::Read + Send>),
+    Embedded(&'a [u8]),
+}
+
+#[cfg(feature = "secret")]
+impl std::io::Read for Source<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            Source::Embedded(contents) => contents.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "secret")]
+/// Load the file at `context.path()` from the watch's file system. If there is
+/// no file, this loads the watch's embedded default instead, or returns
+/// `T::default()` if there isn't one.
+///
+/// Unlike `load_from_buffer`, the file is streamed to `load`, so nothing is
+/// left in the context's scratch buffer.
 fn load_from_file<T, F>(
     context: &mut crate::Context,
     mut load: F,
 ) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
 where
-    T: serde::de::DeserializeOwned + Default,
-    F: FnMut(std::fs::File) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+    T: Default,
+    F: FnMut(&std::path::Path, Source) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
 {
-    match context.path() {
+    if let Some(path) = context.path() {
+        match context.file_system().open(path) {
+            Ok(file) => return load(path, Source::File(file)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Box::new(err)),
+        }
+    }
+
+    match context.embedded_default() {
+        Some(contents) => load(
+            std::path::Path::new(EMBEDDED_DEFAULT),
+            Source::Embedded(contents.as_bytes()),
+        ),
         None => Ok(T::default()),
-        Some(path) => match std::fs::File::open(path) {
-            Ok(file) => load(file),
-            Err(err) => {
-                if err.kind() == std::io::ErrorKind::NotFound {
-                    Ok(T::default())
-                } else {
-                    Err(Box::new(err))
-                }
+    }
+}
+
+#[cfg(any(
+    feature = "json",
+    feature = "yaml",
+    feature = "toml",
+    feature = "env",
+    feature = "rustls"
+))]
+/// Read the file at `context.path()` from the watch's file system into the
+/// context's scratch buffer, and pass its contents to `load`. If there is no
+/// file, this loads the watch's embedded default instead, or returns
+/// `T::default()` if there isn't one.
+fn load_from_buffer<T, F>(
+    context: &mut crate::Context,
+    mut load: F,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: Default,
+    F: FnMut(&std::path::Path, &[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+{
+    use std::io::Read;
+
+    if let Some(path) = context.path().map(std::path::Path::to_path_buf) {
+        match context.file_system().open(&path) {
+            Ok(mut file) => {
+                let buffer = context.buffer();
+                file.read_to_end(buffer)?;
+                return load(&path, buffer);
             }
-        },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Box::new(err)),
+        }
     }
+
+    match context.embedded_default() {
+        Some(contents) => load(std::path::Path::new(EMBEDDED_DEFAULT), contents.as_bytes()),
+        None => Ok(T::default()),
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "env"))]
+/// Convert the contents of a file to a string, with the same error as
+/// `Read::read_to_string()` if it isn't valid UTF-8.
+fn to_str(contents: &[u8]) -> std::io::Result<&str> {
+    std::str::from_utf8(contents).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
 }
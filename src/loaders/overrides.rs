@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+use crate::{Context, Loader};
+
+use super::value::{from_value, set_path};
+
+/// Wraps another loader and applies a fixed set of overrides to the loaded
+/// document every time it is loaded, before it is deserialized. This is
+/// useful for command line flags like `--log-level=debug`, which should take
+/// priority over the config file, and should survive edits to it.
+///
+/// Overrides are `key=value` pairs, where the key is a dot separated path
+/// such as `log.level`. Values are parsed as JSON where possible (so numbers
+/// and booleans have the right type), unless they replace an existing
+/// string, in which case they are always used as strings.
+#[derive(Debug)]
+pub struct OverrideLoader<L> {
+    inner: L,
+    overrides: Vec<(String, String)>,
+}
+
+impl<L> OverrideLoader<L> {
+    /// Create a new OverrideLoader which applies overrides to the document
+    /// loaded by `inner`. `inner` should be a loader which can load a
+    /// `serde_json::Value`, such as `JsonLoader`.
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            overrides: vec![],
+        }
+    }
+
+    /// Override the value at `key`. Later overrides take priority over earlier
+    /// ones.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add a set of overrides.
+    pub fn overrides<I, K, V>(mut self, overrides: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (key, value) in overrides {
+            self.overrides.push((key.into(), value.into()));
+        }
+        self
+    }
+
+    /// Apply the overrides to `value`.
+    fn apply(&self, mut value: Value) -> Value {
+        for (key, raw) in &self.overrides {
+            let path: Vec<&str> = key.split('.').collect();
+            if !path.iter().any(|segment| segment.is_empty()) {
+                set_path(&mut value, &path, raw.clone());
+            }
+        }
+        value
+    }
+}
+
+impl<T, L> Loader<T> for OverrideLoader<L>
+where
+    T: serde::de::DeserializeOwned + Default,
+    L: Loader<Value>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        from_value(self.apply(value))
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn should_apply_overrides() {
+        let loader = OverrideLoader::new(())
+            .set("log.level", "debug")
+            .overrides([("server.port", "8080"), ("server.port", "9090")]);
+
+        let value = loader.apply(json!({
+            "log": { "level": "info", "file": "app.log" },
+            "server": { "port": 80 }
+        }));
+
+        assert_eq!(
+            value,
+            json!({
+                "log": { "level": "debug", "file": "app.log" },
+                "server": { "port": 9090 }
+            })
+        );
+    }
+}
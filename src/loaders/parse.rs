@@ -0,0 +1,183 @@
+#[cfg(any(feature = "json", feature = "env", feature = "yaml"))]
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::Error;
+
+/// Deserialize a value, tracking the path to the field being deserialized so
+/// that failures can be reported as an `Error::ParseError`. `describe` turns a
+/// deserializer error into a message, and a line and column if the format
+/// provides them.
+pub(crate) fn deserialize<'de, D, T>(
+    path: Option<&Path>,
+    deserializer: D,
+    describe: impl FnOnce(D::Error) -> Described,
+) -> Result<T, Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let field_path = err.path().to_string();
+        parse_error(path, field_path, describe(err.into_inner()))
+    })
+}
+
+/// A description of an error, and where it happened.
+pub(crate) type Described = (String, Option<(usize, usize)>);
+
+/// Build an `Error::ParseError`.
+fn parse_error(path: Option<&Path>, field_path: String, described: Described) -> Error {
+    let (message, location) = described;
+    Error::ParseError {
+        path: path.map(Path::to_path_buf),
+        field_path,
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        message,
+    }
+}
+
+/// Describe an error from a format which doesn't track locations.
+#[cfg(any(feature = "json", feature = "env"))]
+pub(crate) fn describe(err: impl Display) -> Described {
+    (err.to_string(), None)
+}
+
+/// Describe an error, removing the location from the end of the message since
+/// we report it separately.
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn describe_at(err: impl Display, location: Option<(usize, usize)>) -> Described {
+    let mut message = err.to_string();
+    if let Some((line, column)) = location {
+        let suffix = format!(" at line {line} column {column}");
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_string();
+        }
+    }
+    (message, location)
+}
+
+/// Describe a JSON error.
+#[cfg(feature = "json")]
+pub(crate) fn describe_json(err: serde_json::Error) -> Described {
+    // serde_json uses line 0 for errors which didn't come from the input.
+    let location = (err.line() > 0).then(|| (err.line(), err.column()));
+    describe_at(err, location)
+}
+
+/// Describe a YAML error.
+#[cfg(feature = "yaml")]
+pub(crate) fn describe_yaml(err: serde_yaml_ng::Error) -> Described {
+    let location = err.location().map(|l| (l.line(), l.column()));
+    describe_at(err, location)
+}
+
+/// Describe a TOML error, given the document it came from.
+#[cfg(feature = "toml")]
+pub(crate) fn describe_toml(contents: &str, err: toml::de::Error) -> Described {
+    let location = err.span().map(|span| {
+        let before = &contents[..span.start.min(contents.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+        (line, column)
+    });
+    (err.message().to_string(), location)
+}
+
+/// Parse a JSON document.
+#[cfg(feature = "json")]
+pub(crate) fn json<T>(path: Option<&Path>, reader: impl std::io::Read) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let value = deserialize(path, &mut deserializer, describe_json)?;
+    deserializer
+        .end()
+        .map_err(|err| parse_error(path, ".".to_string(), describe_json(err)))?;
+    Ok(value)
+}
+
+/// Deserialize an untyped JSON document.
+#[cfg(feature = "json")]
+pub(crate) fn value<T>(path: Option<&Path>, value: serde_json::Value) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    deserialize(path, value, describe)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "json")]
+    use std::path::Path;
+
+    use serde::Deserialize;
+
+    #[cfg(any(feature = "json", feature = "toml"))]
+    use crate::Error;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Server {
+        port: u16,
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn should_report_json_field_path() {
+        let contents = "{\"servers\": [\n  {\"port\": \"http\"}\n]}";
+        let err = super::json::<Config>(Some(Path::new("config.json")), contents.as_bytes());
+        let Err(Error::ParseError {
+            path,
+            field_path,
+            line,
+            column,
+            message,
+        }) = err
+        else {
+            panic!("Expected a parse error, got {err:?}");
+        };
+
+        assert_eq!(path.as_deref(), Some(Path::new("config.json")));
+        assert_eq!(field_path, "servers[0].port");
+        assert_eq!(line, Some(2));
+        assert_eq!(column, Some(17));
+        assert_eq!(message, "invalid type: string \"http\", expected u16");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn should_report_toml_location() {
+        let contents = "[[servers]]\nport = 80\n\n[[servers]]\nport = \"http\"\n";
+        let deserializer = toml::Deserializer::new(contents);
+        let err = super::deserialize::<_, Config>(None, deserializer, |err| {
+            super::describe_toml(contents, err)
+        })
+        .unwrap_err();
+
+        let Error::ParseError {
+            field_path,
+            line,
+            column,
+            message,
+            ..
+        } = err
+        else {
+            panic!("Expected a parse error, got {err:?}");
+        };
+        assert_eq!(field_path, "servers[1].port");
+        assert_eq!(line, Some(5));
+        assert_eq!(column, Some(8));
+        assert_eq!(message, "invalid type: string \"http\", expected u16");
+    }
+}
@@ -0,0 +1,25 @@
+use rustls::pki_types::{pem::PemObject, CertificateDer};
+
+use crate::{Context, Loader};
+
+use super::load_from_buffer;
+
+/// Loads every certificate from a PEM file, such as a CA bundle or a client
+/// certificate chain. Anything in the file which isn't a certificate (such as
+/// a private key) is ignored.
+///
+/// If the file is removed, the watch will be updated with an empty list. If
+/// the file cannot be parsed, the watch's current value will be unchanged.
+#[derive(Debug)]
+pub struct PemBundleLoader;
+
+impl Loader<Vec<CertificateDer<'static>>> for PemBundleLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |_path, contents| {
+            Ok(CertificateDer::pem_slice_iter(contents).collect::<Result<Vec<_>, _>>()?)
+        })
+    }
+}
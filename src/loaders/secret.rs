@@ -0,0 +1,96 @@
+use std::{fmt, io::Read};
+
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::{Context, Loader};
+
+use super::load_from_file;
+
+/// A secret string, such as a password or API token. The contents are wiped
+/// from memory when the value is dropped, and are never printed by `Debug`.
+///
+/// Since a watch drops its old value once nothing is using it any more, a
+/// rotated secret doesn't linger in memory after a reload.
+#[derive(Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Create a new secret.
+    pub fn new(secret: String) -> Self {
+        Self(secret)
+    }
+
+    /// Get the secret. Take care not to copy the result anywhere it won't be
+    /// wiped.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns true if the secret is empty, such as when the file is missing.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl Zeroize for SecretString {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretString {}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+/// Loads the contents of a file, such as a mounted Kubernetes secret, into a
+/// `SecretString`. A trailing newline is removed. The file is read into a
+/// buffer sized to fit it, so no partial copies of the secret are left behind.
+///
+/// If the file is removed, the watch will be updated with an empty secret. If
+/// the file cannot be read or isn't valid UTF-8, the watch's current value
+/// will be unchanged.
+#[derive(Debug)]
+pub struct SecretLoader;
+
+impl Loader<SecretString> for SecretLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<SecretString, Box<dyn std::error::Error + Send + Sync>> {
+        let len = match context.path() {
+            Some(path) => context.file_system().metadata(path).map_or(0, |m| m.len),
+            None => 0,
+        } as usize;
+        load_from_file(context, |_path, mut file| {
+            // Read one byte more than the file's size, so we can detect EOF
+            // without growing the buffer.
+            let mut contents = Zeroizing::new(Vec::with_capacity(len + 1));
+            file.read_to_end(&mut contents)?;
+
+            while contents.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                contents.pop();
+            }
+
+            let secret = std::str::from_utf8(&contents).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "secret is not valid UTF-8")
+            })?;
+            Ok(SecretString::new(secret.to_string()))
+        })
+    }
+}
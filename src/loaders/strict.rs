@@ -0,0 +1,152 @@
+use serde_json::Value;
+
+use crate::{Context, Error, Loader};
+
+use super::{parse, JsonLoader};
+
+/// Wraps another loader, and rejects documents which contain keys the target
+/// type doesn't know about, even if the type doesn't use
+/// `#[serde(deny_unknown_fields)]`. This catches typos in config files (such
+/// as `prot` instead of `port`), which would otherwise be silently ignored.
+///
+/// Unknown keys are reported as an `Error::ParseError`, with `field_path` set
+/// to the first unknown key, so the watch keeps its current value.
+#[derive(Debug)]
+pub struct StrictLoader<L> {
+    inner: L,
+}
+
+impl<L> StrictLoader<L> {
+    /// Create a new StrictLoader which checks the document loaded by `inner`.
+    /// `inner` should be a loader which can load a `serde_json::Value`, such
+    /// as `JsonLoader`.
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl JsonLoader {
+    /// Create a loader which loads JSON files, and rejects any document with
+    /// keys the target type doesn't know about. See `StrictLoader`.
+    pub fn strict() -> StrictLoader<JsonLoader> {
+        StrictLoader::new(JsonLoader)
+    }
+}
+
+impl<T, L> Loader<T> for StrictLoader<L>
+where
+    T: serde::de::DeserializeOwned + Default,
+    L: Loader<Value>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        if value.is_null() {
+            return Ok(T::default());
+        }
+
+        let mut unknown = vec![];
+        let mut record = |path: serde_ignored::Path| unknown.push(format_path(&path));
+        let deserializer = serde_ignored::Deserializer::new(value, &mut record);
+        let result = parse::deserialize(context.path(), deserializer, parse::describe_json)?;
+
+        match unknown.first() {
+            None => Ok(result),
+            Some(first) => {
+                let message = match unknown.len() {
+                    1 => "unknown field".to_string(),
+                    _ => format!("unknown fields {}", unknown.join(", ")),
+                };
+                Err(Box::new(Error::ParseError {
+                    path: context.path().map(|p| p.to_path_buf()),
+                    field_path: first.clone(),
+                    line: None,
+                    column: None,
+                    message,
+                }))
+            }
+        }
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
+
+/// Format a path the same way as `serde_path_to_error`, such as
+/// `servers[0].port`.
+fn format_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => ".".to_string(),
+        Path::Seq { parent, index } => match parent {
+            Path::Root => format!("[{index}]"),
+            parent => format!("{}[{index}]", format_path(parent)),
+        },
+        Path::Map { parent, key } => match parent {
+            Path::Root => key.clone(),
+            parent => format!("{}.{key}", format_path(parent)),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => format_path(parent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde::Deserialize;
+
+    use crate::{Context, Error, Loader};
+
+    use super::StrictLoader;
+
+    #[derive(Debug, Default, Deserialize)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    struct Server {
+        port: u16,
+    }
+
+    fn load(contents: &str) -> Result<Config, Error> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, contents).unwrap();
+
+        let modified_paths = [path.as_path()];
+        let mut watch_paths = vec![];
+        let mut context = Context::for_paths(&modified_paths, &mut watch_paths);
+        StrictLoader::new(crate::JsonLoader)
+            .load(&mut context)
+            .map_err(Error::from_load_error)
+    }
+
+    #[test]
+    fn should_accept_known_fields() {
+        let config = load(r#"{"servers": [{"port": 80}]}"#).unwrap();
+        assert_eq!(config.servers[0].port, 80);
+    }
+
+    #[test]
+    fn should_reject_unknown_fields() {
+        let err = load(r#"{"servers": [{"port": 80}, {"prot": 81, "port": 82}]}"#).unwrap_err();
+        let Error::ParseError {
+            field_path,
+            message,
+            ..
+        } = err
+        else {
+            panic!("Expected a parse error, got {err:?}");
+        };
+        assert_eq!(field_path, "servers[1].prot");
+        assert_eq!(message, "unknown field");
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    sign::CertifiedKey,
+};
+
+use crate::{Context, FileSystem, Loader};
+
+/// Error returned when a certificate and key can't be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("{0:?} does not exist")]
+    MissingFile(PathBuf),
+    #[error("Error reading {0:?}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("No certificates found in {0:?}")]
+    NoCertificates(PathBuf),
+    #[error("Invalid PEM in {0:?}: {1}")]
+    InvalidPem(PathBuf, rustls::pki_types::pem::Error),
+    #[error("No rustls CryptoProvider has been installed")]
+    NoCryptoProvider,
+    #[error("Invalid certificate or key: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// A certificate chain and private key, as DER.
+#[derive(Debug, Default)]
+pub struct TlsPair {
+    /// The certificate chain, starting with the end-entity certificate.
+    pub certs: Vec<CertificateDer<'static>>,
+    /// The private key for the end-entity certificate. This is `None` if the
+    /// certificate and key files don't exist.
+    pub key: Option<PrivateKeyDer<'static>>,
+}
+
+/// Loads a PEM certificate chain and a PEM private key from two separate
+/// files, and checks that the key matches the certificate.
+///
+/// This can load a `TlsPair` with the raw DER, or an
+/// `Option<Arc<CertifiedKey>>` which can be handed to a rustls
+/// `ResolvesServerCert` implementation. If neither file exists, the default
+/// value is used.
+///
+/// Certificates are often rotated by writing the new certificate and then
+/// the new key (or vice versa), so for a short time the two files don't
+/// match. If the files can't be loaded or don't match, the loader waits and
+/// tries again, and only reports an error if they still don't match after
+/// every retry. By default this retries 10 times, 100ms apart.
+///
+/// Both files are added to the watch. Use `Builder::load_tls_pair()` to set
+/// up a watch for a certificate and key.
+#[derive(Debug)]
+pub struct TlsPairLoader {
+    cert: PathBuf,
+    key: PathBuf,
+    retries: u32,
+    retry_delay: Duration,
+    provider: Option<Arc<CryptoProvider>>,
+}
+
+impl TlsPairLoader {
+    /// Create a new TlsPairLoader for the given certificate and key files.
+    pub fn new(cert: impl AsRef<Path>, key: impl AsRef<Path>) -> Self {
+        Self {
+            cert: cert.as_ref().to_path_buf(),
+            key: key.as_ref().to_path_buf(),
+            retries: 10,
+            retry_delay: Duration::from_millis(100),
+            provider: None,
+        }
+    }
+
+    /// Set how many times to retry if the certificate and key don't match,
+    /// and how long to wait between retries.
+    pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Set the CryptoProvider used to parse and check the private key. By
+    /// default the process-wide default provider is used.
+    pub fn provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Load the certificate and key, retrying if they don't match. Returns
+    /// `None` if neither file exists.
+    fn load_pair(
+        &self,
+        context: &mut Context,
+    ) -> Result<
+        Option<(CertifiedKey, PrivateKeyDer<'static>)>,
+        Box<dyn std::error::Error + Send + Sync>,
+    > {
+        context.add_watched_files(&[&self.cert, &self.key])?;
+
+        let provider = match &self.provider {
+            Some(provider) => provider.clone(),
+            None => CryptoProvider::get_default()
+                .cloned()
+                .ok_or(TlsError::NoCryptoProvider)?,
+        };
+
+        let fs = context.file_system();
+        let mut attempt = 0;
+        loop {
+            match self.try_load(&*fs, &provider) {
+                Err(_) if attempt < self.retries => {
+                    attempt += 1;
+                    thread::sleep(self.retry_delay);
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    fn try_load(
+        &self,
+        fs: &dyn FileSystem,
+        provider: &CryptoProvider,
+    ) -> Result<Option<(CertifiedKey, PrivateKeyDer<'static>)>, TlsError> {
+        let (cert, key) = match (read(fs, &self.cert)?, read(fs, &self.key)?) {
+            (None, None) => return Ok(None),
+            (None, _) => return Err(TlsError::MissingFile(self.cert.clone())),
+            (_, None) => return Err(TlsError::MissingFile(self.key.clone())),
+            (Some(cert), Some(key)) => (cert, key),
+        };
+
+        let certs = CertificateDer::pem_slice_iter(&cert)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| TlsError::InvalidPem(self.cert.clone(), err))?;
+        if certs.is_empty() {
+            return Err(TlsError::NoCertificates(self.cert.clone()));
+        }
+        let key = PrivateKeyDer::from_pem_slice(&key)
+            .map_err(|err| TlsError::InvalidPem(self.key.clone(), err))?;
+
+        let certified = CertifiedKey::from_der(certs, key.clone_key(), provider)?;
+        Ok(Some((certified, key)))
+    }
+}
+
+/// Read a file, returning `None` if it does not exist.
+fn read(fs: &dyn FileSystem, path: &Path) -> Result<Option<Vec<u8>>, TlsError> {
+    match fs.read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(TlsError::Io(path.to_path_buf(), err)),
+    }
+}
+
+impl Loader<Option<Arc<CertifiedKey>>> for TlsPairLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<Option<Arc<CertifiedKey>>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self
+            .load_pair(context)?
+            .map(|(certified, _key)| Arc::new(certified)))
+    }
+}
+
+impl Loader<TlsPair> for TlsPairLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<TlsPair, Box<dyn std::error::Error + Send + Sync>> {
+        match self.load_pair(context)? {
+            None => Ok(TlsPair::default()),
+            Some((certified, key)) => Ok(TlsPair {
+                certs: certified.cert,
+                key: Some(key),
+            }),
+        }
+    }
+}
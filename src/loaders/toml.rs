@@ -0,0 +1,50 @@
+use crate::{Context, Loader};
+
+use super::{load_from_buffer, parse, to_str};
+
+/// Deserialize a TOML document.
+fn deserialize<T>(path: &std::path::Path, contents: &str) -> Result<T, crate::Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let deserializer = toml::Deserializer::new(contents);
+    parse::deserialize(Some(path), deserializer, |err| {
+        parse::describe_toml(contents, err)
+    })
+}
+
+#[derive(Debug)]
+pub struct TomlLoader;
+
+impl<T> Loader<T> for TomlLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| {
+            Ok(deserialize(path, to_str(contents)?)?)
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> Loader<super::WithRaw<T>> for TomlLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<super::WithRaw<T>, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| {
+            // Parse the document twice, rather than deserializing the typed
+            // value from the raw one, so errors have line numbers.
+            let contents = to_str(contents)?;
+            let raw = deserialize(path, contents)?;
+            Ok(super::WithRaw::new(raw, deserialize(path, contents)?))
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use std::{io::ErrorKind, path::Path};
+
+use serde_json::Value;
+
+use crate::FileSystem;
+
+use super::{parse, Format};
+
+/// Read a file into an untyped document. The format of the file is picked from
+/// its extension (YAML and TOML are supported if enabled), defaulting to JSON.
+/// Returns `None` if the file does not exist.
+pub(crate) fn read_value(
+    fs: &dyn FileSystem,
+    path: &Path,
+) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let contents = match fs.read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Box::new(err)),
+    };
+
+    let value = match Format::from_path(path) {
+        #[cfg(feature = "yaml")]
+        Some(Format::Yaml) => parse::deserialize(
+            Some(path),
+            serde_yaml_ng::Deserializer::from_str(&contents),
+            parse::describe_yaml,
+        )?,
+        #[cfg(feature = "toml")]
+        Some(Format::Toml) => {
+            parse::deserialize(Some(path), toml::Deserializer::new(&contents), |err| {
+                parse::describe_toml(&contents, err)
+            })?
+        }
+        _ => parse::json(Some(path), contents.as_bytes())?,
+    };
+
+    Ok(Some(value))
+}
+
+/// Deserialize a document produced by another loader. A `null` document is
+/// what a loader returns when the file does not exist, so this is turned into
+/// the default value.
+pub(crate) fn from_value<T>(value: Value) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    match value {
+        Value::Null => Ok(T::default()),
+        value => Ok(parse::value(None, value)?),
+    }
+}
+
+/// Set the value at `path` inside `value`, creating objects as required. Path
+/// segments are matched against existing keys case insensitively. `raw` is
+/// parsed as JSON where possible, unless it replaces an existing string.
+pub(crate) fn set_path(value: &mut Value, path: &[impl AsRef<str>], raw: String) {
+    let Some((segment, rest)) = path.split_first() else {
+        *value = match value {
+            Value::String(_) => Value::String(raw),
+            _ => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+        };
+        return;
+    };
+    let segment = segment.as_ref();
+
+    if !value.is_object() {
+        *value = Value::Object(serde_json::Map::new());
+    }
+    let Value::Object(map) = value else {
+        unreachable!()
+    };
+
+    let key = if map.contains_key(segment) {
+        segment.to_string()
+    } else {
+        map.keys()
+            .find(|key| key.eq_ignore_ascii_case(segment))
+            .cloned()
+            .unwrap_or_else(|| segment.to_string())
+    };
+    set_path(map.entry(key).or_insert(Value::Null), rest, raw);
+}
+
+/// Find the value at a path such as `servers[0].port` inside `value`. Array
+/// elements can also be selected with a numeric segment, as in
+/// `servers.0.port`. An empty path selects the whole document. Returns `None`
+/// if nothing is at the path.
+pub(crate) fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path.split('.') {
+        let (key, indices) = match segment.find('[') {
+            Some(start) => segment.split_at(start),
+            None => (segment, ""),
+        };
+
+        if !key.is_empty() {
+            current = match current {
+                Value::Object(map) => map.get(key)?,
+                Value::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        for index in indices.split_terminator(']') {
+            let index = index.strip_prefix('[')?.parse::<usize>().ok()?;
+            current = current.as_array()?.get(index)?;
+        }
+    }
+    Some(current)
+}
@@ -0,0 +1,44 @@
+use crate::{Context, Loader};
+
+use super::{load_from_buffer, parse};
+
+/// Deserialize a YAML document.
+fn deserialize<T>(path: &std::path::Path, contents: &[u8]) -> Result<T, crate::Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let deserializer = serde_yaml_ng::Deserializer::from_slice(contents);
+    parse::deserialize(Some(path), deserializer, parse::describe_yaml)
+}
+
+#[derive(Debug)]
+pub struct YamlLoader;
+
+impl<T> Loader<T> for YamlLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| Ok(deserialize(path, contents)?))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> Loader<super::WithRaw<T>> for YamlLoader
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<super::WithRaw<T>, Box<dyn std::error::Error + Send + Sync>> {
+        load_from_buffer(context, |path, contents| {
+            // As for TOML, parse twice so errors have line numbers.
+            let raw = deserialize(path, contents)?;
+            Ok(super::WithRaw::new(raw, deserialize(path, contents)?))
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    reloader::{Listener, ReloaderGuard},
+    Context, Error, Guard, UpdatedHandler, Watch,
+};
+
+/// A value derived from one or more watches, created with `Watch::map()`,
+/// `Watch::combine()`, or `Watch::zip()`.
+///
+/// For `map()`, after each reload the part is projected out of the new value
+/// and compared with its previous contents. Only if it changed is it stored
+/// and passed to the view's subscribers, so a subsystem can depend on just its
+/// slice of the config without being told about changes to the rest. For
+/// `combine()` and `zip()`, the value is rebuilt whenever either watch is
+/// updated.
+///
+/// The view shares its watches' watchers and loaders, and stops updating once
+/// every clone of its watches has been dropped.
+pub struct Mapped<U> {
+    inner: Arc<Inner<U>>,
+}
+
+/// The pair of values returned by `Watch::zip()`.
+pub type Zipped<A, B> = Mapped<(Arc<A>, Arc<B>)>;
+
+struct Inner<U> {
+    value: ArcSwap<U>,
+    subscribers: Mutex<Vec<Box<dyn UpdatedHandler<U> + Send>>>,
+}
+
+impl<U> Inner<U> {
+    fn new(value: U) -> Self {
+        Self {
+            value: ArcSwap::from_pointee(value),
+            subscribers: Mutex::new(vec![]),
+        }
+    }
+
+    /// Pass the current value to each subscriber.
+    fn notify(&self, context: &mut Context) {
+        for subscriber in self.subscribers.lock().unwrap().iter_mut() {
+            subscriber.after_update(context, self.value.load());
+        }
+    }
+}
+
+impl<U> Clone for Mapped<U> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<U> fmt::Debug for Mapped<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mapped").finish_non_exhaustive()
+    }
+}
+
+impl<U> Mapped<U> {
+    /// Produces a temporary borrow of the current value of this view.
+    pub fn value(&self) -> Guard<U> {
+        self.inner.value.load()
+    }
+
+    /// Add a handler to call whenever this part of the value changes.
+    /// Handlers are called on the watch's thread, after the new value of the
+    /// view has been stored and before the watch's own `after_update`
+    /// handler.
+    pub fn subscribe(&self, handler: impl UpdatedHandler<U> + Send + 'static) {
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Box::new(handler));
+    }
+}
+
+impl<T> Watch<T> {
+    /// Create a view of the part of this watch's value returned by
+    /// `project`, such as `|config| &config.database`. The part is cloned out
+    /// of each new value, and the view is only updated when it isn't equal to
+    /// the previous part. See `Mapped`.
+    ///
+    /// This will block while a reload is in progress. If it is called from
+    /// within a loader or handler while this watch is busy, it returns
+    /// `Error::WouldDeadlock` instead.
+    pub fn map<U, F>(&self, project: F) -> Result<Mapped<U>, Error>
+    where
+        U: Clone + PartialEq + Send + Sync + 'static,
+        F: Fn(&T) -> &U + Send + 'static,
+    {
+        let mut reloader = ReloaderGuard::lock(&self.reloader)?;
+        let mapped = Mapped {
+            inner: Arc::new(Inner::new(project(&self.value.load()).clone())),
+        };
+
+        let weak = Arc::downgrade(&mapped.inner);
+        reloader.add_listener(Box::new(move |context, value, _error_handler| {
+            let Some(inner) = weak.upgrade() else {
+                return false;
+            };
+
+            let part = project(value);
+            if **inner.value.load() == *part {
+                return true;
+            }
+            inner.value.store(Arc::new(part.clone()));
+            inner.notify(context);
+            true
+        }));
+
+        Ok(mapped)
+    }
+
+    /// Create a value derived from this watch and `other` with `merge`, such
+    /// as a config with its secrets filled in from a second file. `merge` is
+    /// called with both current values whenever either watch is updated, on
+    /// the updated watch's thread, so it should be quick. See `Mapped`.
+    ///
+    /// Returns `Error::WouldDeadlock` if called from within a loader or
+    /// handler while either watch is busy.
+    pub fn combine<B, C, F>(&self, other: &Watch<B>, mut merge: F) -> Result<Mapped<C>, Error>
+    where
+        T: Send + Sync + 'static,
+        B: Send + Sync + 'static,
+        C: Send + Sync + 'static,
+        F: FnMut(&T, &B) -> C + Send + 'static,
+    {
+        self.combine_arcs(other, move |a, b| merge(&a, &b))
+    }
+
+    /// Combine this watch and `other` into a pair of their values, which is
+    /// replaced whenever either watch is updated. See `combine()`.
+    pub fn zip<B>(&self, other: &Watch<B>) -> Result<Zipped<T, B>, Error>
+    where
+        T: Send + Sync + 'static,
+        B: Send + Sync + 'static,
+    {
+        self.combine_arcs(other, |a, b| (a, b))
+    }
+
+    fn combine_arcs<B, C, F>(&self, other: &Watch<B>, merge: F) -> Result<Mapped<C>, Error>
+    where
+        T: Send + Sync + 'static,
+        B: Send + Sync + 'static,
+        C: Send + Sync + 'static,
+        F: FnMut(Arc<T>, Arc<B>) -> C + Send + 'static,
+    {
+        let a = self.value.clone();
+        let b = other.value.clone();
+        let merge = Arc::new(Mutex::new(merge));
+        let initial = (merge.lock().unwrap())(a.load_full(), b.load_full());
+        // Both values are read, and the result stored, while `merge` is
+        // locked, so whichever watch updates last stores a value built from
+        // the latest of each.
+        let rebuild = move |inner: &Inner<C>| {
+            let mut merge = merge.lock().unwrap();
+            inner
+                .value
+                .store(Arc::new(merge(a.load_full(), b.load_full())));
+        };
+
+        let combined = Mapped {
+            inner: Arc::new(Inner::new(initial)),
+        };
+        // Only one reloader can be locked at a time, so the listeners are
+        // added one after the other. The value is rebuilt once both are in
+        // place, in case `self` was updated in between.
+        ReloaderGuard::lock(&self.reloader)?
+            .add_listener(combine_listener(&combined.inner, rebuild.clone()));
+        let mut reloader = ReloaderGuard::lock(&other.reloader)?;
+        reloader.add_listener(combine_listener(&combined.inner, rebuild.clone()));
+        rebuild(&combined.inner);
+        drop(reloader);
+
+        Ok(combined)
+    }
+}
+
+/// Create a listener which rebuilds a combined value.
+fn combine_listener<S, C>(
+    inner: &Arc<Inner<C>>,
+    rebuild: impl Fn(&Inner<C>) + Send + 'static,
+) -> Listener<S>
+where
+    C: Send + Sync + 'static,
+{
+    let weak = Arc::downgrade(inner);
+    Box::new(move |context, _value, _error_handler| {
+        let Some(inner) = weak.upgrade() else {
+            return false;
+        };
+        rebuild(&inner);
+        inner.notify(context);
+        true
+    })
+}
@@ -0,0 +1,87 @@
+//! Detects folders on network file systems, where native file notifications
+//! only report changes made by this machine.
+
+use std::path::Path;
+
+/// Get the name of the network file system `path` is on, such as `"nfs"`, or
+/// `None` if it's on a local file system or this can't be determined.
+pub(crate) fn network_fs_type(path: &Path) -> Option<&'static str> {
+    imp::network_fs_type(path)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt, path::Path};
+
+    /// Magic numbers from `statfs(2)` for network file systems.
+    const NETWORK_FILE_SYSTEMS: &[(u32, &str)] = &[
+        (0x6969, "nfs"),
+        (0x517b, "smb"),
+        (0xff53_4d42, "cifs"),
+        (0xfe53_4d42, "smb2"),
+        (0x0102_1997, "9p"),
+        (0x5346_414f, "afs"),
+        (0x00c3_6400, "ceph"),
+        (0x7375_7245, "coda"),
+        (0x564c, "ncp"),
+    ];
+
+    pub fn network_fs_type(path: &Path) -> Option<&'static str> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `path` is a valid C string, and `stat` is only read if the
+        // call succeeded and filled it in.
+        let stat = unsafe {
+            if libc::statfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return None;
+            }
+            stat.assume_init()
+        };
+        // The type of `f_type` varies between architectures, but the magic
+        // numbers all fit in 32 bits.
+        #[allow(clippy::unnecessary_cast)]
+        let magic = stat.f_type as u32;
+        NETWORK_FILE_SYSTEMS
+            .iter()
+            .find(|(m, _)| *m == magic)
+            .map(|(_, name)| *name)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::{
+        ffi::{CStr, CString},
+        mem::MaybeUninit,
+        os::unix::ffi::OsStrExt,
+        path::Path,
+    };
+
+    const NETWORK_FILE_SYSTEMS: &[&str] = &["nfs", "smbfs", "afpfs", "webdav", "cifs"];
+
+    pub fn network_fs_type(path: &Path) -> Option<&'static str> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `path` is a valid C string, and `stat` is only read if the
+        // call succeeded and filled it in.
+        let stat = unsafe {
+            if libc::statfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return None;
+            }
+            stat.assume_init()
+        };
+        // SAFETY: `f_fstypename` is a nul terminated string.
+        let name = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+        let name = name.to_str().ok()?;
+        NETWORK_FILE_SYSTEMS.iter().find(|n| **n == name).copied()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod imp {
+    use std::path::Path;
+
+    pub fn network_fs_type(_path: &Path) -> Option<&'static str> {
+        None
+    }
+}
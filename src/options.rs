@@ -0,0 +1,273 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The default time to wait after a change before calling the loader.
+pub(crate) const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Settings which control how files are watched. These can be set with the
+/// `Builder`, and changed on a live watch with `Watch::reconfigure()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WatcherOptions {
+    /// How long to collect events for after the first change, before calling
+    /// the loader with every file that changed. Later events don't restart
+    /// the timer, so files which are written continuously can't put off a
+    /// reload for longer than this. If this is `None`, the loader is called
+    /// as soon as each event arrives. The default is 100ms.
+    pub debounce: Option<Duration>,
+    /// If this is longer than `debounce`, the debounce duration adapts to how
+    /// files are written. It starts at `debounce`, and doubles (up to
+    /// `max_debounce`) whenever more writes to the same file arrive just
+    /// after a batch was delivered, such as while a large file is written in
+    /// pieces. Once there have been no changes for a few debounce periods, it
+    /// halves again until it is back to `debounce`. The duration currently in
+    /// use is reported by `Watch::stats()`. The default is `None`.
+    pub max_debounce: Option<Duration>,
+    /// The shortest time between two reloads caused by file changes, however
+    /// often files change. Changes which arrive sooner are held until the
+    /// interval has passed, and then loaded together, so the final state of
+    /// every file is always loaded. Events which don't cause a reload, such
+    /// as an editor's swap files changing, don't start the interval. The
+    /// default is `None`.
+    pub min_reload_interval: Option<Duration>,
+    /// Whether the loader is called at the end of the debounce duration, or
+    /// straight away at the start of it. The default is
+    /// `DebounceMode::Trailing`.
+    pub debounce_mode: DebounceMode,
+    /// How changes to files are detected.
+    pub backend: Backend,
+    /// How the `Backend::Poll` backend decides whether a file has changed.
+    /// This has no effect on the native backend.
+    pub poll_compare: PollCompare,
+    /// Glob patterns for paths to ignore, such as `*.swp`. A pattern which
+    /// contains a `/` is matched against the full path of the changed file,
+    /// otherwise it is matched against the file name.
+    pub ignore: Vec<String>,
+    /// Run loaders for file changes with reduced CPU and IO priority (nice
+    /// and IO priority on Linux, background QoS on macOS), so a large reload
+    /// doesn't compete with latency sensitive threads. The initial load
+    /// still runs on the thread which builds the watch. On Linux, once
+    /// priority has been lowered it can't be raised again without elevated
+    /// privileges, so turning this off with `Watch::reconfigure()` may have
+    /// no effect.
+    pub low_priority: bool,
+    /// Expand a leading `~` to the home directory, and `$VAR`, `${VAR}`, and
+    /// `%VAR%` to the value of the environment variable, in watched files and
+    /// glob patterns. This applies to paths passed to the `Builder`, and to
+    /// every later call to `update_watched_files()` or `add_watched_files()`.
+    /// Variables which aren't set are left unchanged. Changing this on a live
+    /// watch resolves the watched paths again, as for `base_dir`. The default
+    /// is false.
+    pub expand_paths: bool,
+    /// The folder relative watched files and glob patterns are resolved
+    /// against, including paths added later by a loader. If this is `None`,
+    /// relative paths are used as they are, so they depend on the process's
+    /// current directory. Changing this on a live watch resolves the watched
+    /// paths again, and loads any which now point to a different file. The
+    /// default is `None`.
+    pub base_dir: Option<PathBuf>,
+    /// Compare the paths in file system events to watched paths as they are,
+    /// instead of canonicalizing both. This saves a few system calls for
+    /// every event, and makes matching predictable, but a watched path which
+    /// is (or goes through) a symlink, or is spelled differently from the
+    /// paths the backend reports (such as a relative path), won't match. Only
+    /// use this if watched paths are absolute and free of symlinks. The
+    /// default is false.
+    pub literal_paths: bool,
+    /// Pass on events which only change a file's metadata, such as its
+    /// permissions, owner, or access time, which usually don't change the
+    /// config. By default these are ignored, and only events which create,
+    /// modify, remove, or rename files call the loader. This only affects
+    /// `StdFileSystem`. The default is false.
+    pub metadata_events: bool,
+    /// How the watch decides whether a file really changed when the backend
+    /// reports an event for it, before calling the loader. The default is
+    /// `ChangeDetection::Always`.
+    pub change_detection: ChangeDetection,
+    /// Settings passed straight to the underlying `notify` watcher, for
+    /// tuning the backend beyond the options here. With `Backend::Native`,
+    /// the watcher is created with this config. With `Backend::Poll`,
+    /// `notify`'s own `PollWatcher` is used in place of this crate's poller,
+    /// so `poll_compare` is ignored and the backend's `interval` replaces
+    /// the config's poll interval. This only affects `StdFileSystem`. The
+    /// default is `None`.
+    pub notify_config: Option<notify::Config>,
+    /// With `Backend::Native`, poll folders on network file systems (NFS,
+    /// SMB, 9p, and the like) instead of relying on the native backend,
+    /// which only reports changes made by this machine. Other folders are
+    /// still watched natively. Detection is supported on Linux and macOS.
+    /// This only affects `StdFileSystem`. The default is true.
+    pub poll_network_folders: bool,
+}
+
+impl Default for WatcherOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Some(DEFAULT_DEBOUNCE),
+            max_debounce: None,
+            min_reload_interval: None,
+            debounce_mode: DebounceMode::default(),
+            backend: Backend::default(),
+            poll_compare: PollCompare::default(),
+            ignore: vec![],
+            low_priority: false,
+            expand_paths: false,
+            base_dir: None,
+            literal_paths: false,
+            metadata_events: false,
+            change_detection: ChangeDetection::default(),
+            notify_config: None,
+            poll_network_folders: true,
+        }
+    }
+}
+
+impl WatcherOptions {
+    /// Apply `expand_paths` and `base_dir` to a watched path.
+    pub(crate) fn resolve_path(&self, path: &Path) -> PathBuf {
+        let path = match self.expand_paths {
+            true => crate::expand::expand_path(path),
+            false => path.to_path_buf(),
+        };
+        match &self.base_dir {
+            Some(base_dir) if path.is_relative() => base_dir.join(path),
+            _ => path,
+        }
+    }
+}
+
+/// Settings for how a single watched path is watched, set with
+/// `Builder::watch_file_with()`. These override the watch's `WatcherOptions`
+/// for this path only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WatchOptions {
+    /// If the path is a directory, report changes anywhere below it, instead
+    /// of only to the files directly inside it. This has no effect on files.
+    /// The default is false.
+    pub recursive: bool,
+    /// If the path is a symlink to a file in another folder, watch that
+    /// folder too, so changes to the target are reported. The default is
+    /// true.
+    pub follow_symlinks: bool,
+    /// Poll the folder this path is in at this interval, instead of using
+    /// the watch's backend. If another watched path in the same folder is
+    /// polled more often, the shorter interval is used. This only affects
+    /// `StdFileSystem`. The default is `None`.
+    pub poll: Option<Duration>,
+    /// Collect events for this path over this long, instead of the watch's
+    /// `debounce`, such as a long window for a file which is rewritten
+    /// constantly. Events for other paths are batched as usual, and aren't
+    /// held back by this path. The default is `None`.
+    pub debounce: Option<Duration>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            follow_symlinks: true,
+            poll: None,
+            debounce: None,
+        }
+    }
+}
+
+/// How changes to files are detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Use the operating system's file notification API (inotify, FSEvents,
+    /// etc...).
+    #[default]
+    Native,
+    /// Poll the file system for changes. This works on file systems which
+    /// don't support notifications, such as many network file systems. See
+    /// `PollCompare` for how changes are detected.
+    Poll {
+        /// How often to check for changes.
+        interval: Duration,
+    },
+    /// Poll the file system with notify's `PollWatcher`, rather than this
+    /// crate's own poller. This only notices a modification time which moves
+    /// forward, and ignores `PollCompare`; use `Builder::notify_config()` to
+    /// tune it instead. Prefer `Poll` unless you need notify's behaviour.
+    NotifyPoll {
+        /// How often to check for changes.
+        interval: Duration,
+    },
+}
+
+/// How the polling backend decides whether a file has changed since the last
+/// poll. Any difference counts as a change, so a modification time which goes
+/// backwards (e.g. because of clock skew) is still noticed. Only watched files
+/// (and files in watched directories) have their contents compared; other
+/// files in the same folders are always compared by metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PollCompare {
+    /// Compare the modification time and size of each file. This is the
+    /// cheapest option, but can miss quick edits which don't change the size on
+    /// file systems with coarse timestamps, such as FAT (2 second resolution).
+    /// This is the default, as with notify's `PollWatcher`.
+    #[default]
+    Metadata,
+    /// Compare the size and a hash of the contents of each file, ignoring the
+    /// modification time. Use this on file systems where timestamps can't be
+    /// trusted at all, such as exFAT SD cards or NFS mounts with skewed
+    /// clocks. Touching a file without changing it won't trigger a reload.
+    /// Every watched file is read in full on every poll.
+    Contents,
+    /// Compare the modification time, size, and a hash of the contents.
+    MetadataAndContents,
+}
+
+/// When a burst of events is delivered, relative to the debounce duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DebounceMode {
+    /// Collect events for the debounce duration after the first one, and then
+    /// call the loader once with everything that changed.
+    #[default]
+    Trailing,
+    /// Call the loader as soon as the first event for a path arrives, and
+    /// hold any further events for that path until the debounce duration has
+    /// passed. If there were any, the loader is called once more when it
+    /// ends, so the last write is always loaded. This reacts quickly, but if
+    /// a file is still being written when the first event arrives, the loader
+    /// sees it half written until the trailing load, so this suits files
+    /// which are replaced atomically. This has no effect without a debounce
+    /// duration, or on paths with their own `WatchOptions::debounce`.
+    Leading,
+}
+
+/// How a watch decides whether a watched file really changed, when the backend
+/// reports an event for it. Editors and deployment tools often touch files,
+/// change only their permissions, or cause duplicate events, and checking for
+/// a real change first avoids calling the loader for these. Unlike
+/// `PollCompare`, this works with every backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ChangeDetection {
+    /// Call the loader for every event. The one exception is a file which is
+    /// removed and created again: this is reported as a single change, so the
+    /// event for the create is dropped if the file's modification time and
+    /// size haven't changed since.
+    #[default]
+    Always,
+    /// Compare the modification time and size of each file the event is for
+    /// with the last time the file was checked, and only call the loader if
+    /// either differs. This never reads the files, so it is the cheapest way
+    /// to skip touched-but-unchanged events for very large files, but a quick
+    /// edit which doesn't change the size can be missed on file systems with
+    /// coarse timestamps.
+    Metadata,
+    /// Hash the contents of each file the event is for, and only call the
+    /// loader if the hash differs from the last time the file was checked.
+    /// Files are read in full when they start being watched and after every
+    /// event, so this suits small and medium sized files. Watched folders are
+    /// always treated as changed.
+    Hash,
+}
@@ -0,0 +1,106 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Context, Loader, Patcher};
+
+/// Loads a large binary file, such as a model or a generated lookup table,
+/// using a `Patcher` to build each new version from the previous one instead
+/// of reading the whole file again. This is useful when the file is rotated
+/// often and only changes a little each time, for example when a producer
+/// publishes zstd `--patch-from` updates or rsync style deltas next to it.
+///
+/// The loader keeps the contents of the file from the last load the watch
+/// accepted in memory, and passes them to the patcher on the next change. A
+/// version which was rejected, such as by `Builder::validate()`, never becomes
+/// the base for the next patch. If the patcher
+/// returns `None`, or there is no previous version yet (such as on the first
+/// load), the file is read in full. The contents are then turned into a value
+/// by `decode`.
+///
+/// If the file is removed, the watch will be updated with the default value
+/// and the previous contents are discarded. If the patcher or `decode` fails,
+/// the watch's current value will be unchanged.
+pub struct PatchLoader<P, D> {
+    patcher: P,
+    decode: D,
+    /// The file from the last load the watch accepted, and its contents.
+    previous: Option<(PathBuf, Arc<[u8]>)>,
+    /// What `previous` becomes if the watch accepts the last load. This is
+    /// `Some(None)` if the file was removed.
+    pending: Option<Option<(PathBuf, Arc<[u8]>)>>,
+}
+
+impl<P, D> PatchLoader<P, D> {
+    /// Create a new PatchLoader, which uses `patcher` to update the previous
+    /// contents of the file and `decode` to turn them into a value.
+    pub fn new(patcher: P, decode: D) -> Self {
+        Self {
+            patcher,
+            decode,
+            previous: None,
+            pending: None,
+        }
+    }
+
+    /// Get the contents from the last accepted load.
+    fn previous_for(&self, path: &Path) -> Option<Arc<[u8]>> {
+        match &self.previous {
+            Some((previous_path, contents)) if previous_path == path => Some(contents.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<P, D> std::fmt::Debug for PatchLoader<P, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PatchLoader")
+            .field("previous", &self.previous.as_ref().map(|(path, _)| path))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, P, D> Loader<T> for PatchLoader<P, D>
+where
+    T: Default,
+    P: Patcher,
+    D: FnMut(&[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        self.pending = None;
+        let Some(path) = context.path().map(Path::to_path_buf) else {
+            return Ok(T::default());
+        };
+
+        let patched = match self.previous_for(&path) {
+            Some(previous) => self.patcher.patch(context, &path, &previous)?,
+            None => None,
+        };
+        let contents: Arc<[u8]> = match patched {
+            Some(contents) => contents.into(),
+            None => match context.file_system().read(&path) {
+                Ok(contents) => contents.into(),
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    self.pending = Some(None);
+                    return Ok(T::default());
+                }
+                Err(err) => return Err(Box::new(err)),
+            },
+        };
+
+        let value = (self.decode)(&contents)?;
+        self.pending = Some(Some((path, contents)));
+        Ok(value)
+    }
+
+    fn commit(&mut self) {
+        if let Some(previous) = self.pending.take() {
+            self.previous = previous;
+        }
+    }
+}
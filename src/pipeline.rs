@@ -0,0 +1,334 @@
+use std::{fmt, io::ErrorKind, path::Path, time::Instant};
+
+use crate::{expand::interpolate, Context, Error, Loader};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BytesStage = Box<dyn FnMut(Vec<u8>) -> Result<Vec<u8>, BoxError> + Send>;
+type ParseStage<T> = Box<dyn FnMut(&[u8]) -> Result<T, BoxError> + Send>;
+type ValueStage<T> = Box<dyn FnMut(T) -> Result<T, BoxError> + Send>;
+type ValidateStage<T> = Box<dyn FnMut(&T) -> Result<(), BoxError> + Send>;
+
+/// A stage of a `Pipeline`, in the order the stages run. The time each stage
+/// took in the most recent load is reported in `WatchStats::stage_durations`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    /// Reading the file from the watch's file system.
+    Read,
+    /// `Pipeline::decompress()`.
+    Decompress,
+    /// `Pipeline::decrypt()`.
+    Decrypt,
+    /// `Pipeline::interpolate()` or `Pipeline::interpolate_env()`.
+    Interpolate,
+    /// The parser passed to `Pipeline::new()`.
+    Parse,
+    /// `Pipeline::migrate()`.
+    Migrate,
+    /// `Pipeline::validate()`.
+    Validate,
+    /// `Pipeline::transform()`.
+    Transform,
+}
+
+impl Stage {
+    /// The name of the stage, such as `decompress`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Read => "read",
+            Stage::Decompress => "decompress",
+            Stage::Decrypt => "decrypt",
+            Stage::Interpolate => "interpolate",
+            Stage::Parse => "parse",
+            Stage::Migrate => "migrate",
+            Stage::Validate => "validate",
+            Stage::Transform => "transform",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A loader made of separate stages, for when reading a config file involves
+/// more than parsing it. The file is read, then passed through each stage in
+/// this order, skipping any which weren't set:
+///
+/// 1. `decompress()` and `decrypt()` turn the raw bytes into the document.
+/// 2. `interpolate()` (or `interpolate_env()`) fills in placeholders.
+/// 3. The parser passed to `new()` turns the document into a value.
+/// 4. `migrate()` upgrades values written for older versions of the program.
+/// 5. `validate()` rejects invalid values with an `Error::ValidationError`.
+/// 6. `transform()` makes any final changes, such as filling in computed
+///    fields.
+///
+/// Each stage is a plain function, so it can be tested on its own, and
+/// `run()` runs the whole pipeline without a watch. If another stage fails,
+/// the load fails with an `Error::StageError` saying which one, unless the
+/// stage returned an `Error` itself, such as an `Error::ParseError`.
+///
+/// If the file doesn't exist, the watch's embedded default is passed through
+/// the stages from `interpolate()` on, or `T::default()` is used if there
+/// isn't one.
+pub struct Pipeline<T> {
+    decompress: Option<BytesStage>,
+    decrypt: Option<BytesStage>,
+    interpolate: Option<BytesStage>,
+    parse: ParseStage<T>,
+    migrate: Option<ValueStage<T>>,
+    validate: Option<ValidateStage<T>>,
+    transform: Option<ValueStage<T>>,
+}
+
+impl<T> Pipeline<T> {
+    /// Create a new pipeline which parses documents with `parse`.
+    pub fn new<E>(mut parse: impl FnMut(&[u8]) -> Result<T, E> + Send + 'static) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        Self {
+            decompress: None,
+            decrypt: None,
+            interpolate: None,
+            parse: Box::new(move |contents| parse(contents).map_err(Into::into)),
+            migrate: None,
+            validate: None,
+            transform: None,
+        }
+    }
+
+    /// Decompress the file before it is decrypted and parsed.
+    pub fn decompress<E>(
+        mut self,
+        mut f: impl FnMut(Vec<u8>) -> Result<Vec<u8>, E> + Send + 'static,
+    ) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.decompress = Some(Box::new(move |contents| f(contents).map_err(Into::into)));
+        self
+    }
+
+    /// Decrypt the file before it is parsed.
+    pub fn decrypt<E>(
+        mut self,
+        mut f: impl FnMut(Vec<u8>) -> Result<Vec<u8>, E> + Send + 'static,
+    ) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.decrypt = Some(Box::new(move |contents| f(contents).map_err(Into::into)));
+        self
+    }
+
+    /// Fill in placeholders in the document before it is parsed.
+    pub fn interpolate<E>(
+        mut self,
+        mut f: impl FnMut(Vec<u8>) -> Result<Vec<u8>, E> + Send + 'static,
+    ) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.interpolate = Some(Box::new(move |contents| f(contents).map_err(Into::into)));
+        self
+    }
+
+    /// Replace `${VAR}` in the document with the value of the environment
+    /// variable `VAR` before it is parsed. Variables which aren't set are left
+    /// unchanged. The document must be valid UTF-8.
+    pub fn interpolate_env(self) -> Self {
+        self.interpolate(|contents: Vec<u8>| {
+            let text = String::from_utf8(contents)?;
+            Ok::<_, BoxError>(interpolate(&text, |name| std::env::var(name).ok()).into_bytes())
+        })
+    }
+
+    /// Upgrade each parsed value, such as to rename settings from older
+    /// versions of the program.
+    pub fn migrate<E>(mut self, mut f: impl FnMut(T) -> Result<T, E> + Send + 'static) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.migrate = Some(Box::new(move |value| f(value).map_err(Into::into)));
+        self
+    }
+
+    /// Check each value after it is migrated, and reject it if `f` returns an
+    /// error.
+    pub fn validate<E>(mut self, mut f: impl FnMut(&T) -> Result<(), E> + Send + 'static) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.validate = Some(Box::new(move |value| f(value).map_err(Into::into)));
+        self
+    }
+
+    /// Change each value after it is validated.
+    pub fn transform<E>(mut self, mut f: impl FnMut(T) -> Result<T, E> + Send + 'static) -> Self
+    where
+        E: Into<BoxError>,
+    {
+        self.transform = Some(Box::new(move |value| f(value).map_err(Into::into)));
+        self
+    }
+
+    /// Run every stage on the contents of a file, without a watch.
+    pub fn run(&mut self, contents: Vec<u8>) -> Result<T, Error> {
+        self.run_from(Stage::Decompress, contents, &mut |_, _| {})
+    }
+
+    /// Run the stages from `first` on, calling `record` with the time each
+    /// stage took.
+    fn run_from(
+        &mut self,
+        first: Stage,
+        mut contents: Vec<u8>,
+        record: &mut dyn FnMut(Stage, Instant),
+    ) -> Result<T, Error> {
+        let bytes_stages = [
+            (Stage::Decompress, &mut self.decompress),
+            (Stage::Decrypt, &mut self.decrypt),
+            (Stage::Interpolate, &mut self.interpolate),
+        ];
+        for (stage, f) in bytes_stages {
+            if stage < first {
+                continue;
+            }
+            if let Some(f) = f {
+                let start = Instant::now();
+                contents = f(contents).map_err(|err| stage_error(stage, err))?;
+                record(stage, start);
+            }
+        }
+
+        let start = Instant::now();
+        let mut value = (self.parse)(&contents).map_err(|err| stage_error(Stage::Parse, err))?;
+        record(Stage::Parse, start);
+
+        if let Some(migrate) = &mut self.migrate {
+            let start = Instant::now();
+            value = migrate(value).map_err(|err| stage_error(Stage::Migrate, err))?;
+            record(Stage::Migrate, start);
+        }
+        if let Some(validate) = &mut self.validate {
+            let start = Instant::now();
+            validate(&value).map_err(|err| match err.downcast::<Error>() {
+                Ok(err) => *err,
+                Err(err) => Error::ValidationError(err),
+            })?;
+            record(Stage::Validate, start);
+        }
+        if let Some(transform) = &mut self.transform {
+            let start = Instant::now();
+            value = transform(value).map_err(|err| stage_error(Stage::Transform, err))?;
+            record(Stage::Transform, start);
+        }
+        Ok(value)
+    }
+}
+
+/// Convert an error from a stage into an `Error`, keeping errors which are
+/// already an `Error`.
+fn stage_error(stage: Stage, err: BoxError) -> Error {
+    match err.downcast::<Error>() {
+        Ok(err) => *err,
+        Err(source) => Error::StageError { stage, source },
+    }
+}
+
+impl<T> fmt::Debug for Pipeline<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("decompress", &self.decompress.is_some())
+            .field("decrypt", &self.decrypt.is_some())
+            .field("interpolate", &self.interpolate.is_some())
+            .field("migrate", &self.migrate.is_some())
+            .field("validate", &self.validate.is_some())
+            .field("transform", &self.transform.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Loader<T> for Pipeline<T>
+where
+    T: Default,
+{
+    fn load(&mut self, context: &mut Context) -> Result<T, BoxError> {
+        let start = Instant::now();
+        let file = match context.path().map(Path::to_path_buf) {
+            Some(path) => match context.file_system().read(&path) {
+                Ok(contents) => Some(contents),
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(Box::new(stage_error(Stage::Read, Box::new(err)))),
+            },
+            None => None,
+        };
+
+        let (first, contents) = match (file, context.embedded_default()) {
+            (Some(contents), _) => (Stage::Decompress, contents),
+            (None, Some(contents)) => (Stage::Interpolate, contents.as_bytes().to_vec()),
+            (None, None) => return Ok(T::default()),
+        };
+        let mut durations = vec![(Stage::Read, start.elapsed())];
+        let result = self.run_from(first, contents, &mut |stage, start| {
+            durations.push((stage, start.elapsed()))
+        });
+        for (stage, duration) in durations {
+            context.record_stage(stage, duration);
+        }
+        result.map_err(|err| Box::new(err) as BoxError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pipeline, Stage};
+    use crate::Error;
+
+    fn pipeline() -> Pipeline<i32> {
+        Pipeline::new(|contents: &[u8]| {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                std::str::from_utf8(contents)?.trim().parse::<i32>()?,
+            )
+        })
+    }
+
+    #[test]
+    fn should_run_stages_in_order() {
+        let mut pipeline = pipeline()
+            .transform(|value| Ok::<_, Error>(value * 10))
+            .decrypt(|contents: Vec<u8>| {
+                Ok::<_, Error>(contents.into_iter().map(|b| b ^ 1).collect())
+            })
+            .migrate(|value| Ok::<_, Error>(value + 1))
+            .validate(|value: &i32| match *value {
+                0..=100 => Ok(()),
+                _ => Err("too big"),
+            });
+
+        // "0" XOR 1 is "1", which migrates to 2 and transforms to 20.
+        assert_eq!(pipeline.run(b"0".to_vec()).unwrap(), 20);
+
+        let err = pipeline.run(b"200".to_vec()).unwrap_err();
+        assert!(matches!(err, Error::ValidationError(_)), "{err}");
+    }
+
+    #[test]
+    fn should_say_which_stage_failed() {
+        let mut pipeline = pipeline().decompress(|_| Err::<Vec<u8>, _>("bad header"));
+        let err = pipeline.run(b"1".to_vec()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::StageError {
+                    stage: Stage::Decompress,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        assert_eq!(err.to_string(), "decompress failed: bad header");
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, Metadata},
+    hash::Hasher,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use notify::{
+    event::{CreateKind, DataChange, ModifyKind, RemoveKind},
+    Event, EventHandler, EventKind, RecursiveMode, Watcher, WatcherKind,
+};
+
+use crate::PollCompare;
+
+/// A `notify::Watcher` which polls the file system. Unlike notify's own
+/// `PollWatcher`, a file is considered changed if its modification time is
+/// different in either direction (not just newer), and the size and contents
+/// of files can be compared instead of, or as well as, the modification time.
+/// This copes with file systems which have coarse or skewed timestamps. Only
+/// the files the watch is tracking have their contents read; other files in
+/// the polled folders are compared by metadata.
+pub(crate) struct Poller {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wakeup: Condvar,
+    interval: Duration,
+    compare: PollCompare,
+    /// The files whose contents may be compared, and any files under them.
+    /// If this is `None`, every file is.
+    tracked: Option<Arc<ArcSwap<Vec<PathBuf>>>>,
+}
+
+struct State {
+    watches: HashMap<PathBuf, Watched>,
+    /// The id to give the next watch.
+    next_id: u64,
+    stopped: bool,
+}
+
+struct Watched {
+    /// Tells the poll thread if the folder was watched again while it was
+    /// being scanned.
+    id: u64,
+    recursive: bool,
+    files: HashMap<PathBuf, FileState>,
+}
+
+/// What we know about a file from the last poll. Fields which aren't used by
+/// the comparison strategy are left as `None`.
+#[derive(Debug, PartialEq, Eq)]
+struct FileState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: Option<u64>,
+}
+
+impl Poller {
+    /// Create a new Poller, and start a thread which polls every `interval`.
+    /// Only files in `tracked` (or under a folder in it) are compared with
+    /// `compare`; others are compared by metadata.
+    pub fn start<F: EventHandler>(
+        mut event_handler: F,
+        interval: Duration,
+        compare: PollCompare,
+        tracked: Option<Arc<ArcSwap<Vec<PathBuf>>>>,
+    ) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                watches: HashMap::new(),
+                next_id: 0,
+                stopped: false,
+            }),
+            wakeup: Condvar::new(),
+            interval,
+            compare,
+            tracked,
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("config-file-watch-poll".to_string())
+                .spawn(move || loop {
+                    let state = shared.state.lock().unwrap();
+                    let state = shared
+                        .wakeup
+                        .wait_timeout_while(state, interval, |state| !state.stopped)
+                        .unwrap()
+                        .0;
+                    if state.stopped {
+                        return;
+                    }
+                    let roots: Vec<(PathBuf, bool, u64)> = state
+                        .watches
+                        .iter()
+                        .map(|(root, watched)| (root.clone(), watched.recursive, watched.id))
+                        .collect();
+                    drop(state);
+
+                    // Scan without holding the lock, so `watch()` and
+                    // `unwatch()` don't have to wait for a slow file system.
+                    let scans: Vec<_> = roots
+                        .into_iter()
+                        .map(|(root, recursive, id)| {
+                            let files = shared.scan(&root, recursive);
+                            (root, id, files)
+                        })
+                        .collect();
+
+                    let mut state = shared.state.lock().unwrap();
+                    let mut events = vec![];
+                    for (root, id, files) in scans {
+                        // The folder may have been unwatched or watched again
+                        // during the scan.
+                        let Some(watched) = state.watches.get_mut(&root) else {
+                            continue;
+                        };
+                        if watched.id != id {
+                            continue;
+                        }
+                        diff(&watched.files, &files, &mut events);
+                        watched.files = files;
+                    }
+                    drop(state);
+
+                    for event in events {
+                        event_handler.handle_event(Ok(event));
+                    }
+                })?
+        };
+
+        Ok(Poller {
+            shared,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Watcher for Poller {
+    fn new<F: EventHandler>(event_handler: F, config: notify::Config) -> notify::Result<Self> {
+        // Manual polling isn't supported, so fall back to notify's default.
+        let interval = config.poll_interval_v2().unwrap_or(Duration::from_secs(30));
+        Ok(Self::start(
+            event_handler,
+            interval,
+            PollCompare::default(),
+            None,
+        )?)
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> notify::Result<()> {
+        let recursive = recursive_mode == RecursiveMode::Recursive;
+        let files = self.shared.scan(path, recursive);
+        let mut state = self.shared.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.watches.insert(
+            path.to_path_buf(),
+            Watched {
+                id,
+                recursive,
+                files,
+            },
+        );
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        match self.shared.state.lock().unwrap().watches.remove(path) {
+            Some(_) => Ok(()),
+            None => Err(notify::Error::watch_not_found()),
+        }
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::PollWatcher
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wakeup.notify_one();
+        // Wait for a poll in progress to finish, so no events are reported
+        // after the watcher is dropped.
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Poller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Poller")
+            .field("interval", &self.shared.interval)
+            .field("compare", &self.shared.compare)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    /// Record the state of every file under `root`, or of `root` itself if
+    /// it is a file.
+    fn scan(&self, root: &Path, recursive: bool) -> HashMap<PathBuf, FileState> {
+        let tracked = self.tracked.as_ref().map(|tracked| tracked.load_full());
+        let compare = |path: &Path| match &tracked {
+            Some(tracked) if !tracked.iter().any(|file| path.starts_with(file)) => {
+                PollCompare::Metadata
+            }
+            _ => self.compare,
+        };
+        let mut files = HashMap::new();
+        match fs::metadata(root) {
+            Ok(metadata) if metadata.is_dir() => scan_dir(root, recursive, &compare, &mut files),
+            Ok(metadata) => {
+                files.insert(
+                    root.to_path_buf(),
+                    FileState::read(root, &metadata, compare(root)),
+                );
+            }
+            Err(_) => {}
+        }
+        files
+    }
+}
+
+fn scan_dir(
+    dir: &Path,
+    recursive: bool,
+    compare: &dyn Fn(&Path) -> PollCompare,
+    files: &mut HashMap<PathBuf, FileState>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if recursive {
+                scan_dir(&path, recursive, compare, files);
+            }
+        } else {
+            let state = FileState::read(&path, &metadata, compare(&path));
+            files.insert(path, state);
+        }
+    }
+}
+
+/// Add an event to `events` for every file which was created, removed, or
+/// changed between two scans.
+fn diff(
+    old: &HashMap<PathBuf, FileState>,
+    new: &HashMap<PathBuf, FileState>,
+    events: &mut Vec<Event>,
+) {
+    for (path, state) in new {
+        let kind = match old.get(path) {
+            None => EventKind::Create(CreateKind::File),
+            Some(old_state) if old_state != state => {
+                EventKind::Modify(ModifyKind::Data(DataChange::Any))
+            }
+            Some(_) => continue,
+        };
+        events.push(Event::new(kind).add_path(path.clone()));
+    }
+    for path in old.keys() {
+        if !new.contains_key(path) {
+            events.push(Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.clone()));
+        }
+    }
+}
+
+impl FileState {
+    fn read(path: &Path, metadata: &Metadata, compare: PollCompare) -> Self {
+        let (modified, hash) = match compare {
+            PollCompare::Metadata => (metadata.modified().ok(), None),
+            PollCompare::Contents => (None, hash_file(path).ok()),
+            PollCompare::MetadataAndContents => (metadata.modified().ok(), hash_file(path).ok()),
+        };
+        FileState {
+            modified,
+            len: metadata.len(),
+            hash,
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0; 8192];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::{mpsc, Arc},
+        time::{Duration, SystemTime},
+    };
+
+    use arc_swap::ArcSwap;
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    use super::{diff, FileState, Poller};
+    use crate::PollCompare;
+
+    fn state(modified: u64, len: u64, hash: Option<u64>) -> FileState {
+        FileState {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+            len,
+            hash,
+        }
+    }
+
+    #[test]
+    fn should_detect_changes_when_mtime_goes_backwards() {
+        let path = PathBuf::from("config.json");
+        let old = HashMap::from([(path.clone(), state(100, 10, None))]);
+        let new = HashMap::from([(path.clone(), state(50, 10, None))]);
+
+        let mut events = vec![];
+        diff(&old, &new, &mut events);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].kind, EventKind::Modify(_)));
+        assert_eq!(events[0].paths, vec![path]);
+    }
+
+    #[test]
+    fn should_detect_created_and_removed_files() {
+        let a = PathBuf::from("a");
+        let b = PathBuf::from("b");
+        let old = HashMap::from([(a.clone(), state(1, 1, Some(1)))]);
+        let new = HashMap::from([(b.clone(), state(1, 1, Some(1)))]);
+
+        let mut events = vec![];
+        diff(&old, &new, &mut events);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0].kind, EventKind::Create(_)));
+        assert_eq!(events[0].paths, vec![b]);
+        assert!(matches!(events[1].kind, EventKind::Remove(_)));
+        assert_eq!(events[1].paths, vec![a]);
+    }
+
+    #[test]
+    fn should_only_hash_tracked_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.json");
+        let other = dir.path().join("other.log");
+        std::fs::write(&config, "{}").unwrap();
+        std::fs::write(&other, "log").unwrap();
+
+        let tracked = Arc::new(ArcSwap::from_pointee(vec![config.clone()]));
+        let mut poller = Poller::start(
+            |_| {},
+            Duration::from_secs(60),
+            PollCompare::MetadataAndContents,
+            Some(tracked),
+        )
+        .unwrap();
+        poller
+            .watch(dir.path(), RecursiveMode::NonRecursive)
+            .unwrap();
+
+        let state = poller.shared.state.lock().unwrap();
+        let files = &state.watches[dir.path()].files;
+        assert!(files[&config].hash.is_some());
+        assert!(files[&other].hash.is_none());
+    }
+
+    #[test]
+    fn should_stop_the_poll_thread_when_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let poller = Poller::start(
+            move |event| tx.send(event).unwrap(),
+            Duration::from_secs(60),
+            PollCompare::Metadata,
+            None,
+        )
+        .unwrap();
+        drop(poller);
+        // The thread has exited, and dropped the handler with it.
+        assert!(matches!(
+            rx.try_recv(),
+            Err(mpsc::TryRecvError::Disconnected)
+        ));
+    }
+}
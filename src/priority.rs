@@ -0,0 +1,57 @@
+//! Adjusts the scheduling priority of the current thread.
+
+/// Lower (or restore) the CPU and IO priority of the current thread. This is
+/// best effort; errors are ignored, and on platforms without support this
+/// does nothing. Note that on Linux, an unprivileged process can lower its
+/// priority but can't raise it again.
+pub(crate) fn set_low_priority(low: bool) {
+    imp::set_low_priority(low);
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+
+    pub fn set_low_priority(low: bool) {
+        // SAFETY: These syscalls only change the scheduling priority of the
+        // calling thread, and have no memory safety requirements.
+        unsafe {
+            let tid = libc::syscall(libc::SYS_gettid);
+
+            // Niceness on Linux is per-thread.
+            let nice = if low { 10 } else { 0 };
+            libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice);
+
+            // Lowest best-effort IO priority, or 0 to go back to deriving the IO
+            // priority from the CPU priority.
+            let ioprio = if low {
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7
+            } else {
+                0
+            };
+            libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    pub fn set_low_priority(low: bool) {
+        let class = if low {
+            libc::qos_class_t::QOS_CLASS_BACKGROUND
+        } else {
+            libc::qos_class_t::QOS_CLASS_DEFAULT
+        };
+        // SAFETY: This only changes the QoS class of the calling thread.
+        unsafe {
+            libc::pthread_set_qos_class_self_np(class, 0);
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod imp {
+    pub fn set_low_priority(_low: bool) {}
+}
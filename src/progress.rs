@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use crate::Watch;
+
+/// How far through a slow load the loader is, reported with
+/// `Context::report_progress()`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// How much of the load is done, from 0.0 to 1.0.
+    pub fraction: f32,
+    /// What the loader is doing, such as "reading routing table".
+    pub message: String,
+    /// How long the load had been running when the progress was reported.
+    pub elapsed: Duration,
+}
+
+impl<T> Watch<T> {
+    /// Get the most recent progress reported by the loader, if a reload is
+    /// running and the loader has reported any. This can be used to tell a
+    /// slow reload from a hung one, for example in an admin endpoint.
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress
+            .load_full()
+            .map(|progress| (*progress).clone())
+    }
+}
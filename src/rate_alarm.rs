@@ -0,0 +1,89 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::Warning;
+
+/// Warns when a watch reloads more than `max` times within `window`, set
+/// with `Builder::warn_on_reload_rate()`. Once it has warned, it doesn't warn
+/// again until the rate has dropped back to `max` or below.
+#[derive(Debug)]
+pub(crate) struct RateAlarm {
+    max: u32,
+    window: Duration,
+    /// When each reload within the window happened, oldest first.
+    reloads: VecDeque<Instant>,
+    firing: bool,
+}
+
+impl RateAlarm {
+    pub fn new(max: u32, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            reloads: VecDeque::new(),
+            firing: false,
+        }
+    }
+
+    /// Called for each reload. Returns a warning if this reload took the
+    /// rate over the limit.
+    pub fn record(&mut self, now: Instant) -> Option<Warning> {
+        while self
+            .reloads
+            .front()
+            .is_some_and(|&reload| now.duration_since(reload) >= self.window)
+        {
+            self.reloads.pop_front();
+        }
+        self.reloads.push_back(now);
+
+        let count = self.reloads.len();
+        if count <= self.max as usize {
+            self.firing = false;
+            return None;
+        }
+        if std::mem::replace(&mut self.firing, true) {
+            return None;
+        }
+        Some(Warning::new(format!(
+            "reloaded {count} times in {:?}, which is more than the limit of {}; \
+             the file may be flapping",
+            self.window, self.max
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateAlarm;
+
+    #[test]
+    fn should_warn_when_reloading_too_often() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut alarm = RateAlarm::new(3, Duration::from_secs(10));
+
+        for i in 0..3 {
+            assert!(alarm.record(start + second * i).is_none());
+        }
+        let warning = alarm.record(start + second * 3).unwrap();
+        assert_eq!(
+            warning.to_string(),
+            "reloaded 4 times in 10s, which is more than the limit of 3; \
+             the file may be flapping"
+        );
+        // Only warn once while the rate stays high.
+        assert!(alarm.record(start + second * 4).is_none());
+
+        // Once the rate drops, the alarm is re-armed.
+        assert!(alarm.record(start + second * 30).is_none());
+        for i in 31..33 {
+            assert!(alarm.record(start + second * i).is_none());
+        }
+        assert!(alarm.record(start + second * 33).is_some());
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::{Guard, Watch};
+
+/// A read-only handle to the value of a `Watch`, returned by
+/// `Watch::reader()`.
+///
+/// Reading the value never waits for the watch, so a reader can be captured by
+/// another watch's loader (for example, to merge a file with an already-loaded
+/// base config) or by the watch's own handlers. Unlike a clone of the `Watch`,
+/// a reader doesn't keep the watch running, so capturing one can't create a
+/// reference cycle. Once the watch is dropped, the reader keeps returning the
+/// last value.
+#[derive(Debug)]
+pub struct WatchReader<T> {
+    value: Arc<ArcSwap<T>>,
+}
+
+impl<T> Clone for WatchReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> WatchReader<T> {
+    /// Produces a temporary borrow of the current value of the watch. See
+    /// `Watch::value()`.
+    pub fn value(&self) -> Guard<T> {
+        self.value.load()
+    }
+}
+
+impl<T> Watch<T> {
+    /// Get a read-only handle to the value of this watch, which is safe to use
+    /// from any loader or handler. See `WatchReader`.
+    pub fn reader(&self) -> WatchReader<T> {
+        WatchReader {
+            value: self.value.clone(),
+        }
+    }
+}
@@ -0,0 +1,726 @@
+use std::{
+    cell::Cell,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, TryLockError,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+
+use crate::{
+    breaker::CircuitBreaker, context::ProgressSink, dedup::Comparer, fs::Overlay, history::History,
+    rate_alarm::RateAlarm, roots::DependencyRoots, stats::LoadDurations, suppress::ErrorSuppressor,
+    types::DefaultWarningHandler, watchdog::Watchdog, Context, Error, ErrorHandler, FileSystem,
+    Loader, Progress, ProgressHandler, ReloadOutcome, ReloadTrace, ReloadTrigger, Snapshot,
+    StdFileSystem, UpdatedHandler, Warning, WarningHandler, WatchStats,
+};
+
+/// Called with each new value after it has been stored. Returns false once the
+/// listener is no longer needed, so it can be removed.
+pub(crate) type Listener<T> =
+    Box<dyn FnMut(&mut Context, &T, &mut dyn ErrorHandler) -> bool + Send>;
+
+/// Called after every load with its outcome. Returns false once the listener
+/// is no longer needed, so it can be removed.
+pub(crate) type OutcomeListener = Box<dyn FnMut(&mut Context, &ReloadOutcome) -> bool + Send>;
+
+thread_local! {
+    /// The number of reloaders this thread has locked. While this is non-zero
+    /// the thread may be running a loader or handler, so it must never wait
+    /// for a reloader. Since a thread holding a reloader never waits, two
+    /// watches can't end up waiting on each other.
+    static HELD: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A locked Reloader. Keeps track of which threads are holding a reloader, so
+/// that a loader which uses another watch can't deadlock.
+pub(crate) struct ReloaderGuard<'a, T> {
+    guard: MutexGuard<'a, Reloader<T>>,
+}
+
+impl<'a, T> ReloaderGuard<'a, T> {
+    /// Lock a reloader, waiting for any reload in progress to finish. If this
+    /// thread is already holding a reloader (because it was called from a
+    /// loader or handler) this returns `Error::WouldDeadlock` instead of
+    /// waiting.
+    pub fn lock(reloader: &'a Mutex<Reloader<T>>) -> Result<Self, Error> {
+        if HELD.get() == 0 {
+            return Ok(Self::wait(reloader));
+        }
+        match reloader.try_lock() {
+            Ok(guard) => Ok(Self::held(guard)),
+            Err(TryLockError::WouldBlock) => Err(Error::WouldDeadlock),
+            Err(TryLockError::Poisoned(err)) => panic!("{err}"),
+        }
+    }
+
+    /// Lock a reloader, always waiting for any reload in progress to finish.
+    /// This must only be used by threads which can't be holding a reloader,
+    /// such as the file watcher's thread, or for a watch which is still being
+    /// built (which can only be held by its own watcher thread).
+    pub fn wait(reloader: &'a Mutex<Reloader<T>>) -> Self {
+        Self::held(reloader.lock().unwrap())
+    }
+
+    /// Returns true if this thread is holding a reloader, so must not wait.
+    pub fn is_held() -> bool {
+        HELD.get() > 0
+    }
+
+    fn held(guard: MutexGuard<'a, Reloader<T>>) -> Self {
+        HELD.set(HELD.get() + 1);
+        Self { guard }
+    }
+}
+
+impl<T> Deref for ReloaderGuard<'_, T> {
+    type Target = Reloader<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for ReloaderGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for ReloaderGuard<'_, T> {
+    fn drop(&mut self) {
+        HELD.set(HELD.get() - 1);
+    }
+}
+
+/// Owns the user supplied loader and handlers for a Watch, and takes care of
+/// running the loader and storing the result.
+pub(crate) struct Reloader<T> {
+    value: Arc<ArcSwap<T>>,
+    /// The current value along with its version, updated with `value`.
+    snapshot: Arc<ArcSwap<Snapshot<T>>>,
+    /// The version of the current value, for `CachedReader`. This is bumped
+    /// after `value` is stored.
+    version: Arc<AtomicU64>,
+    /// The most recent values, if `Builder::keep_history()` was used.
+    history: Arc<Mutex<History<T>>>,
+    loader: Box<dyn Loader<T> + Send>,
+    after_update: Box<dyn UpdatedHandler<T> + Send>,
+    /// Shared with the watchdog, which reports loads which time out while
+    /// they're still running.
+    error_handler: Arc<Mutex<Box<dyn ErrorHandler + Send>>>,
+    warning_handler: Box<dyn WarningHandler + Send>,
+    /// A trace of the most recent reload.
+    last_trace: Arc<ArcSwapOption<ReloadTrace>>,
+    stats: Arc<Mutex<WatchStats>>,
+    listeners: Vec<Listener<T>>,
+    outcome_listeners: Vec<OutcomeListener>,
+    /// The document to load if the watched file doesn't exist.
+    embedded_default: Option<Arc<str>>,
+    load_durations: LoadDurations,
+    /// Warn if loading takes longer than this.
+    slow_reload_threshold: Option<Duration>,
+    /// Limits how often repeated errors are reported, if set.
+    error_suppressor: Option<ErrorSuppressor>,
+    /// Warns when the watch reloads too often.
+    rate_alarm: Option<RateAlarm>,
+    /// Stops calling the loader after repeated failures.
+    circuit_breaker: Option<CircuitBreaker>,
+    /// The file system the loader reads files from.
+    fs: Arc<dyn FileSystem>,
+    /// Lent to the context during each load. See `Context::buffer()`.
+    buffer: Vec<u8>,
+    /// The progress reported by the running load, if any.
+    progress: Arc<ArcSwapOption<Progress>>,
+    progress_handler: Option<Arc<Mutex<Box<dyn ProgressHandler + Send>>>>,
+    /// The folders the loader may add dependencies from, if restricted.
+    dependency_roots: Option<DependencyRoots>,
+    /// Report loads which take longer than this.
+    load_timeout: Option<Duration>,
+    /// Discard the result of a load which timed out.
+    abandon_timed_out_loads: bool,
+    /// Returns true if a new value is the same as the current one, which is
+    /// then kept. See `Builder::dedup_values()`.
+    dedup: Option<Comparer<T>>,
+}
+
+impl<T> Reloader<T> {
+    pub fn new<LoaderImpl, Updated, ErrorHandlerImpl>(
+        value: Arc<ArcSwap<T>>,
+        loader: LoaderImpl,
+        after_update: Updated,
+        error_handler: ErrorHandlerImpl,
+    ) -> Self
+    where
+        LoaderImpl: Loader<T> + Send + 'static,
+        Updated: UpdatedHandler<T> + Send + 'static,
+        ErrorHandlerImpl: ErrorHandler + Send + 'static,
+    {
+        let snapshot = Arc::new(ArcSwap::from_pointee(Snapshot::initial(value.load_full())));
+        Self {
+            value,
+            snapshot,
+            version: Arc::new(AtomicU64::new(0)),
+            history: Arc::new(Mutex::new(History::new())),
+            loader: Box::new(loader),
+            after_update: Box::new(after_update),
+            error_handler: Arc::new(Mutex::new(Box::new(error_handler))),
+            warning_handler: Box::new(DefaultWarningHandler),
+            last_trace: Arc::new(ArcSwapOption::empty()),
+            stats: Arc::new(Mutex::new(WatchStats::default())),
+            listeners: vec![],
+            outcome_listeners: vec![],
+            embedded_default: None,
+            load_durations: LoadDurations::default(),
+            slow_reload_threshold: None,
+            error_suppressor: None,
+            rate_alarm: None,
+            circuit_breaker: None,
+            fs: Arc::new(StdFileSystem),
+            buffer: vec![],
+            progress: Arc::new(ArcSwapOption::empty()),
+            progress_handler: None,
+            dependency_roots: None,
+            load_timeout: None,
+            abandon_timed_out_loads: false,
+            dedup: None,
+        }
+    }
+
+    /// Set the handler to call with warnings reported by the loader.
+    pub fn set_warning_handler(&mut self, warning_handler: Box<dyn WarningHandler + Send>) {
+        self.warning_handler = warning_handler;
+    }
+
+    /// Set the threshold above which a load is reported as slow. If this is
+    /// `None`, slow loads aren't reported.
+    pub fn set_slow_reload_threshold(&mut self, threshold: Option<Duration>) {
+        self.slow_reload_threshold = threshold;
+    }
+
+    /// Set how repeated errors are suppressed. If this is `None`, every error
+    /// is passed to the error handler.
+    pub fn set_error_suppressor(&mut self, error_suppressor: Option<ErrorSuppressor>) {
+        self.error_suppressor = error_suppressor;
+    }
+
+    /// Set the alarm which warns when the watch reloads too often. If this is
+    /// `None`, the reload rate isn't checked.
+    pub fn set_rate_alarm(&mut self, rate_alarm: Option<RateAlarm>) {
+        self.rate_alarm = rate_alarm;
+    }
+
+    /// Set the circuit breaker which stops calling the loader after repeated
+    /// failures. If this is `None`, every change is loaded.
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = circuit_breaker;
+    }
+
+    /// Report loads which take longer than `timeout`, and if `abandon` is
+    /// true, discard their result. If this is `None`, loads can take as long
+    /// as they need.
+    pub fn set_load_timeout(&mut self, timeout: Option<Duration>, abandon: bool) {
+        self.load_timeout = timeout;
+        self.abandon_timed_out_loads = abandon;
+    }
+
+    /// Set how to tell if a new value is the same as the current one. If this
+    /// is `None`, every value is stored.
+    pub fn set_dedup(&mut self, dedup: Option<Comparer<T>>) {
+        self.dedup = dedup;
+    }
+
+    /// Keep the last `limit` values, starting with the current one. If this is
+    /// 0, no history is kept.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        let current = Snapshot::clone(&self.snapshot.load());
+        self.history.lock().unwrap().enable(limit, current);
+    }
+
+    /// Set the file system the loader reads files from.
+    pub fn set_file_system(&mut self, fs: Arc<dyn FileSystem>) {
+        self.fs = fs;
+    }
+
+    /// Restrict the folders the loader may add dependencies from.
+    pub fn set_dependency_roots(&mut self, roots: Option<DependencyRoots>) {
+        self.dependency_roots = roots;
+    }
+
+    /// Set the handler to call with progress reported by the loader.
+    pub fn set_progress_handler(&mut self, handler: Option<Box<dyn ProgressHandler + Send>>) {
+        self.progress_handler = handler.map(|handler| Arc::new(Mutex::new(handler)));
+    }
+
+    /// Set the document to load if the watched file doesn't exist, and load it
+    /// to replace the current value. Returns an error if it can't be loaded.
+    pub fn set_embedded_default(
+        &mut self,
+        context: &mut Context,
+        embedded_default: Arc<str>,
+    ) -> Result<(), Error> {
+        self.embedded_default = Some(embedded_default);
+        let value = self.call_loader(context).map_err(Error::from_load_error)?;
+        self.store(value);
+        Ok(())
+    }
+
+    /// Get the shared trace of the most recent reload.
+    pub fn last_trace(&self) -> Arc<ArcSwapOption<ReloadTrace>> {
+        self.last_trace.clone()
+    }
+
+    /// Get the shared progress of the running load.
+    pub fn progress(&self) -> Arc<ArcSwapOption<Progress>> {
+        self.progress.clone()
+    }
+
+    /// Get the shared snapshot of the current value.
+    pub fn snapshot(&self) -> Arc<ArcSwap<Snapshot<T>>> {
+        self.snapshot.clone()
+    }
+
+    /// Get the shared version of the current value.
+    pub fn version(&self) -> Arc<AtomicU64> {
+        self.version.clone()
+    }
+
+    /// Get the shared history of recent values.
+    pub fn history(&self) -> Arc<Mutex<History<T>>> {
+        self.history.clone()
+    }
+
+    /// Store a new value.
+    fn store(&mut self, value: T) {
+        let value = Arc::new(value);
+        let snapshot = self.snapshot.load().next(value.clone());
+        let version = snapshot.version();
+        self.value.store(value);
+        self.history.lock().unwrap().record(snapshot.clone());
+        self.snapshot.store(Arc::new(snapshot));
+        self.version.store(version, Ordering::Release);
+    }
+
+    /// Get the shared stats for this watch.
+    pub fn stats(&self) -> Arc<Mutex<WatchStats>> {
+        self.stats.clone()
+    }
+
+    /// Add a listener to call with each new value.
+    ///
+    /// Listeners which feed a handle, such as a `Mapped` view, a `Section` or
+    /// a `Fallback`, should only hold a weak reference to it. The reloader
+    /// owns the listener, so a strong one would keep the handle alive for as
+    /// long as the watch runs; with a weak one, the listener returns false and
+    /// is removed once every clone of the handle has been dropped.
+    pub fn add_listener(&mut self, listener: Listener<T>) {
+        self.listeners.push(listener);
+    }
+
+    /// Add a listener to call after every load, whether or not it succeeds.
+    /// Like `add_listener()`, it should only hold a weak reference to the
+    /// handle it feeds.
+    pub fn add_outcome_listener(&mut self, listener: OutcomeListener) {
+        self.outcome_listeners.push(listener);
+    }
+
+    /// Run the loader, and store the new value if it succeeds.
+    pub fn reload(&mut self, context: &mut Context, trigger: ReloadTrigger) {
+        let trace = Trace::start(context, trigger);
+        if let Some(warning) = self
+            .rate_alarm
+            .as_mut()
+            .and_then(|alarm| alarm.record(Instant::now()))
+        {
+            self.stats.lock().unwrap().warnings += 1;
+            self.warning_handler.on_warning(context, &warning);
+        }
+        if let Some(breaker) = &mut self.circuit_breaker {
+            let allowed = breaker.allow(Instant::now());
+            let mut stats = self.stats.lock().unwrap();
+            stats.circuit = breaker.state();
+            if !allowed {
+                stats.skipped_reloads += 1;
+                drop(stats);
+                trace.finish(&self.last_trace, ReloadOutcome::Skipped, vec![]);
+                return;
+            }
+        }
+        if self.load(context, trace) == ReloadOutcome::Updated {
+            self.notify_listeners(context);
+            self.after_update.after_update(context, self.value.load());
+        }
+    }
+
+    fn notify_listeners(&mut self, context: &mut Context) {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let value = self.value.load_full();
+        let mut error_handler = self.error_handler.lock().unwrap();
+        self.listeners
+            .retain_mut(|listener| listener(context, &value, error_handler.as_mut()));
+    }
+
+    /// Load the initial value for a watch. Unlike `reload`, this calls
+    /// `after_update` even if loading fails, in which case the watch keeps its
+    /// default value. If `load` is false, the loader is skipped entirely.
+    ///
+    /// If `fail_on_error` is true and loading fails, the error is returned
+    /// instead of being passed to the error handler, and `after_update` is not
+    /// called.
+    pub fn initial_load(
+        &mut self,
+        context: &mut Context,
+        load: bool,
+        fail_on_error: bool,
+    ) -> Result<(), Error> {
+        let trace = Trace::start(context, ReloadTrigger::InitialLoad);
+        let outcome = if !load {
+            trace.finish(&self.last_trace, ReloadOutcome::Skipped, vec![]);
+            ReloadOutcome::Skipped
+        } else if fail_on_error {
+            self.try_load(context, trace)?
+        } else {
+            self.load(context, trace)
+        };
+        // With a lazy initial load, views may already have been made from the
+        // watch.
+        if outcome == ReloadOutcome::Updated {
+            self.notify_listeners(context);
+        }
+        self.after_update.after_update(context, self.value.load());
+        Ok(())
+    }
+
+    /// Run the loader, and store the result if it succeeds. Returns the
+    /// outcome of the load.
+    fn load(&mut self, context: &mut Context, trace: Trace) -> ReloadOutcome {
+        let outcome = match self.try_load(context, trace) {
+            Ok(outcome) => outcome,
+            // The loader probably gave up because of the shutdown.
+            Err(err) if context.is_shutting_down() => ReloadOutcome::LoadFailed(err.to_string()),
+            // The watchdog reported this while the load was running.
+            Err(err @ Error::LoadTimeout(_)) => ReloadOutcome::LoadFailed(err.to_string()),
+            Err(err) => {
+                let outcome = ReloadOutcome::LoadFailed(err.to_string());
+                self.report_error(context, err);
+                self.trip_circuit_breaker(context);
+                outcome
+            }
+        };
+        self.outcome_listeners
+            .retain_mut(|listener| listener(context, &outcome));
+        outcome
+    }
+
+    /// Run the loader, and store the result if it succeeds and isn't
+    /// unchanged. Returns `ReloadOutcome::Updated` if a new value was stored.
+    /// Errors are recorded in the trace and stats, and returned.
+    fn try_load(&mut self, context: &mut Context, trace: Trace) -> Result<ReloadOutcome, Error> {
+        context.set_progress_sink(Some(self.progress_sink(trace.start)));
+        let watchdog = self
+            .load_timeout
+            .map(|timeout| self.start_watchdog(context, timeout));
+        let result = self.call_loader(context);
+        let timed_out = watchdog.is_some_and(Watchdog::finish);
+        context.set_progress_sink(None);
+        self.progress.store(None);
+        let slow_warnings = self.record_duration(trace.start.elapsed());
+        self.stats.lock().unwrap().stage_durations = context.take_stage_durations();
+        let abandon = timed_out && self.abandon_timed_out_loads;
+        if let Some(timeout) = self.load_timeout.filter(|_| abandon) {
+            context.take_warnings();
+            let error = Error::LoadTimeout(timeout);
+            trace.finish(
+                &self.last_trace,
+                ReloadOutcome::LoadFailed(error.to_string()),
+                vec![],
+            );
+            self.stats.lock().unwrap().failed_reloads += 1;
+            return Err(error);
+        }
+        // A newer change arrived while loading, so the result is stale.
+        if context.cancelled() {
+            context.take_warnings();
+            trace.finish(&self.last_trace, ReloadOutcome::Cancelled, vec![]);
+            self.stats.lock().unwrap().cancelled_reloads += 1;
+            context.load_again();
+            return Ok(ReloadOutcome::Cancelled);
+        }
+        match result {
+            Ok(v) => {
+                let mut warnings = context.take_warnings();
+                warnings.extend(slow_warnings);
+                if let Some(suppressor) = &mut self.error_suppressor {
+                    warnings.extend(suppressor.on_success());
+                }
+                if let Some(breaker) = &mut self.circuit_breaker {
+                    if let Some(failures) = breaker.on_success() {
+                        warnings.push(Warning::new(format!(
+                            "loaded successfully after {failures} failures in a row, \
+                             and will load every change again"
+                        )));
+                    }
+                    self.stats.lock().unwrap().circuit = breaker.state();
+                }
+                let unchanged = self
+                    .dedup
+                    .as_ref()
+                    .is_some_and(|same| same(&self.value.load(), &v));
+                let outcome = if unchanged {
+                    ReloadOutcome::Unchanged
+                } else {
+                    ReloadOutcome::Updated
+                };
+                trace.finish(&self.last_trace, outcome.clone(), warnings.clone());
+                if !unchanged {
+                    self.store(v);
+                }
+                self.loader.commit();
+                {
+                    let mut stats = self.stats.lock().unwrap();
+                    if unchanged {
+                        stats.unchanged_reloads += 1;
+                    } else {
+                        stats.reloads += 1;
+                    }
+                    stats.warnings += warnings.len() as u64;
+                    stats.last_warnings = warnings.clone();
+                }
+                for warning in &warnings {
+                    self.warning_handler.on_warning(context, warning);
+                }
+                Ok(outcome)
+            }
+            Err(e) => {
+                context.take_warnings();
+                trace.finish(
+                    &self.last_trace,
+                    ReloadOutcome::LoadFailed(e.to_string()),
+                    vec![],
+                );
+                self.stats.lock().unwrap().failed_reloads += 1;
+                Err(Error::from_load_error(e))
+            }
+        }
+    }
+
+    /// Record a failed load with the circuit breaker. If this stops loading,
+    /// report it, and load again once the cool-down is over.
+    fn trip_circuit_breaker(&mut self, context: &mut Context) {
+        let Some(breaker) = &mut self.circuit_breaker else {
+            return;
+        };
+        let opened = breaker.on_failure(Instant::now());
+        let cooldown = breaker.cooldown();
+        self.stats.lock().unwrap().circuit = breaker.state();
+        if let Some(failures) = opened {
+            context.retry_after(cooldown);
+            self.error_handler
+                .lock()
+                .unwrap()
+                .on_error(context, Error::CircuitOpen { failures, cooldown });
+        }
+    }
+
+    /// Give the context everything the loader might need from the watch, run
+    /// the loader, and take back the scratch buffer for the next load.
+    fn call_loader(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        self.prepare_context(context);
+        context.set_buffer(std::mem::take(&mut self.buffer));
+        let result = self.loader.load(context);
+        self.buffer = context.take_buffer();
+        result
+    }
+
+    /// Give the context everything the loader might need from the watch.
+    fn prepare_context(&self, context: &mut Context) {
+        context.set_embedded_default(self.embedded_default.clone());
+        context.set_file_system(self.fs.clone());
+        context.set_dependency_roots(self.dependency_roots.clone());
+    }
+
+    /// Start a watchdog which reports the load about to run with `context` if
+    /// it takes longer than `timeout`.
+    fn start_watchdog(&self, context: &Context, timeout: Duration) -> Watchdog {
+        let error_handler = self.error_handler.clone();
+        let stats = self.stats.clone();
+        let watcher = context.watcher();
+        let modified_paths: Vec<PathBuf> = context
+            .modified_paths()
+            .iter()
+            .map(|p| p.to_path_buf())
+            .collect();
+        let cancel = self.abandon_timed_out_loads.then(|| context.cancel_token());
+        Watchdog::start(timeout, move || {
+            // The load is still running, so the error handler mustn't wait
+            // for this watch.
+            HELD.set(HELD.get() + 1);
+            if let Some(cancel) = cancel {
+                cancel.abandon();
+            }
+            stats.lock().unwrap().timed_out_loads += 1;
+            let modified_paths: Vec<&Path> = modified_paths.iter().map(|p| p.as_path()).collect();
+            let mut watch_paths = vec![];
+            let mut context = match &watcher {
+                Some(watcher) => Context::for_watch(&modified_paths, watcher),
+                None => Context::for_paths(&modified_paths, &mut watch_paths),
+            };
+            error_handler
+                .lock()
+                .unwrap()
+                .on_error(&mut context, Error::LoadTimeout(timeout));
+        })
+    }
+
+    /// Create the sink for progress reported by a load which started at
+    /// `start`.
+    fn progress_sink(&self, start: Instant) -> ProgressSink {
+        let progress = self.progress.clone();
+        let handler = self.progress_handler.clone();
+        Arc::new(move |fraction, message| {
+            let update = Progress {
+                fraction,
+                message,
+                elapsed: start.elapsed(),
+            };
+            let update = Arc::new(update);
+            progress.store(Some(update.clone()));
+            if let Some(handler) = &handler {
+                handler.lock().unwrap().on_progress(&update);
+            }
+        })
+    }
+
+    /// Record how long the loader took, and return warnings if it was slow.
+    fn record_duration(&mut self, duration: Duration) -> Vec<Warning> {
+        let (average, trending) = self.load_durations.record(duration);
+        self.stats.lock().unwrap().average_load_duration = average;
+
+        let Some(threshold) = self.slow_reload_threshold else {
+            return vec![];
+        };
+        let mut warnings = vec![];
+        if duration > threshold {
+            warnings.push(Warning::new(format!(
+                "loading took {duration:?}, which is longer than {threshold:?}"
+            )));
+        }
+        if trending {
+            warnings.push(Warning::new(format!(
+                "loading is getting slower, and now takes {average:?} on average"
+            )));
+        }
+        warnings
+    }
+
+    /// Run the loader without storing the result. The loader isn't told to
+    /// commit, and gets a buffer of its own, so the next real load is
+    /// unaffected. If `candidate` is set, that file reads as the given
+    /// contents instead of what is on disk.
+    pub fn dry_run(
+        &mut self,
+        context: &mut Context,
+        candidate: Option<(PathBuf, Arc<[u8]>)>,
+    ) -> Result<T, Error> {
+        self.prepare_context(context);
+        if let Some((path, contents)) = candidate {
+            context.set_file_system(Arc::new(Overlay::new(self.fs.clone(), path, contents)));
+        }
+        context.set_buffer(vec![]);
+        self.loader.load(context).map_err(Error::from_load_error)
+    }
+
+    /// Report an error from the file watcher to the error handler.
+    pub fn on_watch_error(&mut self, context: &mut Context, error: String) {
+        let trace = Trace::start(
+            context,
+            ReloadTrigger::FileChange {
+                events: vec![],
+                debounce: None,
+            },
+        );
+        trace.finish(
+            &self.last_trace,
+            ReloadOutcome::WatchFailed(error.clone()),
+            vec![],
+        );
+        self.report_error(context, Error::WatchError(error));
+    }
+
+    /// Pass an error to the error handler, unless it is a repeat which should
+    /// be suppressed.
+    fn report_error(&mut self, context: &mut Context, error: Error) {
+        if let Some(suppressor) = &mut self.error_suppressor {
+            if !suppressor.on_error(&error.to_string(), Instant::now()) {
+                self.stats.lock().unwrap().suppressed_errors += 1;
+                return;
+            }
+        }
+        self.error_handler.lock().unwrap().on_error(context, error);
+    }
+}
+
+/// A ReloadTrace which is being recorded.
+struct Trace {
+    started_at: SystemTime,
+    start: Instant,
+    trigger: ReloadTrigger,
+    modified_paths: Vec<PathBuf>,
+    watched_files: Vec<PathBuf>,
+}
+
+impl Trace {
+    fn start(context: &Context, trigger: ReloadTrigger) -> Self {
+        Self {
+            started_at: SystemTime::now(),
+            start: Instant::now(),
+            trigger,
+            modified_paths: context
+                .modified_paths()
+                .iter()
+                .map(|p| p.to_path_buf())
+                .collect(),
+            watched_files: context.watched_files(),
+        }
+    }
+
+    fn finish(
+        self,
+        last_trace: &ArcSwapOption<ReloadTrace>,
+        outcome: ReloadOutcome,
+        warnings: Vec<Warning>,
+    ) {
+        let load_duration = match outcome {
+            ReloadOutcome::Updated
+            | ReloadOutcome::Unchanged
+            | ReloadOutcome::LoadFailed(_)
+            | ReloadOutcome::Cancelled => self.start.elapsed(),
+            _ => Duration::ZERO,
+        };
+        last_trace.store(Some(Arc::new(ReloadTrace {
+            started_at: self.started_at,
+            trigger: self.trigger,
+            modified_paths: self.modified_paths,
+            watched_files: self.watched_files,
+            ignored_events: vec![],
+            load_duration,
+            warnings,
+            outcome,
+        })));
+    }
+}
+
+impl<T> std::fmt::Debug for Reloader<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reloader").finish_non_exhaustive()
+    }
+}
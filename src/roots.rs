@@ -0,0 +1,112 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Error, FileSystem, WatcherOptions};
+
+/// The folders loaders are allowed to add dependencies from. See
+/// `Builder::dependency_roots()`.
+#[derive(Debug, Clone)]
+pub(crate) struct DependencyRoots {
+    roots: Arc<[PathBuf]>,
+    /// Used to resolve paths the same way the watcher does.
+    options: Arc<WatcherOptions>,
+}
+
+impl DependencyRoots {
+    pub fn new(roots: Vec<PathBuf>, options: &WatcherOptions) -> Self {
+        Self {
+            roots: roots
+                .iter()
+                .map(|root| normalize(&options.resolve_path(root)))
+                .collect(),
+            options: Arc::new(options.clone()),
+        }
+    }
+
+    /// Return an error unless `path` is inside one of the roots. Symlinks are
+    /// followed, so a link inside a root which points outside of it is
+    /// rejected.
+    pub fn check(&self, fs: &dyn FileSystem, path: &Path) -> Result<(), Error> {
+        let path = normalize(&self.options.resolve_path(path));
+        let resolved = resolve(fs, &path);
+        let allowed = self
+            .roots
+            .iter()
+            .any(|root| path.starts_with(root) && resolved.starts_with(resolve(fs, root)));
+        match allowed {
+            true => Ok(()),
+            false => Err(Error::PathNotAllowed(path)),
+        }
+    }
+}
+
+/// Make `path` absolute, and remove any `.` and `..` components without
+/// touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let path = match path.is_relative() {
+        true => std::env::current_dir().unwrap_or_default().join(path),
+        false => path.to_path_buf(),
+    };
+
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Follow symlinks in `path`, which may not exist. The deepest folder which
+/// exists is canonicalized, and the rest of the path is added back on.
+fn resolve(fs: &dyn FileSystem, path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = fs.canonicalize(ancestor) {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return match rest.as_os_str().is_empty() {
+                true => canonical,
+                false => canonical.join(rest),
+            };
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, path::PathBuf};
+
+    use crate::{Error, StdFileSystem};
+
+    use super::DependencyRoots;
+
+    #[test]
+    fn should_only_allow_paths_inside_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("conf");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(dir.path().join("secret")).unwrap();
+        symlink(dir.path().join("secret"), root.join("link")).unwrap();
+
+        let roots = DependencyRoots::new(vec![root.clone()], &Default::default());
+        let check = |path: PathBuf| roots.check(&StdFileSystem, &path);
+
+        assert!(check(root.join("a.json")).is_ok());
+        assert!(check(root.join("sub/../b.json")).is_ok());
+        assert!(matches!(
+            check(root.join("../secret/a.json")),
+            Err(Error::PathNotAllowed(_))
+        ));
+        assert!(matches!(
+            check(root.join("link/a.json")),
+            Err(Error::PathNotAllowed(_))
+        ));
+        assert!(check(PathBuf::from("/etc/shadow")).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{file_watcher::FileWatcher, Watch};
+
+/// Owns a set of watches, and stops all of them when the scope is dropped.
+///
+/// When a scope is dropped (or `close` is called), every watch added to it
+/// stops watching for changes, and dropping the scope blocks until any loader
+/// or handler that is currently running for those watches has finished. After
+/// that point, no loader or handler for those watches will be called again,
+/// even if clones of the `Watch` are still alive. The watches' values can still
+/// be read, they just won't update.
+///
+/// A scope must not be dropped from inside the loader or handlers of one of
+/// its own watches, as this would deadlock. In async code, use
+/// `close_async()` or `scope_async()`, which wait on a blocking thread
+/// instead of blocking the runtime.
+#[derive(Debug, Default)]
+pub struct WatchScope {
+    watchers: Mutex<Vec<Arc<FileWatcher>>>,
+}
+
+impl WatchScope {
+    /// Create a new, empty scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a watch to this scope. Returns the watch, so this can be chained
+    /// onto a call to `Builder::build()`.
+    pub fn add<T>(&self, watch: Watch<T>) -> Watch<T> {
+        self.watchers.lock().unwrap().push(watch.watcher.clone());
+        watch
+    }
+
+    /// Stop all watches in this scope. This is the same as dropping the scope.
+    pub fn close(self) {}
+
+    /// Stop all watches in this scope, waiting for any loader or handler
+    /// which is running to finish on a blocking thread.
+    #[cfg(feature = "tokio")]
+    pub async fn close_async(self) {
+        let watchers = self.take();
+        tokio::task::spawn_blocking(move || stop(watchers))
+            .await
+            .unwrap()
+    }
+
+    fn take(&self) -> Vec<Arc<FileWatcher>> {
+        std::mem::take(&mut *self.watchers.lock().unwrap())
+    }
+}
+
+impl Drop for WatchScope {
+    fn drop(&mut self) {
+        stop(self.take());
+    }
+}
+
+fn stop(watchers: Vec<Arc<FileWatcher>>) {
+    for watcher in watchers {
+        watcher.stop();
+    }
+}
+
+/// Run `f` with a new `WatchScope`, and stop all watches added to the scope
+/// before returning (or unwinding, if `f` panics).
+pub fn scope<R>(f: impl FnOnce(&WatchScope) -> R) -> R {
+    let scope = WatchScope::new();
+    f(&scope)
+}
+
+/// Like `scope()`, but for async code. The future returned by `f` is awaited,
+/// and then every watch added to the scope is stopped as by `close_async()`.
+/// If the future is dropped before it completes, the watches are stopped when
+/// the last reference to the scope is dropped.
+#[cfg(feature = "tokio")]
+pub async fn scope_async<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Arc<WatchScope>) -> Fut,
+    Fut: std::future::Future,
+{
+    let scope = Arc::new(WatchScope::new());
+    let result = f(scope.clone()).await;
+    let watchers = scope.take();
+    tokio::task::spawn_blocking(move || stop(watchers))
+        .await
+        .unwrap();
+    result
+}
@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    loaders::value::from_value, reloader::ReloaderGuard, Error, Guard, UpdatedHandler, Watch,
+};
+
+/// A view of one top-level section of a `Watch<serde_json::Value>`, created
+/// with `Watch::section()`.
+///
+/// The document is still parsed only once per reload. After each reload, the
+/// section is compared with its previous contents, and only if it changed is
+/// it deserialized, stored, and passed to the section's subscribers. Changes to
+/// other sections of the file don't affect this section at all.
+pub struct Section<S> {
+    key: Arc<str>,
+    inner: Arc<Inner<S>>,
+}
+
+struct Inner<S> {
+    value: ArcSwap<S>,
+    /// The contents of the section when `value` was last stored.
+    raw: Mutex<Value>,
+    subscribers: Mutex<Vec<Box<dyn UpdatedHandler<S> + Send>>>,
+}
+
+impl<S> Clone for Section<S> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for Section<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Section")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Section<S> {
+    /// The key of this section in the document.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Produces a temporary borrow of the current value of this section.
+    pub fn value(&self) -> Guard<S> {
+        self.inner.value.load()
+    }
+
+    /// Add a handler to call whenever this section changes. Handlers are
+    /// called on the watch's thread, after the new value of the section has
+    /// been stored and before the watch's own `after_update` handler.
+    pub fn subscribe(&self, handler: impl UpdatedHandler<S> + Send + 'static) {
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Box::new(handler));
+    }
+}
+
+impl Watch<Value> {
+    /// Create a sub-watch for the section of the document under `key`. If the
+    /// section is missing or `null`, its value is `S::default()`.
+    ///
+    /// Returns an error if the current contents of the section can't be
+    /// deserialized. If a later reload produces a section which can't be
+    /// deserialized, the error is passed to the watch's error handler and the
+    /// section keeps its previous value.
+    ///
+    /// This will block while a reload is in progress. If it is called from
+    /// within a loader or handler while this watch is busy, it returns
+    /// `Error::WouldDeadlock` instead.
+    pub fn section<S>(&self, key: impl AsRef<str>) -> Result<Section<S>, Error>
+    where
+        S: DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        let key: Arc<str> = Arc::from(key.as_ref());
+
+        let mut reloader = ReloaderGuard::lock(&self.reloader)?;
+        let raw = section_of(&self.value.load(), &key);
+        let value = from_value::<S>(raw.clone()).map_err(|err| section_error(&key, err))?;
+
+        let section = Section {
+            key: key.clone(),
+            inner: Arc::new(Inner {
+                value: ArcSwap::from_pointee(value),
+                raw: Mutex::new(raw),
+                subscribers: Mutex::new(vec![]),
+            }),
+        };
+
+        let weak = Arc::downgrade(&section.inner);
+        reloader.add_listener(Box::new(move |context, document, error_handler| {
+            let Some(inner) = weak.upgrade() else {
+                return false;
+            };
+
+            let raw = section_of(document, &key);
+            if *inner.raw.lock().unwrap() == raw {
+                return true;
+            }
+
+            match from_value::<S>(raw.clone()) {
+                Ok(value) => {
+                    inner.value.store(Arc::new(value));
+                    *inner.raw.lock().unwrap() = raw;
+                    for subscriber in inner.subscribers.lock().unwrap().iter_mut() {
+                        subscriber.after_update(context, inner.value.load());
+                    }
+                }
+                Err(err) => error_handler.on_error(context, section_error(&key, err)),
+            }
+            true
+        }));
+
+        Ok(section)
+    }
+}
+
+/// Get a copy of the section under `key`, or `null` if there isn't one.
+fn section_of(document: &Value, key: &str) -> Value {
+    document.get(key).cloned().unwrap_or(Value::Null)
+}
+
+fn section_error(key: &str, err: Box<dyn std::error::Error + Send + Sync>) -> Error {
+    Error::LoadError(format!("Invalid section {key:?}: {err}").into())
+}
@@ -0,0 +1,90 @@
+use std::{ops::Deref, sync::Arc, time::SystemTime};
+
+use crate::Watch;
+
+/// An owned copy of a watch's value, returned by `Watch::snapshot()`.
+///
+/// Unlike the `Guard` returned by `Watch::value()`, which is meant to be held
+/// briefly, a snapshot can be stored, sent to another thread, or returned from
+/// a function for as long as you like. It never changes; call
+/// `Watch::snapshot()` again to get the latest value. `Snapshot<T>` derefs to
+/// `T`.
+#[derive(Debug)]
+pub struct Snapshot<T> {
+    value: Arc<T>,
+    version: u64,
+    loaded_at: Option<SystemTime>,
+}
+
+impl<T> Clone for Snapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            version: self.version,
+            loaded_at: self.loaded_at,
+        }
+    }
+}
+
+impl<T> Snapshot<T> {
+    /// Create the snapshot for a watch's default value, before anything has
+    /// been loaded.
+    pub(crate) fn initial(value: Arc<T>) -> Self {
+        Self {
+            value,
+            version: 0,
+            loaded_at: None,
+        }
+    }
+
+    /// Create the snapshot which follows this one, for a newly stored value.
+    pub(crate) fn next(&self, value: Arc<T>) -> Self {
+        Self {
+            value,
+            version: self.version + 1,
+            loaded_at: Some(SystemTime::now()),
+        }
+    }
+
+    /// Get the value.
+    pub fn value(&self) -> &Arc<T> {
+        &self.value
+    }
+
+    /// Take the value out of the snapshot.
+    pub fn into_inner(self) -> Arc<T> {
+        self.value
+    }
+
+    /// The number of times the watch's value had been replaced when this
+    /// snapshot was taken. This is 0 for the watch's default value, and goes
+    /// up by one every time a new value is stored, so two snapshots with the
+    /// same version hold the same value.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// When the value was stored, or `None` if this is the watch's default
+    /// value.
+    pub fn loaded_at(&self) -> Option<SystemTime> {
+        self.loaded_at
+    }
+}
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Watch<T> {
+    /// Get an owned snapshot of the current value, along with its version.
+    /// Prefer this over `value()` if you need to keep the value around, for
+    /// example in a request context or a struct; long-lived guards from
+    /// `value()` can slow down updates.
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot::clone(&self.snapshot.load())
+    }
+}
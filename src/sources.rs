@@ -0,0 +1,226 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::FileSystem;
+
+/// Where a candidate config file path came from. See `ConfigSources`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigSource {
+    /// A command line flag, such as `--config`.
+    Flag(String),
+    /// An environment variable, such as `MYAPP_CONFIG`.
+    EnvVar(String),
+    /// One of the program's default locations.
+    Default,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Flag(flag) => write!(f, "the {flag} flag"),
+            ConfigSource::EnvVar(name) => write!(f, "${name}"),
+            ConfigSource::Default => f.write_str("the default locations"),
+        }
+    }
+}
+
+/// The config file chosen by `ConfigSources::resolve()`, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Resolution {
+    /// The file to load.
+    pub path: PathBuf,
+    /// Where `path` came from.
+    pub source: ConfigSource,
+    /// False if none of the candidates exist, in which case `path` is the
+    /// highest precedence candidate.
+    pub exists: bool,
+    /// Higher precedence candidates which were passed over because they
+    /// don't exist.
+    pub skipped: Vec<(PathBuf, ConfigSource)>,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (from {})", self.path.display(), self.source)?;
+        if !self.exists {
+            return f.write_str(", which does not exist yet");
+        }
+        for (path, source) in &self.skipped {
+            write!(f, "; {} from {} does not exist", path.display(), source)?;
+        }
+        Ok(())
+    }
+}
+
+/// The places a program looks for its config file, in precedence order: a
+/// command line flag, then an environment variable, then a list of default
+/// locations. Pass this to `Builder::watch_sources()` to watch every
+/// candidate and load the highest precedence one which exists, and use
+/// `resolve()` to report which one that is.
+///
+/// ```
+/// # use config_file_watch::{ConfigSources, StdFileSystem};
+/// # let flag: Option<String> = None;
+/// let sources = ConfigSources::new()
+///     .flag("--config", flag)
+///     .env("MYAPP_CONFIG")
+///     .default_location("/etc/myapp/config.toml");
+/// if let Some(resolution) = sources.resolve(&StdFileSystem) {
+///     println!("Loading config from {resolution}");
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    candidates: Vec<(PathBuf, ConfigSource)>,
+}
+
+impl ConfigSources {
+    /// Create a new, empty, ConfigSources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the value of a command line flag, if it was given. `flag` is only
+    /// used to explain the choice, such as `"--config"`.
+    pub fn flag(self, flag: impl Into<String>, value: Option<impl AsRef<Path>>) -> Self {
+        let source = ConfigSource::Flag(flag.into());
+        self.push(value.map(|v| v.as_ref().to_path_buf()), source)
+    }
+
+    /// Add the value of an environment variable, if it is set and not empty.
+    /// The variable is read when this is called.
+    pub fn env(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let value = std::env::var_os(&name).filter(|v| !v.is_empty());
+        self.push(value.map(PathBuf::from), ConfigSource::EnvVar(name))
+    }
+
+    /// Add a default location.
+    pub fn default_location(self, path: impl AsRef<Path>) -> Self {
+        self.push(Some(path.as_ref().to_path_buf()), ConfigSource::Default)
+    }
+
+    /// Add several default locations, in precedence order.
+    pub fn default_locations<I>(self, paths: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        paths
+            .into_iter()
+            .fold(self, |sources, path| sources.default_location(path))
+    }
+
+    fn push(mut self, path: Option<PathBuf>, source: ConfigSource) -> Self {
+        if let Some(path) = path {
+            if !self.candidates.iter().any(|(p, _)| *p == path) {
+                self.candidates.push((path, source));
+            }
+        }
+        self
+    }
+
+    /// Every candidate path, in precedence order.
+    pub fn candidates(&self) -> impl Iterator<Item = (&Path, &ConfigSource)> {
+        self.candidates
+            .iter()
+            .map(|(path, source)| (path.as_path(), source))
+    }
+
+    /// Pick the highest precedence candidate which exists on `fs`, the same
+    /// way a watch built with `Builder::watch_sources()` does. If none of the
+    /// candidates exist the highest precedence one is returned, with `exists`
+    /// set to false. Returns `None` if there are no candidates at all.
+    ///
+    /// Paths are checked as given, so if the watch uses
+    /// `Builder::base_dir()` or `Builder::expand_paths()` the result may
+    /// differ from the file the watch loads.
+    pub fn resolve(&self, fs: &dyn FileSystem) -> Option<Resolution> {
+        let (first, first_source) = self.candidates.first()?;
+        let mut skipped = vec![];
+        for (path, source) in &self.candidates {
+            if fs.metadata(path).is_ok_and(|metadata| !metadata.is_dir) {
+                return Some(Resolution {
+                    path: path.clone(),
+                    source: source.clone(),
+                    exists: true,
+                    skipped,
+                });
+            }
+            skipped.push((path.clone(), source.clone()));
+        }
+        Some(Resolution {
+            path: first.clone(),
+            source: first_source.clone(),
+            exists: false,
+            skipped: vec![],
+        })
+    }
+
+    pub(crate) fn into_paths(self) -> impl Iterator<Item = PathBuf> {
+        self.candidates.into_iter().map(|(path, _)| path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::MemoryFileSystem;
+
+    use super::{ConfigSource, ConfigSources};
+
+    #[test]
+    fn should_pick_the_highest_precedence_file_which_exists() {
+        let fs = MemoryFileSystem::new();
+        fs.write("/etc/app.toml", "");
+        std::env::set_var("CFW_TEST_SOURCES_CONFIG", "/env/app.toml");
+
+        let sources = ConfigSources::new()
+            .flag("--config", None::<&str>)
+            .env("CFW_TEST_SOURCES_CONFIG")
+            .env("CFW_TEST_SOURCES_UNSET")
+            .default_locations(["/home/me/app.toml", "/etc/app.toml"]);
+
+        let resolution = sources.resolve(&fs).unwrap();
+        assert_eq!(resolution.path, PathBuf::from("/etc/app.toml"));
+        assert_eq!(resolution.source, ConfigSource::Default);
+        assert!(resolution.exists);
+        assert_eq!(
+            resolution.to_string(),
+            "/etc/app.toml (from the default locations); \
+             /env/app.toml from $CFW_TEST_SOURCES_CONFIG does not exist; \
+             /home/me/app.toml from the default locations does not exist"
+        );
+
+        fs.write("/env/app.toml", "");
+        let resolution = sources.resolve(&fs).unwrap();
+        assert_eq!(
+            resolution.source,
+            ConfigSource::EnvVar("CFW_TEST_SOURCES_CONFIG".to_string())
+        );
+        assert!(resolution.skipped.is_empty());
+    }
+
+    #[test]
+    fn should_fall_back_to_the_first_candidate() {
+        let fs = MemoryFileSystem::new();
+        let sources = ConfigSources::new()
+            .flag("--config", Some("/flag/app.toml"))
+            .default_location("/etc/app.toml");
+
+        let resolution = sources.resolve(&fs).unwrap();
+        assert_eq!(resolution.path, PathBuf::from("/flag/app.toml"));
+        assert_eq!(
+            resolution.source,
+            ConfigSource::Flag("--config".to_string())
+        );
+        assert!(!resolution.exists);
+
+        assert!(ConfigSources::new().resolve(&fs).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use crate::{CircuitState, Stage, Warning};
+
+/// Counters for a watch, returned by `Watch::stats()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WatchStats {
+    /// The number of times a new value was stored, including the initial load.
+    pub reloads: u64,
+    /// The number of times the loader failed.
+    pub failed_reloads: u64,
+    /// The number of times the loader returned a value equal to the current
+    /// one, which was discarded because of `Builder::dedup_values()`.
+    pub unchanged_reloads: u64,
+    /// The number of errors which weren't passed to the error handler because
+    /// of `Builder::suppress_repeated_errors()`.
+    pub suppressed_errors: u64,
+    /// The total number of warnings reported.
+    pub warnings: u64,
+    /// The warnings reported by the most recent successful load.
+    pub last_warnings: Vec<Warning>,
+    /// An exponential moving average of the time taken by the loader, for
+    /// both successful and failed loads. This is zero until the first load.
+    pub average_load_duration: Duration,
+    /// The debounce duration currently in use. With
+    /// `Builder::adaptive_debounce()`, this is the duration it has chosen.
+    pub debounce: Option<Duration>,
+    /// The number of changes which weren't loaded because the circuit breaker
+    /// set with `Builder::circuit_breaker()` was open.
+    pub skipped_reloads: u64,
+    /// The number of loads whose result was discarded because a watched file
+    /// changed again while they were running. See `Context::cancelled()`.
+    pub cancelled_reloads: u64,
+    /// The number of loads which took longer than the timeout set with
+    /// `Builder::load_timeout()`. This goes up while the load is still
+    /// running.
+    pub timed_out_loads: u64,
+    /// The state of the circuit breaker. This is always `Closed` for a watch
+    /// without one.
+    pub circuit: CircuitState,
+    /// How long each stage of a `Pipeline` took in the most recent load, in
+    /// the order they ran. Empty for other loaders.
+    pub stage_durations: Vec<(Stage, Duration)>,
+    /// Why the watch isn't using the backend it was asked for, if it isn't.
+    /// For example, if the operating system's file notification API is
+    /// unavailable, `Backend::Native` falls back to polling.
+    pub degraded: Option<String>,
+}
+
+/// The weight given to the latest load when updating the average.
+const SMOOTHING: f64 = 0.2;
+
+/// The number of loads in a row the average has to go up for before it counts
+/// as trending upward.
+const TREND_LOADS: u32 = 5;
+
+/// Keeps track of how long the loader takes.
+#[derive(Debug, Default)]
+pub(crate) struct LoadDurations {
+    average: Option<Duration>,
+    /// The number of loads in a row which increased the average.
+    rising: u32,
+    /// The average before it started going up.
+    rising_from: Duration,
+}
+
+impl LoadDurations {
+    /// Record how long a load took. Returns the new average, and true if the
+    /// average has just been trending upward: it has gone up for
+    /// `TREND_LOADS` loads in a row, and is at least 50% higher than when it
+    /// started going up.
+    pub fn record(&mut self, duration: Duration) -> (Duration, bool) {
+        let Some(previous) = self.average else {
+            self.average = Some(duration);
+            return (duration, false);
+        };
+
+        let average = previous.mul_f64(1.0 - SMOOTHING) + duration.mul_f64(SMOOTHING);
+        self.average = Some(average);
+
+        if average <= previous {
+            self.rising = 0;
+            return (average, false);
+        }
+        if self.rising == 0 {
+            self.rising_from = previous;
+        }
+        self.rising += 1;
+
+        let trending = self.rising >= TREND_LOADS && average > self.rising_from.mul_f64(1.5);
+        if trending {
+            // Start a new streak, so we only warn again if it keeps going up.
+            self.rising = 0;
+        }
+        (average, trending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LoadDurations;
+
+    #[test]
+    fn should_smooth_load_durations() {
+        let mut durations = LoadDurations::default();
+        assert_eq!(
+            durations.record(Duration::from_millis(100)),
+            (Duration::from_millis(100), false)
+        );
+        assert_eq!(
+            durations.record(Duration::from_millis(200)),
+            (Duration::from_millis(120), false)
+        );
+    }
+
+    #[test]
+    fn should_detect_upward_trend() {
+        let mut durations = LoadDurations::default();
+        for _ in 0..10 {
+            assert!(!durations.record(Duration::from_millis(10)).1);
+        }
+
+        let trending = (1..=5)
+            .map(|i| durations.record(Duration::from_millis(10 + i * 10)).1)
+            .collect::<Vec<_>>();
+        assert_eq!(trending, vec![false, false, false, false, true]);
+
+        // A faster load ends the trend.
+        assert!(!durations.record(Duration::ZERO).1);
+    }
+}
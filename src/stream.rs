@@ -0,0 +1,71 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::sync::watch::Receiver;
+
+use crate::Watch;
+
+type Changed = Pin<Box<dyn Future<Output = (Receiver<()>, bool)> + Send>>;
+
+/// A `Stream` which yields the value of a watch each time a new value is
+/// stored, created with `Watch::stream()` or `Watch::into_stream()`.
+///
+/// If several values are stored before the stream is polled, it only yields
+/// the latest one. The stream keeps the watch alive, so it never ends.
+pub struct WatchStream<T> {
+    watch: Watch<T>,
+    changed: Changed,
+}
+
+impl<T> fmt::Debug for WatchStream<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchStream").finish_non_exhaustive()
+    }
+}
+
+/// Wait for the next change, and hand back the receiver along with whether
+/// the sender is still alive.
+async fn changed(mut updates: Receiver<()>) -> (Receiver<()>, bool) {
+    let alive = updates.changed().await.is_ok();
+    (updates, alive)
+}
+
+impl<T> Watch<T> {
+    /// Get a stream of the values stored from now on. See `WatchStream`.
+    pub fn stream(&self) -> WatchStream<T> {
+        self.clone().into_stream()
+    }
+
+    /// Turn this watch into a stream of the values stored from now on. See
+    /// `WatchStream`.
+    pub fn into_stream(self) -> WatchStream<T> {
+        let mut updates = self.updates.clone();
+        updates.borrow_and_update();
+        WatchStream {
+            watch: self,
+            changed: Box::pin(changed(updates)),
+        }
+    }
+}
+
+impl<T> Stream for WatchStream<T> {
+    type Item = Arc<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Arc<T>>> {
+        let (updates, alive) = match self.changed.as_mut().poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        if !alive {
+            return Poll::Ready(None);
+        }
+        self.changed = Box::pin(changed(updates));
+        Poll::Ready(Some(self.watch.value.load_full()))
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use crate::Warning;
+
+/// Decides which errors are passed to the error handler when
+/// `Builder::suppress_repeated_errors()` is used. The first occurrence of an
+/// error is always reported, and the same error is then reported at most once
+/// per interval until a load succeeds.
+#[derive(Debug)]
+pub(crate) struct ErrorSuppressor {
+    interval: Duration,
+    /// The most recently reported error, and when it was reported.
+    last: Option<(String, Instant)>,
+    /// The number of errors since the last successful load.
+    failures: u64,
+    /// The number of errors which weren't reported since the last successful
+    /// load.
+    suppressed: u64,
+}
+
+impl ErrorSuppressor {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+            failures: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Called for each error. Returns true if the error should be passed to
+    /// the error handler.
+    pub fn on_error(&mut self, message: &str, now: Instant) -> bool {
+        self.failures += 1;
+        match &self.last {
+            Some((last, reported_at))
+                if last == message && now.duration_since(*reported_at) < self.interval =>
+            {
+                self.suppressed += 1;
+                false
+            }
+            _ => {
+                self.last = Some((message.to_string(), now));
+                true
+            }
+        }
+    }
+
+    /// Called when a load succeeds. If any errors were suppressed since the
+    /// last successful load, returns a warning saying the problem is resolved.
+    pub fn on_success(&mut self) -> Option<Warning> {
+        let failures = std::mem::take(&mut self.failures);
+        let suppressed = std::mem::take(&mut self.suppressed);
+        self.last = None;
+        (suppressed > 0).then(|| {
+            Warning::new(format!(
+                "loaded successfully after {failures} failed attempts \
+                 ({suppressed} repeated errors were not reported)"
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::ErrorSuppressor;
+
+    #[test]
+    fn should_suppress_repeated_errors() {
+        let start = Instant::now();
+        let mut suppressor = ErrorSuppressor::new(Duration::from_secs(60));
+
+        assert!(suppressor.on_error("bad", start));
+        assert!(!suppressor.on_error("bad", start + Duration::from_secs(1)));
+        assert!(!suppressor.on_error("bad", start + Duration::from_secs(59)));
+        // A different error is reported straight away.
+        assert!(suppressor.on_error("worse", start + Duration::from_secs(59)));
+        assert!(!suppressor.on_error("worse", start + Duration::from_secs(60)));
+        // The same error is reported again once the interval has passed.
+        assert!(suppressor.on_error("worse", start + Duration::from_secs(120)));
+
+        let warning = suppressor.on_success().unwrap();
+        assert_eq!(
+            warning.message,
+            "loaded successfully after 6 failed attempts (3 repeated errors were not reported)"
+        );
+        assert!(suppressor.on_error("bad", start + Duration::from_secs(121)));
+        assert!(suppressor.on_success().is_none());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::Warning;
+
+/// A record of what happened during a reload, returned by
+/// `Watch::explain_last_reload()`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ReloadTrace {
+    /// When the reload started.
+    pub started_at: SystemTime,
+    /// What caused the reload.
+    pub trigger: ReloadTrigger,
+    /// The watched files which were passed to the loader as
+    /// `Context::modified_paths()`.
+    pub modified_paths: Vec<PathBuf>,
+    /// The files being watched when the loader was called.
+    pub watched_files: Vec<PathBuf>,
+    /// Paths reported by the file system since this reload which didn't match
+    /// any watched file, and so didn't cause a reload. At most 100 paths are
+    /// kept.
+    pub ignored_events: Vec<PathBuf>,
+    /// How long the loader took to run.
+    pub load_duration: Duration,
+    /// Warnings reported by the loader, if the load succeeded.
+    pub warnings: Vec<Warning>,
+    /// The result of the reload.
+    pub outcome: ReloadOutcome,
+}
+
+/// What caused a reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReloadTrigger {
+    /// The initial load when the watch was built.
+    InitialLoad,
+    /// A change to one or more watched files.
+    FileChange {
+        /// Every path reported by the file system in the batch of events which
+        /// caused this reload, including paths which aren't watched.
+        events: Vec<PathBuf>,
+        /// The debounce duration events were collected over, if any.
+        debounce: Option<Duration>,
+    },
+}
+
+/// The result of a reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReloadOutcome {
+    /// The loader succeeded and the new value was stored.
+    Updated,
+    /// The loader was not called, and the current value was kept.
+    Skipped,
+    /// The loader succeeded, but the value was equal to the current one, so
+    /// the current value was kept. See `Builder::dedup_values()`.
+    Unchanged,
+    /// The loader failed, and the current value was kept.
+    LoadFailed(String),
+    /// A watched file changed again while the loader was running, so its
+    /// result was discarded. See `Context::cancelled()`.
+    Cancelled,
+    /// The file watcher reported an error.
+    WatchFailed(String),
+}
@@ -1,4 +1,6 @@
-use crate::{context::Context, Error, Guard};
+use std::path::Path;
+
+use crate::{context::Context, Error, Guard, Progress, Warning};
 
 /// Loads a configuration file.
 pub trait Loader<T> {
@@ -7,7 +9,19 @@ pub trait Loader<T> {
     /// The context can be used to get the list of `modified_paths`, and to
     /// update the current value of the watch, or change the set of files being
     /// watched.
-    fn load(&mut self, context: &mut Context) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Called once the value from the last call to `load()` has been accepted
+    /// by the watch, either stored or kept as unchanged. It isn't called if
+    /// the value was rejected (such as by `Builder::validate()`), was
+    /// discarded because the load was cancelled or timed out, or came from
+    /// `Watch::dry_run()`. A loader which carries state from one load to the
+    /// next should only update it here. Loaders which wrap another loader
+    /// should pass this on.
+    fn commit(&mut self) {}
 }
 
 /// Handles errors that occur during loading.
@@ -22,12 +36,52 @@ pub trait UpdatedHandler<T> {
     fn after_update(&mut self, context: &mut Context, value: Guard<T>);
 }
 
+/// Handles warnings reported while loading.
+pub trait WarningHandler {
+    /// Called for each warning, after the new value has been stored.
+    fn on_warning(&mut self, context: &mut Context, warning: &Warning);
+}
+
+/// Handles progress reported by slow loaders.
+pub trait ProgressHandler {
+    /// Called from the loader's thread each time the loader reports progress.
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+/// Builds the new contents of a file from its previous contents, for
+/// `PatchLoader`.
+pub trait Patcher {
+    /// Return the new contents of the file at `path`, given its contents from
+    /// the last successful load. Return `None` to read the file in full.
+    fn patch(
+        &mut self,
+        context: &mut Context,
+        path: &Path,
+        previous: &[u8],
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Checks a loaded value for non-fatal problems.
+pub trait Linter<T> {
+    /// Return any problems found in `value`.
+    fn lint(&mut self, value: &T) -> Vec<Warning>;
+}
+
+/// Checks a loaded value, and rejects it if it is invalid.
+pub trait Validator<T> {
+    /// Return an error if `value` should not be used.
+    fn validate(&mut self, value: &T) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
 /// Allow passing in a closure as a loader.
 impl<T, F> Loader<T> for F
 where
     F: FnMut(&mut Context) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
 {
-    fn load(&mut self, context: &mut Context) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
         self(context)
     }
 }
@@ -42,6 +96,66 @@ where
     }
 }
 
+/// Allow passing in a `|context, warning|` closure as a warning handler.
+impl<F> WarningHandler for F
+where
+    F: FnMut(&mut Context, &Warning),
+{
+    fn on_warning(&mut self, context: &mut Context, warning: &Warning) {
+        self(context, warning);
+    }
+}
+
+/// Allow passing in a closure as a progress handler.
+impl<F> ProgressHandler for F
+where
+    F: FnMut(&Progress),
+{
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress);
+    }
+}
+
+/// Allow passing in a closure as a patcher.
+impl<F> Patcher for F
+where
+    F: FnMut(
+        &mut Context,
+        &Path,
+        &[u8],
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>,
+{
+    fn patch(
+        &mut self,
+        context: &mut Context,
+        path: &Path,
+        previous: &[u8],
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+        self(context, path, previous)
+    }
+}
+
+/// Allow passing in a closure as a linter.
+impl<F, T> Linter<T> for F
+where
+    F: FnMut(&T) -> Vec<Warning>,
+{
+    fn lint(&mut self, value: &T) -> Vec<Warning> {
+        self(value)
+    }
+}
+
+/// Allow passing in a closure as a validator.
+impl<F, T, E> Validator<T> for F
+where
+    F: FnMut(&T) -> Result<(), E>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    fn validate(&mut self, value: &T) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self(value).map_err(Into::into)
+    }
+}
+
 /// Allow passing in a closure as an event handler.
 impl<F, T> UpdatedHandler<T> for F
 where
@@ -55,7 +169,10 @@ where
 pub struct DefaultLoader;
 
 impl Loader<()> for DefaultLoader {
-    fn load(&mut self, _context: &mut Context) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    fn load(
+        &mut self,
+        _context: &mut Context,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         Ok(())
     }
 }
@@ -75,3 +192,11 @@ impl<T> UpdatedHandler<T> for DefaultUpdatedHandler {
         // Do nothing.
     }
 }
+
+pub struct DefaultWarningHandler;
+
+impl WarningHandler for DefaultWarningHandler {
+    fn on_warning(&mut self, _context: &mut Context, warning: &Warning) {
+        eprintln!("Warning loading config: {warning}");
+    }
+}
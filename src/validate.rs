@@ -0,0 +1,39 @@
+use crate::{Context, Error, Loader, Validator};
+
+/// Wraps another loader, and checks each value it loads with a `Validator`.
+/// If the validator rejects a value, the load fails with an
+/// `Error::ValidationError`, so the watch keeps its current value and the
+/// error handler is called. See `Builder::validate()`.
+#[derive(Debug)]
+pub struct ValidateLoader<L, F> {
+    inner: L,
+    validator: F,
+}
+
+impl<L, F> ValidateLoader<L, F> {
+    /// Create a new ValidateLoader.
+    pub fn new(inner: L, validator: F) -> Self {
+        Self { inner, validator }
+    }
+}
+
+impl<T, L, F> Loader<T> for ValidateLoader<L, F>
+where
+    L: Loader<T>,
+    F: Validator<T>,
+{
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let value = self.inner.load(context)?;
+        match self.validator.validate(&value) {
+            Ok(()) => Ok(value),
+            Err(err) => Err(Box::new(Error::ValidationError(err))),
+        }
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit();
+    }
+}
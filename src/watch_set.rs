@@ -0,0 +1,255 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use crate::{
+    loaders::load_format, AutoFormatLoader, Backend, Builder, Context, Error, Format, Loader, Watch,
+};
+
+/// A set of named watches, declared in a manifest file rather than in code.
+/// Created with `WatchSet::from_manifest()`.
+///
+/// The manifest can be in any supported format (see `Format`), and lists each
+/// config under `configs`:
+///
+/// ```toml
+/// [configs.database]
+/// path = "database.yaml"
+///
+/// [configs.features]
+/// path = "/etc/myapp/features"
+/// format = "json"          # Defaults to the file's extension.
+/// debounce_ms = 500        # Defaults to the usual debounce.
+/// poll_interval_ms = 2000  # Poll for changes instead of using native events.
+/// ```
+///
+/// Each config is loaded into an untyped `serde_json::Value`. Relative paths
+/// are resolved against the folder the manifest is in.
+///
+/// The manifest is watched too. When it changes, new configs are added,
+/// removed configs are dropped, and configs whose settings changed are
+/// replaced with a new watch; configs which didn't change keep their existing
+/// watch. If the new manifest is invalid, or one of its watches can't be
+/// started, the error is reported and the set is unchanged.
+#[derive(Debug, Clone)]
+pub struct WatchSet {
+    manifest: Watch<BTreeMap<String, Member>>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    spec: Spec,
+    watch: Watch<Value>,
+}
+
+/// The settings for one config in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Spec {
+    path: PathBuf,
+    format: Option<Format>,
+    debounce: Option<Duration>,
+    poll_interval: Option<Duration>,
+}
+
+impl WatchSet {
+    /// Load the manifest at `path`, and start a watch for every config it
+    /// lists. Returns an error if the manifest is invalid, or if any of its
+    /// watches can't be started. If the manifest doesn't exist the set starts
+    /// out empty.
+    pub fn from_manifest(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let manifest = Builder::new()
+            .watch_file(&path)
+            .load(ManifestLoader {
+                base,
+                members: BTreeMap::new(),
+            })
+            .fail_on_initial_error()
+            .build()?;
+        Ok(Self { manifest })
+    }
+
+    /// Get the watch for the config called `name`.
+    pub fn get(&self, name: &str) -> Option<Watch<Value>> {
+        self.manifest
+            .value()
+            .get(name)
+            .map(|member| member.watch.clone())
+    }
+
+    /// The names of every config in the set, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        self.manifest.value().keys().cloned().collect()
+    }
+}
+
+/// Loads the manifest, and starts or reuses a watch for each config in it.
+struct ManifestLoader {
+    /// The folder relative paths are resolved against.
+    base: PathBuf,
+    /// The members from the last successful load.
+    members: BTreeMap<String, Member>,
+}
+
+impl Loader<BTreeMap<String, Member>> for ManifestLoader {
+    fn load(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<BTreeMap<String, Member>, Box<dyn std::error::Error + Send + Sync>> {
+        let document: Value = AutoFormatLoader::new().load(context)?;
+        let specs = parse_manifest(context.path(), &document)?;
+
+        let mut members = BTreeMap::new();
+        for (name, spec) in specs {
+            let member = match self.members.get(&name) {
+                Some(member) if member.spec == spec => member.clone(),
+                _ => Member {
+                    watch: self.start(&spec).map_err(|err| {
+                        Error::LoadError(format!("Unable to watch config {name:?}: {err}").into())
+                    })?,
+                    spec,
+                },
+            };
+            members.insert(name, member);
+        }
+
+        self.members = members.clone();
+        Ok(members)
+    }
+}
+
+impl ManifestLoader {
+    /// Start a watch for a config.
+    fn start(&self, spec: &Spec) -> Result<Watch<Value>, Error> {
+        let mut builder = Builder::new().base_dir(&self.base).watch_file(&spec.path);
+        if let Some(debounce) = spec.debounce {
+            builder = builder.debounce(debounce);
+        }
+        if let Some(interval) = spec.poll_interval {
+            builder = builder.backend(Backend::Poll { interval });
+        }
+        match spec.format {
+            Some(format) => builder
+                .load(move |context: &mut Context| load_format(format, context))
+                .build(),
+            None => builder.load(AutoFormatLoader::new()).build(),
+        }
+    }
+}
+
+/// Read the specs for every config from a manifest.
+fn parse_manifest(path: Option<&Path>, document: &Value) -> Result<Vec<(String, Spec)>, Error> {
+    let invalid = |field_path: String, message: &str| Error::ParseError {
+        path: path.map(Path::to_path_buf),
+        field_path,
+        line: None,
+        column: None,
+        message: message.to_string(),
+    };
+
+    let configs = match document {
+        Value::Null => return Ok(vec![]),
+        Value::Object(document) => match document.get("configs") {
+            None | Some(Value::Null) => return Ok(vec![]),
+            Some(Value::Object(configs)) => configs,
+            Some(_) => return Err(invalid("configs".into(), "expected a table of configs")),
+        },
+        _ => return Err(invalid(".".into(), "expected a table")),
+    };
+
+    configs
+        .iter()
+        .map(|(name, entry)| {
+            let field = |key: &str| format!("configs.{name}.{key}");
+            let Value::Object(entry) = entry else {
+                return Err(invalid(format!("configs.{name}"), "expected a table"));
+            };
+            if let Some(key) = entry.keys().find(|key| !SPEC_KEYS.contains(&key.as_str())) {
+                return Err(invalid(field(key), "unknown field"));
+            }
+
+            let path = match entry.get("path") {
+                Some(Value::String(path)) => PathBuf::from(path),
+                Some(_) => return Err(invalid(field("path"), "expected a string")),
+                None => return Err(invalid(field("path"), "missing field")),
+            };
+            let format = match entry.get("format") {
+                None => None,
+                Some(Value::String(format)) => match Format::from_name(format) {
+                    Some(format) => Some(format),
+                    None => return Err(invalid(field("format"), "unknown format")),
+                },
+                Some(_) => return Err(invalid(field("format"), "expected a string")),
+            };
+            let millis = |key: &str| match entry.get(key) {
+                None => Ok(None),
+                Some(value) => match value.as_u64() {
+                    Some(millis) => Ok(Some(Duration::from_millis(millis))),
+                    None => Err(invalid(field(key), "expected a number of milliseconds")),
+                },
+            };
+
+            let spec = Spec {
+                path,
+                format,
+                debounce: millis("debounce_ms")?,
+                poll_interval: millis("poll_interval_ms")?,
+            };
+            Ok((name.clone(), spec))
+        })
+        .collect()
+}
+
+/// The keys allowed in each config in the manifest.
+const SPEC_KEYS: &[&str] = &["path", "format", "debounce_ms", "poll_interval_ms"];
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use serde_json::json;
+
+    use crate::{Error, Format};
+
+    use super::parse_manifest;
+
+    #[test]
+    fn should_parse_manifest() {
+        let specs = parse_manifest(
+            None,
+            &json!({"configs": {
+                "db": {"path": "db.yaml"},
+                "features": {"path": "features", "format": "JSON", "debounce_ms": 500},
+            }}),
+        )
+        .unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].0, "db");
+        assert_eq!(specs[0].1.path, PathBuf::from("db.yaml"));
+        assert_eq!(specs[1].1.format, Some(Format::Json));
+        assert_eq!(specs[1].1.debounce, Some(Duration::from_millis(500)));
+
+        assert!(parse_manifest(None, &json!(null)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_reject_invalid_manifest() {
+        let err = parse_manifest(
+            None,
+            &json!({"configs": {"db": {"path": "db.json", "debounce": 1}}}),
+        )
+        .unwrap_err();
+        let Error::ParseError { field_path, .. } = err else {
+            panic!("Expected a parse error, got {err:?}");
+        };
+        assert_eq!(field_path, "configs.db.debounce");
+
+        let err = parse_manifest(None, &json!({"configs": {"db": {}}})).unwrap_err();
+        assert!(err.to_string().contains("configs.db.path"), "{err}");
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Watches a running load, and calls a function if it takes longer than the
+/// timeout set with `Builder::load_timeout()`. The function is called on the
+/// watchdog's own thread while the load is still running, so a loader which
+/// hangs (such as on a read from an unresponsive network file system) is
+/// reported instead of silently stopping the watch.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    finished: bool,
+    timed_out: bool,
+}
+
+impl Watchdog {
+    /// Start a thread which calls `on_timeout` if `finish()` isn't called
+    /// within `timeout`.
+    pub fn start(timeout: Duration, on_timeout: impl FnOnce() + Send + 'static) -> Self {
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let shared = state.clone();
+        // If the thread can't be started, the load just isn't watched.
+        let _ = thread::Builder::new()
+            .name("config-file-watch-watchdog".to_string())
+            .spawn(move || {
+                let (state, wakeup) = &*shared;
+                let guard = state.lock().unwrap();
+                let (mut guard, _) = wakeup
+                    .wait_timeout_while(guard, timeout, |state| !state.finished)
+                    .unwrap();
+                if guard.finished {
+                    return;
+                }
+                guard.timed_out = true;
+                drop(guard);
+                on_timeout();
+            });
+        Self { state }
+    }
+
+    /// Stop watching the load. Returns true if it took longer than the
+    /// timeout.
+    pub fn finish(self) -> bool {
+        let (state, wakeup) = &*self.state;
+        let mut state = state.lock().unwrap();
+        state.finished = true;
+        wakeup.notify_one();
+        state.timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::Watchdog;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn should_report_a_load_which_takes_too_long() {
+        let (tx, rx) = mpsc::channel();
+        let watchdog = Watchdog::start(ms(50), move || tx.send(()).unwrap());
+
+        // The timeout is reported while the load is still running.
+        rx.recv_timeout(ms(1000)).unwrap();
+        assert!(watchdog.finish());
+    }
+
+    #[test]
+    fn should_not_report_a_load_which_finishes_in_time() {
+        let (tx, rx) = mpsc::channel();
+        let watchdog = Watchdog::start(ms(100), move || tx.send(()).unwrap());
+
+        thread::sleep(ms(20));
+        assert!(!watchdog.finish());
+        assert!(rx.recv_timeout(ms(200)).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The files a watch is watching, returned by `Watch::watched_files()`. Each
+/// file appears once.
+///
+/// Files are in a stable order: first the files added with
+/// `Builder::watch_file()` and by the loader, in the order they were first
+/// added, and then the files which match each glob from
+/// `Builder::watch_glob()`, sorted by path. When the loader replaces the set
+/// of watched files with `Context::update_watched_files()`, files which were
+/// already being watched keep their place, and new files go on the end.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchedFiles(Arc<Vec<PathBuf>>);
+
+impl WatchedFiles {
+    pub(crate) fn new(files: Arc<Vec<PathBuf>>) -> Self {
+        Self(files)
+    }
+
+    /// Returns true if `path` is being watched.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.0.iter().any(|file| file == path)
+    }
+}
+
+impl Deref for WatchedFiles {
+    type Target = [PathBuf];
+
+    fn deref(&self) -> &[PathBuf] {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a WatchedFiles {
+    type Item = &'a PathBuf;
+    type IntoIter = std::slice::Iter<'a, PathBuf>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl PartialEq<Vec<PathBuf>> for WatchedFiles {
+    fn eq(&self, other: &Vec<PathBuf>) -> bool {
+        *self.0 == *other
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::{atomic::AtomicU64, mpsc, Arc, Condvar, Mutex, Weak};
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+
+use crate::{
+    file_watcher::FileWatcher, history::History, reloader::Reloader, Progress, ReloadTrace,
+    Snapshot, Watch, WatchStats,
+};
+
+/// A handle to a watch which doesn't keep it watching files, returned by
+/// `Watch::downgrade()`.
+///
+/// This is for long-lived callbacks, such as ones registered with another
+/// library, which need to read the config but shouldn't stop the watch from
+/// shutting down. Once every `Watch` has been dropped, files stop being
+/// watched and `upgrade()` returns `None`. Holding a `WeakWatch` does keep the
+/// watch's current value in memory.
+#[derive(Debug)]
+pub struct WeakWatch<T> {
+    value: Arc<ArcSwap<T>>,
+    reloader: Arc<Mutex<Reloader<T>>>,
+    watcher: Weak<FileWatcher>,
+    last_trace: Arc<ArcSwapOption<ReloadTrace>>,
+    stats: Arc<Mutex<WatchStats>>,
+    snapshot: Arc<ArcSwap<Snapshot<T>>>,
+    version: Arc<AtomicU64>,
+    history: Arc<Mutex<History<T>>>,
+    progress: Arc<ArcSwapOption<Progress>>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Arc<T>>>>>,
+    stored: Arc<(Mutex<u64>, Condvar)>,
+    #[cfg(feature = "crossbeam")]
+    crossbeam_subscribers: Arc<Mutex<Vec<crossbeam_channel::Sender<Arc<T>>>>>,
+    #[cfg(feature = "tokio")]
+    updates: tokio::sync::watch::Receiver<()>,
+    #[cfg(feature = "tokio")]
+    values: tokio::sync::watch::Receiver<Arc<T>>,
+    #[cfg(feature = "tokio")]
+    broadcast: tokio::sync::broadcast::Sender<Arc<T>>,
+}
+
+impl<T> Clone for WeakWatch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            reloader: self.reloader.clone(),
+            watcher: self.watcher.clone(),
+            last_trace: self.last_trace.clone(),
+            stats: self.stats.clone(),
+            snapshot: self.snapshot.clone(),
+            version: self.version.clone(),
+            history: self.history.clone(),
+            progress: self.progress.clone(),
+            subscribers: self.subscribers.clone(),
+            stored: self.stored.clone(),
+            #[cfg(feature = "crossbeam")]
+            crossbeam_subscribers: self.crossbeam_subscribers.clone(),
+            #[cfg(feature = "tokio")]
+            updates: self.updates.clone(),
+            #[cfg(feature = "tokio")]
+            values: self.values.clone(),
+            #[cfg(feature = "tokio")]
+            broadcast: self.broadcast.clone(),
+        }
+    }
+}
+
+impl<T> WeakWatch<T> {
+    /// Get the watch back, or `None` if every `Watch` has been dropped.
+    pub fn upgrade(&self) -> Option<Watch<T>> {
+        let watcher = self.watcher.upgrade()?;
+        Some(Watch {
+            value: self.value.clone(),
+            reloader: self.reloader.clone(),
+            watcher,
+            last_trace: self.last_trace.clone(),
+            stats: self.stats.clone(),
+            snapshot: self.snapshot.clone(),
+            version: self.version.clone(),
+            history: self.history.clone(),
+            progress: self.progress.clone(),
+            subscribers: self.subscribers.clone(),
+            stored: self.stored.clone(),
+            #[cfg(feature = "crossbeam")]
+            crossbeam_subscribers: self.crossbeam_subscribers.clone(),
+            #[cfg(feature = "tokio")]
+            updates: self.updates.clone(),
+            #[cfg(feature = "tokio")]
+            values: self.values.clone(),
+            #[cfg(feature = "tokio")]
+            broadcast: self.broadcast.clone(),
+        })
+    }
+}
+
+impl<T> Watch<T> {
+    /// Get a handle to this watch which doesn't keep it watching files. See
+    /// `WeakWatch`.
+    pub fn downgrade(&self) -> WeakWatch<T> {
+        WeakWatch {
+            value: self.value.clone(),
+            reloader: self.reloader.clone(),
+            watcher: Arc::downgrade(&self.watcher),
+            last_trace: self.last_trace.clone(),
+            stats: self.stats.clone(),
+            snapshot: self.snapshot.clone(),
+            version: self.version.clone(),
+            history: self.history.clone(),
+            progress: self.progress.clone(),
+            subscribers: self.subscribers.clone(),
+            stored: self.stored.clone(),
+            #[cfg(feature = "crossbeam")]
+            crossbeam_subscribers: self.crossbeam_subscribers.clone(),
+            #[cfg(feature = "tokio")]
+            updates: self.updates.clone(),
+            #[cfg(feature = "tokio")]
+            values: self.values.clone(),
+            #[cfg(feature = "tokio")]
+            broadcast: self.broadcast.clone(),
+        }
+    }
+}
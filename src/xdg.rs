@@ -0,0 +1,98 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// The places to look for the config file `relative`, such as
+/// `myapp/config.toml`, from highest to lowest priority. See
+/// `Builder::watch_xdg_config()`.
+pub(crate) fn config_candidates(relative: &Path) -> Vec<PathBuf> {
+    candidates(relative, |name| env::var_os(name))
+}
+
+/// Get the candidates for `relative`, using `var` to read environment
+/// variables.
+fn candidates(relative: &Path, var: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    // Relative paths in these variables are invalid, and should be ignored.
+    let absolute = |value: OsString| {
+        let path = PathBuf::from(value);
+        path.is_absolute().then_some(path)
+    };
+    let home = var(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).and_then(absolute);
+
+    let mut dirs = vec![];
+    if cfg!(windows) {
+        dirs.extend(var("APPDATA").and_then(absolute));
+        dirs.extend(var("PROGRAMDATA").and_then(absolute));
+    } else {
+        if cfg!(target_os = "macos") {
+            dirs.extend(
+                home.as_ref()
+                    .map(|home| home.join("Library/Application Support")),
+            );
+        }
+
+        match var("XDG_CONFIG_HOME").and_then(absolute) {
+            Some(config_home) => dirs.push(config_home),
+            None => dirs.extend(home.as_ref().map(|home| home.join(".config"))),
+        }
+
+        match var("XDG_CONFIG_DIRS").filter(|dirs| !dirs.is_empty()) {
+            Some(config_dirs) => {
+                dirs.extend(env::split_paths(&config_dirs).filter(|dir| dir.is_absolute()))
+            }
+            None => dirs.push(PathBuf::from("/etc/xdg")),
+        }
+    }
+
+    let mut result: Vec<PathBuf> = vec![];
+    for dir in dirs {
+        let candidate = dir.join(relative);
+        if !result.contains(&candidate) {
+            result.push(candidate);
+        }
+    }
+    result
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+    use super::candidates;
+
+    fn search(vars: &[(&str, &str)]) -> Vec<PathBuf> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        candidates("myapp/config.toml".as_ref(), |name| {
+            vars.get(name).map(OsString::from)
+        })
+    }
+
+    #[test]
+    fn should_use_xdg_defaults() {
+        assert_eq!(
+            search(&[("HOME", "/home/me")]),
+            vec![
+                PathBuf::from("/home/me/.config/myapp/config.toml"),
+                PathBuf::from("/etc/xdg/myapp/config.toml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_use_xdg_variables() {
+        assert_eq!(
+            search(&[
+                ("HOME", "/home/me"),
+                ("XDG_CONFIG_HOME", "/home/me/conf"),
+                ("XDG_CONFIG_DIRS", "/opt/xdg:relative:/etc/xdg"),
+            ]),
+            vec![
+                PathBuf::from("/home/me/conf/myapp/config.toml"),
+                PathBuf::from("/opt/xdg/myapp/config.toml"),
+                PathBuf::from("/etc/xdg/myapp/config.toml"),
+            ]
+        );
+    }
+}
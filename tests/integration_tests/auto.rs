@@ -0,0 +1,104 @@
+use std::{fs, path::Path, sync::mpsc};
+
+use config_file_watch::{AutoFormatLoader, Builder, Context, Error, Format, Guard, Watch, WithRaw};
+use serde::Deserialize;
+
+use crate::utils::create_files;
+
+#[derive(Debug, Deserialize, Default)]
+struct ConfigFile {
+    value: i32,
+}
+
+#[test]
+fn should_pick_loader_from_extension() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[
+        ("config.json", r#"{"value": 1}"#),
+        ("config.yml", "value: 2\n"),
+        ("config.toml", "value = 3\n"),
+    ])?;
+
+    for (file, expected) in files.iter().zip([1, 2, 3]) {
+        let watch: Watch<ConfigFile> = Builder::new().watch_file(file).load_auto().build()?;
+        assert_eq!(watch.value().value, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn should_use_default_format_for_unknown_extension() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[("config.conf", "value = 4\n")])?;
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(&files[0])
+        .load(AutoFormatLoader::new().with_default(Format::Toml))
+        .build()?;
+    assert_eq!(watch.value().value, 4);
+
+    Ok(())
+}
+
+#[test]
+fn should_fall_back_to_embedded_default() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("other.toml", "")])?;
+    let config_file = files[0].with_file_name("config.toml");
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(&config_file)
+        .embedded_default("value = 7\n")
+        .load_auto()
+        .after_update(move |_context: &mut Context, value: Guard<ConfigFile>| {
+            tx.send(value.value).unwrap();
+        })
+        .build()?;
+    assert_eq!(rx.recv().unwrap(), 7);
+
+    fs::write(&config_file, "value = 8\n")?;
+    assert_eq!(rx.recv().unwrap(), 8);
+
+    // Removing the file should go back to the embedded default.
+    fs::remove_file(&config_file)?;
+    assert_eq!(rx.recv().unwrap(), 7);
+    assert_eq!(watch.value().value, 7);
+
+    Ok(())
+}
+
+#[test]
+fn should_fail_to_build_with_invalid_embedded_default() {
+    let result: Result<Watch<ConfigFile>, _> = Builder::new()
+        .embedded_default("value = \"seven\"\n")
+        .load_toml()
+        .build();
+    let Err(Error::ParseError {
+        path, field_path, ..
+    }) = result
+    else {
+        panic!("Expected a parse error");
+    };
+    assert_eq!(path.as_deref(), Some(Path::new("<embedded default>")));
+    assert_eq!(field_path, "value");
+}
+
+#[test]
+fn should_keep_raw_yaml_and_toml_documents() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[
+        ("config.yml", "value: 1\nextra: a\n"),
+        ("config.toml", "value = 2\nextra = \"b\"\n"),
+    ])?;
+
+    let yaml: Watch<WithRaw<ConfigFile>> =
+        Builder::new().watch_file(&files[0]).load_yaml().build()?;
+    assert_eq!(yaml.value().value, 1);
+    assert_eq!(yaml.raw_value()["extra"], "a");
+
+    let toml: Watch<WithRaw<ConfigFile>> =
+        Builder::new().watch_file(&files[1]).load_toml().build()?;
+    assert_eq!(toml.value().value, 2);
+    assert_eq!(toml.raw_value()["extra"], "b");
+
+    Ok(())
+}
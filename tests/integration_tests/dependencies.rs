@@ -111,7 +111,7 @@ fn should_handle_dependencies() {
     rx.recv().unwrap();
     assert_eq!(**watch.value(), vec![1, 2, 3]);
     assert_eq!(
-        **watch.watched_files(),
+        watch.watched_files(),
         vec![
             main_config_file.clone(),
             included_1.clone(),
@@ -125,7 +125,7 @@ fn should_handle_dependencies() {
     rx.recv().unwrap();
     assert_eq!(**watch.value(), vec![1, 5, 3]);
     assert_eq!(
-        **watch.watched_files(),
+        watch.watched_files(),
         vec![
             main_config_file.clone(),
             included_1.clone(),
@@ -146,7 +146,103 @@ fn should_handle_dependencies() {
     assert_eq!(**watch.value(), vec![1, 3]);
     // Should no longer be watching the extra dependency.
     assert_eq!(
-        **watch.watched_files(),
+        watch.watched_files(),
         vec![main_config_file.clone(), included_2.clone()]
     );
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn should_resolve_includes() {
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        host: String,
+        port: u16,
+    }
+
+    let (_guard, files) = create_files(&[
+        (
+            "config.json",
+            r#"{ "include": ["defaults.json"], "port": 8080 }"#,
+        ),
+        ("defaults.json", r#"{ "host": "localhost", "port": 80 }"#),
+    ])
+    .unwrap();
+    let config_file = &files[0];
+    let defaults = &files[1];
+    thread::sleep(Duration::from_millis(100));
+
+    let (tx, rx) = mpsc::channel();
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load_includes()
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    rx.recv().unwrap();
+    let value: config_file_watch::Guard<ConfigFile> = watch.value();
+    assert_eq!(value.host, "localhost");
+    assert_eq!(value.port, 8080);
+    assert_eq!(
+        watch.watched_files(),
+        vec![config_file.clone(), defaults.clone()]
+    );
+
+    // Changing an included file causes a reload.
+    fs::write(defaults, r#"{ "host": "example.com" }"#).unwrap();
+    rx.recv().unwrap();
+    assert_eq!(watch.value().host, "example.com");
+
+    // Removing the include stops watching the included file.
+    fs::write(config_file, r#"{ "host": "other", "port": 8080 }"#).unwrap();
+    rx.recv().unwrap();
+    assert_eq!(watch.value().host, "other");
+    assert_eq!(watch.watched_files(), vec![config_file.clone()]);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_reject_includes_outside_dependency_roots() {
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        value: i32,
+    }
+
+    let (_guard, files) = create_files(&[
+        ("config.json", r#"{ "include": ["extra.json"] }"#),
+        ("extra.json", r#"{ "value": 1 }"#),
+    ])
+    .unwrap();
+    let (_secret_guard, secret) = create_files(&[("secret.json", r#"{ "value": 2 }"#)]).unwrap();
+    let config_file = &files[0];
+    let conf_dir = config_file.parent().unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .dependency_roots([conf_dir])
+        .load_includes()
+        .on_error(
+            move |_context: &mut Context, err: config_file_watch::Error| {
+                tx.send(err).unwrap();
+            },
+        )
+        .build()
+        .unwrap();
+    let value: config_file_watch::Guard<ConfigFile> = watch.value();
+    assert_eq!(value.value, 1);
+
+    // Including a file outside the roots fails, and the file isn't watched.
+    let include = serde_json::json!({ "include": [secret[0]] });
+    fs::write(config_file, include.to_string()).unwrap();
+    let err = rx.recv().unwrap();
+    assert!(
+        matches!(err, config_file_watch::Error::PathNotAllowed(_)),
+        "{err:?}"
+    );
+    assert_eq!(watch.value().value, 1);
+    assert!(!watch.watched_files().contains(&secret[0]));
+}
@@ -0,0 +1,36 @@
+use std::{collections::HashMap, fs, sync::mpsc};
+
+use config_file_watch::{Builder, Context, Watch};
+
+#[test]
+fn should_load_every_file_in_a_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let dir = tempfile::tempdir()?;
+    let conf_d = dir.path().join("conf.d");
+    fs::create_dir(&conf_d)?;
+    fs::write(conf_d.join("10-base.json"), r#"{"a": 1, "b": 1}"#)?;
+    fs::write(conf_d.join("20-override.json"), r#"{"b": 2}"#)?;
+    fs::write(conf_d.join(".hidden.json"), r#"{"a": 100}"#)?;
+
+    let watch: Watch<HashMap<String, i32>> = Builder::new()
+        .load_directory(&conf_d)
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().get("a"), Some(&1));
+    assert_eq!(watch.value().get("b"), Some(&2));
+    assert!(watch
+        .watched_files()
+        .contains(conf_d.join("20-override.json")));
+
+    // Adding a new file should trigger a reload.
+    fs::write(conf_d.join("30-new.json"), r#"{"c": 3}"#)?;
+    rx.recv().unwrap();
+    assert_eq!(watch.value().get("c"), Some(&3));
+
+    Ok(())
+}
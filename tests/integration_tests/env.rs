@@ -0,0 +1,68 @@
+use std::{collections::HashMap, fs, sync::mpsc};
+
+use config_file_watch::{Builder, Context, Watch};
+use serde::Deserialize;
+
+use crate::utils::create_files;
+
+#[test]
+fn should_load_an_env_file_into_a_struct() -> Result<(), Box<dyn std::error::Error>> {
+    // TX and RX so we can signal when the value has changed.
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        host: String,
+        port: u16,
+        debug: Option<bool>,
+        #[serde(default)]
+        features: Vec<String>,
+    }
+
+    let (_guard, files) = create_files(&[(".env", "HOST=localhost\nPORT=8080\nFEATURES=a,b\n")])?;
+    let config_file = &files[0];
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(config_file)
+        .load_env_file()
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    let config = watch.value();
+    assert_eq!(config.host, "localhost");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.debug, None);
+    assert_eq!(config.features, vec!["a", "b"]);
+
+    // Update the config file.
+    fs::write(config_file, "HOST=example.com\nPORT=9090\nDEBUG=true\n").unwrap();
+
+    rx.recv().unwrap();
+    let config = watch.value();
+    assert_eq!(config.host, "example.com");
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.debug, Some(true));
+    assert!(config.features.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn should_load_an_env_file_into_a_map() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[(".env", "export API_KEY=\"secret\"\nRetries=3\n")])?;
+
+    let watch: Watch<HashMap<String, String>> = Builder::new()
+        .watch_file(&files[0])
+        .load_env_file()
+        .build()?;
+
+    // Keys are not lowercased when loading into a map.
+    let config = watch.value();
+    assert_eq!(config.get("API_KEY").map(String::as_str), Some("secret"));
+    assert_eq!(config.get("Retries").map(String::as_str), Some("3"));
+
+    Ok(())
+}
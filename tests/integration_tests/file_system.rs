@@ -0,0 +1,402 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use config_file_watch::{
+    Builder, ChangeDetection, ChangeHandler, ConfigSource, ConfigSources, Context, Error,
+    FileMetadata, FileSystem, FileSystemWatcher, Guard, MemoryFileSystem, Warning, WatcherOptions,
+};
+
+fn loader(context: &mut Context) -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
+    match context.path() {
+        Some(path) => match context.file_system().read_to_string(path) {
+            Ok(contents) => Ok(contents.parse::<i32>()?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(Box::new(err)),
+        },
+        None => Ok(0),
+    }
+}
+
+#[test]
+fn should_watch_a_memory_file_system() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app/config", "1");
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/etc/app/config")
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    fs.write("/etc/app/config", "2");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+
+    // Changes to other files in the folder are ignored.
+    fs.write("/etc/app/other", "3");
+    fs.remove("/etc/app/config");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 0);
+    assert_eq!(**watch.value(), 0);
+}
+
+#[test]
+fn should_only_load_files_whose_contents_changed() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app/config", "1");
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/etc/app/config")
+        .change_detection(ChangeDetection::Hash)
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // Writing the same contents again doesn't call the loader.
+    fs.write("/etc/app/config", "1");
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(watch.stats().reloads, 1);
+
+    fs.write("/etc/app/config", "2");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+
+    fs.remove("/etc/app/config");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 0);
+}
+
+#[test]
+fn should_match_globs_on_a_memory_file_system() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app/conf.d/a.conf", "1");
+    fs.write("/etc/app/conf.d/b.txt", "2");
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_glob("/etc/app/conf.d/*.conf")
+        .load(|context: &mut Context| {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(context.watched_files())
+        })
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()
+        .unwrap();
+    rx.recv().unwrap();
+    assert_eq!(
+        watch.watched_files(),
+        vec![PathBuf::from("/etc/app/conf.d/a.conf")]
+    );
+
+    fs.write("/etc/app/conf.d/c.conf", "3");
+    rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(
+        **watch.value(),
+        vec![
+            PathBuf::from("/etc/app/conf.d/a.conf"),
+            PathBuf::from("/etc/app/conf.d/c.conf")
+        ]
+    );
+}
+
+#[test]
+fn should_lengthen_the_debounce_during_bursts_of_writes() {
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app/config", "0");
+
+    let min = Duration::from_millis(50);
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/etc/app/config")
+        .load(loader)
+        .adaptive_debounce(min, Duration::from_millis(400))
+        .build()
+        .unwrap();
+    assert_eq!(watch.stats().debounce, Some(min));
+
+    // Write the file in pieces, slightly slower than the initial debounce.
+    for i in 1..=20 {
+        fs.write("/etc/app/config", i.to_string());
+        std::thread::sleep(Duration::from_millis(60));
+    }
+    let debounce = watch.stats().debounce.unwrap();
+    assert!(debounce > min, "debounce should grow, but is {debounce:?}");
+
+    // Once the writes stop, it shrinks back down.
+    std::thread::sleep(Duration::from_secs(4));
+    assert_eq!(watch.stats().debounce, Some(min));
+    assert_eq!(**watch.value(), 20);
+}
+
+#[test]
+fn should_watch_the_first_candidate_which_exists() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/home/user/.config/app.conf", "2");
+    fs.create_dir("/etc");
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_first_of(["/etc/app.conf", "/home/user/.config/app.conf"])
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+
+    // A higher priority file takes over when it is created.
+    fs.write("/etc/app.conf", "1");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+
+    // Changes to lower priority files don't change the value.
+    fs.write("/home/user/.config/app.conf", "3");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+
+    // Falls back to the next candidate when the chosen file is deleted.
+    fs.remove("/etc/app.conf");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 3);
+
+    // With no candidates left, the loader gets the first one.
+    fs.remove("/home/user/.config/app.conf");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 0);
+    assert_eq!(**watch.value(), 0);
+}
+
+#[test]
+fn should_switch_to_a_higher_precedence_source() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app.conf", "2");
+    fs.create_dir("/srv");
+
+    let sources = ConfigSources::new()
+        .flag("--config", Some("/srv/app.conf"))
+        .default_location("/etc/app.conf");
+    let resolution = sources.resolve(&fs).unwrap();
+    assert_eq!(resolution.source, ConfigSource::Default);
+
+    let _watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_sources(sources.clone())
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+
+    fs.write("/srv/app.conf", "1");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+    let resolution = sources.resolve(&fs).unwrap();
+    assert_eq!(
+        resolution.source,
+        ConfigSource::Flag("--config".to_string())
+    );
+}
+
+#[test]
+fn should_fall_back_to_another_watch_while_one_is_failing() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/srv/primary.conf", "1");
+    fs.write("/var/cache/app.conf", "2");
+
+    let watch = |path: &str| {
+        Builder::new()
+            .file_system(fs.clone())
+            .watch_file(path)
+            .load(loader)
+            .on_error(|_context: &mut Context, _err| {})
+            .build()
+            .unwrap()
+    };
+    let primary = watch("/srv/primary.conf");
+    let cached = watch("/var/cache/app.conf");
+
+    let fallback = primary.or(&cached).unwrap().recover_after(2);
+    fallback.subscribe(move |_context: &mut Context, value: Guard<i32>| {
+        tx.send(**value).unwrap();
+    });
+    assert_eq!(**fallback.value(), 1);
+    assert_eq!(fallback.active(), 0);
+
+    // Switches to the fallback as soon as the primary fails.
+    fs.write("/srv/primary.conf", "oops");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+    assert_eq!(fallback.active(), 1);
+
+    // Only switches back once the primary has loaded twice in a row.
+    fs.write("/srv/primary.conf", "3");
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(fallback.active(), 1);
+    fs.write("/srv/primary.conf", "4");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 4);
+    assert_eq!(fallback.active(), 0);
+}
+
+#[test]
+fn should_keep_using_a_watch_which_reloads_an_unchanged_value() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/srv/primary.conf", "1");
+    fs.write("/var/cache/app.conf", "2");
+
+    let watch = |path: &str| {
+        Builder::new()
+            .file_system(fs.clone())
+            .watch_file(path)
+            .load(loader)
+            .dedup_values()
+            .on_error(|_context: &mut Context, _err| {})
+            .build()
+            .unwrap()
+    };
+    let primary = watch("/srv/primary.conf");
+    let cached = watch("/var/cache/app.conf");
+
+    let fallback = primary.or(&cached).unwrap();
+    fallback.subscribe(move |_context: &mut Context, value: Guard<i32>| {
+        tx.send(**value).unwrap();
+    });
+    assert_eq!(fallback.active(), 0);
+
+    // Rewriting the same value is a successful load, not a failure.
+    fs.write("/srv/primary.conf", "1");
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(primary.stats().unchanged_reloads, 1);
+    assert_eq!(fallback.active(), 0);
+    assert_eq!(**fallback.value(), 1);
+}
+
+#[test]
+fn should_dry_run_candidate_contents() {
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app.conf", "1");
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/etc/app.conf")
+        .load(loader)
+        .build()
+        .unwrap();
+
+    // The candidate doesn't need to exist on the file system.
+    assert_eq!(
+        watch.dry_run_contents("/tmp/candidate.conf", "7").unwrap(),
+        7
+    );
+    assert!(watch.dry_run_contents("/etc/app.conf", "foo").is_err());
+
+    // The live value is untouched.
+    assert_eq!(**watch.value(), 1);
+}
+
+/// A MemoryFileSystem which rewrites `/etc/app/config` as soon as a folder is
+/// watched, while the watch is still being built.
+#[derive(Debug)]
+struct WriteOnWatch(MemoryFileSystem);
+
+struct WriteOnWatchWatcher {
+    fs: MemoryFileSystem,
+    inner: Box<dyn FileSystemWatcher>,
+}
+
+impl FileSystem for WriteOnWatch {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+        self.0.open(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.0.metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.0.read_dir(path)
+    }
+
+    fn watcher(
+        &self,
+        options: &WatcherOptions,
+        on_change: ChangeHandler,
+    ) -> Result<Box<dyn FileSystemWatcher>, Error> {
+        Ok(Box::new(WriteOnWatchWatcher {
+            fs: self.0.clone(),
+            inner: self.0.watcher(options, on_change)?,
+        }))
+    }
+}
+
+impl FileSystemWatcher for WriteOnWatchWatcher {
+    fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), Error> {
+        self.inner.watch(path, recursive)?;
+        self.fs.write("/etc/app/config", "2");
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), Error> {
+        self.inner.unwatch(path)
+    }
+}
+
+#[test]
+fn should_configure_the_watch_before_loading_changes() {
+    let (tx, rx) = mpsc::channel();
+    let loads = Arc::new(AtomicUsize::new(0));
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/etc/app/config", "1");
+
+    let watch = Builder::new()
+        .file_system(WriteOnWatch(fs.clone()))
+        .watch_file("/etc/app/config")
+        .load({
+            let loads = loads.clone();
+            move |context: &mut Context| {
+                loads.fetch_add(1, Ordering::SeqCst);
+                context.warn("loaded");
+                loader(context)
+            }
+        })
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    // The change made while the watch was being built is loaded, and the
+    // load's warning goes to the handler from the builder, as does every
+    // other load's.
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(**watch.value(), 2);
+    let loads = loads.load(Ordering::SeqCst);
+    assert!(loads >= 2);
+    assert_eq!(rx.try_iter().count(), loads);
+}
@@ -0,0 +1,35 @@
+use std::{collections::HashMap, fs, sync::mpsc};
+
+use config_file_watch::{Builder, Context, Watch};
+
+use crate::utils::create_files;
+
+#[test]
+fn should_watch_files_matching_a_glob() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (guard, files) = create_files(&[("a.json", r#"{"a": 1}"#), ("ignored.txt", "")])?;
+    let pattern = guard.path().join("*.json");
+
+    let watch: Watch<HashMap<String, i32>> = Builder::new()
+        .watch_glob(pattern.to_str().unwrap())
+        .load_layered()
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.watched_files(), vec![files[0].clone()]);
+    assert_eq!(watch.value().get("a"), Some(&1));
+
+    // Creating a new matching file should cause a reload, and the new file
+    // should be passed to the loader.
+    let b = guard.path().join("b.json");
+    fs::write(&b, r#"{"b": 2}"#)?;
+    rx.recv().unwrap();
+    assert_eq!(watch.watched_files(), vec![files[0].clone(), b]);
+    assert_eq!(watch.value().get("b"), Some(&2));
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
-use std::{fs, sync::mpsc};
+use std::{fs, sync::mpsc, time::Duration};
 
-use config_file_watch::{Builder, Context, Watch};
+use config_file_watch::{
+    Builder, ConditionalLoader, Context, Error, Guard, JsonLoader, MemoryFileSystem, Watch, WithRaw,
+};
 use serde::Deserialize;
 
 use crate::utils::create_files;
@@ -82,3 +84,327 @@ fn should_load_a_json_file_with_default() -> Result<(), Box<dyn std::error::Erro
 
     Ok(())
 }
+
+#[test]
+fn should_keep_raw_document() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        value: i32,
+    }
+
+    let (_guard, files) = create_files(&[("config.json", r#"{"value": 1, "extra": "a"}"#)])?;
+    let config_file = &files[0];
+
+    let watch: Watch<WithRaw<ConfigFile>> = Builder::new()
+        .watch_file(config_file)
+        .load_json()
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().value, 1);
+    assert_eq!(watch.raw_value()["extra"], "a");
+
+    fs::write(config_file, r#"{"value": 2, "extra": "b"}"#).unwrap();
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().value, 2);
+    assert_eq!(watch.raw_value()["extra"], "b");
+
+    Ok(())
+}
+
+#[test]
+fn should_apply_conditional_sections() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        host: String,
+    }
+
+    let (_guard, files) = create_files(&[(
+        "config.json",
+        r#"{"host": "localhost", "when(env=prod)": {"host": "db.internal"}}"#,
+    )])?;
+
+    let prod: Watch<ConfigFile> = Builder::new()
+        .watch_file(&files[0])
+        .load(ConditionalLoader::new(JsonLoader).variable("env", "prod"))
+        .build()?;
+    assert_eq!(prod.value().host, "db.internal");
+
+    let dev: Watch<ConfigFile> = Builder::new()
+        .watch_file(&files[0])
+        .load(ConditionalLoader::new(JsonLoader).variable("env", "dev"))
+        .build()?;
+    assert_eq!(dev.value().host, "localhost");
+
+    Ok(())
+}
+
+#[test]
+fn should_reapply_overrides_on_reload() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        log_level: String,
+        port: u16,
+    }
+
+    let (_guard, files) = create_files(&[("config.json", r#"{"log_level": "info", "port": 80}"#)])?;
+    let config_file = &files[0];
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(config_file)
+        .load_json()
+        .with_overrides([("log_level", "debug")])
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().log_level, "debug");
+    assert_eq!(watch.value().port, 80);
+
+    fs::write(config_file, r#"{"log_level": "warn", "port": 8080}"#)?;
+    rx.recv().unwrap();
+    assert_eq!(watch.value().log_level, "debug");
+    assert_eq!(watch.value().port, 8080);
+
+    Ok(())
+}
+
+#[test]
+fn should_not_update_when_a_wrapped_value_is_unchanged() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default, PartialEq)]
+    struct ConfigFile {
+        log_level: String,
+        port: u16,
+    }
+
+    let (_guard, files) = create_files(&[("config.json", r#"{"log_level": "info", "port": 80}"#)])?;
+    let config_file = &files[0];
+
+    // dedup_values() compares the final value, so it can come before the
+    // loader and wrappers.
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(config_file)
+        .dedup_values()
+        .load_json()
+        .with_overrides([("log_level", "debug")])
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+    rx.recv().unwrap();
+
+    // Reformatting the file doesn't change the value.
+    fs::write(config_file, r#"{ "port": 80, "log_level": "info" }"#)?;
+    assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+    assert_eq!(watch.stats().unchanged_reloads, 1);
+
+    fs::write(config_file, r#"{"log_level": "info", "port": 8080}"#)?;
+    rx.recv().unwrap();
+    assert_eq!(watch.value().port, 8080);
+
+    Ok(())
+}
+
+#[test]
+fn should_only_notify_sections_which_changed() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default)]
+    struct DbConfig {
+        host: String,
+    }
+
+    #[derive(Debug, Deserialize, Default)]
+    struct CacheConfig {
+        size: u32,
+    }
+
+    let (_guard, files) = create_files(&[(
+        "config.json",
+        r#"{"database": {"host": "db1"}, "cache": {"size": 1}}"#,
+    )])?;
+    let config_file = &files[0];
+
+    let watch: Watch<serde_json::Value> =
+        Builder::new().watch_file(config_file).load_json().build()?;
+
+    let database = watch.section::<DbConfig>("database")?;
+    let cache = watch.section::<CacheConfig>("cache")?;
+    let missing = watch.section::<Option<CacheConfig>>("missing")?;
+    assert_eq!(database.value().host, "db1");
+    assert_eq!(cache.value().size, 1);
+    assert!(missing.value().is_none());
+
+    {
+        let tx = tx.clone();
+        database.subscribe(move |_context: &mut Context, value: Guard<DbConfig>| {
+            tx.send(format!("database: {}", value.host)).unwrap();
+        });
+    }
+    cache.subscribe(move |_context: &mut Context, value: Guard<CacheConfig>| {
+        tx.send(format!("cache: {}", value.size)).unwrap();
+    });
+
+    // Only the cache section changed.
+    fs::write(
+        config_file,
+        r#"{"database": {"host": "db1"}, "cache": {"size": 2}}"#,
+    )?;
+    assert_eq!(rx.recv().unwrap(), "cache: 2");
+
+    // Only the database section changed. The cache section is reformatted, but
+    // its contents are the same.
+    fs::write(
+        config_file,
+        r#"{"database": {"host": "db2"},
+            "cache": {"size": 2}}"#,
+    )?;
+    assert_eq!(rx.recv().unwrap(), "database: db2");
+    assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    assert_eq!(cache.value().size, 2);
+
+    Ok(())
+}
+
+#[test]
+fn should_report_invalid_fields() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    #[derive(Debug, Deserialize, Default)]
+    struct ConfigFile {
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    let (_guard, files) = create_files(&[("config.json", r#"{"port": 80}"#)])?;
+    let config_file = &files[0];
+
+    let _watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(config_file)
+        .load_json()
+        .on_error(move |_context: &mut Context, err: Error| {
+            tx.send(err).unwrap();
+        })
+        .build()?;
+
+    fs::write(config_file, "{\n  \"port\": \"http\"\n}")?;
+    let err = rx.recv().unwrap();
+    let Error::ParseError {
+        path,
+        field_path,
+        line,
+        ..
+    } = &err
+    else {
+        panic!("Expected a parse error, got {err:?}");
+    };
+    assert_eq!(path.as_ref(), Some(config_file));
+    assert_eq!(field_path, "port");
+    assert_eq!(*line, Some(2));
+    assert!(err.to_string().contains("in field port"));
+
+    Ok(())
+}
+
+#[test]
+fn should_get_settings_by_path() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[(
+        "config.json",
+        r#"{"server": {"port": 80}, "backends": [{"host": "a"}, {"host": "b"}]}"#,
+    )])?;
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load_json_value()
+        .build()?;
+
+    assert_eq!(watch.get_path::<u16>("server.port")?, Some(80));
+    assert_eq!(
+        watch.get_path::<String>("backends[1].host")?,
+        Some("b".to_string())
+    );
+    assert_eq!(
+        watch.get_path::<String>("backends.0.host")?,
+        Some("a".to_string())
+    );
+    assert_eq!(watch.get_path::<u16>("server.timeout")?, None);
+    assert_eq!(watch.get_path::<String>("backends[2].host")?, None);
+
+    let err = watch.get_path::<u16>("backends").unwrap_err();
+    let Error::ParseError { field_path, .. } = err else {
+        panic!("Expected a parse error, got {err:?}");
+    };
+    assert_eq!(field_path, "backends");
+
+    Ok(())
+}
+
+#[test]
+fn should_load_json_from_a_memory_file_system() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Debug, Default, Deserialize)]
+    struct ConfigFile {
+        value: i32,
+    }
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/config.json", r#"{"value": 1}"#);
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/config.json")
+        .load_json()
+        .build()?;
+    assert_eq!(watch.value().value, 1);
+
+    fs.write("/config.json", r#"{"value": 2}"#);
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(watch.value().value, 2);
+
+    Ok(())
+}
+
+#[test]
+fn should_list_overridden_settings() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Debug, Deserialize, serde::Serialize)]
+    #[serde(default)]
+    struct ConfigFile {
+        host: String,
+        port: u16,
+    }
+
+    impl Default for ConfigFile {
+        fn default() -> Self {
+            Self {
+                host: "localhost".to_string(),
+                port: 80,
+            }
+        }
+    }
+
+    let (_guard, files) = create_files(&[("config.json", r#"{"port": 8080}"#)])?;
+    let watch: Watch<ConfigFile> = Builder::new().watch_file(&files[0]).load_json().build()?;
+
+    assert_eq!(
+        watch.defaults_snapshot()?,
+        serde_json::json!({"host": "localhost", "port": 80})
+    );
+    let overridden = watch.overridden_settings()?;
+    assert_eq!(overridden.len(), 1);
+    assert_eq!(overridden[0].to_string(), "port = 8080 (default 80)");
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::{fs, path::Path, sync::mpsc};
+
+use config_file_watch::{Builder, Context, Error, RefResolver, Watch};
+use serde::Deserialize;
+
+use crate::utils::create_files;
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+struct Database {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ConfigFile {
+    primary: Database,
+    replica: Database,
+}
+
+fn ref_loader(
+    root: &Path,
+) -> impl FnMut(&mut Context) -> Result<ConfigFile, Box<dyn std::error::Error + Send + Sync>> {
+    let root = root.to_path_buf();
+    let mut resolver = RefResolver::new();
+    move |context: &mut Context| {
+        // Always load the root file, even if it was a referenced file that changed.
+        let value = resolver.load(context, &root)?.unwrap_or_default();
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[test]
+fn should_resolve_references() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[
+        (
+            "config.json",
+            r##"{
+                "primary": {"$ref": "db.json#/primary"},
+                "replica": {"$ref": "#/primary"}
+            }"##,
+        ),
+        ("db.json", r#"{"primary": {"host": "db", "port": 5432}}"#),
+    ])?;
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(&files[0])
+        .load(ref_loader(&files[0]))
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().primary.port, 5432);
+    assert_eq!(watch.value().replica.host, "db");
+    assert!(watch.watched_files().contains(&files[1]));
+
+    // Changing the referenced file should cause a reload.
+    fs::write(&files[1], r#"{"primary": {"host": "db2", "port": 5433}}"#)?;
+    rx.recv().unwrap();
+    assert_eq!(
+        watch.value().primary,
+        Database {
+            host: "db2".to_string(),
+            port: 5433
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn should_detect_circular_references() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[
+        ("a.json", r#"{"primary": {"$ref": "b.json"}}"#),
+        ("b.json", r#"{"$ref": "a.json#/primary"}"#),
+    ])?;
+
+    let _watch: Watch<ConfigFile> = Builder::new()
+        .watch_file(&files[0])
+        .load(ref_loader(&files[0]))
+        .on_error(move |_context: &mut Context, err: Error| {
+            tx.send(err.to_string()).unwrap();
+        })
+        .build()?;
+
+    let err = rx.recv().unwrap();
+    assert!(err.contains("Circular reference"), "{err}");
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::{fs, sync::mpsc};
+
+use config_file_watch::{ArrayMerge, Builder, Context, LayeredLoader, MergePolicy, Watch};
+use serde::Deserialize;
+
+use crate::utils::create_files;
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ConfigFile {
+    server: Server,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn should_merge_layers() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (guard, files) = create_files(&[(
+        "base.json",
+        r#"{"server": {"host": "localhost", "port": 80}, "tags": ["base"]}"#,
+    )])?;
+    let base = &files[0];
+    let override_file = guard.path().join("override.json");
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_files([base, &override_file])
+        .load_layered()
+        .after_update(move |_context: &mut Context, _value: _| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    // Override doesn't exist yet, so we should just get the base.
+    rx.recv().unwrap();
+    assert_eq!(watch.value().server.port, 80);
+
+    // Create the override.
+    fs::write(
+        &override_file,
+        r#"{"server": {"port": 8080}, "tags": ["override"]}"#,
+    )?;
+    rx.recv().unwrap();
+    let config = watch.value();
+    assert_eq!(
+        config.server,
+        Server {
+            host: "localhost".to_string(),
+            port: 8080
+        }
+    );
+    assert_eq!(config.tags, vec!["override"]);
+
+    Ok(())
+}
+
+#[test]
+fn should_append_arrays_when_configured() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[
+        (
+            "base.json",
+            r#"{"server": {"host": "a", "port": 1}, "tags": ["a"]}"#,
+        ),
+        ("override.json", r#"{"tags": ["b"]}"#),
+    ])?;
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_files(&files)
+        .load(LayeredLoader::new().array_merge(ArrayMerge::Append))
+        .build()?;
+
+    assert_eq!(watch.value().tags, vec!["a", "b"]);
+
+    Ok(())
+}
+
+#[test]
+fn should_merge_with_policy() -> Result<(), Box<dyn std::error::Error>> {
+    let (_guard, files) = create_files(&[
+        (
+            "base.json",
+            r#"{"server": {"host": "a", "port": 1}, "tags": ["a", "b"]}"#,
+        ),
+        ("override.json", r#"{"tags": ["b", "c"]}"#),
+        ("local.json", r#"{"server": {"port": 2}, "tags": null}"#),
+    ])?;
+
+    let policy = MergePolicy::new()
+        .arrays_at("tags", ArrayMerge::Union)
+        .null_deletes(true);
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_files(&files[..2])
+        .load(LayeredLoader::new().merge_policy(policy.clone()))
+        .build()?;
+    assert_eq!(watch.value().tags, vec!["a", "b", "c"]);
+
+    let watch: Watch<ConfigFile> = Builder::new()
+        .watch_files(&files)
+        .load(LayeredLoader::new().merge_policy(policy))
+        .build()?;
+    let config = watch.value();
+    assert_eq!(
+        config.server,
+        Server {
+            host: "a".to_string(),
+            port: 2
+        }
+    );
+    assert!(config.tags.is_empty());
+
+    Ok(())
+}
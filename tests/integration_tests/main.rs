@@ -1,9 +1,42 @@
-mod utils;
 mod dependencies;
+mod file_system;
+mod patch;
+mod scope;
 mod simple;
+mod utils;
 
 #[cfg(feature = "tokio")]
 mod tokio;
 
 #[cfg(feature = "json")]
 mod json;
+
+#[cfg(feature = "env")]
+mod env;
+
+#[cfg(all(feature = "json", feature = "yaml", feature = "toml"))]
+mod auto;
+
+#[cfg(feature = "json")]
+mod directory;
+
+#[cfg(feature = "json")]
+mod glob;
+
+#[cfg(feature = "json")]
+mod layered;
+
+#[cfg(feature = "json")]
+mod json_ref;
+
+#[cfg(feature = "json")]
+mod watch_set;
+
+#[cfg(feature = "manifest")]
+mod manifest;
+
+#[cfg(feature = "secret")]
+mod secret;
+
+#[cfg(feature = "rustls")]
+mod tls;
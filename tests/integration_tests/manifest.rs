@@ -0,0 +1,62 @@
+use std::{fs, path::Path, time::Duration};
+
+use config_file_watch::{Builder, Context, Error, Watch};
+use sha2::{Digest, Sha256};
+
+use crate::utils::create_files;
+
+/// Write a manifest listing `files` with their current checksums.
+fn write_manifest(manifest: &Path, files: &[&str]) {
+    let folder = manifest.parent().unwrap();
+    let lines = files
+        .iter()
+        .map(|name| {
+            let digest = Sha256::digest(fs::read(folder.join(name)).unwrap());
+            let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+            format!("{hex}  {name}\n")
+        })
+        .collect::<String>();
+    fs::write(manifest, lines).unwrap();
+}
+
+#[test]
+fn should_only_load_files_matching_the_manifest() {
+    let (_guard, files) = create_files(&[("a", "1"), ("b", "2"), ("MANIFEST", "")]).unwrap();
+    let manifest = &files[2];
+    write_manifest(manifest, &["a", "b"]);
+
+    let (error_tx, error_rx) = std::sync::mpsc::channel();
+    let watch: Watch<i32> = Builder::new()
+        .load(|context: &mut Context| {
+            let mut sum = 0;
+            for path in context.modified_paths() {
+                sum += fs::read_to_string(path)?.parse::<i32>()?;
+            }
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(sum)
+        })
+        .verify_manifest(manifest)
+        .on_error(move |_context: &mut Context, err: Error| {
+            error_tx.send(err.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 3);
+
+    // Changing a file on its own doesn't reload.
+    fs::write(&files[0], "10").unwrap();
+    assert!(watch.wait_for_update(Duration::from_millis(500)).is_none());
+
+    // Once the manifest is written, the whole set is loaded.
+    fs::write(&files[1], "20").unwrap();
+    write_manifest(manifest, &["a", "b"]);
+    let value = watch.wait_for_update(Duration::from_secs(2)).unwrap();
+    assert_eq!(**value, 30);
+
+    // A manifest which doesn't match the files is rejected.
+    fs::write(&files[1], "25").unwrap();
+    let stale = fs::read_to_string(manifest).unwrap();
+    fs::write(manifest, format!("# rollout 3\n{stale}")).unwrap();
+    let err = error_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert!(err.contains("but the manifest expects"), "{err}");
+    assert_eq!(**watch.value(), 30);
+}
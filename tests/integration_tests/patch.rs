@@ -0,0 +1,146 @@
+use std::{
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use config_file_watch::{Builder, Context, Error, Guard, MemoryFileSystem, PatchLoader};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[test]
+fn should_patch_the_previous_contents() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/data/blob", "base");
+
+    // Apply the delta file next to the blob, if there is one, instead of
+    // reading the blob again.
+    let patched = Arc::new(Mutex::new(vec![]));
+    let patcher = {
+        let patched = patched.clone();
+        move |context: &mut Context, _path: &Path, previous: &[u8]| {
+            patched
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(previous).to_string());
+            match context.file_system().read(Path::new("/data/blob.delta")) {
+                Ok(delta) => Ok(Some([previous, &delta].concat())),
+                Err(_) => Ok::<_, BoxError>(None),
+            }
+        }
+    };
+    let decode =
+        |contents: &[u8]| -> Result<String, BoxError> { Ok(String::from_utf8(contents.to_vec())?) };
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/data/blob")
+        .load(PatchLoader::new(patcher, decode))
+        .after_update(move |_context: &mut Context, value: Guard<String>| {
+            tx.send(value.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), "base");
+    assert!(patched.lock().unwrap().is_empty());
+
+    // With a delta available, the previous contents are patched.
+    fs.write("/data/blob.delta", "+1");
+    fs.write("/data/blob", "ignored");
+    let timeout = Duration::from_secs(1);
+    assert_eq!(rx.recv_timeout(timeout).unwrap(), "base+1");
+    assert_eq!(*patched.lock().unwrap(), vec!["base"]);
+
+    // Without one, the file is read in full.
+    fs.remove("/data/blob.delta");
+    fs.write("/data/blob", "full");
+    assert_eq!(rx.recv_timeout(timeout).unwrap(), "full");
+    assert_eq!(*patched.lock().unwrap(), vec!["base", "base+1"]);
+    assert_eq!(**watch.value(), "full");
+}
+
+#[test]
+fn should_not_patch_a_rejected_version() {
+    let (tx, rx) = mpsc::channel();
+    let (err_tx, err_rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/data/blob", "base");
+
+    let patcher = |context: &mut Context, _path: &Path, previous: &[u8]| match context
+        .file_system()
+        .read(Path::new("/data/blob.delta"))
+    {
+        Ok(delta) => Ok(Some([previous, &delta].concat())),
+        Err(_) => Ok::<_, BoxError>(None),
+    };
+    let decode =
+        |contents: &[u8]| -> Result<String, BoxError> { Ok(String::from_utf8(contents.to_vec())?) };
+
+    let _watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/data/blob")
+        .load(PatchLoader::new(patcher, decode))
+        .validate(|value: &String| match value.contains("bad") {
+            true => Err("bad version"),
+            false => Ok(()),
+        })
+        .on_error(move |_context: &mut Context, err: Error| {
+            err_tx.send(err.to_string()).unwrap();
+        })
+        .after_update(move |_context: &mut Context, value: Guard<String>| {
+            tx.send(value.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), "base");
+
+    let timeout = Duration::from_secs(1);
+    fs.write("/data/blob.delta", "+bad");
+    fs.write("/data/blob", "ignored");
+    err_rx.recv_timeout(timeout).unwrap();
+
+    // The next delta is applied to the version the watch accepted.
+    fs.write("/data/blob.delta", "+2");
+    fs.write("/data/blob", "ignored");
+    assert_eq!(rx.recv_timeout(timeout).unwrap(), "base+2");
+}
+
+#[test]
+fn should_not_change_the_patch_base_in_a_dry_run() {
+    let (tx, rx) = mpsc::channel();
+
+    let fs = MemoryFileSystem::new();
+    fs.write("/data/blob", "base");
+
+    let patcher = |context: &mut Context, _path: &Path, previous: &[u8]| match context
+        .file_system()
+        .read(Path::new("/data/blob.delta"))
+    {
+        Ok(delta) => Ok(Some([previous, &delta].concat())),
+        Err(_) => Ok::<_, BoxError>(None),
+    };
+    let decode =
+        |contents: &[u8]| -> Result<String, BoxError> { Ok(String::from_utf8(contents.to_vec())?) };
+
+    let watch = Builder::new()
+        .file_system(fs.clone())
+        .watch_file("/data/blob")
+        .load(PatchLoader::new(patcher, decode))
+        .after_update(move |_context: &mut Context, value: Guard<String>| {
+            tx.send(value.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), "base");
+
+    fs.write("/data/blob.delta", "+1");
+    assert_eq!(watch.dry_run("/data/blob").unwrap(), "base+1");
+
+    // The next real reload patches the version the watch loaded, not the
+    // one from the dry run.
+    fs.write("/data/blob", "ignored");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "base+1");
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use config_file_watch::{scope, Builder, Context, Error};
+
+use crate::utils::create_files;
+
+#[test]
+fn should_stop_watches_when_scope_exits() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = scope(|scope| {
+        let watch = scope.add(
+            Builder::new()
+                .watch_file(config_file)
+                .load(|context: &mut Context| {
+                    let contents = fs::read_to_string(context.path().unwrap())?;
+                    Ok(contents.parse::<i32>()?)
+                })
+                .after_update(move |_context: &mut Context, value: _| {
+                    tx.send(value).unwrap();
+                })
+                .build()
+                .unwrap(),
+        );
+
+        rx.recv().unwrap();
+        fs::write(config_file, "2").unwrap();
+        rx.recv().unwrap();
+        assert_eq!(**watch.value(), 2);
+
+        watch
+    });
+
+    // The watch is still readable, but should no longer update.
+    fs::write(config_file, "3").unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(**watch.value(), 2);
+}
+
+#[test]
+fn should_let_loaders_give_up_when_scope_exits() {
+    let (started_tx, started_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let start = scope(|scope| {
+        scope.add(
+            Builder::new()
+                .watch_file(config_file)
+                .load(move |context: &mut Context| {
+                    let value = fs::read_to_string(context.path().unwrap())?.parse::<i32>()?;
+                    if value == 1 {
+                        return Ok(value);
+                    }
+
+                    // Simulate a slow load, which checks for shutdown as it goes.
+                    started_tx.send(()).unwrap();
+                    for _ in 0..100 {
+                        if context.is_shutting_down() {
+                            return Err("shutting down".into());
+                        }
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    Ok(value)
+                })
+                .on_error(move |_context: &mut Context, err: Error| {
+                    error_tx.send(err.to_string()).unwrap();
+                })
+                .build()
+                .unwrap(),
+        );
+
+        fs::write(config_file, "2").unwrap();
+        started_rx.recv().unwrap();
+        Instant::now()
+    });
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(error_rx.try_iter().next().is_none());
+}
+
+#[test]
+fn should_wait_for_load_in_progress_on_shutdown() {
+    let (started_tx, started_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(move |context: &mut Context| {
+            let value = fs::read_to_string(context.path().unwrap())?.parse::<i32>()?;
+            if value == 2 {
+                started_tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(300));
+            }
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(value)
+        })
+        .build()
+        .unwrap();
+    let reader = watch.clone();
+
+    fs::write(config_file, "2").unwrap();
+    started_rx.recv().unwrap();
+    // The load which was in progress finishes before shutdown returns.
+    assert_eq!(*watch.shutdown().unwrap(), 2);
+
+    // Nothing is loaded after shutdown.
+    fs::write(config_file, "3").unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(**reader.value(), 2);
+}
@@ -0,0 +1,39 @@
+use std::{fs, sync::mpsc};
+
+use config_file_watch::{Builder, Context, Guard, SecretString, Watch};
+
+use crate::utils::create_files;
+
+#[test]
+fn should_load_a_secret() -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("token", "hunter2\n")])?;
+    let token_file = &files[0];
+
+    let watch: Watch<SecretString> = Builder::new()
+        .watch_file(token_file)
+        .load_secret()
+        .after_update(move |_context: &mut Context, _value: Guard<SecretString>| {
+            tx.send(()).unwrap();
+        })
+        .build()?;
+
+    rx.recv().unwrap();
+    assert_eq!(watch.value().expose_secret(), "hunter2");
+    assert_eq!(format!("{:?}", watch.value()), "SecretString([REDACTED])");
+
+    fs::write(token_file, "correct horse battery staple")?;
+    rx.recv().unwrap();
+    assert_eq!(
+        watch.value().expose_secret(),
+        "correct horse battery staple"
+    );
+
+    // Removing the file should clear the secret.
+    fs::remove_file(token_file)?;
+    rx.recv().unwrap();
+    assert!(watch.value().is_empty());
+
+    Ok(())
+}
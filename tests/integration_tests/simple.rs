@@ -1,6 +1,17 @@
-use std::{collections::HashSet, fs, sync::mpsc, thread, time::Duration};
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
 
-use config_file_watch::{Builder, Context};
+use config_file_watch::{
+    Backend, Builder, ChangeDetection, CircuitState, Context, DebounceMode, Error, Guard,
+    NotifyConfig, Pipeline, PollCompare, Progress, ReloadOutcome, ReloadTrigger, Stage, Warning,
+    Watch, WatchOptions, WatcherOptions,
+};
 use map_macro::hash_set;
 
 use crate::utils::create_files;
@@ -78,6 +89,305 @@ fn should_create_file_watch_with_default_value() {
     assert_eq!(**watch.value(), 2);
 }
 
+#[test]
+fn should_take_snapshots_of_the_current_value() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, value: _| {
+            tx.send(value).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    rx.recv().unwrap();
+    let first = watch.snapshot();
+    assert_eq!(*first, 1);
+    assert!(first.loaded_at().is_some());
+
+    fs::write(config_file, "2").unwrap();
+    rx.recv().unwrap();
+    let second = watch.snapshot();
+    assert_eq!(*second, 2);
+    assert_eq!(second.version(), first.version() + 1);
+
+    // The first snapshot keeps its value after the watch is updated.
+    let first = thread::spawn(move || *first).join().unwrap();
+    assert_eq!(first, 1);
+}
+
+#[test]
+fn should_find_the_value_live_at_a_time() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .keep_history(2)
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, value: _| {
+            tx.send(value).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    rx.recv().unwrap();
+    let first = watch.snapshot();
+    let between = SystemTime::now();
+
+    fs::write(config_file, "2").unwrap();
+    rx.recv().unwrap();
+
+    assert_eq!(*watch.value_at(between).unwrap(), 1);
+    assert_eq!(*watch.value_at(SystemTime::now()).unwrap(), 2);
+    let before = first.loaded_at().unwrap() - Duration::from_millis(1);
+    assert!(watch.value_at(before).is_none());
+
+    let versions: Vec<_> = watch.history().iter().map(|s| **s).collect();
+    assert_eq!(versions, vec![1, 2]);
+}
+
+#[test]
+fn should_upgrade_weak_watch_until_the_watch_is_dropped() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+
+    let weak = watch.downgrade();
+    assert_eq!(**weak.upgrade().unwrap().value(), 1);
+
+    drop(watch);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn should_only_notify_mapped_views_when_their_part_changes() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1,10")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(|context: &mut Context| {
+            let contents = fs::read_to_string(context.path().unwrap())?;
+            let (a, b) = contents.trim().split_once(',').ok_or("missing comma")?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>((
+                a.parse::<i32>()?,
+                b.parse::<i32>()?,
+            ))
+        })
+        .debounce(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let b = watch.map(|config: &(i32, i32)| &config.1).unwrap();
+    assert_eq!(**b.value(), 10);
+    b.subscribe(move |_context: &mut Context, value: Guard<i32>| {
+        tx.send(**value).unwrap();
+    });
+
+    // Only the first part changed.
+    fs::write(config_file, "2,10").unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+    assert_eq!(watch.value().0, 2);
+
+    fs::write(config_file, "2,20").unwrap();
+    assert_eq!(rx.recv().unwrap(), 20);
+}
+
+#[test]
+fn should_combine_two_watches() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1"), ("secrets_file", "10")]).unwrap();
+
+    let config = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    let secrets = Builder::new()
+        .watch_file(&files[1])
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let combined = config.combine(&secrets, |a: &i32, b: &i32| a + b).unwrap();
+    assert_eq!(**combined.value(), 11);
+    combined.subscribe(move |_context: &mut Context, value: Guard<i32>| {
+        tx.send(**value).unwrap();
+    });
+
+    fs::write(&files[1], "20").unwrap();
+    assert_eq!(rx.recv().unwrap(), 21);
+    fs::write(&files[0], "2").unwrap();
+    assert_eq!(rx.recv().unwrap(), 22);
+
+    let zipped = config.zip(&secrets).unwrap();
+    let (a, b) = &**zipped.value();
+    assert_eq!((**a, **b), (2, 20));
+}
+
+#[test]
+fn should_create_fixed_watch() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+
+    let watch = Watch::fixed(5);
+    assert_eq!(**watch.value(), 5);
+    assert_eq!(watch.snapshot().version(), 0);
+    assert!(watch.watched_files().is_empty());
+    assert!(watch.watch_additionally(&files).is_err());
+
+    assert_eq!(*watch.shutdown().unwrap(), 5);
+}
+
+#[test]
+fn should_call_closure_with_current_value() {
+    let watch = Watch::fixed(String::from("hello"));
+    assert_eq!(watch.with_value(|value| value.len()), 5);
+}
+
+#[test]
+fn should_keep_owned_value_after_update() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, value: _| {
+            tx.send(value).unwrap();
+        })
+        .build()
+        .unwrap();
+    rx.recv().unwrap();
+
+    let first: Arc<i32> = watch.value_arc();
+    fs::write(config_file, "2").unwrap();
+    rx.recv().unwrap();
+
+    assert_eq!(*first, 1);
+    assert_eq!(*watch.value_arc(), 2);
+}
+
+#[test]
+fn should_not_update_when_value_is_unchanged() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .dedup_values()
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+    let version = watch.snapshot().version();
+
+    // Saving the file without changing it doesn't update the watch.
+    fs::write(config_file, "1").unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+    assert_eq!(watch.stats().unchanged_reloads, 1);
+    let trace = watch.explain_last_reload().unwrap();
+    assert_eq!(trace.outcome, ReloadOutcome::Unchanged);
+    assert!(trace.load_duration > Duration::ZERO);
+    assert_eq!(watch.snapshot().version(), version);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+}
+
+#[test]
+fn should_skip_files_with_unchanged_metadata() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+    let modified = fs::metadata(config_file).unwrap().modified().unwrap();
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .change_detection(ChangeDetection::Metadata)
+        .load(loader)
+        .debounce(Duration::from_millis(200))
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // The contents change, but the size and modification time don't, so the
+    // loader isn't called.
+    fs::write(config_file, "2").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(config_file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+
+    fs::write(config_file, "23").unwrap();
+    assert_eq!(rx.recv().unwrap(), 23);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn should_ignore_metadata_only_events() {
+    use std::os::unix::fs::PermissionsExt;
+
+    for include_metadata_events in [false, true] {
+        let (tx, rx) = mpsc::channel();
+
+        let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+        let config_file = &files[0];
+
+        let mut builder = Builder::new().watch_file(config_file);
+        if include_metadata_events {
+            builder = builder.include_metadata_events();
+        }
+        let _watch = builder
+            .load(loader)
+            .after_update(move |_context: &mut Context, value: Guard<i32>| {
+                tx.send(**value).unwrap();
+            })
+            .build()
+            .unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        fs::set_permissions(config_file, fs::Permissions::from_mode(0o600)).unwrap();
+        let reloaded = rx.recv_timeout(Duration::from_millis(800)).is_ok();
+        assert_eq!(reloaded, include_metadata_events);
+    }
+}
+
 #[test]
 fn should_create_watch_with_no_watched_files() {
     // tx and rx so we can signal when the value has changed.
@@ -152,6 +462,37 @@ fn should_create_file_watch_with_optional_value() {
     assert_eq!(**watch.value(), None);
 }
 
+#[test]
+fn should_build_with_an_initial_value() {
+    // A type with no sensible default.
+    #[derive(Debug, PartialEq)]
+    struct Port(u16);
+
+    let (_guard, files) = create_files(&[("config_file", "80")]).unwrap();
+    let missing = files[0].with_file_name("missing_file");
+
+    let watch = Builder::new()
+        .watch_file(&missing)
+        .load(|context: &mut Context| match context.path() {
+            Some(path) => Ok(Port(fs::read_to_string(path)?.parse()?)),
+            None => Err("no file".into()),
+        })
+        .build_with(Port(8080))
+        .unwrap();
+    // The file can't be loaded, so the initial value is kept.
+    assert_eq!(**watch.value(), Port(8080));
+
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(|context: &mut Context| match context.path() {
+            Some(path) => Ok(Port(fs::read_to_string(path)?.parse()?)),
+            None => Err("no file".into()),
+        })
+        .build_with(Port(8080))
+        .unwrap();
+    assert_eq!(**watch.value(), Port(80));
+}
+
 #[test]
 fn should_create_a_watch_for_file_that_does_not_exist() {
     let dir = tempfile::tempdir().unwrap();
@@ -207,3 +548,1287 @@ fn should_create_file_watch_for_multiple_files() {
         hash_set![config_file_1.to_path_buf(), config_file_2.to_path_buf()]
     );
 }
+
+#[test]
+fn should_dry_run_a_candidate_file() {
+    let (_guard, files) = create_files(&[
+        ("config_file", "1"),
+        ("good_candidate", "5"),
+        ("bad_candidate", "foo"),
+    ])
+    .unwrap();
+
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .build()
+        .unwrap();
+
+    assert_eq!(watch.dry_run(&files[1]).unwrap(), 5);
+    assert!(watch.dry_run(&files[2]).is_err());
+
+    // The live value should not have changed.
+    assert_eq!(**watch.value(), 1);
+}
+
+#[test]
+fn should_explain_last_reload() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .on_error(move |_context: &mut Context, _err: Error| {
+            tx.send(()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    let trace = watch.explain_last_reload().unwrap();
+    assert_eq!(trace.trigger, ReloadTrigger::InitialLoad);
+    assert_eq!(trace.outcome, ReloadOutcome::Updated);
+    assert_eq!(trace.modified_paths, vec![config_file.clone()]);
+
+    // Write an invalid value, which should fail to load.
+    fs::write(config_file, "foo").unwrap();
+    rx.recv().unwrap();
+
+    let trace = watch.explain_last_reload().unwrap();
+    assert!(matches!(trace.trigger, ReloadTrigger::FileChange { .. }));
+    assert!(matches!(trace.outcome, ReloadOutcome::LoadFailed(_)));
+    assert_eq!(trace.modified_paths, vec![config_file.clone()]);
+    assert_eq!(**watch.value(), 1);
+}
+
+#[test]
+fn should_reconfigure_a_live_watch() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // Switch to polling, with a longer debounce.
+    let mut options = WatcherOptions::default();
+    options.debounce = Some(Duration::from_millis(200));
+    options.backend = Backend::Poll {
+        interval: Duration::from_millis(50),
+    };
+    watch.reconfigure(options.clone()).unwrap();
+    assert_eq!(watch.options(), options);
+
+    // Changes should still be picked up, and the value should be kept.
+    assert_eq!(**watch.value(), 1);
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+}
+
+#[test]
+fn should_poll_for_changes_when_mtime_is_unreliable() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+    let modified = fs::metadata(config_file).unwrap().modified().unwrap();
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .backend(Backend::Poll {
+            interval: Duration::from_millis(50),
+        })
+        .poll_compare(PollCompare::Contents)
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // Change the file without changing its size, and wind the modification
+    // time back, as can happen with a skewed clock.
+    fs::write(config_file, "2").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(config_file)
+        .unwrap()
+        .set_modified(modified - Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+}
+
+fn modified_paths(
+    context: &mut Context,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(context
+        .modified_paths()
+        .iter()
+        .map(|path| path.to_path_buf())
+        .collect())
+}
+
+#[test]
+fn should_watch_a_directory_recursively_alongside_a_file() {
+    let (tx, rx) = mpsc::channel();
+    let (guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let templates = guard.path().join("templates");
+    fs::create_dir_all(templates.join("email")).unwrap();
+
+    let mut options = WatchOptions::default();
+    options.recursive = true;
+    let _watch = Builder::new()
+        .watch_file(&files[0])
+        .watch_file_with(&templates, options)
+        .load(modified_paths)
+        .after_update(move |_context: &mut Context, value: Guard<Vec<PathBuf>>| {
+            tx.send(value.to_vec()).unwrap();
+        })
+        .build()
+        .unwrap();
+    rx.recv().unwrap();
+
+    fs::write(templates.join("email").join("welcome.html"), "hi").unwrap();
+    assert_eq!(rx.recv().unwrap(), vec![templates]);
+}
+
+#[test]
+fn should_reload_at_the_start_of_the_debounce_in_leading_mode() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .debounce(Duration::from_secs(2))
+        .debounce_mode(DebounceMode::Leading)
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // Replace the file atomically, so the first event sees the whole write.
+    let temp_file = config_file.with_extension("tmp");
+    fs::write(&temp_file, "2").unwrap();
+    fs::rename(&temp_file, config_file).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+}
+
+#[test]
+fn should_limit_how_often_the_watch_reloads() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "0")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .debounce(Duration::from_millis(10))
+        .min_reload_interval(Duration::from_millis(500))
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 0);
+
+    // Hammer the file for a second.
+    for value in 1..=20 {
+        fs::write(config_file, value.to_string()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // Only a few reloads happen, and the last one sees the final value.
+    let mut values = vec![];
+    while let Ok(value) = rx.recv_timeout(Duration::from_millis(1000)) {
+        values.push(value);
+    }
+    assert!(values.len() <= 4, "{values:?}");
+    assert_eq!(values.last(), Some(&20));
+}
+
+#[test]
+fn should_pass_notify_config_to_the_poll_watcher() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .backend(Backend::Poll {
+            interval: Duration::from_millis(50),
+        })
+        .notify_config(NotifyConfig::default().with_compare_contents(true))
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+}
+
+#[test]
+fn should_poll_with_notify_poll_watcher() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .backend(Backend::NotifyPoll {
+            interval: Duration::from_millis(50),
+        })
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    // Make sure the modification time moves forward, even on file systems
+    // with coarse timestamps.
+    let modified = fs::metadata(config_file).unwrap().modified().unwrap();
+    fs::write(config_file, "2").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(config_file)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 2);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn should_reload_with_low_priority() {
+    // Read the nice value of the current thread.
+    fn nice() -> i32 {
+        let stat = fs::read_to_string("/proc/thread-self/stat").unwrap();
+        let fields = stat.rsplit_once(')').unwrap().1;
+        fields.split_whitespace().nth(16).unwrap().parse().unwrap()
+    }
+
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .low_priority()
+        .load(move |context: &mut Context| {
+            let value = loader(context)?;
+            tx.send((value, nice())).unwrap();
+            Ok(value)
+        })
+        .build()
+        .unwrap();
+
+    // The initial load happens on this thread, so the priority is unchanged.
+    assert_eq!(rx.recv().unwrap(), (1, nice()));
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().unwrap(), (2, 10));
+}
+
+#[test]
+fn should_watch_files_temporarily() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1"), ("override", "2")]).unwrap();
+    let config_file = &files[0];
+    let override_file = &files[1];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    {
+        let guard = watch
+            .watch_additionally([config_file, override_file])
+            .unwrap();
+        // The config file was already being watched, so only the override
+        // file should be removed when the guard is dropped.
+        assert_eq!(guard.files(), std::slice::from_ref(override_file));
+        assert_eq!(
+            watch.watched_files(),
+            vec![config_file.clone(), override_file.clone()]
+        );
+
+        fs::write(override_file, "3").unwrap();
+        assert_eq!(rx.recv().unwrap(), 3);
+    }
+
+    assert_eq!(watch.watched_files(), vec![config_file.clone()]);
+    fs::write(override_file, "4").unwrap();
+    fs::write(config_file, "5").unwrap();
+    assert_eq!(rx.recv().unwrap(), 5);
+}
+
+#[test]
+fn should_report_lint_warnings() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .lint(|value: &i32| {
+            if *value > 10 {
+                vec![Warning::for_key("value", "should be at most 10")]
+            } else {
+                vec![]
+            }
+        })
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    assert_eq!(watch.stats().warnings, 0);
+
+    // The value should still be used, even though it generates a warning.
+    fs::write(config_file, "20").unwrap();
+    assert_eq!(rx.recv().unwrap(), "value: should be at most 10");
+    assert_eq!(**watch.value(), 20);
+
+    let stats = watch.stats();
+    assert_eq!(stats.reloads, 2);
+    assert_eq!(stats.warnings, 1);
+    assert_eq!(stats.last_warnings.len(), 1);
+    assert_eq!(watch.explain_last_reload().unwrap().warnings.len(), 1);
+}
+
+#[test]
+fn should_reject_values_which_fail_validation() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .validate(|value: &i32| match *value {
+            0..=10 => Ok(()),
+            _ => Err(format!("{value} is out of range")),
+        })
+        .on_error(move |_context: &mut Context, err: Error| {
+            tx.send(err.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 1);
+
+    fs::write(config_file, "20").unwrap();
+    assert_eq!(rx.recv().unwrap(), "Validation error: 20 is out of range");
+    assert_eq!(**watch.value(), 1);
+
+    fs::write(config_file, "5").unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(**watch.value(), 5);
+}
+
+#[test]
+fn should_read_other_watches_from_loader() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("base", "1"), ("config_file", "2")]).unwrap();
+    let base_file = &files[0];
+    let config_file = &files[1];
+
+    let base = Builder::new()
+        .watch_file(base_file)
+        .load(loader)
+        .build()
+        .unwrap();
+
+    let reader = base.reader();
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(move |context: &mut Context| Ok(loader(context)? + **reader.value()))
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 3);
+
+    fs::write(base_file, "10").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(**base.value(), 10);
+
+    fs::write(config_file, "3").unwrap();
+    assert_eq!(rx.recv().unwrap(), 13);
+    assert_eq!(**watch.value(), 13);
+}
+
+#[test]
+fn should_not_deadlock_when_loader_waits_for_its_own_watch() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = files[0].clone();
+
+    let this: Arc<Mutex<Option<Watch<i32>>>> = Arc::new(Mutex::new(None));
+    let watch = {
+        let this = this.clone();
+        let config_file = config_file.clone();
+        Builder::new()
+            .watch_file(&config_file)
+            .load(move |context: &mut Context| {
+                let watch = this.lock().unwrap().clone();
+                if let Some(watch) = watch {
+                    let result = watch.dry_run(&config_file);
+                    tx.send(matches!(result, Err(Error::WouldDeadlock)))
+                        .unwrap();
+                }
+                loader(context)
+            })
+            .build()
+            .unwrap()
+    };
+    *this.lock().unwrap() = Some(watch.clone());
+
+    fs::write(&config_file, "2").unwrap();
+    assert!(rx.recv().unwrap());
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(**watch.value(), 2);
+
+    // Outside of a loader, dry_run waits as usual.
+    assert_eq!(watch.dry_run(&config_file).unwrap(), 2);
+
+    // Break the reference cycle so the watch can stop.
+    this.lock().unwrap().take();
+}
+
+#[test]
+fn should_warn_about_slow_reloads() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(|context: &mut Context| {
+            let value = loader(context)?;
+            thread::sleep(Duration::from_millis(value as u64 * 10));
+            Ok(value)
+        })
+        .warn_on_slow_reload(Duration::from_millis(50))
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert!(watch.stats().average_load_duration >= Duration::from_millis(10));
+    assert!(rx.try_recv().is_err());
+
+    fs::write(config_file, "10").unwrap();
+    let warning = rx.recv().unwrap();
+    assert!(
+        warning.starts_with("loading took") && warning.ends_with("which is longer than 50ms"),
+        "{warning}"
+    );
+    assert_eq!(watch.stats().warnings, 1);
+}
+
+#[test]
+fn should_report_progress_of_slow_reloads() {
+    let (progress_tx, progress_rx) = mpsc::channel();
+    let (resume_tx, resume_rx) = mpsc::channel::<()>();
+    let (updated_tx, updated_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(move |context: &mut Context| {
+            let value = loader(context)?;
+            if value > 1 {
+                context.report_progress(0.6, "reading routing table");
+                resume_rx.recv().unwrap();
+            }
+            Ok(value)
+        })
+        .on_progress(move |progress: &Progress| {
+            progress_tx.send(progress.clone()).unwrap();
+        })
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            updated_tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(updated_rx.recv().unwrap(), 1);
+    assert_eq!(watch.progress(), None);
+
+    fs::write(config_file, "2").unwrap();
+    let progress = progress_rx.recv().unwrap();
+    assert_eq!(progress.fraction, 0.6);
+    assert_eq!(progress.message, "reading routing table");
+    assert_eq!(watch.progress(), Some(progress));
+
+    // Progress is cleared once the load finishes.
+    resume_tx.send(()).unwrap();
+    assert_eq!(updated_rx.recv().unwrap(), 2);
+    assert_eq!(watch.progress(), None);
+}
+
+#[test]
+fn should_cancel_a_load_when_the_file_changes_again() {
+    let (started_tx, started_rx) = mpsc::channel();
+    let (updated_tx, updated_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(move |context: &mut Context| {
+            let value = loader(context)?;
+            if value == 2 {
+                // Simulate a slow load, which checks for cancellation as it
+                // goes.
+                started_tx.send(()).unwrap();
+                let cancel = context.cancel_token();
+                for _ in 0..200 {
+                    if cancel.is_cancelled() {
+                        return Err("cancelled".into());
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+            Ok(value)
+        })
+        .debounce(Duration::from_millis(50))
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            updated_tx.send(**value).unwrap();
+        })
+        .on_error(move |_context: &mut Context, error: Error| {
+            error_tx.send(error.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(updated_rx.recv().unwrap(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    started_rx.recv().unwrap();
+    fs::write(config_file, "3").unwrap();
+
+    // The load of 2 is abandoned, and 3 is loaded instead.
+    assert_eq!(updated_rx.recv_timeout(Duration::from_secs(1)).unwrap(), 3);
+    assert!(watch.stats().cancelled_reloads >= 1);
+    assert!(error_rx.try_recv().is_err());
+    assert!(updated_rx.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(**watch.value(), 3);
+}
+
+#[test]
+fn should_report_a_load_which_takes_too_long() {
+    let (updated_tx, updated_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(|context: &mut Context| {
+            let value = loader(context)?;
+            if value == 2 {
+                thread::sleep(Duration::from_millis(500));
+            }
+            Ok(value)
+        })
+        .load_timeout(Duration::from_millis(100))
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            updated_tx.send(**value).unwrap();
+        })
+        .on_error(move |_context: &mut Context, error: Error| {
+            error_tx.send(error).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(updated_rx.recv().unwrap(), 1);
+
+    // The timeout is reported while the load is still running.
+    fs::write(config_file, "2").unwrap();
+    let error = error_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(matches!(error, Error::LoadTimeout(timeout) if timeout == Duration::from_millis(100)));
+    assert_eq!(watch.stats().timed_out_loads, 1);
+    assert_eq!(**watch.value(), 1);
+
+    // Once it finishes, the value it loaded is still used.
+    assert_eq!(updated_rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+    assert!(error_rx.try_recv().is_err());
+}
+
+#[test]
+fn should_abandon_a_load_which_times_out() {
+    let (updated_tx, updated_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(|context: &mut Context| {
+            let value = loader(context)?;
+            if value == 2 {
+                // Hang until the load is abandoned.
+                while !context.cancelled() {
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+            Ok(value)
+        })
+        .load_timeout(Duration::from_millis(100))
+        .abandon_timed_out_loads()
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            updated_tx.send(**value).unwrap();
+        })
+        .on_error(move |_context: &mut Context, error: Error| {
+            error_tx.send(error.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(updated_rx.recv().unwrap(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(
+        error_rx.recv_timeout(Duration::from_secs(1)).unwrap(),
+        "Loading took longer than 100ms"
+    );
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(**watch.value(), 1);
+    assert!(matches!(
+        watch.explain_last_reload().unwrap().outcome,
+        ReloadOutcome::LoadFailed(_)
+    ));
+    assert!(updated_rx.try_recv().is_err());
+    assert!(error_rx.try_recv().is_err());
+
+    // The watch goes on loading changes.
+    fs::write(config_file, "3").unwrap();
+    assert_eq!(updated_rx.recv_timeout(Duration::from_secs(1)).unwrap(), 3);
+}
+
+#[test]
+fn should_expand_watched_paths() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1"), ("other_file", "2")]).unwrap();
+    let dir = files[0].parent().unwrap();
+    std::env::set_var("CONFIG_FILE_WATCH_TEST_DIR", dir);
+
+    let watch = Builder::new()
+        .expand_paths()
+        .watch_file("$CONFIG_FILE_WATCH_TEST_DIR/config_file")
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+    assert_eq!(watch.watched_files(), vec![files[0].clone()]);
+
+    watch
+        .update_watched_files(["${CONFIG_FILE_WATCH_TEST_DIR}/other_file"])
+        .unwrap();
+    assert_eq!(watch.watched_files(), vec![files[1].clone()]);
+
+    fs::write(&files[1], "3").unwrap();
+    assert_eq!(rx.recv().unwrap(), 3);
+}
+
+#[test]
+fn should_keep_watched_files_in_insertion_order() {
+    let (_guard, files) =
+        create_files(&[("config_file", "1"), ("b_file", "2"), ("a_file", "3")]).unwrap();
+
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .build()
+        .unwrap();
+
+    watch.update_watched_files([&files[1], &files[2]]).unwrap();
+    assert_eq!(
+        watch.watched_files(),
+        vec![files[1].clone(), files[2].clone()]
+    );
+
+    // Files which are already being watched keep their place.
+    watch
+        .update_watched_files([&files[2], &files[0], &files[1]])
+        .unwrap();
+    assert_eq!(
+        watch.watched_files(),
+        vec![files[1].clone(), files[2].clone(), files[0].clone()]
+    );
+    assert!(watch.watched_files().contains(&files[0]));
+}
+
+#[test]
+fn should_resolve_relative_paths_against_base_dir() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1"), ("other_file", "2")]).unwrap();
+    let dir = files[0].parent().unwrap();
+
+    let watch = Builder::new()
+        .base_dir(dir)
+        .watch_file("config_file")
+        .load(move |context: &mut Context| {
+            // Relative dependencies are resolved against the base dir too.
+            context.add_watched_files(&["other_file"])?;
+            loader(context)
+        })
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+    assert_eq!(watch.watched_files(), files);
+
+    fs::write(&files[1], "3").unwrap();
+    assert_eq!(rx.recv().unwrap(), 3);
+}
+
+#[test]
+fn should_resolve_watched_paths_again_when_base_dir_changes() {
+    let (tx, rx) = mpsc::channel();
+
+    let dir = tempfile::tempdir().unwrap();
+    let dir_a = dir.path().join("a");
+    let dir_b = dir.path().join("b");
+    fs::create_dir(&dir_a).unwrap();
+    fs::create_dir(&dir_b).unwrap();
+    let files = [dir_a.join("config_file"), dir_b.join("config_file")];
+    fs::write(&files[0], "1").unwrap();
+    fs::write(&files[1], "2").unwrap();
+
+    let watch = Builder::new()
+        .base_dir(&dir_a)
+        .watch_file("config_file")
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+
+    let mut options = watch.options();
+    options.base_dir = Some(dir_b);
+    watch.reconfigure(options).unwrap();
+    assert_eq!(watch.watched_files(), vec![files[1].clone()]);
+    assert_eq!(rx.recv().unwrap(), 2);
+
+    fs::write(&files[1], "3").unwrap();
+    assert_eq!(rx.recv().unwrap(), 3);
+}
+
+#[test]
+fn should_fail_to_build_if_initial_load_fails() {
+    let (_guard, files) = create_files(&[("config_file", "not a number")]).unwrap();
+
+    let err = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .on_error(|_context: &mut Context, err: Error| {
+            panic!("Error should be returned from build(), not passed to the handler: {err}")
+        })
+        .fail_on_initial_error()
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, Error::LoadError(_)));
+
+    fs::write(&files[0], "1").unwrap();
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .fail_on_initial_error()
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 1);
+}
+
+#[test]
+fn should_require_watched_files_to_exist() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let missing = files[0].with_file_name("missing_file");
+
+    let err = Builder::new()
+        .watch_files([&files[0], &missing])
+        .load(loader)
+        .require_exists()
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, Error::FileNotFound(path) if path == missing));
+
+    // Only one candidate has to exist.
+    let watch = Builder::new()
+        .watch_first_of([&missing, &files[0]])
+        .load(loader)
+        .require_exists()
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 1);
+}
+
+#[test]
+fn should_load_initial_value_in_background() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(|context: &mut Context| {
+            thread::sleep(Duration::from_millis(200));
+            loader(context)
+        })
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send((**value, thread::current().id())).unwrap();
+        })
+        .lazy_initial_load()
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 0);
+
+    let (value, thread) = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert_eq!(value, 1);
+    assert_ne!(thread, thread::current().id());
+    assert_eq!(**watch.value(), 1);
+}
+
+#[test]
+fn should_update_views_after_a_lazy_initial_load() {
+    let (tx, rx) = mpsc::channel();
+    let (_guard, files) = create_files(&[("config_file", "5")]).unwrap();
+
+    let watch = Builder::new()
+        .watch_file(&files[0])
+        .load(|context: &mut Context| {
+            thread::sleep(Duration::from_millis(200));
+            loader(context)
+        })
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .lazy_initial_load()
+        .build()
+        .unwrap();
+    let mapped = watch.map(|value: &i32| value).unwrap();
+    assert_eq!(**mapped.value(), 0);
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), 5);
+    assert_eq!(**mapped.value(), 5);
+}
+
+#[test]
+fn should_not_combine_lazy_initial_load_with_fail_on_initial_error() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+
+    let result = Builder::new()
+        .watch_file(&files[0])
+        .load(loader)
+        .lazy_initial_load()
+        .fail_on_initial_error()
+        .build();
+    assert!(matches!(result, Err(Error::InvalidConfiguration(_))));
+}
+
+#[test]
+fn should_suppress_repeated_errors() {
+    let (error_tx, error_rx) = mpsc::channel();
+    let (warning_tx, warning_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .suppress_repeated_errors(Duration::from_secs(3600))
+        .on_error(move |_context: &mut Context, err: Error| {
+            error_tx.send(err.to_string()).unwrap();
+        })
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            warning_tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    for _ in 0..3 {
+        fs::write(config_file, "invalid").unwrap();
+        thread::sleep(Duration::from_millis(300));
+    }
+    assert_eq!(
+        error_rx.try_iter().collect::<Vec<_>>(),
+        vec!["Load error: invalid digit found in string"]
+    );
+    assert_eq!(watch.stats().suppressed_errors, 2);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(
+        warning_rx.recv().unwrap(),
+        "loaded successfully after 3 failed attempts (2 repeated errors were not reported)"
+    );
+    assert_eq!(**watch.value(), 2);
+}
+
+#[test]
+fn should_stop_loading_while_circuit_breaker_is_open() {
+    let (error_tx, error_rx) = mpsc::channel();
+    let (warning_tx, warning_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .circuit_breaker(2, Duration::from_secs(1))
+        .on_error(move |_context: &mut Context, err: Error| {
+            error_tx.send(err.to_string()).unwrap();
+        })
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            warning_tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        fs::write(config_file, "invalid").unwrap();
+        thread::sleep(Duration::from_millis(300));
+    }
+    assert_eq!(
+        error_rx.try_iter().collect::<Vec<_>>(),
+        vec![
+            "Load error: invalid digit found in string",
+            "Load error: invalid digit found in string",
+            "Stopped loading for 1s after 2 failures in a row",
+        ]
+    );
+    assert_eq!(watch.stats().circuit, CircuitState::Open);
+
+    // Changes are skipped until the cool-down is over.
+    fs::write(config_file, "2").unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(**watch.value(), 1);
+    assert_eq!(watch.stats().skipped_reloads, 1);
+    assert_eq!(
+        watch.explain_last_reload().unwrap().outcome,
+        ReloadOutcome::Skipped
+    );
+
+    assert_eq!(
+        warning_rx.recv_timeout(Duration::from_secs(2)).unwrap(),
+        "loaded successfully after 2 failures in a row, and will load every change again"
+    );
+    assert_eq!(**watch.value(), 2);
+    assert_eq!(watch.stats().circuit, CircuitState::Closed);
+    assert!(error_rx.try_iter().next().is_none());
+}
+
+#[test]
+fn should_warn_when_reloading_too_often() {
+    let (warning_tx, warning_rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .warn_on_reload_rate(2, Duration::from_secs(60))
+        .on_warning(move |_context: &mut Context, warning: &Warning| {
+            warning_tx.send(warning.to_string()).unwrap();
+        })
+        .build()
+        .unwrap();
+
+    for value in 2..5 {
+        fs::write(config_file, value.to_string()).unwrap();
+        thread::sleep(Duration::from_millis(300));
+    }
+    assert_eq!(
+        warning_rx.try_iter().collect::<Vec<_>>(),
+        vec![
+            "reloaded 3 times in 60s, which is more than the limit of 2; the file may be flapping"
+        ]
+    );
+}
+
+#[test]
+fn should_reuse_scratch_buffer_between_loads() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .load(move |context: &mut Context| {
+            let buffer = context.buffer();
+            tx.send((buffer.len(), buffer.capacity())).unwrap();
+            buffer.extend_from_slice(&[0; 4096]);
+            loader(context)
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), (0, 0));
+
+    fs::write(config_file, "2").unwrap();
+    let (len, capacity) = rx.recv().unwrap();
+    assert_eq!(len, 0);
+    assert!(capacity >= 4096, "{capacity}");
+}
+
+#[test]
+fn should_reload_with_literal_paths() {
+    let (tx, rx) = mpsc::channel();
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .literal_paths()
+        .load(loader)
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.send(**value).unwrap();
+        })
+        .build()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+    assert!(watch.options().literal_paths);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), 2);
+}
+
+#[test]
+fn should_send_updates_to_subscribers() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 1);
+
+    let first = watch.subscribe();
+    let second = watch.subscribe();
+    // Values stored before subscribing aren't sent.
+    assert!(first.try_recv().is_err());
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(*first.recv_timeout(Duration::from_secs(2)).unwrap(), 2);
+    assert_eq!(*second.recv_timeout(Duration::from_secs(2)).unwrap(), 2);
+
+    // Dropping a receiver doesn't affect the others.
+    drop(first);
+    fs::write(config_file, "3").unwrap();
+    assert_eq!(*second.recv_timeout(Duration::from_secs(2)).unwrap(), 3);
+
+    drop(watch);
+    assert!(second.recv_timeout(Duration::from_secs(2)).is_err());
+}
+
+#[cfg(feature = "crossbeam")]
+#[test]
+fn should_send_updates_to_crossbeam_subscribers() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+    let updates = watch.subscribe_crossbeam();
+    let timeout = crossbeam_channel::after(Duration::from_secs(2));
+
+    fs::write(config_file, "2").unwrap();
+    crossbeam_channel::select! {
+        recv(updates) -> value => assert_eq!(*value.unwrap(), 2),
+        recv(timeout) -> _ => panic!("timed out waiting for update"),
+    }
+}
+
+#[test]
+fn should_load_with_a_pipeline() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load_pipeline(
+            Pipeline::new(|contents: &[u8]| {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                    std::str::from_utf8(contents)?.trim().parse::<i32>()?,
+                )
+            })
+            .migrate(|value| Ok::<_, Error>(value * 10))
+            .validate(|value: &i32| if *value < 100 { Ok(()) } else { Err("too big") }),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 10);
+    let stages: Vec<Stage> = watch
+        .stats()
+        .stage_durations
+        .iter()
+        .map(|(stage, _)| *stage)
+        .collect();
+    assert_eq!(
+        stages,
+        vec![Stage::Read, Stage::Parse, Stage::Migrate, Stage::Validate]
+    );
+
+    // A value the validator rejects is not used.
+    fs::write(config_file, "20").unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(**watch.value(), 10);
+    assert_eq!(watch.stats().failed_reloads, 1);
+}
+
+#[test]
+fn should_wait_for_an_update() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+    assert!(watch.wait_for_update(Duration::from_millis(200)).is_none());
+
+    fs::write(config_file, "2").unwrap();
+    let value = watch.wait_for_update(Duration::from_secs(2)).unwrap();
+    assert_eq!(**value, 2);
+}
+
+#[test]
+fn should_read_new_values_through_cached_reader() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+    let mut reader = watch.cached_reader();
+    assert_eq!(**reader.value(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    watch.wait_for_update(Duration::from_secs(2)).unwrap();
+    assert_eq!(**reader.value(), 2);
+}
+
+#[test]
+fn should_load_changes_made_while_paused_on_resume() {
+    let (_guard, files) = create_files(&[("first", "1"), ("second", "2")]).unwrap();
+
+    let watch = Builder::new()
+        .watch_files(&files)
+        .load(|context: &mut Context| {
+            let mut sum = 0;
+            for path in context.watched_files() {
+                sum += fs::read_to_string(path)?.parse::<i32>()?;
+            }
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(sum)
+        })
+        .build()
+        .unwrap();
+    assert_eq!(**watch.value(), 3);
+
+    watch.pause();
+    assert!(watch.is_paused());
+    fs::write(&files[0], "10").unwrap();
+    fs::write(&files[1], "20").unwrap();
+    assert!(watch.wait_for_update(Duration::from_millis(500)).is_none());
+    assert_eq!(watch.stats().reloads, 1);
+
+    let updates = watch.subscribe();
+    watch.resume();
+    assert_eq!(*updates.recv_timeout(Duration::from_secs(2)).unwrap(), 30);
+    assert_eq!(watch.stats().reloads, 2);
+    let trace = watch.explain_last_reload().unwrap();
+    assert_eq!(trace.modified_paths.len(), 2);
+}
+
+#[test]
+fn should_wait_for_an_expected_change() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build()
+        .unwrap();
+
+    // The current value already matches.
+    let value = watch.expect_change(|v| *v == 1, Duration::ZERO).unwrap();
+    assert_eq!(*value, 1);
+
+    let writer = {
+        let config_file = config_file.clone();
+        thread::spawn(move || {
+            for value in ["2", "3"] {
+                thread::sleep(Duration::from_millis(200));
+                fs::write(&config_file, value).unwrap();
+            }
+        })
+    };
+    let value = watch
+        .expect_change(|v| *v == 3, Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(*value, 3);
+    writer.join().unwrap();
+
+    assert!(watch
+        .expect_change(|v| *v == 4, Duration::from_millis(200))
+        .is_none());
+}
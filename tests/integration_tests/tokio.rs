@@ -1,6 +1,7 @@
-use std::fs;
+use std::{fs, future::poll_fn, pin::Pin, time::Duration};
 
-use config_file_watch::{Builder, Context};
+use config_file_watch::{scope_async, Builder, Context, Guard};
+use futures_core::Stream;
 
 use crate::utils::create_files;
 
@@ -29,3 +30,189 @@ async fn should_create_watch_async() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn should_load_with_an_async_loader() {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load_async(|context: &mut Context| {
+            let path = context.path().unwrap().to_path_buf();
+            async move {
+                tokio::task::yield_now().await;
+                let contents = fs::read_to_string(path)?;
+                Ok(contents.parse::<i32>()?)
+            }
+        })
+        .after_update(move |_context: &mut Context, value: Guard<i32>| {
+            tx.blocking_send(**value).unwrap();
+        })
+        .build_async()
+        .await
+        .unwrap();
+    assert_eq!(rx.recv().await.unwrap(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().await.unwrap(), 2);
+    assert_eq!(**watch.value(), 2);
+}
+
+#[tokio::test]
+async fn should_call_async_after_update() {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let _watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .after_update_async(move |_context: &mut Context, value: Guard<i32>| {
+            let tx = tx.clone();
+            async move {
+                tx.send(**value).await.unwrap();
+            }
+        })
+        .build_async()
+        .await
+        .unwrap();
+    assert_eq!(rx.recv().await.unwrap(), 1);
+
+    fs::write(config_file, "2").unwrap();
+    assert_eq!(rx.recv().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn should_wait_for_changes() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build_async()
+        .await
+        .unwrap();
+
+    let changed = watch.changed();
+    fs::write(config_file, "2").unwrap();
+    tokio::time::timeout(Duration::from_secs(2), changed)
+        .await
+        .unwrap();
+    assert_eq!(**watch.value(), 2);
+}
+
+#[tokio::test]
+async fn should_stream_updates() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let mut stream = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build_async()
+        .await
+        .unwrap()
+        .into_stream();
+    fs::write(config_file, "2").unwrap();
+    let value = tokio::time::timeout(
+        Duration::from_secs(2),
+        poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value.as_deref(), Some(&2));
+
+    fs::write(config_file, "3").unwrap();
+    let value = tokio::time::timeout(
+        Duration::from_secs(2),
+        poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value.as_deref(), Some(&3));
+}
+
+#[tokio::test]
+async fn should_update_tokio_watch() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build_async()
+        .await
+        .unwrap();
+    let mut values = watch.to_tokio_watch();
+    assert_eq!(**values.borrow(), 1);
+    assert!(!values.has_changed().unwrap());
+
+    fs::write(config_file, "2").unwrap();
+    tokio::time::timeout(Duration::from_secs(2), values.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(**values.borrow_and_update(), 2);
+
+    drop(watch);
+    assert!(values.changed().await.is_err());
+}
+
+#[tokio::test]
+async fn should_broadcast_updates_to_every_subscriber() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = Builder::new()
+        .watch_file(config_file)
+        .load(loader)
+        .build_async()
+        .await
+        .unwrap();
+    let mut first = watch.subscribe_broadcast();
+    let mut second = watch.subscribe_broadcast();
+
+    fs::write(config_file, "2").unwrap();
+    for receiver in [&mut first, &mut second] {
+        let value = tokio::time::timeout(Duration::from_secs(2), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*value, 2);
+    }
+}
+
+#[tokio::test]
+async fn should_stop_watches_when_async_scope_exits() {
+    let (_guard, files) = create_files(&[("config_file", "1")]).unwrap();
+    let config_file = &files[0];
+
+    let watch = scope_async(|scope| async move {
+        let watch = scope.add(
+            Builder::new()
+                .watch_file(config_file)
+                .load(loader)
+                .build_async()
+                .await
+                .unwrap(),
+        );
+        let changed = watch.changed();
+        fs::write(config_file, "2").unwrap();
+        tokio::time::timeout(Duration::from_secs(2), changed)
+            .await
+            .unwrap();
+        watch
+    })
+    .await;
+    assert_eq!(**watch.value(), 2);
+
+    // The watch is still readable, but should no longer update.
+    fs::write(config_file, "3").unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(**watch.value(), 2);
+}
@@ -0,0 +1,73 @@
+use std::{fs, thread, time::Duration};
+
+use config_file_watch::{Error, WatchSet};
+use serde_json::json;
+
+use crate::utils::create_files;
+
+/// Wait for `check` to pass, since the manifest is reloaded on another
+/// thread.
+fn eventually(check: impl Fn() -> bool) {
+    for _ in 0..50 {
+        if check() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("Timed out waiting for condition");
+}
+
+#[test]
+fn should_build_watches_from_manifest() {
+    let (_guard, files) = create_files(&[
+        (
+            "manifest.json",
+            r#"{"configs": {"db": {"path": "db.json"}}}"#,
+        ),
+        ("db.json", r#"{"port": 5432}"#),
+        ("cache.json", r#"{"size": 10}"#),
+    ])
+    .unwrap();
+
+    let set = WatchSet::from_manifest(&files[0]).unwrap();
+    assert_eq!(set.names(), vec!["db"]);
+    let db = set.get("db").unwrap();
+    assert_eq!(**db.value(), json!({"port": 5432}));
+
+    // Adding a config to the manifest starts a new watch, and leaves the
+    // existing ones alone.
+    fs::write(
+        &files[0],
+        r#"{"configs": {"db": {"path": "db.json"}, "cache": {"path": "cache.json"}}}"#,
+    )
+    .unwrap();
+    eventually(|| set.names() == vec!["cache", "db"]);
+    assert_eq!(**set.get("cache").unwrap().value(), json!({"size": 10}));
+    assert_eq!(**set.get("db").unwrap().value(), json!({"port": 5432}));
+
+    // An invalid manifest is ignored.
+    fs::write(&files[0], r#"{"configs": {"db": {"file": "db.json"}}}"#).unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(set.names(), vec!["cache", "db"]);
+
+    // Removing a config drops its watch.
+    fs::write(
+        &files[0],
+        r#"{"configs": {"cache": {"path": "cache.json"}}}"#,
+    )
+    .unwrap();
+    eventually(|| set.names() == vec!["cache"]);
+    assert!(set.get("db").is_none());
+}
+
+#[test]
+fn should_reject_invalid_manifest() {
+    let (_guard, files) =
+        create_files(&[("manifest.json", r#"{"configs": {"db": {"path": 1}}}"#)]).unwrap();
+
+    let err = WatchSet::from_manifest(&files[0]).unwrap_err();
+    let Error::ParseError { field_path, .. } = err else {
+        panic!("Expected a parse error, got {err:?}");
+    };
+    assert_eq!(field_path, "configs.db.path");
+}